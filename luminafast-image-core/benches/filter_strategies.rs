@@ -0,0 +1,80 @@
+//! Cost of the global filter chain per evaluation strategy.
+//!
+//! For each filter set, times the strategy picked by `CompiledFilters::compile`
+//! against the exact per-pixel path, plus the compile step itself. Only
+//! per-channel chains are compiled into tables: they match the per-pixel path
+//! bit for bit and are faster; channel-mixing chains stay on the per-pixel
+//! path.
+//!
+//! Usage: `cargo bench --bench filter_strategies [-- <width> <height>]`
+//! (default: 6000x4000, i.e. 24 MP).
+
+use luminafast_image_core::{CompiledFilters, PixelFilters};
+use std::hint::black_box;
+use std::time::Instant;
+
+const FRAMES: usize = 5;
+
+fn millis_per_frame(source: &[u8], compiled: &CompiledFilters) -> f64 {
+    let mut pixels = source.to_vec();
+    compiled.apply(&mut pixels);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        pixels.copy_from_slice(source);
+        compiled.apply(black_box(&mut pixels));
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64
+}
+
+fn main() {
+    let mut args = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<usize>().ok());
+    let width = args.next().unwrap_or(6000);
+    let height = args.next().unwrap_or(4000);
+
+    let source: Vec<u8> = (0..width * height)
+        .flat_map(|index| {
+            [
+                (index % 251) as u8,
+                (index % 241) as u8,
+                (index % 239) as u8,
+                255,
+            ]
+        })
+        .collect();
+
+    let tone = PixelFilters {
+        exposure: -0.7,
+        contrast: 1.5,
+        color_temp: 4000.0,
+        tint: 12.0,
+        ..PixelFilters::default()
+    };
+    let colour = PixelFilters {
+        saturation: 1.6,
+        ..tone
+    };
+    let luma = PixelFilters {
+        highlights: -0.4,
+        shadows: 0.3,
+        ..colour
+    };
+
+    println!("{width}x{height}, {FRAMES} frames per measurement");
+    for (name, filters) in [("tone", tone), ("saturation", colour), ("luma", luma)] {
+        let start = Instant::now();
+        let compiled = CompiledFilters::compile(&filters);
+        let compile_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let compiled_ms = millis_per_frame(&source, &compiled);
+        let per_pixel_ms = millis_per_frame(&source, &CompiledFilters::PerPixel(filters));
+
+        println!(
+            "{name:>10}: {:?} {compiled_ms:.1} ms/frame (compile {compile_ms:.3} ms), \
+             per-pixel {per_pixel_ms:.1} ms/frame",
+            compiled.strategy()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Memoization of intermediate pipeline buffers.
+//!
+//! `PipelineCache` stores RGBA buffers keyed by a chained fingerprint of the
+//! source and of every step that produced them. `ImagePipeline::execute_cached`
+//! resumes from the deepest cached stage, so changing only the last step
+//! re-runs only that step. Entries are evicted least-recently-used first once
+//! the configured memory budget is exceeded.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Default memory budget for cached intermediate buffers (256 MiB).
+pub const DEFAULT_CACHE_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug)]
+struct CacheEntry {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    last_used: u64,
+}
+
+/// LRU store of intermediate RGBA buffers bounded by a byte budget.
+#[derive(Debug)]
+pub struct PipelineCache {
+    budget_bytes: usize,
+    used_bytes: usize,
+    clock: u64,
+    entries: HashMap<u64, CacheEntry>,
+}
+
+impl Default for PipelineCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BUDGET_BYTES)
+    }
+}
+
+impl PipelineCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            budget_bytes,
+            used_bytes: 0,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// Changes the budget, evicting entries until the cache fits in it.
+    pub fn set_budget_bytes(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+        self.evict_until_fits(0);
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.entries.contains_key(&key)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used_bytes = 0;
+    }
+
+    /// Returns the cached buffer for `key` if it matches the requested size.
+    pub fn get(&mut self, key: u64, width: u32, height: u32) -> Option<&[u8]> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        let entry = self.entries.get_mut(&key)?;
+        if entry.width != width || entry.height != height {
+            return None;
+        }
+
+        entry.last_used = clock;
+        Some(&entry.pixels)
+    }
+
+    /// Stores a copy of `pixels`; returns false when the buffer alone exceeds
+    /// the budget and was therefore not cached.
+    pub fn insert(&mut self, key: u64, width: u32, height: u32, pixels: &[u8]) -> bool {
+        if pixels.len() > self.budget_bytes {
+            return false;
+        }
+
+        self.remove(key);
+        self.evict_until_fits(pixels.len());
+
+        self.clock += 1;
+        self.used_bytes += pixels.len();
+        self.entries.insert(
+            key,
+            CacheEntry {
+                width,
+                height,
+                pixels: pixels.to_vec(),
+                last_used: self.clock,
+            },
+        );
+
+        true
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.used_bytes -= entry.pixels.len();
+        }
+    }
+
+    fn evict_until_fits(&mut self, incoming: usize) {
+        while self.used_bytes + incoming > self.budget_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.remove(oldest);
+        }
+    }
+}
+
+/// Fingerprint of the pipeline input, the first link of every stage key.
+pub(crate) fn source_stage_key(source_key: u64, width: u32, height: u32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source_key.hash(&mut hasher);
+    width.hash(&mut hasher);
+    height.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Fingerprint of the buffer produced by a step with `step_key` applied to
+/// the buffer fingerprinted by `previous`.
+pub(crate) fn chain_stage_key(previous: u64, step_key: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    previous.hash(&mut hasher);
+    step_key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_rejects_mismatched_dimensions() {
+        let mut cache = PipelineCache::new(64);
+        assert!(cache.insert(1, 2, 1, &[0; 8]));
+
+        assert!(cache.get(1, 1, 2).is_none());
+        assert_eq!(cache.get(1, 2, 1), Some(&[0_u8; 8][..]));
+    }
+
+    #[test]
+    fn insert_evicts_least_recently_used_entry() {
+        let mut cache = PipelineCache::new(16);
+        cache.insert(1, 1, 2, &[1; 8]);
+        cache.insert(2, 1, 2, &[2; 8]);
+        assert!(cache.get(1, 1, 2).is_some());
+
+        cache.insert(3, 1, 2, &[3; 8]);
+
+        assert!(cache.contains(1));
+        assert!(!cache.contains(2));
+        assert!(cache.contains(3));
+        assert_eq!(cache.used_bytes(), 16);
+    }
+
+    #[test]
+    fn oversized_buffers_are_not_cached() {
+        let mut cache = PipelineCache::new(4);
+
+        assert!(!cache.insert(1, 1, 2, &[0; 8]));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn shrinking_budget_evicts_entries() {
+        let mut cache = PipelineCache::new(16);
+        cache.insert(1, 1, 2, &[1; 8]);
+        cache.insert(2, 1, 2, &[2; 8]);
+
+        cache.set_budget_bytes(8);
+
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(2));
+    }
+}
@@ -0,0 +1,460 @@
+//! Objective comparison of two RGBA8 renders of the same size.
+//!
+//! - PSNR over the RGB channels (alpha is ignored), in dB.
+//! - SSIM (Wang et al. 2004) with the standard 11-tap Gaussian window
+//!   (σ = 1.5), averaged over R, G and B. The window is truncated and
+//!   renormalised at the borders, so tiny test images are supported.
+//! - CIEDE2000 colour difference per pixel (sRGB, D65), reported as mean and
+//!   maximum, with an optional false-colour heatmap of the per-pixel values.
+
+use crate::errors::ProcessingError;
+use crate::pipeline::validate_rgba_input;
+
+/// ΔE00 shown as full red in the heatmap.
+pub const DEFAULT_HEATMAP_MAX_DELTA_E: f64 = 10.0;
+
+const SSIM_RADIUS: usize = 5;
+const SSIM_SIGMA: f64 = 1.5;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Heatmap colour stops, from no difference to `heatmap_max_delta_e`.
+const HEATMAP_STOPS: [[f64; 3]; 5] = [
+    [0.0, 0.0, 0.0],
+    [0.0, 0.0, 255.0],
+    [0.0, 255.0, 0.0],
+    [255.0, 255.0, 0.0],
+    [255.0, 0.0, 0.0],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComparisonOptions {
+    /// Also render the per-pixel ΔE00 heatmap.
+    pub heatmap: bool,
+    /// ΔE00 mapped to the top of the heatmap ramp; larger values saturate.
+    pub heatmap_max_delta_e: f64,
+}
+
+impl Default for ComparisonOptions {
+    fn default() -> Self {
+        Self {
+            heatmap: false,
+            heatmap_max_delta_e: DEFAULT_HEATMAP_MAX_DELTA_E,
+        }
+    }
+}
+
+/// Output of `compare_rgba8`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageComparison {
+    /// dB; `f64::INFINITY` when the RGB channels are identical.
+    pub psnr: f64,
+    /// 1.0 for identical images.
+    pub ssim: f64,
+    pub mean_delta_e: f64,
+    pub max_delta_e: f64,
+    /// RGBA8, same size as the inputs, when requested.
+    pub heatmap: Option<Vec<u8>>,
+}
+
+/// Compares `candidate` against `reference`; both are RGBA8 `width`x`height`.
+pub fn compare_rgba8(
+    reference: &[u8],
+    candidate: &[u8],
+    width: u32,
+    height: u32,
+    options: &ComparisonOptions,
+) -> Result<ImageComparison, ProcessingError> {
+    validate_rgba_input(reference, width, height)?;
+    validate_rgba_input(candidate, width, height)?;
+
+    let delta_e = delta_e_map(reference, candidate);
+    let mean_delta_e = delta_e.iter().sum::<f64>() / delta_e.len() as f64;
+    let max_delta_e = delta_e.iter().copied().fold(0.0, f64::max);
+    let heatmap = options
+        .heatmap
+        .then(|| render_heatmap(&delta_e, options.heatmap_max_delta_e));
+
+    Ok(ImageComparison {
+        psnr: psnr_rgba8(reference, candidate, width, height)?,
+        ssim: ssim_rgba8(reference, candidate, width, height)?,
+        mean_delta_e,
+        max_delta_e,
+        heatmap,
+    })
+}
+
+/// Peak signal-to-noise ratio over the RGB channels, in dB.
+pub fn psnr_rgba8(
+    reference: &[u8],
+    candidate: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<f64, ProcessingError> {
+    validate_rgba_input(reference, width, height)?;
+    validate_rgba_input(candidate, width, height)?;
+
+    let mut squared_error = 0.0;
+    for (expected, actual) in reference.chunks_exact(4).zip(candidate.chunks_exact(4)) {
+        for channel in 0..3 {
+            let difference = expected[channel] as f64 - actual[channel] as f64;
+            squared_error += difference * difference;
+        }
+    }
+    let mse = squared_error / (width as f64 * height as f64 * 3.0);
+    if mse == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok(10.0 * (255.0 * 255.0 / mse).log10())
+}
+
+/// Mean structural similarity, averaged over the RGB channels.
+pub fn ssim_rgba8(
+    reference: &[u8],
+    candidate: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<f64, ProcessingError> {
+    validate_rgba_input(reference, width, height)?;
+    validate_rgba_input(candidate, width, height)?;
+
+    let (width, height) = (width as usize, height as usize);
+    let kernel = gaussian_kernel();
+    let mut total = 0.0;
+    for channel in 0..3 {
+        let x: Vec<f64> = reference
+            .chunks_exact(4)
+            .map(|px| px[channel] as f64)
+            .collect();
+        let y: Vec<f64> = candidate
+            .chunks_exact(4)
+            .map(|px| px[channel] as f64)
+            .collect();
+        let xx: Vec<f64> = x.iter().map(|v| v * v).collect();
+        let yy: Vec<f64> = y.iter().map(|v| v * v).collect();
+        let xy: Vec<f64> = x.iter().zip(&y).map(|(a, b)| a * b).collect();
+
+        let mu_x = gaussian_blur(&x, width, height, &kernel);
+        let mu_y = gaussian_blur(&y, width, height, &kernel);
+        let mean_xx = gaussian_blur(&xx, width, height, &kernel);
+        let mean_yy = gaussian_blur(&yy, width, height, &kernel);
+        let mean_xy = gaussian_blur(&xy, width, height, &kernel);
+
+        let mut sum = 0.0;
+        for index in 0..x.len() {
+            let (mx, my) = (mu_x[index], mu_y[index]);
+            let variance_x = (mean_xx[index] - mx * mx).max(0.0);
+            let variance_y = (mean_yy[index] - my * my).max(0.0);
+            let covariance = mean_xy[index] - mx * my;
+            sum += ((2.0 * mx * my + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mx * mx + my * my + SSIM_C1) * (variance_x + variance_y + SSIM_C2));
+        }
+        total += sum / x.len() as f64;
+    }
+    Ok(total / 3.0)
+}
+
+fn gaussian_kernel() -> [f64; 2 * SSIM_RADIUS + 1] {
+    let mut kernel = [0.0; 2 * SSIM_RADIUS + 1];
+    for (index, weight) in kernel.iter_mut().enumerate() {
+        let offset = index as f64 - SSIM_RADIUS as f64;
+        *weight = (-offset * offset / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp();
+    }
+    kernel
+}
+
+/// Separable Gaussian blur, renormalising the taps that fall inside the image.
+fn gaussian_blur(values: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let blur_line = |sample: &dyn Fn(usize) -> f64, length: usize, center: usize| {
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (tap, weight) in kernel.iter().enumerate() {
+            let position = center as isize + tap as isize - SSIM_RADIUS as isize;
+            if position >= 0 && (position as usize) < length {
+                sum += weight * sample(position as usize);
+                weight_sum += weight;
+            }
+        }
+        sum / weight_sum
+    };
+
+    let mut horizontal = vec![0.0; values.len()];
+    for y in 0..height {
+        let row = &values[y * width..(y + 1) * width];
+        for x in 0..width {
+            horizontal[y * width + x] = blur_line(&|position| row[position], width, x);
+        }
+    }
+
+    let mut blurred = vec![0.0; values.len()];
+    for x in 0..width {
+        for y in 0..height {
+            blurred[y * width + x] =
+                blur_line(&|position| horizontal[position * width + x], height, y);
+        }
+    }
+    blurred
+}
+
+fn delta_e_map(reference: &[u8], candidate: &[u8]) -> Vec<f64> {
+    reference
+        .chunks_exact(4)
+        .zip(candidate.chunks_exact(4))
+        .map(|(expected, actual)| {
+            delta_e_2000(
+                srgb8_to_lab([expected[0], expected[1], expected[2]]),
+                srgb8_to_lab([actual[0], actual[1], actual[2]]),
+            )
+        })
+        .collect()
+}
+
+fn render_heatmap(delta_e: &[f64], max_delta_e: f64) -> Vec<u8> {
+    let segments = (HEATMAP_STOPS.len() - 1) as f64;
+    delta_e
+        .iter()
+        .flat_map(|&value| {
+            let t = if max_delta_e > 0.0 {
+                (value / max_delta_e).clamp(0.0, 1.0) * segments
+            } else {
+                segments
+            };
+            let stop = (t.floor() as usize).min(HEATMAP_STOPS.len() - 2);
+            let fraction = t - stop as f64;
+            let (from, to) = (HEATMAP_STOPS[stop], HEATMAP_STOPS[stop + 1]);
+            let channel =
+                |index: usize| (from[index] + (to[index] - from[index]) * fraction).round() as u8;
+            [channel(0), channel(1), channel(2), 255]
+        })
+        .collect()
+}
+
+/// CIELAB (D65) of an 8-bit sRGB colour.
+pub fn srgb8_to_lab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|channel| {
+        let value = channel as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+
+    let f = |t: f64| {
+        const DELTA: f64 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// CIEDE2000 colour difference (Sharma, Wu & Dalal 2005), kL = kC = kH = 1.
+pub fn delta_e_2000(lab1: [f64; 3], lab2: [f64; 3]) -> f64 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+
+    let c_bar = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c_bar7 = c_bar.powi(7);
+    let g = 0.5 * (1.0 - (c_bar7 / (c_bar7 + 25.0_f64.powi(7))).sqrt());
+    let a1p = (1.0 + g) * a1;
+    let a2p = (1.0 + g) * a2;
+    let c1p = (a1p * a1p + b1 * b1).sqrt();
+    let c2p = (a2p * a2p + b2 * b2).sqrt();
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let h1p = hue(b1, a1p);
+    let h2p = hue(b2, a2p);
+
+    let delta_lp = l2 - l1;
+    let delta_cp = c2p - c1p;
+    let delta_hp = if c1p * c2p == 0.0 {
+        0.0
+    } else if (h2p - h1p).abs() <= 180.0 {
+        h2p - h1p
+    } else if h2p - h1p > 180.0 {
+        h2p - h1p - 360.0
+    } else {
+        h2p - h1p + 360.0
+    };
+    let delta_big_hp = 2.0 * (c1p * c2p).sqrt() * (delta_hp / 2.0).to_radians().sin();
+
+    let l_bar_p = (l1 + l2) / 2.0;
+    let c_bar_p = (c1p + c2p) / 2.0;
+    let h_bar_p = if c1p * c2p == 0.0 {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar_p - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_bar_p).to_radians().cos()
+        + 0.32 * (3.0 * h_bar_p + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_bar_p - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_bar_p - 275.0) / 25.0).powi(2)).exp();
+    let c_bar_p7 = c_bar_p.powi(7);
+    let r_c = 2.0 * (c_bar_p7 / (c_bar_p7 + 25.0_f64.powi(7))).sqrt();
+    let l_offset = (l_bar_p - 50.0) * (l_bar_p - 50.0);
+    let s_l = 1.0 + 0.015 * l_offset / (20.0 + l_offset).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar_p;
+    let s_h = 1.0 + 0.015 * c_bar_p * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let l_term = delta_lp / s_l;
+    let c_term = delta_cp / s_c;
+    let h_term = delta_big_hp / s_h;
+    (l_term * l_term + c_term * c_term + h_term * h_term + r_t * c_term * h_term).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|index| {
+                let (x, y) = (index % width, index / width);
+                [
+                    (x * 255 / width) as u8,
+                    (y * 255 / height) as u8,
+                    ((x + y) * 127 / (width + height)) as u8,
+                    255,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delta_e_2000_matches_sharma_reference_pairs() {
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+            ([2.0776, 0.0795, -1.135], [0.9033, -0.0636, -0.5514], 0.9082),
+        ];
+        for (lab1, lab2, expected) in pairs {
+            let forward = delta_e_2000(lab1, lab2);
+            assert!(
+                (forward - expected).abs() < 1e-4,
+                "{:?} vs {:?}: {} != {}",
+                lab1,
+                lab2,
+                forward,
+                expected
+            );
+            assert!((delta_e_2000(lab2, lab1) - forward).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn identical_images_are_a_perfect_match() {
+        let image = gradient(16, 12);
+
+        let comparison =
+            compare_rgba8(&image, &image, 16, 12, &ComparisonOptions::default()).unwrap();
+
+        assert_eq!(comparison.psnr, f64::INFINITY);
+        assert!((comparison.ssim - 1.0).abs() < 1e-12);
+        assert_eq!(comparison.mean_delta_e, 0.0);
+        assert_eq!(comparison.max_delta_e, 0.0);
+        assert!(comparison.heatmap.is_none());
+    }
+
+    #[test]
+    fn psnr_of_uniform_error_matches_closed_form() {
+        let reference = gradient(8, 8);
+        let candidate: Vec<u8> = reference
+            .chunks_exact(4)
+            .flat_map(|px| [px[0] ^ 4, px[1] ^ 4, px[2] ^ 4, px[3]])
+            .collect();
+
+        // Every channel is off by exactly 4: 10 * log10(255² / 16).
+        let psnr = psnr_rgba8(&reference, &candidate, 8, 8).unwrap();
+        assert!((psnr - 36.0896).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ssim_drops_with_structure_loss_more_than_with_brightness_shift() {
+        let reference = gradient(32, 32);
+        let brighter: Vec<u8> = reference
+            .chunks_exact(4)
+            .flat_map(|px| [px[0] / 2 + 100, px[1] / 2 + 100, px[2] / 2 + 100, 255])
+            .collect();
+        let noisy: Vec<u8> = reference
+            .chunks_exact(4)
+            .enumerate()
+            .flat_map(|(index, px)| {
+                let noise = if index % 2 == 0 { 40 } else { -40 };
+                px[..3]
+                    .iter()
+                    .map(|&v| (v as i32 + noise).clamp(0, 255) as u8)
+                    .chain([255])
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+
+        let shifted = ssim_rgba8(&reference, &brighter, 32, 32).unwrap();
+        let degraded = ssim_rgba8(&reference, &noisy, 32, 32).unwrap();
+
+        assert!(shifted < 1.0);
+        assert!(degraded < shifted, "{} >= {}", degraded, shifted);
+    }
+
+    #[test]
+    fn heatmap_marks_changed_pixels() {
+        let reference = vec![128_u8, 128, 128, 255, 128, 128, 128, 255];
+        let candidate = vec![128_u8, 128, 128, 255, 255, 0, 0, 255];
+        let options = ComparisonOptions {
+            heatmap: true,
+            ..ComparisonOptions::default()
+        };
+
+        let comparison = compare_rgba8(&reference, &candidate, 2, 1, &options).unwrap();
+        let heatmap = comparison.heatmap.expect("heatmap requested");
+
+        assert_eq!(&heatmap[..4], &[0, 0, 0, 255]);
+        assert_eq!(&heatmap[4..], &[255, 0, 0, 255]);
+        assert!(comparison.max_delta_e > DEFAULT_HEATMAP_MAX_DELTA_E);
+        assert!((comparison.mean_delta_e - comparison.max_delta_e / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compare_rejects_mismatched_buffers() {
+        let reference = gradient(4, 4);
+        let result = compare_rgba8(
+            &reference,
+            &reference[..32],
+            4,
+            4,
+            &ComparisonOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::InvalidPixelCount {
+                expected: 64,
+                got: 32
+            })
+        ));
+    }
+}
@@ -18,4 +18,38 @@ pub enum ProcessingError {
     /// RAW decoder failed to produce a valid linear RGB buffer.
     #[error("RAW decode error: {message}")]
     RawDecodeError { message: String },
+
+    /// Requested render region is empty or lies outside the source image.
+    #[error(
+        "Invalid render region {x},{y} {width}x{height} for {source_width}x{source_height} source"
+    )]
+    InvalidRegion {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        source_width: u32,
+        source_height: u32,
+    },
+
+    /// Resample filter name is not one of `box`, `mitchell`, `lanczos3`.
+    #[error("Unsupported resample filter: {name}")]
+    UnsupportedResampleFilter { name: String },
+
+    /// Retouch spot geometry or blend parameters are invalid.
+    #[error("Invalid retouch spot: {message}")]
+    InvalidRetouch { message: String },
+
+    /// Edit recipe targets a process version this build cannot render.
+    #[error("Unsupported process version: {version}")]
+    UnsupportedProcessVersion { version: u32 },
+
+    /// Edit recipe could not be parsed or migrated to the current schema.
+    #[error("Invalid edit recipe: {message}")]
+    InvalidRecipe { message: String },
+
+    /// Frames passed to a merge are missing, mismatched or carry invalid
+    /// exposure metadata.
+    #[error("Invalid merge input: {message}")]
+    InvalidMerge { message: String },
 }
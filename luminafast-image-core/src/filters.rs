@@ -1,4 +1,9 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
 use crate::errors::ProcessingError;
+use crate::lut::CompiledFilters;
 use crate::pipeline::{validate_rgba_input, ImagePipeline, ImagePipelineStep};
 
 pub const EPSILON: f32 = 0.001;
@@ -62,97 +67,193 @@ impl Default for PixelFilters {
     }
 }
 
-#[derive(Clone, Copy)]
-struct FilterTransformStep {
-    filters: PixelFilters,
+impl PixelFilters {
+    /// Bitwise fingerprint of every field, used as a pipeline cache key.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for bits in self.field_bits() {
+            bits.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Bit pattern of every field, in declaration order.
+    pub(crate) fn field_bits(&self) -> [u32; 9] {
+        [
+            self.exposure,
+            self.contrast,
+            self.saturation,
+            self.highlights,
+            self.shadows,
+            self.clarity,
+            self.vibrance,
+            self.color_temp,
+            self.tint,
+        ]
+        .map(f32::to_bits)
+    }
+}
+
+/// Which adjustments of a `PixelFilters` differ from their no-op value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ActiveFilters {
+    pub exposure: bool,
+    pub contrast: bool,
+    pub saturation: bool,
+    pub highlights: bool,
+    pub shadows: bool,
+    pub color_temp: bool,
+    pub tint: bool,
+}
+
+impl ActiveFilters {
+    pub(crate) fn of(filters: &PixelFilters) -> Self {
+        Self {
+            exposure: (filters.exposure - EXPOSURE_NOOP).abs() >= EPSILON,
+            contrast: (filters.contrast - CONTRAST_NOOP).abs() >= EPSILON,
+            saturation: (filters.saturation - SATURATION_NOOP).abs() >= EPSILON,
+            highlights: (filters.highlights - HIGHLIGHTS_NOOP).abs() >= EPSILON,
+            shadows: (filters.shadows - SHADOWS_NOOP).abs() >= EPSILON,
+            color_temp: (filters.color_temp - COLOR_TEMP_NOOP).abs() >= EPSILON,
+            tint: (filters.tint - TINT_NOOP).abs() >= EPSILON,
+        }
+    }
+
+    pub(crate) fn any(&self) -> bool {
+        self.exposure
+            || self.contrast
+            || self.saturation
+            || self.highlights
+            || self.shadows
+            || self.color_temp
+            || self.tint
+    }
+
+    /// True when a step mixes channels (saturation) or branches on luma
+    /// (highlights, shadows), so per-channel LUTs cannot express the chain.
+    pub(crate) fn mixes_channels(&self) -> bool {
+        self.saturation || self.highlights || self.shadows
+    }
+}
+
+pub(crate) struct FilterTransformStep {
+    compiled: Arc<CompiledFilters>,
+    cache_key: u64,
 }
 
 impl FilterTransformStep {
-    fn new(filters: PixelFilters) -> Self {
-        Self { filters }
+    pub(crate) fn new(filters: &PixelFilters) -> Self {
+        let mut hasher = DefaultHasher::new();
+        "filter_transform".hash(&mut hasher);
+        filters.fingerprint().hash(&mut hasher);
+
+        Self {
+            compiled: CompiledFilters::compile_cached(filters),
+            cache_key: hasher.finish(),
+        }
     }
 }
 
 impl ImagePipelineStep for FilterTransformStep {
     fn apply(&self, pixels: &mut [u8], _width: u32, _height: u32) -> Result<(), ProcessingError> {
-        apply_filters_single_pass(pixels, &self.filters);
+        self.compiled.apply(pixels);
         Ok(())
     }
+
+    fn cache_key(&self) -> Option<u64> {
+        Some(self.cache_key)
+    }
+
+    fn is_pixel_local(&self) -> bool {
+        true
+    }
 }
 
-fn apply_filters_single_pass(pixels: &mut [u8], filters: &PixelFilters) {
-    for chunk in pixels.chunks_exact_mut(4) {
-        let mut r = chunk[0] as f32;
-        let mut g = chunk[1] as f32;
-        let mut b = chunk[2] as f32;
-
-        if (filters.exposure - EXPOSURE_NOOP).abs() >= EPSILON {
-            let exposure = filters.exposure.clamp(EXPOSURE_MIN, EXPOSURE_MAX);
-            let brightness_factor = 1.0 + exposure * 0.15;
-            r *= brightness_factor;
-            g *= brightness_factor;
-            b *= brightness_factor;
-        }
+/// Runs the filter chain on one RGB sample without the final clamp.
+///
+/// This is the reference per-pixel path; LUT compilation samples it so the
+/// compiled and uncompiled paths share a single definition of each step.
+pub(crate) fn transform_rgb(rgb: [f32; 3], filters: &PixelFilters) -> [f32; 3] {
+    let [mut r, mut g, mut b] = rgb;
+
+    if (filters.exposure - EXPOSURE_NOOP).abs() >= EPSILON {
+        let exposure = filters.exposure.clamp(EXPOSURE_MIN, EXPOSURE_MAX);
+        let brightness_factor = 1.0 + exposure * 0.15;
+        r *= brightness_factor;
+        g *= brightness_factor;
+        b *= brightness_factor;
+    }
 
-        if (filters.contrast - CONTRAST_NOOP).abs() >= EPSILON {
-            let contrast = filters.contrast.clamp(CONTRAST_MIN, CONTRAST_MAX);
-            let contrast_factor = 1.0 + contrast * 0.25;
-            r = (r - 128.0) * contrast_factor + 128.0;
-            g = (g - 128.0) * contrast_factor + 128.0;
-            b = (b - 128.0) * contrast_factor + 128.0;
-        }
+    if (filters.contrast - CONTRAST_NOOP).abs() >= EPSILON {
+        let contrast = filters.contrast.clamp(CONTRAST_MIN, CONTRAST_MAX);
+        let contrast_factor = 1.0 + contrast * 0.25;
+        r = (r - 128.0) * contrast_factor + 128.0;
+        g = (g - 128.0) * contrast_factor + 128.0;
+        b = (b - 128.0) * contrast_factor + 128.0;
+    }
 
-        if (filters.saturation - SATURATION_NOOP).abs() >= EPSILON {
-            let saturation = filters.saturation.clamp(SATURATION_MIN, SATURATION_MAX);
-            let luma = 0.299 * r + 0.587 * g + 0.114 * b;
-            r = luma + (r - luma) * saturation;
-            g = luma + (g - luma) * saturation;
-            b = luma + (b - luma) * saturation;
-        }
+    if (filters.saturation - SATURATION_NOOP).abs() >= EPSILON {
+        let saturation = filters.saturation.clamp(SATURATION_MIN, SATURATION_MAX);
+        let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+        r = luma + (r - luma) * saturation;
+        g = luma + (g - luma) * saturation;
+        b = luma + (b - luma) * saturation;
+    }
 
-        if (filters.highlights - HIGHLIGHTS_NOOP).abs() >= EPSILON {
-            let highlight = filters.highlights.clamp(HIGHLIGHTS_MIN, HIGHLIGHTS_MAX);
-            let luma = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
-            if luma > 180 {
-                let factor = (1.0 + highlight * 0.3).clamp(0.7, 1.3);
-                r *= factor;
-                g *= factor;
-                b *= factor;
-            }
+    if (filters.highlights - HIGHLIGHTS_NOOP).abs() >= EPSILON {
+        let highlight = filters.highlights.clamp(HIGHLIGHTS_MIN, HIGHLIGHTS_MAX);
+        let luma = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
+        if luma > 180 {
+            let factor = (1.0 + highlight * 0.3).clamp(0.7, 1.3);
+            r *= factor;
+            g *= factor;
+            b *= factor;
         }
+    }
 
-        if (filters.shadows - SHADOWS_NOOP).abs() >= EPSILON {
-            let shadow = filters.shadows.clamp(SHADOWS_MIN, SHADOWS_MAX);
-            let luma = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
-            if luma < 75 {
-                let factor = (1.0 + shadow * 0.3).clamp(0.7, 1.3);
-                r *= factor;
-                g *= factor;
-                b *= factor;
-            }
+    if (filters.shadows - SHADOWS_NOOP).abs() >= EPSILON {
+        let shadow = filters.shadows.clamp(SHADOWS_MIN, SHADOWS_MAX);
+        let luma = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
+        if luma < 75 {
+            let factor = (1.0 + shadow * 0.3).clamp(0.7, 1.3);
+            r *= factor;
+            g *= factor;
+            b *= factor;
         }
+    }
 
-        if (filters.color_temp - COLOR_TEMP_NOOP).abs() >= EPSILON {
-            let color_temp = filters.color_temp.clamp(COLOR_TEMP_MIN, COLOR_TEMP_MAX);
-            let temp_offset = (color_temp - COLOR_TEMP_NOOP) / 1000.0;
-            let red_factor = 1.0 + (temp_offset * -0.1).clamp(-0.3, 0.3);
-            let blue_factor = 1.0 + (temp_offset * 0.1).clamp(-0.3, 0.3);
-            r *= red_factor;
-            b *= blue_factor;
-        }
+    if (filters.color_temp - COLOR_TEMP_NOOP).abs() >= EPSILON {
+        let color_temp = filters.color_temp.clamp(COLOR_TEMP_MIN, COLOR_TEMP_MAX);
+        let temp_offset = (color_temp - COLOR_TEMP_NOOP) / 1000.0;
+        let red_factor = 1.0 + (temp_offset * -0.1).clamp(-0.3, 0.3);
+        let blue_factor = 1.0 + (temp_offset * 0.1).clamp(-0.3, 0.3);
+        r *= red_factor;
+        b *= blue_factor;
+    }
 
-        if (filters.tint - TINT_NOOP).abs() >= EPSILON {
-            let tint = filters.tint.clamp(TINT_MIN, TINT_MAX);
-            let green_factor = 1.0 + (tint / 50.0) * 0.2;
-            let magenta_factor = 1.0 - (tint / 50.0) * 0.1;
-            r *= magenta_factor;
-            g *= green_factor;
-            b *= magenta_factor;
-        }
+    if (filters.tint - TINT_NOOP).abs() >= EPSILON {
+        let tint = filters.tint.clamp(TINT_MIN, TINT_MAX);
+        let green_factor = 1.0 + (tint / 50.0) * 0.2;
+        let magenta_factor = 1.0 - (tint / 50.0) * 0.1;
+        r *= magenta_factor;
+        g *= green_factor;
+        b *= magenta_factor;
+    }
+
+    [r, g, b]
+}
+
+pub(crate) fn clamp_to_u8(sample: f32) -> u8 {
+    sample.clamp(0.0, 255.0) as u8
+}
+
+pub(crate) fn apply_filters_single_pass(pixels: &mut [u8], filters: &PixelFilters) {
+    for chunk in pixels.chunks_exact_mut(4) {
+        let [r, g, b] = transform_rgb([chunk[0] as f32, chunk[1] as f32, chunk[2] as f32], filters);
 
-        chunk[0] = r.clamp(0.0, 255.0) as u8;
-        chunk[1] = g.clamp(0.0, 255.0) as u8;
-        chunk[2] = b.clamp(0.0, 255.0) as u8;
+        chunk[0] = clamp_to_u8(r);
+        chunk[1] = clamp_to_u8(g);
+        chunk[2] = clamp_to_u8(b);
     }
 }
 
@@ -166,26 +267,11 @@ pub fn apply_filters(
 
     let mut result = pixels.to_vec();
 
-    let exposure_active = (filters.exposure - EXPOSURE_NOOP).abs() >= EPSILON;
-    let contrast_active = (filters.contrast - CONTRAST_NOOP).abs() >= EPSILON;
-    let saturation_active = (filters.saturation - SATURATION_NOOP).abs() >= EPSILON;
-    let highlights_active = (filters.highlights - HIGHLIGHTS_NOOP).abs() >= EPSILON;
-    let shadows_active = (filters.shadows - SHADOWS_NOOP).abs() >= EPSILON;
-    let color_temp_active = (filters.color_temp - COLOR_TEMP_NOOP).abs() >= EPSILON;
-    let tint_active = (filters.tint - TINT_NOOP).abs() >= EPSILON;
-
-    if !exposure_active
-        && !contrast_active
-        && !saturation_active
-        && !highlights_active
-        && !shadows_active
-        && !color_temp_active
-        && !tint_active
-    {
+    if !ActiveFilters::of(filters).any() {
         return Ok(result);
     }
 
-    let pipeline = ImagePipeline::new().with_step(FilterTransformStep::new(*filters));
+    let pipeline = ImagePipeline::new().with_step(FilterTransformStep::new(filters));
     debug_assert!(!pipeline.is_empty());
     pipeline.execute(&mut result, width, height)?;
 
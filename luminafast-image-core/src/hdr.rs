@@ -0,0 +1,484 @@
+//! HDR merge of bracketed exposures.
+//!
+//! Frames are decoded linear RGB (`LinearImage`) tagged with their relative
+//! exposure. Each frame is aligned to the middle exposure with a translation
+//! found by median threshold bitmaps, which compare the same way regardless of
+//! exposure. Radiance is then the exposure-normalised weighted mean of the
+//! aligned samples: longer exposures weigh more (better signal to noise) and
+//! samples near clipping fade out. The result is expressed at the reference
+//! exposure, so mid-tones match the middle frame and highlights exceed 1.0.
+
+use crate::errors::ProcessingError;
+use crate::raw_decoder::LinearImage;
+
+/// Default search radius of the translation alignment, in pixels.
+pub const DEFAULT_MAX_ALIGNMENT_SHIFT: u32 = 64;
+/// Samples above this level carry no weight (sensor or decoder clipping).
+const CLIPPED_LEVEL: f32 = 0.98;
+/// Weight starts fading out above this level.
+const HIGHLIGHT_ROLLOFF_START: f32 = 0.85;
+/// Gamma-encoded distance to the median excluded from the bitmaps.
+const MTB_NOISE_TOLERANCE: f32 = 4.0 / 255.0;
+/// Pyramid levels smaller than this are not searched.
+const MIN_PYRAMID_SIDE: u32 = 8;
+
+/// One bracketed exposure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrFrame {
+    pub image: LinearImage,
+    /// Relative exposure (see `relative_exposure`); only ratios matter.
+    pub exposure: f32,
+}
+
+impl HdrFrame {
+    pub fn new(image: LinearImage, exposure: f32) -> Result<Self, ProcessingError> {
+        if !exposure.is_finite() || exposure <= 0.0 {
+            return Err(ProcessingError::InvalidFilterValue {
+                field: "exposure".to_string(),
+                value: exposure,
+            });
+        }
+
+        Ok(Self { image, exposure })
+    }
+}
+
+/// Output of `merge_exposures`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrMergeOutput {
+    /// Merged radiance at the reference frame's exposure.
+    pub image: LinearImage,
+    /// Index of the frame every other frame was aligned to.
+    pub reference_index: usize,
+    /// Per-frame `(dx, dy)`: frame pixel `(x + dx, y + dy)` lands on output `(x, y)`.
+    pub offsets: Vec<(i32, i32)>,
+}
+
+/// Relative exposure from capture metadata: `seconds * (iso / 100) / f_number²`.
+pub fn relative_exposure(
+    shutter_seconds: f32,
+    f_number: f32,
+    iso: f32,
+) -> Result<f32, ProcessingError> {
+    for (field, value) in [
+        ("shutter_seconds", shutter_seconds),
+        ("f_number", f_number),
+        ("iso", iso),
+    ] {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(ProcessingError::InvalidFilterValue {
+                field: field.to_string(),
+                value,
+            });
+        }
+    }
+
+    Ok(shutter_seconds * (iso / 100.0) / (f_number * f_number))
+}
+
+/// Aligns and merges bracketed frames into one linear radiance image.
+pub fn merge_exposures(
+    frames: &[HdrFrame],
+    max_shift: u32,
+) -> Result<HdrMergeOutput, ProcessingError> {
+    if frames.len() < 2 {
+        return Err(ProcessingError::InvalidMerge {
+            message: format!("at least 2 frames required, got {}", frames.len()),
+        });
+    }
+
+    let width = frames[0].image.width;
+    let height = frames[0].image.height;
+    if let Some(frame) = frames
+        .iter()
+        .find(|frame| frame.image.width != width || frame.image.height != height)
+    {
+        return Err(ProcessingError::InvalidMerge {
+            message: format!(
+                "frame size {}x{} differs from {}x{}",
+                frame.image.width, frame.image.height, width, height
+            ),
+        });
+    }
+
+    let reference_index = reference_frame_index(frames);
+    let reference = &frames[reference_index];
+    let reference_grey = grey(&reference.image);
+    let offsets: Vec<(i32, i32)> = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            if index == reference_index {
+                (0, 0)
+            } else {
+                align_grey(
+                    &reference_grey,
+                    &grey(&frame.image),
+                    width,
+                    height,
+                    max_shift,
+                )
+            }
+        })
+        .collect();
+
+    let shortest = frames
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.exposure.total_cmp(&b.1.exposure))
+        .map(|(index, _)| index)
+        .unwrap_or(reference_index);
+
+    let mut merged = Vec::with_capacity(reference.image.pixels_rgb_f32.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let mut sum = [0.0_f32; 3];
+            let mut total_weight = 0.0_f32;
+
+            for (frame, &(dx, dy)) in frames.iter().zip(&offsets) {
+                let Some(sample) = sample_at(&frame.image, x + dx as i64, y + dy as i64) else {
+                    continue;
+                };
+                let weight = frame.exposure * highlight_weight(max_channel(sample));
+                if weight <= 0.0 {
+                    continue;
+                }
+                for channel in 0..3 {
+                    sum[channel] += weight * sample[channel] / frame.exposure;
+                }
+                total_weight += weight;
+            }
+
+            if total_weight > 0.0 {
+                merged.extend(
+                    sum.iter()
+                        .map(|value| value / total_weight * reference.exposure),
+                );
+            } else {
+                // Clipped in every frame: the shortest exposure is the best bound.
+                let frame = &frames[shortest];
+                let (dx, dy) = offsets[shortest];
+                let (sample, exposure) = sample_at(&frame.image, x + dx as i64, y + dy as i64)
+                    .map(|sample| (sample, frame.exposure))
+                    .unwrap_or_else(|| {
+                        let sample = sample_at(&reference.image, x, y).unwrap_or([0.0; 3]);
+                        (sample, reference.exposure)
+                    });
+                merged.extend(
+                    sample
+                        .iter()
+                        .map(|value| value / exposure * reference.exposure),
+                );
+            }
+        }
+    }
+
+    Ok(HdrMergeOutput {
+        image: LinearImage::new(width, height, merged)?,
+        reference_index,
+        offsets,
+    })
+}
+
+/// Translation `(dx, dy)` aligning `frame` to `reference`, within `max_shift`.
+pub fn estimate_translation(
+    reference: &LinearImage,
+    frame: &LinearImage,
+    max_shift: u32,
+) -> Result<(i32, i32), ProcessingError> {
+    if reference.width != frame.width || reference.height != frame.height {
+        return Err(ProcessingError::InvalidMerge {
+            message: format!(
+                "frame size {}x{} differs from {}x{}",
+                frame.width, frame.height, reference.width, reference.height
+            ),
+        });
+    }
+
+    Ok(align_grey(
+        &grey(reference),
+        &grey(frame),
+        reference.width,
+        reference.height,
+        max_shift,
+    ))
+}
+
+/// Frame with the median exposure.
+fn reference_frame_index(frames: &[HdrFrame]) -> usize {
+    let mut order: Vec<usize> = (0..frames.len()).collect();
+    order.sort_by(|&a, &b| frames[a].exposure.total_cmp(&frames[b].exposure));
+    order[(order.len() - 1) / 2]
+}
+
+fn sample_at(image: &LinearImage, x: i64, y: i64) -> Option<[f32; 3]> {
+    if x < 0 || y < 0 || x >= image.width as i64 || y >= image.height as i64 {
+        return None;
+    }
+    let index = (y as usize * image.width as usize + x as usize) * 3;
+    let rgb = &image.pixels_rgb_f32[index..index + 3];
+    Some([rgb[0], rgb[1], rgb[2]])
+}
+
+fn max_channel(sample: [f32; 3]) -> f32 {
+    sample[0].max(sample[1]).max(sample[2])
+}
+
+fn highlight_weight(level: f32) -> f32 {
+    if level >= CLIPPED_LEVEL {
+        0.0
+    } else if level <= HIGHLIGHT_ROLLOFF_START {
+        1.0
+    } else {
+        (CLIPPED_LEVEL - level) / (CLIPPED_LEVEL - HIGHLIGHT_ROLLOFF_START)
+    }
+}
+
+/// Gamma-encoded luminance used by the bitmap alignment.
+fn grey(image: &LinearImage) -> Vec<f32> {
+    image
+        .pixels_rgb_f32
+        .chunks_exact(3)
+        .map(|rgb| {
+            let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            luminance.clamp(0.0, 1.0).powf(1.0 / 2.2)
+        })
+        .collect()
+}
+
+fn half_size(grey: &[f32], width: u32, height: u32) -> (Vec<f32>, u32, u32) {
+    let half_width = width / 2;
+    let half_height = height / 2;
+    let mut output = Vec::with_capacity((half_width * half_height) as usize);
+
+    for y in 0..half_height as usize {
+        for x in 0..half_width as usize {
+            let top = 2 * y * width as usize + 2 * x;
+            let bottom = top + width as usize;
+            output.push((grey[top] + grey[top + 1] + grey[bottom] + grey[bottom + 1]) / 4.0);
+        }
+    }
+
+    (output, half_width, half_height)
+}
+
+/// Threshold and exclusion bitmaps of one pyramid level.
+struct Bitmaps {
+    threshold: Vec<bool>,
+    valid: Vec<bool>,
+}
+
+fn median_threshold_bitmaps(grey: &[f32]) -> Bitmaps {
+    let mut sorted = grey.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    Bitmaps {
+        threshold: grey.iter().map(|&value| value > median).collect(),
+        valid: grey
+            .iter()
+            .map(|&value| (value - median).abs() > MTB_NOISE_TOLERANCE)
+            .collect(),
+    }
+}
+
+/// Fraction of mismatching, non-excluded bits with `frame` shifted by `(dx, dy)`.
+fn mismatch_rate(
+    reference: &Bitmaps,
+    frame: &Bitmaps,
+    width: u32,
+    height: u32,
+    dx: i32,
+    dy: i32,
+) -> f32 {
+    let mut mismatches = 0_u64;
+    let mut compared = 0_u64;
+
+    for y in 0..height as i64 {
+        let fy = y + dy as i64;
+        if fy < 0 || fy >= height as i64 {
+            continue;
+        }
+        for x in 0..width as i64 {
+            let fx = x + dx as i64;
+            if fx < 0 || fx >= width as i64 {
+                continue;
+            }
+            let r = (y * width as i64 + x) as usize;
+            let f = (fy * width as i64 + fx) as usize;
+            if reference.valid[r] && frame.valid[f] {
+                compared += 1;
+                if reference.threshold[r] != frame.threshold[f] {
+                    mismatches += 1;
+                }
+            }
+        }
+    }
+
+    if compared == 0 {
+        1.0
+    } else {
+        mismatches as f32 / compared as f32
+    }
+}
+
+/// Coarse-to-fine search: each level refines the doubled shift of the level
+/// below by at most one pixel in each direction.
+fn align_grey(
+    reference: &[f32],
+    frame: &[f32],
+    width: u32,
+    height: u32,
+    max_shift: u32,
+) -> (i32, i32) {
+    let max_shift = max_shift as i32;
+    if max_shift == 0 {
+        return (0, 0);
+    }
+
+    let (base_dx, base_dy) =
+        if max_shift > 1 && width / 2 >= MIN_PYRAMID_SIDE && height / 2 >= MIN_PYRAMID_SIDE {
+            let (half_reference, half_width, half_height) = half_size(reference, width, height);
+            let (half_frame, _, _) = half_size(frame, width, height);
+            let (dx, dy) = align_grey(
+                &half_reference,
+                &half_frame,
+                half_width,
+                half_height,
+                max_shift as u32 / 2,
+            );
+            (dx * 2, dy * 2)
+        } else {
+            (0, 0)
+        };
+
+    let reference_bits = median_threshold_bitmaps(reference);
+    let frame_bits = median_threshold_bitmaps(frame);
+    let mut best = (base_dx, base_dy);
+    let mut best_rate = f32::INFINITY;
+
+    for step_y in -1..=1 {
+        for step_x in -1..=1 {
+            let dx = (base_dx + step_x).clamp(-max_shift, max_shift);
+            let dy = (base_dy + step_y).clamp(-max_shift, max_shift);
+            let rate = mismatch_rate(&reference_bits, &frame_bits, width, height, dx, dy);
+            if rate < best_rate {
+                best_rate = rate;
+                best = (dx, dy);
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smooth scene radiance with enough structure for alignment.
+    fn scene(x: i64, y: i64) -> f32 {
+        let fx = x as f32;
+        let fy = y as f32;
+        0.02 + 0.5 * (((fx * 0.31).sin() * (fy * 0.23).cos()) * 0.5 + 0.5) + 0.004 * fx
+    }
+
+    fn capture(width: u32, height: u32, exposure: f32, shift: (i64, i64)) -> LinearImage {
+        let mut pixels = Vec::new();
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let value = (scene(x - shift.0, y - shift.1) * exposure).min(1.0);
+                pixels.extend_from_slice(&[value, value * 0.9, value * 0.8]);
+            }
+        }
+        LinearImage::new(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn relative_exposure_follows_exposure_triangle() {
+        let base = relative_exposure(1.0 / 100.0, 4.0, 100.0).unwrap();
+
+        let double_time = relative_exposure(2.0 / 100.0, 4.0, 100.0).unwrap();
+        let one_stop_smaller = relative_exposure(1.0 / 100.0, 4.0 * 2f32.sqrt(), 100.0).unwrap();
+        let double_iso = relative_exposure(1.0 / 100.0, 4.0, 200.0).unwrap();
+
+        assert!((double_time / base - 2.0).abs() < 1e-5);
+        assert!((base / one_stop_smaller - 2.0).abs() < 1e-4);
+        assert!((double_iso / base - 2.0).abs() < 1e-5);
+        assert!(relative_exposure(0.0, 4.0, 100.0).is_err());
+    }
+
+    #[test]
+    fn estimates_translation_between_differently_exposed_frames() {
+        let reference = capture(64, 48, 1.0, (0, 0));
+        let shifted = capture(64, 48, 0.25, (3, -2));
+
+        let offset = estimate_translation(&reference, &shifted, 16).unwrap();
+
+        assert_eq!(offset, (3, -2));
+    }
+
+    #[test]
+    fn merge_recovers_highlights_clipped_in_reference() {
+        let frames = vec![
+            HdrFrame::new(capture(32, 24, 0.5, (0, 0)), 0.5).unwrap(),
+            HdrFrame::new(capture(32, 24, 2.0, (0, 0)), 2.0).unwrap(),
+            HdrFrame::new(capture(32, 24, 8.0, (0, 0)), 8.0).unwrap(),
+        ];
+
+        let output = merge_exposures(&frames, 0).unwrap();
+
+        assert_eq!(output.reference_index, 1);
+        for y in 0..24 {
+            for x in 0..32 {
+                let index = ((y * 32 + x) * 3) as usize;
+                let expected = scene(x, y) * 2.0;
+                let merged = output.image.pixels_rgb_f32[index];
+                assert!(
+                    (merged - expected).abs() < 1e-3,
+                    "({x},{y}): {merged} vs {expected}"
+                );
+            }
+        }
+        assert!(output.image.pixels_rgb_f32.iter().any(|&value| value > 1.0));
+    }
+
+    #[test]
+    fn merge_aligns_shifted_frames() {
+        let frames = vec![
+            HdrFrame::new(capture(64, 48, 0.5, (2, 1)), 0.5).unwrap(),
+            HdrFrame::new(capture(64, 48, 1.0, (0, 0)), 1.0).unwrap(),
+            HdrFrame::new(capture(64, 48, 2.0, (-1, 3)), 2.0).unwrap(),
+        ];
+
+        let output = merge_exposures(&frames, 8).unwrap();
+
+        assert_eq!(output.offsets, vec![(2, 1), (0, 0), (-1, 3)]);
+        let index = ((20 * 64 + 30) * 3) as usize;
+        assert!((output.image.pixels_rgb_f32[index] - scene(30, 20)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn merge_rejects_single_frame_and_mismatched_sizes() {
+        let single = vec![HdrFrame::new(capture(8, 8, 1.0, (0, 0)), 1.0).unwrap()];
+        let mismatched = vec![
+            HdrFrame::new(capture(8, 8, 1.0, (0, 0)), 1.0).unwrap(),
+            HdrFrame::new(capture(8, 6, 2.0, (0, 0)), 2.0).unwrap(),
+        ];
+
+        assert!(matches!(
+            merge_exposures(&single, 0),
+            Err(ProcessingError::InvalidMerge { .. })
+        ));
+        assert!(matches!(
+            merge_exposures(&mismatched, 0),
+            Err(ProcessingError::InvalidMerge { .. })
+        ));
+    }
+
+    #[test]
+    fn frame_rejects_non_positive_exposure() {
+        assert!(HdrFrame::new(capture(2, 2, 1.0, (0, 0)), 0.0).is_err());
+        assert!(HdrFrame::new(capture(2, 2, 1.0, (0, 0)), f32::NAN).is_err());
+    }
+}
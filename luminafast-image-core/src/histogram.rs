@@ -19,6 +19,44 @@ pub fn compute_histogram_from_pixels(
     Ok(histogram)
 }
 
+/// Like `compute_histogram_from_pixels`, counting row bands on the rayon
+/// thread pool and summing the per-band bins.
+#[cfg(feature = "parallel")]
+pub fn compute_histogram_parallel(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<Vec<u32>, ProcessingError> {
+    use crate::pipeline::PARALLEL_BAND_ROWS;
+    use rayon::prelude::*;
+
+    validate_rgba_input(pixels, width, height)?;
+
+    let band_len = width as usize * 4 * PARALLEL_BAND_ROWS;
+    let histogram = pixels
+        .par_chunks(band_len)
+        .map(|band| {
+            let mut bins = vec![0_u32; 768];
+            for chunk in band.chunks_exact(4) {
+                bins[chunk[0] as usize] += 1;
+                bins[256 + chunk[1] as usize] += 1;
+                bins[512 + chunk[2] as usize] += 1;
+            }
+            bins
+        })
+        .reduce(
+            || vec![0_u32; 768],
+            |mut total, bins| {
+                for (sum, count) in total.iter_mut().zip(bins) {
+                    *sum += count;
+                }
+                total
+            },
+        );
+
+    Ok(histogram)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +143,18 @@ mod tests {
 
         assert_eq!(h1, h2);
     }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_histogram_matches_serial() {
+        let (width, height) = (37_u32, 150_u32);
+        let pixels: Vec<u8> = (0..width * height * 4)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+
+        assert_eq!(
+            compute_histogram_parallel(&pixels, width, height).unwrap(),
+            compute_histogram_from_pixels(&pixels, width, height).unwrap()
+        );
+    }
 }
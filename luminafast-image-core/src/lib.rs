@@ -8,15 +8,75 @@
 //! - `ProcessingError`: explicit validation errors for dimensions and buffers.
 //! - `apply_filters`: deterministic RGBA filtering preserving alpha channel.
 //! - `compute_histogram_from_pixels`: RGB histogram over RGBA buffer (768 bins).
+//! - `parallel` feature: `ImagePipeline::execute_parallel` and
+//!   `compute_histogram_parallel` split row bands across the rayon pool with
+//!   output identical to the serial path; pipeline steps must then be `Sync`.
+//! - `EditRecipe`: serde-serializable edit recipe pinned to a process version,
+//!   with schema migrations from the legacy loose JSON patch.
+//! - `CompiledFilters`: LUT compile stage used by `apply_filters` for the
+//!   global filter chain.
+//! - `PipelineCache`: memory-bounded memoization of intermediate pipeline
+//!   buffers used by `ImagePipeline::execute_cached`.
+//! - `render_region`: renders a source viewport at a requested scale, with
+//!   neighbourhood filters (`SharpenStep`, `NoiseReductionStep`, built from
+//!   the recipe's `sharpening` / `noiseReduction`) scaling their radii to the
+//!   render scale.
+//! - `resize_rgba8`: linear-light separable resampling (box, Mitchell,
+//!   Lanczos3) shared by preview pyramid generation and export sizing.
+//! - `RetouchSpot` / `apply_retouch`: circular and brushed heal/clone spots,
+//!   replayed from the edit recipe in WASM preview and export alike.
+//! - `EditRecipe::render_rgba16` / `resize_rgba16`: the same recipe and
+//!   resampling on RGBA16 buffers, for 16-bit exports of linear RAW data.
+//! - `merge_exposures`: translation-aligned, exposure-weighted merge of
+//!   bracketed `LinearImage` frames into one linear HDR radiance image.
+//! - `stitch_panorama`: feature-matched, homography-registered stitching of
+//!   overlapping frames onto a cylindrical or spherical surface.
+//! - `compare_rgba8`: PSNR, SSIM and mean/max CIEDE2000 between two renders,
+//!   with an optional difference heatmap, used by preview/export parity tests.
 
+pub mod cache;
+pub mod compare;
 pub mod errors;
 pub mod filters;
+pub mod hdr;
 pub mod histogram;
+pub mod lut;
+pub mod neighbourhood;
+pub mod panorama;
 pub mod pipeline;
 pub mod raw_decoder;
+pub mod recipe;
+pub mod region;
+pub mod resample;
+pub mod retouch;
+pub mod rgba16;
 
+pub use cache::{PipelineCache, DEFAULT_CACHE_BUDGET_BYTES};
+pub use compare::{
+    compare_rgba8, delta_e_2000, psnr_rgba8, srgb8_to_lab, ssim_rgba8, ComparisonOptions,
+    ImageComparison, DEFAULT_HEATMAP_MAX_DELTA_E,
+};
 pub use errors::ProcessingError;
 pub use filters::{apply_filters, PixelFilters};
+pub use hdr::{
+    estimate_translation, merge_exposures, relative_exposure, HdrFrame, HdrMergeOutput,
+    DEFAULT_MAX_ALIGNMENT_SHIFT,
+};
 pub use histogram::compute_histogram_from_pixels;
-pub use pipeline::{ImagePipeline, ImagePipelineStep};
+#[cfg(feature = "parallel")]
+pub use histogram::compute_histogram_parallel;
+pub use lut::{CompiledFilters, LutStrategy};
+pub use neighbourhood::{NoiseReductionStep, SharpenStep};
+pub use panorama::{
+    stitch_panorama, Homography, PanoramaOptions, PanoramaOutput, PanoramaProjection,
+    DEFAULT_MAX_PANORAMA_PIXELS, DEFAULT_WORKING_LONG_EDGE,
+};
+pub use pipeline::{CachedExecution, ImagePipeline, ImagePipelineStep};
 pub use raw_decoder::{LinearImage, RawDecoder};
+pub use recipe::{EditRecipe, CURRENT_PROCESS_VERSION};
+pub use region::{render_region, RenderedRegion, Viewport};
+pub use resample::{long_edge_dimensions, resize_rgba16, resize_rgba8, ResampleFilter};
+pub use retouch::{
+    apply_retouch, apply_retouch_rgba16, RetouchMode, RetouchPoint, RetouchSpot, RetouchStep,
+};
+pub use rgba16::{apply_filters_rgba16, decode_raw_to_rgba16, linear_image_to_rgba16};
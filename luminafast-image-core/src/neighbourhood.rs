@@ -0,0 +1,338 @@
+//! Neighbourhood filters (sharpening, noise reduction).
+//!
+//! Unlike the global filter chain these steps read the pixels around each
+//! output pixel. Their radii are expressed in source pixels and are scaled by
+//! the render scale in `apply_at_scale`, so a preview rendered at 25% looks
+//! like a downscaled version of the full-resolution render rather than an
+//! over-sharpened one.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::errors::ProcessingError;
+use crate::pipeline::ImagePipelineStep;
+use crate::rgba16::{validate_rgba16_input, Sample};
+
+/// Largest accepted radius, in source pixels.
+pub const MAX_NEIGHBOURHOOD_RADIUS: f32 = 50.0;
+/// Below this effective radius the blur is a no-op at the rendered scale.
+const MIN_EFFECTIVE_RADIUS: f32 = 0.1;
+/// Gaussian kernels are truncated at this many standard deviations.
+const KERNEL_EXTENT_SIGMAS: f32 = 3.0;
+
+/// Unsharp mask: `pixel + amount * (pixel - gaussian_blur(pixel, radius))`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharpenStep {
+    amount: f32,
+    radius: f32,
+}
+
+impl SharpenStep {
+    /// `amount` in [0.0, 5.0], `radius` (gaussian sigma) in source pixels.
+    pub fn new(amount: f32, radius: f32) -> Result<Self, ProcessingError> {
+        validate_range("sharpen.amount", amount, 0.0, 5.0)?;
+        validate_range("sharpen.radius", radius, 0.0, MAX_NEIGHBOURHOOD_RADIUS)?;
+        Ok(Self { amount, radius })
+    }
+
+    /// `apply` on an RGBA16 buffer, at full resolution.
+    pub fn apply_rgba16(
+        &self,
+        pixels: &mut [u16],
+        width: u32,
+        height: u32,
+    ) -> Result<(), ProcessingError> {
+        validate_rgba16_input(pixels, width, height)?;
+        self.sharpen(pixels, width, height, 1.0);
+        Ok(())
+    }
+
+    fn sharpen<S: Sample>(&self, pixels: &mut [S], width: u32, height: u32, scale: f32) {
+        let Some(blurred) = gaussian_blur_rgb(pixels, width, height, self.radius * scale) else {
+            return;
+        };
+
+        for (chunk, blur) in pixels.chunks_exact_mut(4).zip(blurred.chunks_exact(3)) {
+            for channel in 0..3 {
+                let original = chunk[channel].to_f32();
+                let sharpened = original + self.amount * (original - blur[channel]);
+                chunk[channel] = S::from_f32(sharpened);
+            }
+        }
+    }
+}
+
+impl ImagePipelineStep for SharpenStep {
+    fn apply(&self, pixels: &mut [u8], width: u32, height: u32) -> Result<(), ProcessingError> {
+        self.apply_at_scale(pixels, width, height, 1.0)
+    }
+
+    fn apply_at_scale(
+        &self,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(), ProcessingError> {
+        self.sharpen(pixels, width, height, scale);
+        Ok(())
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        Some(step_cache_key("sharpen", self.amount, self.radius))
+    }
+
+    fn neighbourhood_radius(&self) -> f32 {
+        self.radius * KERNEL_EXTENT_SIGMAS
+    }
+}
+
+/// Blends each pixel towards its gaussian-blurred neighbourhood.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseReductionStep {
+    strength: f32,
+    radius: f32,
+}
+
+impl NoiseReductionStep {
+    /// `strength` in [0.0, 1.0], `radius` (gaussian sigma) in source pixels.
+    pub fn new(strength: f32, radius: f32) -> Result<Self, ProcessingError> {
+        validate_range("noise_reduction.strength", strength, 0.0, 1.0)?;
+        validate_range(
+            "noise_reduction.radius",
+            radius,
+            0.0,
+            MAX_NEIGHBOURHOOD_RADIUS,
+        )?;
+        Ok(Self { strength, radius })
+    }
+
+    /// `apply` on an RGBA16 buffer, at full resolution.
+    pub fn apply_rgba16(
+        &self,
+        pixels: &mut [u16],
+        width: u32,
+        height: u32,
+    ) -> Result<(), ProcessingError> {
+        validate_rgba16_input(pixels, width, height)?;
+        self.smooth(pixels, width, height, 1.0);
+        Ok(())
+    }
+
+    fn smooth<S: Sample>(&self, pixels: &mut [S], width: u32, height: u32, scale: f32) {
+        let Some(blurred) = gaussian_blur_rgb(pixels, width, height, self.radius * scale) else {
+            return;
+        };
+
+        for (chunk, blur) in pixels.chunks_exact_mut(4).zip(blurred.chunks_exact(3)) {
+            for channel in 0..3 {
+                let original = chunk[channel].to_f32();
+                let smoothed = original + self.strength * (blur[channel] - original);
+                chunk[channel] = S::from_f32(smoothed);
+            }
+        }
+    }
+}
+
+impl ImagePipelineStep for NoiseReductionStep {
+    fn apply(&self, pixels: &mut [u8], width: u32, height: u32) -> Result<(), ProcessingError> {
+        self.apply_at_scale(pixels, width, height, 1.0)
+    }
+
+    fn apply_at_scale(
+        &self,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(), ProcessingError> {
+        self.smooth(pixels, width, height, scale);
+        Ok(())
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        Some(step_cache_key(
+            "noise_reduction",
+            self.strength,
+            self.radius,
+        ))
+    }
+
+    fn neighbourhood_radius(&self) -> f32 {
+        self.radius * KERNEL_EXTENT_SIGMAS
+    }
+}
+
+fn validate_range(field: &str, value: f32, min: f32, max: f32) -> Result<(), ProcessingError> {
+    if !value.is_finite() || value < min || value > max {
+        return Err(ProcessingError::InvalidFilterValue {
+            field: field.to_string(),
+            value,
+        });
+    }
+
+    Ok(())
+}
+
+fn step_cache_key(tag: &str, first: f32, second: f32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    tag.hash(&mut hasher);
+    first.to_bits().hash(&mut hasher);
+    second.to_bits().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let half = (sigma * KERNEL_EXTENT_SIGMAS).ceil() as i32;
+    let denominator = 2.0 * sigma * sigma;
+    let mut kernel: Vec<f32> = (-half..=half)
+        .map(|offset| (-((offset * offset) as f32) / denominator).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= sum);
+    kernel
+}
+
+/// Separable gaussian blur of the RGB channels with clamped edges.
+///
+/// Returns `None` when `sigma` is too small to have a visible effect.
+fn gaussian_blur_rgb<S: Sample>(
+    pixels: &[S],
+    width: u32,
+    height: u32,
+    sigma: f32,
+) -> Option<Vec<f32>> {
+    if sigma < MIN_EFFECTIVE_RADIUS {
+        return None;
+    }
+
+    let width = width as usize;
+    let height = height as usize;
+    let kernel = gaussian_kernel(sigma);
+    let half = (kernel.len() / 2) as isize;
+
+    let mut horizontal = vec![0.0_f32; width * height * 3];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0_f32; 3];
+            for (tap, weight) in kernel.iter().enumerate() {
+                let sx = (x as isize + tap as isize - half).clamp(0, width as isize - 1) as usize;
+                let source = (y * width + sx) * 4;
+                for channel in 0..3 {
+                    sum[channel] += pixels[source + channel].to_f32() * weight;
+                }
+            }
+            horizontal[(y * width + x) * 3..][..3].copy_from_slice(&sum);
+        }
+    }
+
+    let mut blurred = vec![0.0_f32; width * height * 3];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0_f32; 3];
+            for (tap, weight) in kernel.iter().enumerate() {
+                let sy = (y as isize + tap as isize - half).clamp(0, height as isize - 1) as usize;
+                let source = (sy * width + x) * 3;
+                for channel in 0..3 {
+                    sum[channel] += horizontal[source + channel] * weight;
+                }
+            }
+            blurred[(y * width + x) * 3..][..3].copy_from_slice(&sum);
+        }
+    }
+
+    Some(blurred)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let value = if (x + y) % 2 == 0 { 80 } else { 170 };
+                pixels.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn sharpen_increases_local_contrast_and_preserves_alpha() {
+        let mut pixels = checkerboard(6, 6);
+        SharpenStep::new(1.0, 1.0)
+            .unwrap()
+            .apply(&mut pixels, 6, 6)
+            .unwrap();
+
+        assert!(pixels[0] < 80);
+        assert!(pixels[4] > 170);
+        assert!(pixels.chunks_exact(4).all(|px| px[3] == 255));
+    }
+
+    #[test]
+    fn noise_reduction_pulls_pixels_towards_neighbourhood_mean() {
+        let mut pixels = checkerboard(6, 6);
+        NoiseReductionStep::new(1.0, 1.5)
+            .unwrap()
+            .apply(&mut pixels, 6, 6)
+            .unwrap();
+
+        assert!(pixels
+            .chunks_exact(4)
+            .all(|px| (110..=140).contains(&px[0])));
+    }
+
+    #[test]
+    fn apply_at_scale_uses_scaled_radius() {
+        let step = SharpenStep::new(0.8, 2.0).unwrap();
+        let half_radius = SharpenStep::new(0.8, 1.0).unwrap();
+
+        let mut scaled = checkerboard(8, 8);
+        step.apply_at_scale(&mut scaled, 8, 8, 0.5).unwrap();
+        let mut expected = checkerboard(8, 8);
+        half_radius.apply(&mut expected, 8, 8).unwrap();
+
+        assert_eq!(scaled, expected);
+    }
+
+    #[test]
+    fn tiny_effective_radius_is_a_noop() {
+        let mut pixels = checkerboard(4, 4);
+        SharpenStep::new(2.0, 1.0)
+            .unwrap()
+            .apply_at_scale(&mut pixels, 4, 4, 0.05)
+            .unwrap();
+
+        assert_eq!(pixels, checkerboard(4, 4));
+    }
+
+    #[test]
+    fn rgba16_passes_track_the_8_bit_steps() {
+        let pixels8 = checkerboard(6, 6);
+        let pixels16: Vec<u16> = pixels8.iter().map(|&v| v as u16 * 257).collect();
+        let sharpen = SharpenStep::new(1.0, 1.0).unwrap();
+        let denoise = NoiseReductionStep::new(0.6, 1.5).unwrap();
+
+        let mut narrow = pixels8.clone();
+        sharpen.apply(&mut narrow, 6, 6).unwrap();
+        denoise.apply(&mut narrow, 6, 6).unwrap();
+        let mut wide = pixels16.clone();
+        sharpen.apply_rgba16(&mut wide, 6, 6).unwrap();
+        denoise.apply_rgba16(&mut wide, 6, 6).unwrap();
+
+        for (&n, &w) in narrow.iter().zip(&wide) {
+            assert!((n as f32 - w as f32 / 257.0).abs() <= 1.0, "{n} vs {w}");
+        }
+    }
+
+    #[test]
+    fn constructors_reject_out_of_range_parameters() {
+        assert!(SharpenStep::new(-0.1, 1.0).is_err());
+        assert!(SharpenStep::new(1.0, f32::NAN).is_err());
+        assert!(NoiseReductionStep::new(1.5, 1.0).is_err());
+        assert!(NoiseReductionStep::new(0.5, MAX_NEIGHBOURHOOD_RADIUS + 1.0).is_err());
+    }
+}
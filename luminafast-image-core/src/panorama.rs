@@ -0,0 +1,1134 @@
+//! Panorama stitching of overlapping frames.
+//!
+//! Everything runs on the CPU in three stages:
+//! 1. Registration at a reduced working size: Harris corners, normalised
+//!    patch descriptors, ratio-test matching and RANSAC homographies for
+//!    every frame pair. Frames are chained to the best-connected frame along
+//!    a maximum spanning tree of inlier counts.
+//! 2. Projection: the focal length is estimated from the homographies (or
+//!    falls back to the long side of the reference frame) and each output
+//!    pixel is mapped through a cylinder or sphere centred on the reference
+//!    camera back into every frame.
+//! 3. Blending: samples are feathered by their distance to the frame edges.
+
+use crate::errors::ProcessingError;
+use crate::raw_decoder::LinearImage;
+
+/// Registration runs on frames downscaled to this long side.
+pub const DEFAULT_WORKING_LONG_EDGE: u32 = 800;
+/// Output larger than this is rendered at a reduced scale.
+pub const DEFAULT_MAX_PANORAMA_PIXELS: u64 = 64_000_000;
+
+const MAX_FEATURES: usize = 600;
+const FEATURE_MIN_DISTANCE: f32 = 6.0;
+const HARRIS_K: f32 = 0.04;
+/// Half-size of the descriptor window, in working pixels.
+const DESCRIPTOR_RADIUS: i32 = 8;
+const DESCRIPTOR_GRID: i32 = 8;
+const DESCRIPTOR_LEN: usize = (DESCRIPTOR_GRID * DESCRIPTOR_GRID) as usize;
+/// Lowe ratio test on squared distances (0.75²).
+const MATCH_RATIO_SQUARED: f32 = 0.5625;
+const RANSAC_ITERATIONS: usize = 2000;
+const RANSAC_THRESHOLD: f64 = 3.0;
+const MIN_INLIERS: usize = 12;
+const RANSAC_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Row-major 3x3 homography mapping frame pixels onto reference pixels.
+pub type Homography = [f64; 9];
+
+/// Matched point pair `(frame point, target point)`.
+type Correspondence = ((f64, f64), (f64, f64));
+
+/// Surface the frames are projected onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanoramaProjection {
+    /// Straight verticals; suited to horizontal sweeps.
+    Cylindrical,
+    /// Suited to multi-row or wide vertical coverage.
+    Spherical,
+}
+
+impl PanoramaProjection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cylindrical => "cylindrical",
+            Self::Spherical => "spherical",
+        }
+    }
+}
+
+impl TryFrom<&str> for PanoramaProjection {
+    type Error = ProcessingError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "cylindrical" => Ok(Self::Cylindrical),
+            "spherical" => Ok(Self::Spherical),
+            other => Err(ProcessingError::InvalidMerge {
+                message: format!("unsupported panorama projection: {other}"),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanoramaOptions {
+    pub projection: PanoramaProjection,
+    pub working_long_edge: u32,
+    pub max_output_pixels: u64,
+}
+
+impl Default for PanoramaOptions {
+    fn default() -> Self {
+        Self {
+            projection: PanoramaProjection::Cylindrical,
+            working_long_edge: DEFAULT_WORKING_LONG_EDGE,
+            max_output_pixels: DEFAULT_MAX_PANORAMA_PIXELS,
+        }
+    }
+}
+
+/// Output of `stitch_panorama`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanoramaOutput {
+    /// Blended panorama; pixels covered by no frame are black.
+    pub image: LinearImage,
+    /// Frame whose camera the projection is centred on.
+    pub reference_index: usize,
+    /// Per-frame homography onto the reference frame, in full-size pixels.
+    pub homographies: Vec<Homography>,
+    /// Focal length used for the projection, in reference pixels.
+    pub focal_length: f64,
+}
+
+/// Registers, projects and blends `frames` into one panorama.
+pub fn stitch_panorama(
+    frames: &[LinearImage],
+    options: &PanoramaOptions,
+) -> Result<PanoramaOutput, ProcessingError> {
+    if frames.len() < 2 {
+        return Err(ProcessingError::InvalidMerge {
+            message: format!("at least 2 frames required, got {}", frames.len()),
+        });
+    }
+    if options.working_long_edge < 2 * DESCRIPTOR_RADIUS as u32 || options.max_output_pixels == 0 {
+        return Err(ProcessingError::InvalidMerge {
+            message: "working size and output budget must be positive".to_string(),
+        });
+    }
+
+    let working: Vec<WorkingFrame> = frames
+        .iter()
+        .map(|frame| WorkingFrame::new(frame, options.working_long_edge))
+        .collect();
+
+    let mut pairs = Vec::new();
+    for i in 0..working.len() {
+        for j in i + 1..working.len() {
+            if let Some((homography, inliers)) = register_pair(&working[i], &working[j]) {
+                // `homography` maps j onto i at working scale.
+                let to_i = rescale_homography(&homography, working[j].scale, working[i].scale);
+                pairs.push((i, j, to_i, inliers));
+            }
+        }
+    }
+
+    let (reference_index, homographies) = chain_to_reference(frames.len(), &pairs)?;
+    let focal_length = estimate_focal_length(frames, reference_index, &homographies)
+        .unwrap_or_else(|| {
+            frames[reference_index]
+                .width
+                .max(frames[reference_index].height) as f64
+        });
+
+    let image = render_projection(
+        frames,
+        reference_index,
+        &homographies,
+        focal_length,
+        options,
+    )?;
+
+    Ok(PanoramaOutput {
+        image,
+        reference_index,
+        homographies,
+        focal_length,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Registration
+// ---------------------------------------------------------------------------
+
+/// Grey frame at working scale with its features.
+struct WorkingFrame {
+    /// Working pixels per full-size pixel.
+    scale: f64,
+    features: Vec<Feature>,
+}
+
+struct Feature {
+    x: f32,
+    y: f32,
+    descriptor: [f32; DESCRIPTOR_LEN],
+}
+
+impl WorkingFrame {
+    fn new(frame: &LinearImage, working_long_edge: u32) -> Self {
+        let long_side = frame.width.max(frame.height);
+        let scale = (working_long_edge as f64 / long_side as f64).min(1.0);
+        let width = ((frame.width as f64 * scale).round() as u32).max(1);
+        let height = ((frame.height as f64 * scale).round() as u32).max(1);
+
+        let grey = downsample_grey(frame, width, height);
+        let features = detect_features(&grey, width, height);
+
+        Self {
+            scale: width as f64 / frame.width as f64,
+            features,
+        }
+    }
+}
+
+/// Gamma-encoded luminance, area-averaged to `width`x`height`.
+fn downsample_grey(frame: &LinearImage, width: u32, height: u32) -> Vec<f32> {
+    let source_width = frame.width as usize;
+    let source_height = frame.height as usize;
+    let mut grey = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height as usize {
+        let y0 = y * source_height / height as usize;
+        let y1 = ((y + 1) * source_height / height as usize).max(y0 + 1);
+        for x in 0..width as usize {
+            let x0 = x * source_width / width as usize;
+            let x1 = ((x + 1) * source_width / width as usize).max(x0 + 1);
+            let mut sum = 0.0;
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let rgb = &frame.pixels_rgb_f32[(sy * source_width + sx) * 3..][..3];
+                    sum += 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+                }
+            }
+            let mean = sum / ((y1 - y0) * (x1 - x0)) as f32;
+            grey.push(mean.clamp(0.0, 1.0).powf(1.0 / 2.2));
+        }
+    }
+
+    grey
+}
+
+fn box_blur_3x3(values: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut output = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            for sy in y.saturating_sub(1)..(y + 2).min(height) {
+                for sx in x.saturating_sub(1)..(x + 2).min(width) {
+                    sum += values[sy * width + sx];
+                    count += 1.0;
+                }
+            }
+            output[y * width + x] = sum / count;
+        }
+    }
+    output
+}
+
+/// Harris corners with greedy spatial suppression, strongest first.
+fn detect_features(grey: &[f32], width: u32, height: u32) -> Vec<Feature> {
+    let width = width as usize;
+    let height = height as usize;
+    let border = DESCRIPTOR_RADIUS as usize + 1;
+    if width <= 2 * border || height <= 2 * border {
+        return Vec::new();
+    }
+
+    let mut ixx = vec![0.0; width * height];
+    let mut iyy = vec![0.0; width * height];
+    let mut ixy = vec![0.0; width * height];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let at = |dx: isize, dy: isize| {
+                grey[(y as isize + dy) as usize * width + (x as isize + dx) as usize]
+            };
+            let gx = (at(1, -1) + 2.0 * at(1, 0) + at(1, 1))
+                - (at(-1, -1) + 2.0 * at(-1, 0) + at(-1, 1));
+            let gy = (at(-1, 1) + 2.0 * at(0, 1) + at(1, 1))
+                - (at(-1, -1) + 2.0 * at(0, -1) + at(1, -1));
+            let index = y * width + x;
+            ixx[index] = gx * gx;
+            iyy[index] = gy * gy;
+            ixy[index] = gx * gy;
+        }
+    }
+    let ixx = box_blur_3x3(&box_blur_3x3(&ixx, width, height), width, height);
+    let iyy = box_blur_3x3(&box_blur_3x3(&iyy, width, height), width, height);
+    let ixy = box_blur_3x3(&box_blur_3x3(&ixy, width, height), width, height);
+
+    let mut candidates = Vec::new();
+    let response = |index: usize| {
+        let trace = ixx[index] + iyy[index];
+        ixx[index] * iyy[index] - ixy[index] * ixy[index] - HARRIS_K * trace * trace
+    };
+    for y in border..height - border {
+        for x in border..width - border {
+            let index = y * width + x;
+            let value = response(index);
+            if value <= 1e-6 {
+                continue;
+            }
+            let is_peak = (y - 1..=y + 1).all(|ny| {
+                (x - 1..=x + 1).all(|nx| (nx, ny) == (x, y) || response(ny * width + nx) < value)
+            });
+            if is_peak {
+                candidates.push((value, x, y));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let blurred = box_blur_3x3(grey, width, height);
+    let mut features: Vec<Feature> = Vec::new();
+    for (_, x, y) in candidates {
+        if features.len() >= MAX_FEATURES {
+            break;
+        }
+        let (fx, fy) = (x as f32, y as f32);
+        let too_close = features.iter().any(|feature| {
+            let dx = feature.x - fx;
+            let dy = feature.y - fy;
+            dx * dx + dy * dy < FEATURE_MIN_DISTANCE * FEATURE_MIN_DISTANCE
+        });
+        if too_close {
+            continue;
+        }
+        if let Some(descriptor) = describe(&blurred, width, x, y) {
+            features.push(Feature {
+                x: fx,
+                y: fy,
+                descriptor,
+            });
+        }
+    }
+
+    features
+}
+
+/// Bias/gain normalised grid of samples around `(x, y)`.
+fn describe(blurred: &[f32], width: usize, x: usize, y: usize) -> Option<[f32; DESCRIPTOR_LEN]> {
+    let step = 2 * DESCRIPTOR_RADIUS / DESCRIPTOR_GRID;
+    let mut descriptor = [0.0_f32; DESCRIPTOR_LEN];
+    for gy in 0..DESCRIPTOR_GRID {
+        for gx in 0..DESCRIPTOR_GRID {
+            let sx = (x as i32 - DESCRIPTOR_RADIUS + gx * step + step / 2) as usize;
+            let sy = (y as i32 - DESCRIPTOR_RADIUS + gy * step + step / 2) as usize;
+            descriptor[(gy * DESCRIPTOR_GRID + gx) as usize] = blurred[sy * width + sx];
+        }
+    }
+
+    let mean = descriptor.iter().sum::<f32>() / DESCRIPTOR_LEN as f32;
+    let variance = descriptor
+        .iter()
+        .map(|value| (value - mean) * (value - mean))
+        .sum::<f32>()
+        / DESCRIPTOR_LEN as f32;
+    if variance < 1e-6 {
+        return None;
+    }
+    let deviation = variance.sqrt();
+    descriptor
+        .iter_mut()
+        .for_each(|value| *value = (*value - mean) / deviation);
+    Some(descriptor)
+}
+
+fn descriptor_distance(a: &[f32; DESCRIPTOR_LEN], b: &[f32; DESCRIPTOR_LEN]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Best match of each feature of `from` in `to`, passing the ratio test.
+fn ratio_matches(from: &[Feature], to: &[Feature]) -> Vec<Option<usize>> {
+    from.iter()
+        .map(|feature| {
+            let mut best = (f32::INFINITY, usize::MAX);
+            let mut second = f32::INFINITY;
+            for (index, candidate) in to.iter().enumerate() {
+                let distance = descriptor_distance(&feature.descriptor, &candidate.descriptor);
+                if distance < best.0 {
+                    second = best.0;
+                    best = (distance, index);
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+            (best.1 != usize::MAX && best.0 < MATCH_RATIO_SQUARED * second).then_some(best.1)
+        })
+        .collect()
+}
+
+/// Homography mapping `b` onto `a` (working pixels) and its inlier count.
+fn register_pair(a: &WorkingFrame, b: &WorkingFrame) -> Option<(Homography, usize)> {
+    let forward = ratio_matches(&b.features, &a.features);
+    let backward = ratio_matches(&a.features, &b.features);
+    let correspondences: Vec<Correspondence> = forward
+        .iter()
+        .enumerate()
+        .filter_map(|(index_b, matched)| {
+            let index_a = (*matched)?;
+            (backward[index_a] == Some(index_b)).then(|| {
+                let from = &b.features[index_b];
+                let to = &a.features[index_a];
+                ((from.x as f64, from.y as f64), (to.x as f64, to.y as f64))
+            })
+        })
+        .collect();
+
+    let (homography, inliers) = ransac_homography(&correspondences)?;
+    // Brown & Lowe acceptance test: inliers must explain most matches.
+    if inliers < MIN_INLIERS || (inliers as f64) < 8.0 + 0.3 * correspondences.len() as f64 {
+        return None;
+    }
+    Some((homography, inliers))
+}
+
+/// Deterministic xorshift generator so stitching is reproducible.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_index(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+fn ransac_homography(correspondences: &[Correspondence]) -> Option<(Homography, usize)> {
+    if correspondences.len() < MIN_INLIERS {
+        return None;
+    }
+
+    let mut rng = XorShift(RANSAC_SEED);
+    let mut best_inliers: Vec<usize> = Vec::new();
+    for _ in 0..RANSAC_ITERATIONS {
+        let mut sample = [0_usize; 4];
+        for slot in 0..4 {
+            sample[slot] = loop {
+                let candidate = rng.next_index(correspondences.len());
+                if !sample[..slot].contains(&candidate) {
+                    break candidate;
+                }
+            };
+        }
+        let points: Vec<_> = sample.iter().map(|&index| correspondences[index]).collect();
+        let Some(candidate) = fit_homography(&points) else {
+            continue;
+        };
+        let inliers = inlier_indices(&candidate, correspondences);
+        if inliers.len() > best_inliers.len() {
+            best_inliers = inliers;
+        }
+    }
+
+    if best_inliers.len() < 4 {
+        return None;
+    }
+    let points: Vec<_> = best_inliers
+        .iter()
+        .map(|&index| correspondences[index])
+        .collect();
+    let refined = fit_homography(&points)?;
+    let inliers = inlier_indices(&refined, correspondences).len();
+    Some((refined, inliers))
+}
+
+fn inlier_indices(homography: &Homography, correspondences: &[Correspondence]) -> Vec<usize> {
+    correspondences
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(from, to))| {
+            let (x, y) = apply_homography(homography, from.0, from.1)?;
+            let error = (x - to.0).powi(2) + (y - to.1).powi(2);
+            (error < RANSAC_THRESHOLD * RANSAC_THRESHOLD).then_some(index)
+        })
+        .collect()
+}
+
+/// Normalising similarity (Hartley): centroid at origin, mean distance √2.
+fn normalising_transform(points: impl Iterator<Item = (f64, f64)> + Clone) -> Homography {
+    let count = points.clone().count() as f64;
+    let (sum_x, sum_y) = points
+        .clone()
+        .fold((0.0, 0.0), |acc, (x, y)| (acc.0 + x, acc.1 + y));
+    let (cx, cy) = (sum_x / count, sum_y / count);
+    let mean_distance = points
+        .map(|(x, y)| ((x - cx).powi(2) + (y - cy).powi(2)).sqrt())
+        .sum::<f64>()
+        / count;
+    let scale = if mean_distance > 1e-12 {
+        std::f64::consts::SQRT_2 / mean_distance
+    } else {
+        1.0
+    };
+    [
+        scale,
+        0.0,
+        -scale * cx,
+        0.0,
+        scale,
+        -scale * cy,
+        0.0,
+        0.0,
+        1.0,
+    ]
+}
+
+/// Least-squares DLT (h33 = 1) over at least 4 correspondences.
+fn fit_homography(correspondences: &[Correspondence]) -> Option<Homography> {
+    if correspondences.len() < 4 {
+        return None;
+    }
+    let from_norm = normalising_transform(correspondences.iter().map(|c| c.0));
+    let to_norm = normalising_transform(correspondences.iter().map(|c| c.1));
+
+    let mut normal = [[0.0_f64; 8]; 8];
+    let mut rhs = [0.0_f64; 8];
+    for &(from, to) in correspondences {
+        let (x, y) = apply_homography(&from_norm, from.0, from.1)?;
+        let (u, v) = apply_homography(&to_norm, to.0, to.1)?;
+        let rows = [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v], v),
+        ];
+        for (row, target) in rows {
+            for i in 0..8 {
+                rhs[i] += row[i] * target;
+                for j in 0..8 {
+                    normal[i][j] += row[i] * row[j];
+                }
+            }
+        }
+    }
+
+    let h = solve_linear_system(normal, rhs)?;
+    let normalised = [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0];
+    let homography = multiply(&multiply(&invert(&to_norm)?, &normalised), &from_norm);
+    normalize_homography(homography)
+}
+
+fn solve_linear_system(mut a: [[f64; 8]; 8], mut b: [f64; 8]) -> Option<[f64; 8]> {
+    for column in 0..8 {
+        let pivot =
+            (column..8).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        for row in column + 1..8 {
+            let factor = a[row][column] / a[column][column];
+            let pivot_row = a[column];
+            for (value, pivot_value) in a[row].iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+
+    let mut x = [0.0_f64; 8];
+    for row in (0..8).rev() {
+        let sum: f64 = (row + 1..8).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x.iter().all(|value| value.is_finite()).then_some(x)
+}
+
+fn multiply(a: &Homography, b: &Homography) -> Homography {
+    let mut output = [0.0; 9];
+    for row in 0..3 {
+        for column in 0..3 {
+            output[row * 3 + column] = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + column]).sum();
+        }
+    }
+    output
+}
+
+fn invert(m: &Homography) -> Option<Homography> {
+    let cofactor = [
+        m[4] * m[8] - m[5] * m[7],
+        m[2] * m[7] - m[1] * m[8],
+        m[1] * m[5] - m[2] * m[4],
+        m[5] * m[6] - m[3] * m[8],
+        m[0] * m[8] - m[2] * m[6],
+        m[2] * m[3] - m[0] * m[5],
+        m[3] * m[7] - m[4] * m[6],
+        m[1] * m[6] - m[0] * m[7],
+        m[0] * m[4] - m[1] * m[3],
+    ];
+    let determinant = m[0] * cofactor[0] + m[1] * cofactor[3] + m[2] * cofactor[6];
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    Some(cofactor.map(|value| value / determinant))
+}
+
+fn normalize_homography(h: Homography) -> Option<Homography> {
+    (h[8].abs() > 1e-12 && h.iter().all(|value| value.is_finite())).then(|| h.map(|v| v / h[8]))
+}
+
+fn apply_homography(h: &Homography, x: f64, y: f64) -> Option<(f64, f64)> {
+    let w = h[6] * x + h[7] * y + h[8];
+    if w.abs() < 1e-12 {
+        return None;
+    }
+    Some((
+        (h[0] * x + h[1] * y + h[2]) / w,
+        (h[3] * x + h[4] * y + h[5]) / w,
+    ))
+}
+
+/// Converts a working-scale homography (`from` -> `to`) to full-size pixels.
+fn rescale_homography(h: &Homography, from_scale: f64, to_scale: f64) -> Homography {
+    let from = [from_scale, 0.0, 0.0, 0.0, from_scale, 0.0, 0.0, 0.0, 1.0];
+    let to_inverse = [
+        1.0 / to_scale,
+        0.0,
+        0.0,
+        0.0,
+        1.0 / to_scale,
+        0.0,
+        0.0,
+        0.0,
+        1.0,
+    ];
+    multiply(&multiply(&to_inverse, h), &from)
+}
+
+/// Picks the best-connected frame as reference and chains every frame to it
+/// along the maximum spanning tree of pairwise inlier counts.
+fn chain_to_reference(
+    frame_count: usize,
+    pairs: &[(usize, usize, Homography, usize)],
+) -> Result<(usize, Vec<Homography>), ProcessingError> {
+    let mut connectivity = vec![0_usize; frame_count];
+    for &(i, j, _, inliers) in pairs {
+        connectivity[i] += inliers;
+        connectivity[j] += inliers;
+    }
+    let reference = (0..frame_count)
+        .max_by_key(|&index| (connectivity[index], std::cmp::Reverse(index)))
+        .unwrap_or(0);
+
+    const IDENTITY: Homography = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    let mut to_reference: Vec<Option<Homography>> = vec![None; frame_count];
+    to_reference[reference] = Some(IDENTITY);
+
+    loop {
+        // Strongest edge from the tree to a frame outside it (Prim).
+        let mut best: Option<(usize, Homography, usize)> = None;
+        for &(i, j, ref j_to_i, inliers) in pairs {
+            let edge = match (to_reference[i], to_reference[j]) {
+                (Some(i_to_ref), None) => Some((j, multiply(&i_to_ref, j_to_i))),
+                (None, Some(j_to_ref)) => {
+                    invert(j_to_i).map(|i_to_j| (i, multiply(&j_to_ref, &i_to_j)))
+                }
+                _ => None,
+            };
+            if let Some((frame, homography)) = edge {
+                if best.map_or(true, |(_, _, count)| inliers > count) {
+                    best = Some((frame, homography, inliers));
+                }
+            }
+        }
+        match best {
+            Some((frame, homography, _)) => {
+                to_reference[frame] = Some(normalize_homography(homography).ok_or_else(|| {
+                    ProcessingError::InvalidMerge {
+                        message: format!("degenerate homography for frame {frame}"),
+                    }
+                })?)
+            }
+            None => break,
+        }
+    }
+
+    let homographies = to_reference
+        .iter()
+        .enumerate()
+        .map(|(index, homography)| {
+            homography.ok_or_else(|| ProcessingError::InvalidMerge {
+                message: format!("frame {index} does not overlap the other frames"),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((reference, homographies))
+}
+
+/// Focal length candidates of a centred homography (Szeliski & Shum), as
+/// implemented by OpenCV's `focalsFromHomography`.
+fn focals_from_homography(h: &Homography) -> (Option<f64>, Option<f64>) {
+    let pick = |d1: f64, d2: f64, v1: f64, v2: f64| {
+        let (v1, v2) = if v1 < v2 { (v2, v1) } else { (v1, v2) };
+        let value = if v1 > 0.0 && v2 > 0.0 {
+            if d1.abs() > d2.abs() {
+                v1
+            } else {
+                v2
+            }
+        } else if v1 > 0.0 {
+            v1
+        } else {
+            return None;
+        };
+        let focal = value.sqrt();
+        focal.is_finite().then_some(focal)
+    };
+
+    let d1 = h[6] * h[7];
+    let d2 = (h[7] - h[6]) * (h[7] + h[6]);
+    let f1 = pick(
+        d1,
+        d2,
+        -(h[0] * h[1] + h[3] * h[4]) / d1,
+        (h[0] * h[0] + h[3] * h[3] - h[1] * h[1] - h[4] * h[4]) / d2,
+    );
+
+    let d1 = h[0] * h[3] + h[1] * h[4];
+    let d2 = h[0] * h[0] + h[1] * h[1] - h[3] * h[3] - h[4] * h[4];
+    let f0 = pick(d1, d2, -h[2] * h[5] / d1, (h[5] * h[5] - h[2] * h[2]) / d2);
+
+    (f0, f1)
+}
+
+/// Median focal estimate over the non-reference frames, if any is usable.
+fn estimate_focal_length(
+    frames: &[LinearImage],
+    reference_index: usize,
+    homographies: &[Homography],
+) -> Option<f64> {
+    let centre = |frame: &LinearImage| (frame.width as f64 / 2.0, frame.height as f64 / 2.0);
+    let (rx, ry) = centre(&frames[reference_index]);
+    let to_reference_centre = [1.0, 0.0, -rx, 0.0, 1.0, -ry, 0.0, 0.0, 1.0];
+
+    let mut estimates: Vec<f64> = frames
+        .iter()
+        .zip(homographies)
+        .enumerate()
+        .filter(|(index, _)| *index != reference_index)
+        .filter_map(|(_, (frame, homography))| {
+            let (fx, fy) = centre(frame);
+            let from_frame_centre = [1.0, 0.0, fx, 0.0, 1.0, fy, 0.0, 0.0, 1.0];
+            let centred = normalize_homography(multiply(
+                &multiply(&to_reference_centre, homography),
+                &from_frame_centre,
+            ))?;
+            match focals_from_homography(&centred) {
+                (Some(f0), Some(f1)) => Some((f0 * f1).sqrt()),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let long_side = frames[reference_index]
+        .width
+        .max(frames[reference_index].height) as f64;
+    // Reject estimates outside a plausible 5°-170° horizontal field of view.
+    estimates.retain(|focal| *focal > long_side * 0.05 && *focal < long_side * 12.0);
+    if estimates.is_empty() {
+        return None;
+    }
+    estimates.sort_by(f64::total_cmp);
+    Some(estimates[estimates.len() / 2])
+}
+
+// ---------------------------------------------------------------------------
+// Projection and blending
+// ---------------------------------------------------------------------------
+
+/// Maps reference-plane points to surface angles and back.
+struct Surface {
+    projection: PanoramaProjection,
+    focal: f64,
+    cx: f64,
+    cy: f64,
+}
+
+impl Surface {
+    /// Reference pixel -> (horizontal angle, vertical coordinate), both in radians.
+    fn plane_to_surface(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = x - self.cx;
+        let dy = y - self.cy;
+        let theta = dx.atan2(self.focal);
+        let radius = (dx * dx + self.focal * self.focal).sqrt();
+        let vertical = match self.projection {
+            PanoramaProjection::Cylindrical => dy / radius,
+            PanoramaProjection::Spherical => dy.atan2(radius),
+        };
+        (theta, vertical)
+    }
+
+    /// Surface coordinates -> reference pixel, if the ray hits the image plane.
+    fn surface_to_plane(&self, theta: f64, vertical: f64) -> Option<(f64, f64)> {
+        let (x, y, z) = match self.projection {
+            PanoramaProjection::Cylindrical => (theta.sin(), vertical, theta.cos()),
+            PanoramaProjection::Spherical => (
+                theta.sin() * vertical.cos(),
+                vertical.sin(),
+                theta.cos() * vertical.cos(),
+            ),
+        };
+        if z <= 1e-6 {
+            return None;
+        }
+        Some((self.focal * x / z + self.cx, self.focal * y / z + self.cy))
+    }
+}
+
+/// Frame prepared for sampling: inverse homography and output bounds.
+struct ProjectedFrame<'a> {
+    image: &'a LinearImage,
+    from_reference: Homography,
+    /// Inclusive output pixel bounds `(min_u, min_v, max_u, max_v)`.
+    bounds: (usize, usize, usize, usize),
+}
+
+fn frame_outline(frame: &LinearImage) -> Vec<(f64, f64)> {
+    const STEPS: usize = 32;
+    let (w, h) = (frame.width as f64, frame.height as f64);
+    (0..=STEPS)
+        .flat_map(|step| {
+            let t = step as f64 / STEPS as f64;
+            [(t * w, 0.0), (t * w, h), (0.0, t * h), (w, t * h)]
+        })
+        .collect()
+}
+
+fn render_projection(
+    frames: &[LinearImage],
+    reference_index: usize,
+    homographies: &[Homography],
+    focal_length: f64,
+    options: &PanoramaOptions,
+) -> Result<LinearImage, ProcessingError> {
+    let reference = &frames[reference_index];
+    let surface = Surface {
+        projection: options.projection,
+        focal: focal_length,
+        cx: reference.width as f64 / 2.0,
+        cy: reference.height as f64 / 2.0,
+    };
+
+    let outlines: Vec<Vec<(f64, f64)>> = frames
+        .iter()
+        .zip(homographies)
+        .map(|(frame, homography)| {
+            frame_outline(frame)
+                .into_iter()
+                .filter_map(|(x, y)| apply_homography(homography, x, y))
+                .map(|(x, y)| surface.plane_to_surface(x, y))
+                .collect()
+        })
+        .collect();
+
+    let (mut min_theta, mut min_vertical) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_theta, mut max_vertical) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(theta, vertical) in outlines.iter().flatten() {
+        min_theta = min_theta.min(theta);
+        max_theta = max_theta.max(theta);
+        min_vertical = min_vertical.min(vertical);
+        max_vertical = max_vertical.max(vertical);
+    }
+    if !(min_theta.is_finite() && max_vertical.is_finite()) {
+        return Err(ProcessingError::InvalidMerge {
+            message: "frames do not project onto the panorama surface".to_string(),
+        });
+    }
+
+    // Output pixels per radian; full reference resolution unless over budget.
+    let mut pixels_per_radian = focal_length;
+    let full_pixels =
+        (max_theta - min_theta) * (max_vertical - min_vertical) * pixels_per_radian.powi(2);
+    if full_pixels > options.max_output_pixels as f64 {
+        pixels_per_radian *= (options.max_output_pixels as f64 / full_pixels).sqrt();
+    }
+    let width = (((max_theta - min_theta) * pixels_per_radian).ceil() as u32).max(1);
+    let height = (((max_vertical - min_vertical) * pixels_per_radian).ceil() as u32).max(1);
+
+    let to_output = |(theta, vertical): (f64, f64)| {
+        (
+            (theta - min_theta) * pixels_per_radian,
+            (vertical - min_vertical) * pixels_per_radian,
+        )
+    };
+    let projected: Vec<ProjectedFrame> = frames
+        .iter()
+        .zip(homographies)
+        .zip(&outlines)
+        .map(|((image, homography), outline)| {
+            let from_reference =
+                invert(homography).ok_or_else(|| ProcessingError::InvalidMerge {
+                    message: "degenerate frame homography".to_string(),
+                })?;
+            let (mut min_u, mut min_v) = (f64::INFINITY, f64::INFINITY);
+            let (mut max_u, mut max_v) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+            for &point in outline {
+                let (u, v) = to_output(point);
+                min_u = min_u.min(u);
+                min_v = min_v.min(v);
+                max_u = max_u.max(u);
+                max_v = max_v.max(v);
+            }
+            let clamp_u = |value: f64| (value.max(0.0) as usize).min(width as usize - 1);
+            let clamp_v = |value: f64| (value.max(0.0) as usize).min(height as usize - 1);
+            Ok(ProjectedFrame {
+                image,
+                from_reference,
+                bounds: (
+                    clamp_u(min_u.floor() - 1.0),
+                    clamp_v(min_v.floor() - 1.0),
+                    clamp_u(max_u.ceil() + 1.0),
+                    clamp_v(max_v.ceil() + 1.0),
+                ),
+            })
+        })
+        .collect::<Result<_, ProcessingError>>()?;
+
+    let mut pixels = vec![0.0_f32; width as usize * height as usize * 3];
+    for v in 0..height as usize {
+        let vertical = (v as f64 + 0.5) / pixels_per_radian + min_vertical;
+        for u in 0..width as usize {
+            let theta = (u as f64 + 0.5) / pixels_per_radian + min_theta;
+            let Some((x, y)) = surface.surface_to_plane(theta, vertical) else {
+                continue;
+            };
+
+            let mut sum = [0.0_f32; 3];
+            let mut total_weight = 0.0_f32;
+            for frame in &projected {
+                let (min_u, min_v, max_u, max_v) = frame.bounds;
+                if u < min_u || u > max_u || v < min_v || v > max_v {
+                    continue;
+                }
+                let Some((fx, fy)) = apply_homography(&frame.from_reference, x, y) else {
+                    continue;
+                };
+                let Some((sample, weight)) = sample_feathered(frame.image, fx, fy) else {
+                    continue;
+                };
+                for channel in 0..3 {
+                    sum[channel] += sample[channel] * weight;
+                }
+                total_weight += weight;
+            }
+
+            if total_weight > 0.0 {
+                let output = &mut pixels[(v * width as usize + u) * 3..][..3];
+                for channel in 0..3 {
+                    output[channel] = sum[channel] / total_weight;
+                }
+            }
+        }
+    }
+
+    LinearImage::new(width, height, pixels)
+}
+
+/// Bilinear sample at pixel-centre coordinates `(x, y)` with a feather
+/// weight falling to zero at the frame edges.
+fn sample_feathered(image: &LinearImage, x: f64, y: f64) -> Option<([f32; 3], f32)> {
+    let (width, height) = (image.width as f64, image.height as f64);
+    if x < 0.0 || y < 0.0 || x >= width || y >= height {
+        return None;
+    }
+    let edge_x = (x.min(width - x) / (width / 2.0)) as f32;
+    let edge_y = (y.min(height - y) / (height / 2.0)) as f32;
+    let weight = edge_x * edge_y;
+    if weight <= 0.0 {
+        return None;
+    }
+
+    let px = (x - 0.5).clamp(0.0, width - 1.0);
+    let py = (y - 0.5).clamp(0.0, height - 1.0);
+    let (x0, y0) = (px.floor() as usize, py.floor() as usize);
+    let (x1, y1) = (
+        (x0 + 1).min(image.width as usize - 1),
+        (y0 + 1).min(image.height as usize - 1),
+    );
+    let (tx, ty) = ((px - x0 as f64) as f32, (py - y0 as f64) as f32);
+    let at = |sx: usize, sy: usize, channel: usize| {
+        image.pixels_rgb_f32[(sy * image.width as usize + sx) * 3 + channel]
+    };
+
+    let mut sample = [0.0_f32; 3];
+    for (channel, value) in sample.iter_mut().enumerate() {
+        let top = at(x0, y0, channel) * (1.0 - tx) + at(x1, y0, channel) * tx;
+        let bottom = at(x0, y1, channel) * (1.0 - tx) + at(x1, y1, channel) * tx;
+        *value = top * (1.0 - ty) + bottom * ty;
+    }
+    Some((sample, weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic scene of random-intensity cells (strong corners).
+    fn scene(x: i64, y: i64) -> f32 {
+        let (cx, cy) = (x.div_euclid(7), y.div_euclid(7));
+        let mut hash = (cx as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (cy as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash ^= hash >> 32;
+        0.05 + 0.8 * (hash % 1000) as f32 / 1000.0
+    }
+
+    fn crop(origin_x: i64, origin_y: i64, width: u32, height: u32) -> LinearImage {
+        let mut pixels = Vec::new();
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let value = scene(origin_x + x, origin_y + y);
+                pixels.extend_from_slice(&[value, value * 0.9, value * 0.7]);
+            }
+        }
+        LinearImage::new(width, height, pixels).unwrap()
+    }
+
+    fn options(projection: PanoramaProjection) -> PanoramaOptions {
+        PanoramaOptions {
+            projection,
+            ..PanoramaOptions::default()
+        }
+    }
+
+    #[test]
+    fn fit_homography_recovers_exact_transform() {
+        let truth: Homography = [1.1, 0.05, 12.0, -0.03, 0.95, -7.0, 0.0004, -0.0002, 1.0];
+        let correspondences: Vec<_> = [
+            (0.0, 0.0),
+            (100.0, 5.0),
+            (20.0, 80.0),
+            (90.0, 95.0),
+            (50.0, 40.0),
+        ]
+        .iter()
+        .map(|&(x, y)| {
+            (
+                (x, y),
+                apply_homography(&truth, x, y).expect("affine point"),
+            )
+        })
+        .collect();
+
+        let fitted = fit_homography(&correspondences).expect("homography");
+
+        for (value, expected) in fitted.iter().zip(truth) {
+            assert!((value - expected).abs() < 1e-6, "{fitted:?}");
+        }
+    }
+
+    #[test]
+    fn ransac_ignores_outliers() {
+        let truth: Homography = [1.0, 0.0, 30.0, 0.0, 1.0, -4.0, 0.0, 0.0, 1.0];
+        let mut correspondences: Vec<_> = (0..40)
+            .map(|index| {
+                let (x, y) = ((index * 7 % 97) as f64, (index * 13 % 89) as f64);
+                (
+                    (x, y),
+                    apply_homography(&truth, x, y).expect("affine point"),
+                )
+            })
+            .collect();
+        correspondences.extend((0..10).map(|index| {
+            let value = index as f64 * 11.0;
+            ((value, value), (200.0 - value, value * 3.0))
+        }));
+
+        let (fitted, inliers) = ransac_homography(&correspondences).expect("homography");
+
+        assert_eq!(inliers, 40);
+        assert!((fitted[2] - 30.0).abs() < 1e-6);
+        assert!((fitted[5] + 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stitches_overlapping_crops_into_wider_image() {
+        let frames = vec![
+            crop(0, 0, 120, 90),
+            crop(70, 3, 120, 90),
+            crop(140, 1, 120, 90),
+        ];
+
+        let output = stitch_panorama(&frames, &options(PanoramaProjection::Cylindrical)).unwrap();
+
+        assert_eq!(output.reference_index, 1);
+        // Frame 0 starts 70px left of the reference, frame 2 70px right.
+        let (x0, y0) = apply_homography(&output.homographies[0], 0.0, 0.0).expect("frame 0");
+        let (x2, y2) = apply_homography(&output.homographies[2], 0.0, 0.0).expect("frame 2");
+        assert!(
+            (x0 + 70.0).abs() < 1.0 && (y0 + 3.0).abs() < 1.0,
+            "{x0},{y0}"
+        );
+        assert!(
+            (x2 - 70.0).abs() < 1.0 && (y2 + 2.0).abs() < 1.0,
+            "{x2},{y2}"
+        );
+        // 260px of plane coverage, compressed by the cylinder at f = 120px.
+        assert!(output.image.width >= 190, "width {}", output.image.width);
+        assert!(output.image.height >= 90);
+    }
+
+    #[test]
+    fn reference_centre_is_reproduced_in_both_projections() {
+        let frames = vec![crop(0, 0, 120, 90), crop(60, 0, 120, 90)];
+
+        for projection in [
+            PanoramaProjection::Cylindrical,
+            PanoramaProjection::Spherical,
+        ] {
+            let output = stitch_panorama(&frames, &options(projection)).unwrap();
+            let reference = &frames[output.reference_index];
+            // Near the optical axis both projections are close to the plane.
+            let image = &output.image;
+            let mean = image.pixels_rgb_f32.iter().sum::<f32>() / image.pixels_rgb_f32.len() as f32;
+            let reference_mean = reference.pixels_rgb_f32.iter().sum::<f32>()
+                / reference.pixels_rgb_f32.len() as f32;
+            assert!((mean - reference_mean).abs() < 0.1, "{projection:?}");
+        }
+    }
+
+    #[test]
+    fn respects_output_pixel_budget() {
+        let frames = vec![crop(0, 0, 120, 90), crop(70, 0, 120, 90)];
+        let options = PanoramaOptions {
+            max_output_pixels: 4_000,
+            ..PanoramaOptions::default()
+        };
+
+        let output = stitch_panorama(&frames, &options).unwrap();
+
+        assert!((output.image.width as u64 * output.image.height as u64) <= 4_200);
+    }
+
+    #[test]
+    fn rejects_frames_without_overlap() {
+        let frames = vec![crop(0, 0, 120, 90), crop(5_000, 5_000, 120, 90)];
+
+        let result = stitch_panorama(&frames, &PanoramaOptions::default());
+
+        assert!(matches!(result, Err(ProcessingError::InvalidMerge { .. })));
+    }
+
+    #[test]
+    fn rejects_single_frame_and_parses_projection_names() {
+        assert!(stitch_panorama(&[crop(0, 0, 40, 40)], &PanoramaOptions::default()).is_err());
+        assert_eq!(
+            PanoramaProjection::try_from("Spherical").unwrap(),
+            PanoramaProjection::Spherical
+        );
+        assert!(PanoramaProjection::try_from("planar").is_err());
+    }
+}
@@ -1,8 +1,70 @@
+use crate::cache::{chain_stage_key, source_stage_key, PipelineCache};
 use crate::errors::ProcessingError;
 use crate::raw_decoder::{LinearImage, RawDecoder};
 
-pub trait ImagePipelineStep {
+/// Thread-safety required of pipeline steps: `Sync` with the `parallel`
+/// feature, so steps can be shared across worker threads; no bound otherwise.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Sync> MaybeSync for T {}
+
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "parallel"))]
+impl<T> MaybeSync for T {}
+
+pub trait ImagePipelineStep: MaybeSync {
     fn apply(&self, pixels: &mut [u8], width: u32, height: u32) -> Result<(), ProcessingError>;
+
+    /// Fingerprint of the step kind and parameters, used to memoize its output.
+    ///
+    /// Two steps returning the same key must produce identical output for
+    /// identical input. `None` (the default) disables caching for this step
+    /// and every step after it.
+    fn cache_key(&self) -> Option<u64> {
+        None
+    }
+
+    /// Applies the step to a buffer rendered at `scale` output pixels per
+    /// source pixel. Neighbourhood steps scale their radii accordingly; the
+    /// default ignores the scale.
+    fn apply_at_scale(
+        &self,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        _scale: f32,
+    ) -> Result<(), ProcessingError> {
+        self.apply(pixels, width, height)
+    }
+
+    /// Distance in source pixels this step reads around each output pixel.
+    fn neighbourhood_radius(&self) -> f32 {
+        0.0
+    }
+
+    /// Whether each output pixel depends only on the same input pixel, so the
+    /// step can run on independent row bands. Defaults to `false`.
+    fn is_pixel_local(&self) -> bool {
+        false
+    }
+}
+
+/// Rows per band in `ImagePipeline::execute_parallel` and
+/// `compute_histogram_parallel`.
+#[cfg(feature = "parallel")]
+pub const PARALLEL_BAND_ROWS: usize = 64;
+
+/// Options of `ImagePipeline::execute_cached_with`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CachedExecution {
+    /// Runs pixel-local steps on row bands, like `execute_parallel`. Ignored
+    /// without the `parallel` feature.
+    pub parallel: bool,
+    /// Does not memoize the last step's output, for callers that keep the
+    /// rendered buffer themselves: only upstream stages are cached.
+    pub skip_output_stage: bool,
 }
 
 #[derive(Default)]
@@ -43,6 +105,51 @@ impl ImagePipeline {
         Ok(())
     }
 
+    /// Like `execute`, splitting pixel-local steps into row bands run on the
+    /// rayon thread pool. Other steps run on the whole buffer, so the output
+    /// is identical to `execute`.
+    #[cfg(feature = "parallel")]
+    pub fn execute_parallel(
+        &self,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), ProcessingError> {
+        validate_rgba_input(pixels, width, height)?;
+
+        for step in &self.steps {
+            apply_step_in_bands(step.as_ref(), pixels, width, height)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs every step on a buffer rendered at `scale` output pixels per
+    /// source pixel (see `ImagePipelineStep::apply_at_scale`).
+    pub fn execute_at_scale(
+        &self,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(), ProcessingError> {
+        validate_rgba_input(pixels, width, height)?;
+
+        for step in &self.steps {
+            step.apply_at_scale(pixels, width, height, scale)?;
+        }
+
+        Ok(())
+    }
+
+    /// Total distance in source pixels the pipeline reads around each pixel.
+    pub fn neighbourhood_radius(&self) -> f32 {
+        self.steps
+            .iter()
+            .map(|step| step.neighbourhood_radius())
+            .sum()
+    }
+
     pub fn execute_on_linear_image(
         &self,
         linear_image: &LinearImage,
@@ -51,6 +158,129 @@ impl ImagePipeline {
         self.execute(&mut rgba_pixels, width, height)?;
         Ok(rgba_pixels)
     }
+
+    /// Like `execute`, but resumes from the deepest stage memoized in `cache`
+    /// and stores every newly computed cacheable stage.
+    ///
+    /// `source_key` identifies the input buffer (e.g. image id and source
+    /// revision); callers must change it whenever the input pixels change.
+    /// Returns the number of leading steps whose output came from the cache.
+    pub fn execute_cached(
+        &self,
+        cache: &mut PipelineCache,
+        source_key: u64,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+    ) -> Result<usize, ProcessingError> {
+        self.execute_cached_with(
+            cache,
+            source_key,
+            pixels,
+            width,
+            height,
+            CachedExecution::default(),
+        )
+    }
+
+    /// `execute_cached` with explicit threading and output memoization.
+    pub fn execute_cached_with(
+        &self,
+        cache: &mut PipelineCache,
+        source_key: u64,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        execution: CachedExecution,
+    ) -> Result<usize, ProcessingError> {
+        validate_rgba_input(pixels, width, height)?;
+
+        let mut stage_keys = self.stage_keys(source_key, width, height);
+        if execution.skip_output_stage {
+            if let Some(output) = stage_keys.last_mut() {
+                *output = None;
+            }
+        }
+
+        let start = match Self::deepest_cached_stage(cache, &stage_keys, width, height) {
+            Some((stage, cached)) => {
+                pixels.copy_from_slice(cached);
+                stage
+            }
+            None => 0,
+        };
+
+        for (index, step) in self.steps.iter().enumerate().skip(start) {
+            #[cfg(feature = "parallel")]
+            if execution.parallel {
+                apply_step_in_bands(step.as_ref(), pixels, width, height)?;
+            } else {
+                step.apply(pixels, width, height)?;
+            }
+            #[cfg(not(feature = "parallel"))]
+            step.apply(pixels, width, height)?;
+
+            if let Some(key) = stage_keys[index + 1] {
+                cache.insert(key, width, height, pixels);
+            }
+        }
+
+        Ok(start)
+    }
+
+    /// Stage `0` is the input; stage `i + 1` is the output of step `i`.
+    /// A stage has no key once any step up to it is not cacheable.
+    fn stage_keys(&self, source_key: u64, width: u32, height: u32) -> Vec<Option<u64>> {
+        let mut keys = Vec::with_capacity(self.steps.len() + 1);
+        let mut current = Some(source_stage_key(source_key, width, height));
+        keys.push(current);
+
+        for step in &self.steps {
+            current = current
+                .zip(step.cache_key())
+                .map(|(previous, step_key)| chain_stage_key(previous, step_key));
+            keys.push(current);
+        }
+
+        keys
+    }
+
+    fn deepest_cached_stage<'a>(
+        cache: &'a mut PipelineCache,
+        stage_keys: &[Option<u64>],
+        width: u32,
+        height: u32,
+    ) -> Option<(usize, &'a [u8])> {
+        let (stage, key) = stage_keys
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(stage, key)| key.map(|key| (stage, key)))
+            .find(|(_, key)| cache.contains(*key))?;
+
+        cache.get(key, width, height).map(|pixels| (stage, pixels))
+    }
+}
+
+/// Applies a pixel-local step on row bands in parallel, other steps on the
+/// whole buffer.
+#[cfg(feature = "parallel")]
+fn apply_step_in_bands(
+    step: &dyn ImagePipelineStep,
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+) -> Result<(), ProcessingError> {
+    use rayon::prelude::*;
+
+    if !step.is_pixel_local() {
+        return step.apply(pixels, width, height);
+    }
+
+    let row_len = width as usize * 4;
+    pixels
+        .par_chunks_mut(row_len * PARALLEL_BAND_ROWS)
+        .try_for_each(|band| step.apply(band, width, (band.len() / row_len) as u32))
 }
 
 pub(crate) fn expected_rgba_len(width: u32, height: u32) -> Option<usize> {
@@ -88,6 +318,17 @@ pub fn decode_raw_to_rgba8(
 fn linear_image_to_rgba8(
     linear_image: &LinearImage,
 ) -> Result<(Vec<u8>, u32, u32), ProcessingError> {
+    let mut rgba = Vec::new();
+    write_linear_image_rgba8(linear_image, &mut rgba)?;
+    Ok((rgba, linear_image.width, linear_image.height))
+}
+
+/// Converts `linear_image` to opaque RGBA8 into `rgba`, replacing its
+/// contents and reusing its capacity.
+pub fn write_linear_image_rgba8(
+    linear_image: &LinearImage,
+    rgba: &mut Vec<u8>,
+) -> Result<(), ProcessingError> {
     let width = linear_image.width;
     let height = linear_image.height;
 
@@ -107,12 +348,10 @@ fn linear_image_to_rgba8(
         });
     }
 
-    let mut rgba = Vec::with_capacity(
-        (width as usize)
-            .checked_mul(height as usize)
-            .and_then(|px| px.checked_mul(4))
-            .ok_or(ProcessingError::InvalidDimensions { width, height })?,
-    );
+    let expected_rgba = expected_rgba_len(width, height)
+        .ok_or(ProcessingError::InvalidDimensions { width, height })?;
+    rgba.clear();
+    rgba.reserve(expected_rgba);
 
     for (index, rgb) in linear_image.pixels_rgb_f32.chunks_exact(3).enumerate() {
         for (channel, sample) in rgb.iter().enumerate() {
@@ -128,12 +367,14 @@ fn linear_image_to_rgba8(
         rgba.push(255_u8);
     }
 
-    Ok((rgba, width, height))
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     struct SetChannelStep {
         channel: usize,
@@ -174,6 +415,42 @@ mod tests {
         }
     }
 
+    struct CountingAddStep {
+        delta: u8,
+        runs: Arc<AtomicUsize>,
+        cacheable: bool,
+    }
+
+    impl CountingAddStep {
+        fn new(delta: u8, runs: &Arc<AtomicUsize>) -> Self {
+            Self {
+                delta,
+                runs: Arc::clone(runs),
+                cacheable: true,
+            }
+        }
+    }
+
+    impl ImagePipelineStep for CountingAddStep {
+        fn apply(
+            &self,
+            pixels: &mut [u8],
+            _width: u32,
+            _height: u32,
+        ) -> Result<(), ProcessingError> {
+            self.runs.fetch_add(1, Ordering::Relaxed);
+            for chunk in pixels.chunks_exact_mut(4) {
+                chunk[0] = chunk[0].saturating_add(self.delta);
+            }
+
+            Ok(())
+        }
+
+        fn cache_key(&self) -> Option<u64> {
+            self.cacheable.then_some(u64::from(self.delta))
+        }
+    }
+
     struct FailingStep;
 
     struct MockRawDecoder;
@@ -282,4 +559,224 @@ mod tests {
 
         assert_eq!(result, vec![61, 51, 51, 255]);
     }
+
+    #[test]
+    fn write_linear_image_rgba8_replaces_buffer_contents() {
+        let linear = LinearImage::new(1, 1, vec![1.0, 0.5, 0.0]).unwrap();
+        let mut rgba = vec![9_u8; 64];
+        let capacity = rgba.capacity();
+
+        write_linear_image_rgba8(&linear, &mut rgba).unwrap();
+
+        assert_eq!(rgba, vec![255, 128, 0, 255]);
+        assert_eq!(rgba.capacity(), capacity);
+    }
+
+    #[test]
+    fn cached_execution_reruns_only_downstream_steps() {
+        let first = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::default();
+
+        let pipeline = ImagePipeline::new()
+            .with_step(CountingAddStep::new(10, &first))
+            .with_step(CountingAddStep::new(1, &last));
+        let mut pixels = vec![0_u8, 0, 0, 255];
+        pipeline
+            .execute_cached(&mut cache, 7, &mut pixels, 1, 1)
+            .unwrap();
+        assert_eq!(pixels, vec![11, 0, 0, 255]);
+
+        let pipeline = ImagePipeline::new()
+            .with_step(CountingAddStep::new(10, &first))
+            .with_step(CountingAddStep::new(2, &last));
+        let mut pixels = vec![0_u8, 0, 0, 255];
+        pipeline
+            .execute_cached(&mut cache, 7, &mut pixels, 1, 1)
+            .unwrap();
+
+        assert_eq!(pixels, vec![12, 0, 0, 255]);
+        assert_eq!(first.load(Ordering::Relaxed), 1);
+        assert_eq!(last.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn cached_execution_skips_all_steps_when_unchanged() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::default();
+        let pipeline = ImagePipeline::new()
+            .with_step(CountingAddStep::new(3, &runs))
+            .with_step(CountingAddStep::new(4, &runs));
+
+        for _ in 0..3 {
+            let mut pixels = vec![1_u8, 0, 0, 255];
+            pipeline
+                .execute_cached(&mut cache, 1, &mut pixels, 1, 1)
+                .unwrap();
+            assert_eq!(pixels, vec![8, 0, 0, 255]);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn cached_execution_keys_on_source() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::default();
+        let pipeline = ImagePipeline::new().with_step(CountingAddStep::new(3, &runs));
+
+        let mut pixels = vec![1_u8, 0, 0, 255];
+        pipeline
+            .execute_cached(&mut cache, 1, &mut pixels, 1, 1)
+            .unwrap();
+        let mut pixels = vec![5_u8, 0, 0, 255];
+        pipeline
+            .execute_cached(&mut cache, 2, &mut pixels, 1, 1)
+            .unwrap();
+
+        assert_eq!(pixels, vec![8, 0, 0, 255]);
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn uncacheable_step_disables_memoization_downstream() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::default();
+        let mut uncacheable = CountingAddStep::new(1, &runs);
+        uncacheable.cacheable = false;
+        let pipeline = ImagePipeline::new()
+            .with_step(uncacheable)
+            .with_step(CountingAddStep::new(2, &runs));
+
+        for _ in 0..2 {
+            let mut pixels = vec![0_u8, 0, 0, 255];
+            pipeline
+                .execute_cached(&mut cache, 1, &mut pixels, 1, 1)
+                .unwrap();
+            assert_eq!(pixels, vec![3, 0, 0, 255]);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 4);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn cached_execution_is_correct_without_budget() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::new(0);
+        let pipeline = ImagePipeline::new().with_step(CountingAddStep::new(5, &runs));
+
+        for _ in 0..2 {
+            let mut pixels = vec![0_u8, 0, 0, 255];
+            pipeline
+                .execute_cached(&mut cache, 1, &mut pixels, 1, 1)
+                .unwrap();
+            assert_eq!(pixels, vec![5, 0, 0, 255]);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn skipping_output_stage_caches_only_upstream_steps() {
+        let first = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+        let mut cache = PipelineCache::default();
+        let pipeline = ImagePipeline::new()
+            .with_step(CountingAddStep::new(10, &first))
+            .with_step(CountingAddStep::new(1, &last));
+        let execution = CachedExecution {
+            skip_output_stage: true,
+            ..CachedExecution::default()
+        };
+
+        for expected_start in [0, 1] {
+            let mut pixels = vec![0_u8, 0, 0, 255];
+            let start = pipeline
+                .execute_cached_with(&mut cache, 1, &mut pixels, 1, 1, execution)
+                .unwrap();
+            assert_eq!(start, expected_start);
+            assert_eq!(pixels, vec![11, 0, 0, 255]);
+        }
+
+        assert_eq!(first.load(Ordering::Relaxed), 1);
+        assert_eq!(last.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn filter_steps_share_cache_key_for_equal_filters() {
+        use crate::filters::{FilterTransformStep, PixelFilters};
+
+        let filters = PixelFilters {
+            exposure: 0.5,
+            ..PixelFilters::default()
+        };
+        let other = PixelFilters {
+            exposure: 0.6,
+            ..PixelFilters::default()
+        };
+
+        assert_eq!(
+            FilterTransformStep::new(&filters).cache_key(),
+            FilterTransformStep::new(&filters).cache_key()
+        );
+        assert_ne!(
+            FilterTransformStep::new(&filters).cache_key(),
+            FilterTransformStep::new(&other).cache_key()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_execution_matches_serial_for_recipe_pipeline() {
+        use crate::recipe::EditRecipe;
+
+        let recipe = EditRecipe::from_json(
+            r#"{"schemaVersion":1,"processVersion":1,
+                "adjustments":{"exposure":25,"contrast":-15,"highlights":-40,"shadows":30,
+                               "clarity":20,"vibrance":35,"saturation":10,"colorTemp":6200,"tint":8},
+                "retouch":[{"mode":"heal","target":[{"x":0.3,"y":0.4}],"source":{"x":0.7,"y":0.6},"radius":0.1}]}"#,
+        )
+        .unwrap();
+        let pipeline = recipe.build_pipeline().unwrap();
+        // Not a multiple of the band height, so the last band is partial.
+        let (width, height) = (53_u32, 2 * PARALLEL_BAND_ROWS as u32 + 17);
+        let source: Vec<u8> = (0..width * height * 4)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 11) as u8)
+            .collect();
+
+        let mut serial = source.clone();
+        pipeline.execute(&mut serial, width, height).unwrap();
+        let mut parallel = source.clone();
+        pipeline
+            .execute_parallel(&mut parallel, width, height)
+            .unwrap();
+        let mut cached = source;
+        pipeline
+            .execute_cached_with(
+                &mut PipelineCache::default(),
+                1,
+                &mut cached,
+                width,
+                height,
+                CachedExecution {
+                    parallel: true,
+                    ..CachedExecution::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(parallel, serial);
+        assert_eq!(cached, serial);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_execution_propagates_step_error() {
+        let pipeline = ImagePipeline::new().with_step(FailingStep);
+        let mut pixels = vec![0_u8; 4];
+
+        assert!(pipeline.execute_parallel(&mut pixels, 1, 1).is_err());
+    }
 }
@@ -0,0 +1,626 @@
+//! Serializable, versioned edit recipe ("process version").
+//!
+//! An `EditRecipe` is the explicit description of how an image is rendered:
+//! slider values in UI units plus the process version whose algorithms and
+//! scale factors interpret them. Recipes are pinned to a process version so an
+//! algorithm upgrade never changes the rendering of an existing catalog; only
+//! recipes created (or explicitly upgraded) afterwards use the new version.
+//!
+//! The serialized form carries its own `schemaVersion`. `EditRecipe::from_value`
+//! migrates older schemas step by step before deserializing:
+//! - schema 0: the legacy loose JSON patch (`{"exposure": 50, "colorTemp": 6500}`),
+//!   always interpreted with process version 1.
+//! - schema 1: `{ schemaVersion, processVersion, adjustments, retouch? }`.
+//!
+//! Noise reduction and sharpening read neighbouring pixels; they are only
+//! added to the pipeline when their adjustment is non-zero, so recipes saved
+//! before they existed keep rendering exactly as before.
+
+use crate::errors::ProcessingError;
+use crate::filters::{FilterTransformStep, PixelFilters};
+use crate::neighbourhood::{NoiseReductionStep, SharpenStep};
+use crate::pipeline::ImagePipeline;
+use crate::retouch::{apply_retouch_rgba16, RetouchSpot, RetouchStep};
+use crate::rgba16::{apply_filters_rgba16, validate_rgba16_input};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Current layout of the serialized recipe.
+pub const RECIPE_SCHEMA_VERSION: u32 = 1;
+/// First process version: the v1 core algorithms with the original UI scale factors.
+pub const PROCESS_VERSION_1: u32 = 1;
+/// Process version assigned to newly created recipes.
+pub const CURRENT_PROCESS_VERSION: u32 = PROCESS_VERSION_1;
+
+/// Process version 1 slider scale factors (UI units to `PixelFilters` units).
+const V1_EXPOSURE_SCALE: f64 = 50.0;
+const V1_CONTRAST_SCALE: f64 = 50.0;
+const V1_PERCENT_SCALE: f64 = 100.0;
+const V1_TINT_SCALE: f64 = 2.0;
+/// Process version 1 neighbourhood filters: sharpening 100 is an unsharp mask
+/// of amount 1.0, noise reduction 100 a full blend; radii in source pixels.
+const V1_SHARPEN_RADIUS: f32 = 1.0;
+const V1_NOISE_REDUCTION_RADIUS: f32 = 1.5;
+
+/// A single global adjustment, in the order a process version applies them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessStep {
+    Retouch,
+    NoiseReduction,
+    Exposure,
+    Contrast,
+    Saturation,
+    Highlights,
+    Shadows,
+    ColorTemp,
+    Tint,
+    Sharpen,
+}
+
+const PROCESS_V1_STEPS: &[ProcessStep] = &[
+    ProcessStep::Retouch,
+    ProcessStep::NoiseReduction,
+    ProcessStep::Exposure,
+    ProcessStep::Contrast,
+    ProcessStep::Saturation,
+    ProcessStep::Highlights,
+    ProcessStep::Shadows,
+    ProcessStep::ColorTemp,
+    ProcessStep::Tint,
+    ProcessStep::Sharpen,
+];
+
+/// Returns the step order applied by `process_version`.
+pub fn step_order(process_version: u32) -> Result<&'static [ProcessStep], ProcessingError> {
+    match process_version {
+        PROCESS_VERSION_1 => Ok(PROCESS_V1_STEPS),
+        version => Err(ProcessingError::UnsupportedProcessVersion { version }),
+    }
+}
+
+/// Slider values in UI units, as emitted by the develop panel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Adjustments {
+    /// UI range [-100, 100], no-op: 0.
+    pub exposure: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub contrast: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub highlights: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub shadows: f64,
+    /// Kelvin, no-op: 5500.
+    #[serde(alias = "temp", alias = "color_temp")]
+    pub color_temp: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub tint: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub vibrance: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub saturation: f64,
+    /// UI range [-100, 100], no-op: 0.
+    pub clarity: f64,
+    /// UI range [0, 150], no-op: 0.
+    #[serde(alias = "sharpen")]
+    pub sharpening: f64,
+    /// UI range [0, 100], no-op: 0.
+    #[serde(alias = "noise_reduction")]
+    pub noise_reduction: f64,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            contrast: 0.0,
+            highlights: 0.0,
+            shadows: 0.0,
+            color_temp: 5500.0,
+            tint: 0.0,
+            vibrance: 0.0,
+            saturation: 0.0,
+            clarity: 0.0,
+            sharpening: 0.0,
+            noise_reduction: 0.0,
+        }
+    }
+}
+
+impl Adjustments {
+    /// Sets one adjustment from an edit patch key. Unknown keys are ignored.
+    pub fn set(&mut self, key: &str, value: f64) -> bool {
+        match key {
+            "exposure" => self.exposure = value,
+            "contrast" => self.contrast = value,
+            "highlights" => self.highlights = value,
+            "shadows" => self.shadows = value,
+            "temp" | "colorTemp" | "color_temp" => self.color_temp = value,
+            "tint" => self.tint = value,
+            "vibrance" => self.vibrance = value,
+            "saturation" => self.saturation = value,
+            "clarity" => self.clarity = value,
+            "sharpening" | "sharpen" => self.sharpening = value,
+            "noiseReduction" | "noise_reduction" => self.noise_reduction = value,
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditRecipe {
+    pub schema_version: u32,
+    pub process_version: u32,
+    #[serde(default)]
+    pub adjustments: Adjustments,
+    /// Heal/clone spots, replayed in order before the global adjustments.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retouch: Vec<RetouchSpot>,
+}
+
+impl Default for EditRecipe {
+    fn default() -> Self {
+        Self::new(CURRENT_PROCESS_VERSION)
+    }
+}
+
+impl EditRecipe {
+    pub fn new(process_version: u32) -> Self {
+        Self {
+            schema_version: RECIPE_SCHEMA_VERSION,
+            process_version,
+            adjustments: Adjustments::default(),
+            retouch: Vec::new(),
+        }
+    }
+
+    /// Parses a serialized recipe of any known schema version.
+    pub fn from_json(json: &str) -> Result<Self, ProcessingError> {
+        let value: Value = serde_json::from_str(json).map_err(invalid_recipe)?;
+        Self::from_value(value)
+    }
+
+    /// Migrates a recipe value to the current schema and deserializes it.
+    pub fn from_value(value: Value) -> Result<Self, ProcessingError> {
+        let migrated = migrate_recipe_value(value)?;
+        serde_json::from_value(migrated).map_err(invalid_recipe)
+    }
+
+    pub fn to_json(&self) -> Result<String, ProcessingError> {
+        serde_json::to_string(self).map_err(invalid_recipe)
+    }
+
+    /// Applies a loose edit patch (`{"exposure": 12, ...}`) on top of the recipe.
+    ///
+    /// Retouch edits use `retouchSpot` (one spot, appended) or `retouch` (the
+    /// full spot list, replacing the current one). Returns the number of
+    /// recognized edits.
+    pub fn apply_patch(&mut self, patch: &Map<String, Value>) -> usize {
+        let mut applied = 0;
+
+        for (key, value) in patch {
+            match key.as_str() {
+                "retouchSpot" | "retouch_spot" => {
+                    if let Ok(spot) = serde_json::from_value::<RetouchSpot>(value.clone()) {
+                        self.retouch.push(spot);
+                        applied += 1;
+                    }
+                    continue;
+                }
+                "retouch" => {
+                    if let Ok(spots) = serde_json::from_value::<Vec<RetouchSpot>>(value.clone()) {
+                        self.retouch = spots;
+                        applied += 1;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let Some(v) = value_to_f64(value) else {
+                continue;
+            };
+
+            if self.adjustments.set(key, v) {
+                applied += 1;
+            }
+        }
+
+        applied
+    }
+
+    pub fn step_order(&self) -> Result<&'static [ProcessStep], ProcessingError> {
+        step_order(self.process_version)
+    }
+
+    /// Maps UI adjustments to core filter values using this recipe's process version.
+    pub fn to_pixel_filters(&self) -> Result<PixelFilters, ProcessingError> {
+        match self.process_version {
+            PROCESS_VERSION_1 => Ok(self.to_pixel_filters_v1()),
+            version => Err(ProcessingError::UnsupportedProcessVersion { version }),
+        }
+    }
+
+    /// Builds the pipeline rendering this recipe on an RGBA buffer.
+    pub fn build_pipeline(&self) -> Result<ImagePipeline, ProcessingError> {
+        let filters = self.to_pixel_filters()?;
+        let (noise_reduction, sharpen) = self.neighbourhood_steps()?;
+        let mut pipeline = ImagePipeline::new();
+
+        if !self.retouch.is_empty() {
+            pipeline.add_step(RetouchStep::new(self.retouch.clone())?);
+        }
+        if let Some(step) = noise_reduction {
+            pipeline.add_step(step);
+        }
+        pipeline.add_step(FilterTransformStep::new(&filters));
+        if let Some(step) = sharpen {
+            pipeline.add_step(step);
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Renders this recipe on an RGBA16 buffer in place, with the same steps
+    /// and order as `build_pipeline`.
+    pub fn render_rgba16(
+        &self,
+        pixels: &mut [u16],
+        width: u32,
+        height: u32,
+    ) -> Result<(), ProcessingError> {
+        let filters = self.to_pixel_filters()?;
+        let (noise_reduction, sharpen) = self.neighbourhood_steps()?;
+        validate_rgba16_input(pixels, width, height)?;
+
+        if !self.retouch.is_empty() {
+            apply_retouch_rgba16(pixels, width, height, &self.retouch)?;
+        }
+        if let Some(step) = noise_reduction {
+            step.apply_rgba16(pixels, width, height)?;
+        }
+        apply_filters_rgba16(pixels, width, height, &filters)?;
+        if let Some(step) = sharpen {
+            step.apply_rgba16(pixels, width, height)?;
+        }
+        Ok(())
+    }
+
+    /// Noise reduction and sharpening steps of this recipe, `None` when neutral.
+    fn neighbourhood_steps(
+        &self,
+    ) -> Result<(Option<NoiseReductionStep>, Option<SharpenStep>), ProcessingError> {
+        match self.process_version {
+            PROCESS_VERSION_1 => self.neighbourhood_steps_v1(),
+            version => Err(ProcessingError::UnsupportedProcessVersion { version }),
+        }
+    }
+
+    fn neighbourhood_steps_v1(
+        &self,
+    ) -> Result<(Option<NoiseReductionStep>, Option<SharpenStep>), ProcessingError> {
+        let a = &self.adjustments;
+
+        let noise_reduction = (a.noise_reduction != 0.0)
+            .then(|| {
+                NoiseReductionStep::new(
+                    (a.noise_reduction / V1_PERCENT_SCALE) as f32,
+                    V1_NOISE_REDUCTION_RADIUS,
+                )
+            })
+            .transpose()?;
+        let sharpen = (a.sharpening != 0.0)
+            .then(|| SharpenStep::new((a.sharpening / V1_PERCENT_SCALE) as f32, V1_SHARPEN_RADIUS))
+            .transpose()?;
+
+        Ok((noise_reduction, sharpen))
+    }
+
+    fn to_pixel_filters_v1(&self) -> PixelFilters {
+        let a = &self.adjustments;
+
+        PixelFilters {
+            exposure: (a.exposure / V1_EXPOSURE_SCALE) as f32,
+            contrast: (a.contrast / V1_CONTRAST_SCALE) as f32,
+            saturation: (1.0 + a.saturation / V1_PERCENT_SCALE) as f32,
+            highlights: (a.highlights / V1_PERCENT_SCALE) as f32,
+            shadows: (a.shadows / V1_PERCENT_SCALE) as f32,
+            clarity: (a.clarity / V1_PERCENT_SCALE) as f32,
+            vibrance: (a.vibrance / V1_PERCENT_SCALE) as f32,
+            color_temp: a.color_temp as f32,
+            tint: (a.tint / V1_TINT_SCALE) as f32,
+        }
+    }
+}
+
+/// Upgrades a serialized recipe to `RECIPE_SCHEMA_VERSION`, one schema at a time.
+pub fn migrate_recipe_value(mut value: Value) -> Result<Value, ProcessingError> {
+    loop {
+        let schema_version = schema_version_of(&value)?;
+
+        value = match schema_version {
+            0 => migrate_v0_to_v1(&value)?,
+            RECIPE_SCHEMA_VERSION => return Ok(value),
+            newer => {
+                return Err(ProcessingError::InvalidRecipe {
+                    message: format!(
+                        "schema version {newer} is newer than supported {RECIPE_SCHEMA_VERSION}"
+                    ),
+                })
+            }
+        };
+    }
+}
+
+fn schema_version_of(value: &Value) -> Result<u32, ProcessingError> {
+    let object = value.as_object().ok_or(ProcessingError::InvalidRecipe {
+        message: "recipe must be a JSON object".to_string(),
+    })?;
+
+    match object
+        .get("schemaVersion")
+        .or_else(|| object.get("schema_version"))
+    {
+        None => Ok(0),
+        Some(raw) => {
+            raw.as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or(ProcessingError::InvalidRecipe {
+                    message: format!("invalid schemaVersion: {raw}"),
+                })
+        }
+    }
+}
+
+fn migrate_v0_to_v1(value: &Value) -> Result<Value, ProcessingError> {
+    let patch = value.as_object().ok_or(ProcessingError::InvalidRecipe {
+        message: "legacy recipe must be a JSON object".to_string(),
+    })?;
+
+    let mut recipe = EditRecipe::new(PROCESS_VERSION_1);
+    recipe.apply_patch(patch);
+
+    serde_json::to_value(recipe).map_err(invalid_recipe)
+}
+
+fn invalid_recipe(err: serde_json::Error) -> ProcessingError {
+    ProcessingError::InvalidRecipe {
+        message: err.to_string(),
+    }
+}
+
+fn value_to_f64(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_i64().map(|v| v as f64))
+        .or_else(|| value.as_u64().map(|v| v as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn default_recipe_renders_as_noop_filters() {
+        let filters = EditRecipe::default().to_pixel_filters().unwrap();
+
+        assert_eq!(filters.exposure, 0.0);
+        assert_eq!(filters.saturation, 1.0);
+        assert_eq!(filters.color_temp, 5500.0);
+    }
+
+    #[test]
+    fn legacy_patch_migrates_to_process_version_1() {
+        let recipe = EditRecipe::from_value(json!({
+            "exposure": 50,
+            "temp": 6500.0,
+            "unknown": 3
+        }))
+        .unwrap();
+
+        assert_eq!(recipe.schema_version, RECIPE_SCHEMA_VERSION);
+        assert_eq!(recipe.process_version, PROCESS_VERSION_1);
+        assert_eq!(recipe.adjustments.exposure, 50.0);
+        assert_eq!(recipe.adjustments.color_temp, 6500.0);
+    }
+
+    #[test]
+    fn recipe_round_trips_through_json() {
+        let mut recipe = EditRecipe::default();
+        recipe.adjustments.contrast = 25.0;
+        recipe.adjustments.tint = -8.0;
+
+        let json = recipe.to_json().unwrap();
+        let parsed = EditRecipe::from_json(&json).unwrap();
+
+        assert_eq!(parsed, recipe);
+        assert!(json.contains("\"processVersion\":1"));
+    }
+
+    #[test]
+    fn v1_scale_factors_match_legacy_accumulator() {
+        let mut recipe = EditRecipe::new(PROCESS_VERSION_1);
+        let Value::Object(patch) = json!({
+            "exposure": 35.0, "contrast": 10.0, "saturation": 12.0,
+            "highlights": -20.0, "shadows": 65.0, "colorTemp": 5600.0, "tint": 5.0
+        }) else {
+            panic!("patch literal must be an object");
+        };
+        assert_eq!(recipe.apply_patch(&patch), 7);
+
+        let filters = recipe.to_pixel_filters().unwrap();
+
+        assert_eq!(filters.exposure, (35.0_f64 / 50.0) as f32);
+        assert_eq!(filters.contrast, (10.0_f64 / 50.0) as f32);
+        assert_eq!(filters.saturation, (1.0_f64 + 12.0 / 100.0) as f32);
+        assert_eq!(filters.highlights, (-20.0_f64 / 100.0) as f32);
+        assert_eq!(filters.shadows, (65.0_f64 / 100.0) as f32);
+        assert_eq!(filters.tint, (5.0_f64 / 2.0) as f32);
+    }
+
+    #[test]
+    fn unknown_process_version_is_rejected() {
+        let recipe = EditRecipe::new(99);
+
+        assert!(matches!(
+            recipe.to_pixel_filters(),
+            Err(ProcessingError::UnsupportedProcessVersion { version: 99 })
+        ));
+        assert!(recipe.build_pipeline().is_err());
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let result = EditRecipe::from_value(json!({
+            "schemaVersion": RECIPE_SCHEMA_VERSION + 1,
+            "processVersion": 1
+        }));
+
+        assert!(matches!(result, Err(ProcessingError::InvalidRecipe { .. })));
+    }
+
+    #[test]
+    fn step_order_is_defined_for_process_version_1() {
+        let steps = step_order(PROCESS_VERSION_1).unwrap();
+
+        assert_eq!(steps.first(), Some(&ProcessStep::Retouch));
+        assert_eq!(steps.get(1), Some(&ProcessStep::NoiseReduction));
+        assert_eq!(steps.get(2), Some(&ProcessStep::Exposure));
+        assert_eq!(steps.last(), Some(&ProcessStep::Sharpen));
+    }
+
+    #[test]
+    fn retouch_patches_append_or_replace_spots() {
+        let spot = json!({
+            "mode": "clone",
+            "target": [{ "x": 0.25, "y": 0.5 }],
+            "source": { "x": 0.75, "y": 0.5 },
+            "radius": 0.1
+        });
+        let mut recipe = EditRecipe::default();
+
+        let Value::Object(append) = json!({ "retouchSpot": spot.clone() }) else {
+            panic!("patch literal must be an object");
+        };
+        assert_eq!(recipe.apply_patch(&append), 1);
+        assert_eq!(recipe.apply_patch(&append), 1);
+        assert_eq!(recipe.retouch.len(), 2);
+
+        let Value::Object(replace) = json!({ "retouch": [spot] }) else {
+            panic!("patch literal must be an object");
+        };
+        assert_eq!(recipe.apply_patch(&replace), 1);
+        assert_eq!(recipe.retouch.len(), 1);
+
+        let parsed = EditRecipe::from_json(&recipe.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, recipe);
+    }
+
+    #[test]
+    fn recipe_pipeline_replays_retouch_before_adjustments() {
+        let mut recipe = EditRecipe::default();
+        let Value::Object(patch) = json!({
+            "retouchSpot": {
+                "mode": "clone",
+                "target": [{ "x": 0.25, "y": 0.5 }],
+                "source": { "x": 0.75, "y": 0.5 },
+                "radius": 0.1
+            }
+        }) else {
+            panic!("patch literal must be an object");
+        };
+        recipe.apply_patch(&patch);
+
+        let mut pixels = Vec::new();
+        for _y in 0..10 {
+            for x in 0..20 {
+                let value = if x < 10 { 40 } else { 200 };
+                pixels.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        recipe
+            .build_pipeline()
+            .unwrap()
+            .execute(&mut pixels, 20, 10)
+            .unwrap();
+
+        let offset = (5 * 20 + 5) * 4;
+        assert_eq!(&pixels[offset..offset + 4], &[200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn rgba16_render_tracks_the_rgba8_pipeline() {
+        let recipe = EditRecipe::from_json(
+            r#"{"schemaVersion":1,"processVersion":1,
+                "adjustments":{"exposure":25,"contrast":-15,"saturation":30,"colorTemp":5200},
+                "retouch":[{"mode":"heal","target":[{"x":0.3,"y":0.4}],"source":{"x":0.7,"y":0.5},"radius":0.15}]}"#,
+        )
+        .unwrap();
+        let (width, height) = (16_u32, 12_u32);
+        let mut pixels8: Vec<u8> = (0..width * height)
+            .flat_map(|index| [(index * 3 % 256) as u8, (index * 7 % 256) as u8, 120, 255])
+            .collect();
+        let mut pixels16: Vec<u16> = pixels8.iter().map(|&v| v as u16 * 257).collect();
+
+        recipe
+            .build_pipeline()
+            .unwrap()
+            .execute(&mut pixels8, width, height)
+            .unwrap();
+        recipe.render_rgba16(&mut pixels16, width, height).unwrap();
+
+        for (&narrow, &wide) in pixels8.iter().zip(&pixels16) {
+            let delta = (narrow as f32 - wide as f32 / 257.0).abs();
+            assert!(delta <= 2.0, "8-bit {narrow} vs 16-bit {wide}");
+        }
+    }
+
+    #[test]
+    fn neighbourhood_adjustments_add_scale_aware_steps() {
+        let neutral = EditRecipe::default();
+        assert_eq!(
+            neutral.build_pipeline().unwrap().neighbourhood_radius(),
+            0.0
+        );
+
+        let mut recipe = EditRecipe::default();
+        let Value::Object(patch) = json!({ "sharpening": 80, "noiseReduction": 30 }) else {
+            panic!("patch literal must be an object");
+        };
+        assert_eq!(recipe.apply_patch(&patch), 2);
+        let pipeline = recipe.build_pipeline().unwrap();
+        // Three sigmas of the 1.5 px noise reduction and 1.0 px sharpening.
+        assert_eq!(pipeline.neighbourhood_radius(), 7.5);
+
+        let mut pixels: Vec<u8> = (0..8 * 8)
+            .flat_map(|index| {
+                let value = if (index + index / 8) % 2 == 0 {
+                    90
+                } else {
+                    160
+                };
+                [value, value, value, 255]
+            })
+            .collect();
+        let mut pixels16: Vec<u16> = pixels.iter().map(|&v| v as u16 * 257).collect();
+        let original = pixels.clone();
+        pipeline.execute(&mut pixels, 8, 8).unwrap();
+        recipe.render_rgba16(&mut pixels16, 8, 8).unwrap();
+        assert_ne!(pixels, original);
+        for (&narrow, &wide) in pixels.iter().zip(&pixels16) {
+            let delta = (narrow as f32 - wide as f32 / 257.0).abs();
+            assert!(delta <= 2.0, "8-bit {narrow} vs 16-bit {wide}");
+        }
+
+        recipe.adjustments.sharpening = 1_000.0;
+        assert!(matches!(
+            recipe.build_pipeline(),
+            Err(ProcessingError::InvalidFilterValue { .. })
+        ));
+    }
+}
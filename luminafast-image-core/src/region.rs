@@ -0,0 +1,300 @@
+//! Region and scaled rendering for zoomed previews.
+//!
+//! `render_region` renders a viewport of a decoded RGBA source at a requested
+//! scale (output pixels per source pixel), e.g. a 1:1 crop of a 45 MP image
+//! or a quarter-scale overview. The viewport is padded by the pipeline's
+//! neighbourhood radius before rendering so that neighbourhood filters see the
+//! same surroundings they would in a full render, then cropped back. Scaling
+//! uses the shared linear-light box resampler.
+
+use crate::errors::ProcessingError;
+use crate::pipeline::{validate_rgba_input, ImagePipeline};
+use crate::resample::{resize_rgba8, ResampleFilter};
+
+/// Largest accepted zoom-in factor.
+pub const MAX_RENDER_SCALE: f32 = 16.0;
+
+/// Rectangle in source pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full(width: u32, height: u32) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    fn right(&self) -> u64 {
+        self.x as u64 + self.width as u64
+    }
+
+    fn bottom(&self) -> u64 {
+        self.y as u64 + self.height as u64
+    }
+}
+
+/// RGBA output of `render_region`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedRegion {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Output size of `length` source pixels rendered at `scale`.
+pub fn scaled_length(length: u32, scale: f32) -> u32 {
+    ((length as f64 * scale as f64).round() as u32).max(1)
+}
+
+/// Renders `viewport` of `source` through `pipeline` at `scale`.
+pub fn render_region(
+    pipeline: &ImagePipeline,
+    source: &[u8],
+    source_width: u32,
+    source_height: u32,
+    viewport: Viewport,
+    scale: f32,
+) -> Result<RenderedRegion, ProcessingError> {
+    validate_rgba_input(source, source_width, source_height)?;
+    validate_viewport(viewport, source_width, source_height)?;
+
+    if !scale.is_finite() || scale <= 0.0 || scale > MAX_RENDER_SCALE {
+        return Err(ProcessingError::InvalidFilterValue {
+            field: "scale".to_string(),
+            value: scale,
+        });
+    }
+
+    let margin = pipeline.neighbourhood_radius().ceil() as u32;
+    let padded = pad_viewport(viewport, margin, source_width, source_height);
+
+    let padded_width = scaled_length(padded.width, scale);
+    let padded_height = scaled_length(padded.height, scale);
+    let cropped = crop_rgba(
+        source,
+        source_width,
+        padded.x,
+        padded.y,
+        padded.width,
+        padded.height,
+    );
+    let mut pixels = resize_rgba8(
+        &cropped,
+        padded.width,
+        padded.height,
+        padded_width,
+        padded_height,
+        ResampleFilter::Box,
+    )?;
+
+    pipeline.execute_at_scale(&mut pixels, padded_width, padded_height, scale)?;
+
+    let offset_x = scaled_offset(viewport.x - padded.x, padded.width, padded_width);
+    let offset_y = scaled_offset(viewport.y - padded.y, padded.height, padded_height);
+    let width = scaled_length(viewport.width, scale).min(padded_width - offset_x);
+    let height = scaled_length(viewport.height, scale).min(padded_height - offset_y);
+
+    Ok(RenderedRegion {
+        pixels: crop_rgba(&pixels, padded_width, offset_x, offset_y, width, height),
+        width,
+        height,
+    })
+}
+
+fn validate_viewport(
+    viewport: Viewport,
+    source_width: u32,
+    source_height: u32,
+) -> Result<(), ProcessingError> {
+    if viewport.width == 0
+        || viewport.height == 0
+        || viewport.right() > source_width as u64
+        || viewport.bottom() > source_height as u64
+    {
+        return Err(ProcessingError::InvalidRegion {
+            x: viewport.x,
+            y: viewport.y,
+            width: viewport.width,
+            height: viewport.height,
+            source_width,
+            source_height,
+        });
+    }
+
+    Ok(())
+}
+
+fn pad_viewport(
+    viewport: Viewport,
+    margin: u32,
+    source_width: u32,
+    source_height: u32,
+) -> Viewport {
+    let x = viewport.x.saturating_sub(margin);
+    let y = viewport.y.saturating_sub(margin);
+    let right = (viewport.right() + margin as u64).min(source_width as u64) as u32;
+    let bottom = (viewport.bottom() + margin as u64).min(source_height as u64) as u32;
+
+    Viewport::new(x, y, right - x, bottom - y)
+}
+
+fn scaled_offset(offset: u32, source_length: u32, output_length: u32) -> u32 {
+    ((offset as u64 * output_length as u64) / source_length as u64) as u32
+}
+
+fn crop_rgba(pixels: &[u8], stride_width: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    let row_bytes = width as usize * 4;
+    let mut output = Vec::with_capacity(row_bytes * height as usize);
+
+    for row in y..y + height {
+        let start = (row as usize * stride_width as usize + x as usize) * 4;
+        output.extend_from_slice(&pixels[start..start + row_bytes]);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighbourhood::SharpenStep;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&[
+                    (x * 255 / width) as u8,
+                    (y * 255 / height) as u8,
+                    ((x * y) % 256) as u8,
+                    255,
+                ]);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn one_to_one_region_matches_crop_of_full_render() {
+        let source = gradient(32, 24);
+        let pipeline = ImagePipeline::new().with_step(SharpenStep::new(1.2, 1.0).unwrap());
+
+        let mut full = source.clone();
+        pipeline.execute(&mut full, 32, 24).unwrap();
+        let viewport = Viewport::new(10, 6, 12, 9);
+
+        let region = render_region(&pipeline, &source, 32, 24, viewport, 1.0).unwrap();
+
+        assert_eq!(region.width, 12);
+        assert_eq!(region.height, 9);
+        assert_eq!(region.pixels, crop_rgba(&full, 32, 10, 6, 12, 9));
+    }
+
+    #[test]
+    fn scaled_region_has_scaled_dimensions() {
+        let source = gradient(40, 20);
+
+        let region = render_region(
+            &ImagePipeline::new(),
+            &source,
+            40,
+            20,
+            Viewport::full(40, 20),
+            0.25,
+        )
+        .unwrap();
+
+        assert_eq!((region.width, region.height), (10, 5));
+        assert_eq!(region.pixels.len(), 10 * 5 * 4);
+    }
+
+    #[test]
+    fn downscaling_matches_shared_resampler() {
+        let source = gradient(16, 8);
+
+        let region = render_region(
+            &ImagePipeline::new(),
+            &source,
+            16,
+            8,
+            Viewport::full(16, 8),
+            0.5,
+        )
+        .unwrap();
+
+        assert_eq!(
+            region.pixels,
+            resize_rgba8(&source, 16, 8, 8, 4, ResampleFilter::Box).unwrap()
+        );
+    }
+
+    #[test]
+    fn upscaling_replicates_source_pixels() {
+        let source = vec![10, 20, 30, 255, 40, 50, 60, 255];
+
+        let region = render_region(
+            &ImagePipeline::new(),
+            &source,
+            2,
+            1,
+            Viewport::new(1, 0, 1, 1),
+            2.0,
+        )
+        .unwrap();
+
+        assert_eq!((region.width, region.height), (2, 2));
+        assert!(region
+            .pixels
+            .chunks_exact(4)
+            .all(|px| px == [40, 50, 60, 255]));
+    }
+
+    #[test]
+    fn rejects_viewport_outside_source() {
+        let source = gradient(8, 8);
+
+        let result = render_region(
+            &ImagePipeline::new(),
+            &source,
+            8,
+            8,
+            Viewport::new(4, 4, 5, 2),
+            1.0,
+        );
+
+        assert!(matches!(result, Err(ProcessingError::InvalidRegion { .. })));
+    }
+
+    #[test]
+    fn rejects_invalid_scale() {
+        let source = gradient(8, 8);
+
+        for scale in [0.0, -1.0, f32::NAN, MAX_RENDER_SCALE * 2.0] {
+            let result = render_region(
+                &ImagePipeline::new(),
+                &source,
+                8,
+                8,
+                Viewport::full(8, 8),
+                scale,
+            );
+            assert!(matches!(
+                result,
+                Err(ProcessingError::InvalidFilterValue { ref field, .. }) if field == "scale"
+            ));
+        }
+    }
+}
@@ -0,0 +1,472 @@
+//! Linear-light separable resampling shared by previews and exports.
+//!
+//! Samples are decoded from sRGB to linear light and premultiplied by alpha
+//! before filtering, so downscaled edges neither darken nor pick up colour
+//! fringes from transparent pixels. The image is filtered horizontally, then
+//! vertically, with per-axis weights widened by the downscale ratio. Source
+//! rows are converted as the vertical pass reaches them, so no float copy of
+//! the full frame is ever held.
+
+use crate::errors::ProcessingError;
+use crate::pipeline::validate_rgba_input;
+use crate::rgba16::{validate_rgba16_input, Sample};
+use std::collections::VecDeque;
+
+/// Reconstruction kernel used by `resize_rgba8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleFilter {
+    /// Area average; fastest, slightly soft.
+    Box,
+    /// Mitchell-Netravali cubic (B = C = 1/3); little ringing.
+    Mitchell,
+    /// Windowed sinc with three lobes; sharpest.
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Box => "box",
+            Self::Mitchell => "mitchell",
+            Self::Lanczos3 => "lanczos3",
+        }
+    }
+
+    fn support(self) -> f32 {
+        match self {
+            Self::Box => 0.5,
+            Self::Mitchell => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            Self::Box => {
+                if (-0.5..0.5).contains(&x) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Mitchell => mitchell(x.abs()),
+            Self::Lanczos3 => {
+                if x.abs() < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for ResampleFilter {
+    type Error = ProcessingError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "box" => Ok(Self::Box),
+            "mitchell" => Ok(Self::Mitchell),
+            "lanczos3" | "lanczos" => Ok(Self::Lanczos3),
+            other => Err(ProcessingError::UnsupportedResampleFilter {
+                name: other.to_string(),
+            }),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let pi_x = std::f32::consts::PI * x;
+        pi_x.sin() / pi_x
+    }
+}
+
+fn mitchell(x: f32) -> f32 {
+    const B: f32 = 1.0 / 3.0;
+    const C: f32 = 1.0 / 3.0;
+
+    if x < 1.0 {
+        ((12.0 - 9.0 * B - 6.0 * C) * x * x * x
+            + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+            + (6.0 - 2.0 * B))
+            / 6.0
+    } else if x < 2.0 {
+        ((-B - 6.0 * C) * x * x * x
+            + (6.0 * B + 30.0 * C) * x * x
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+/// Dimensions fitting `width`x`height` into `long_edge` on its longest side,
+/// preserving aspect ratio. Never returns a zero dimension.
+pub fn long_edge_dimensions(width: u32, height: u32, long_edge: u32) -> (u32, u32) {
+    let scale = long_edge as f64 / width.max(height).max(1) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    (new_width, new_height)
+}
+
+/// Resizes an RGBA8 sRGB buffer to `new_width`x`new_height` in linear light.
+pub fn resize_rgba8(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+    filter: ResampleFilter,
+) -> Result<Vec<u8>, ProcessingError> {
+    validate_rgba_input(pixels, width, height)?;
+    if new_width == 0 || new_height == 0 {
+        return Err(ProcessingError::InvalidDimensions {
+            width: new_width,
+            height: new_height,
+        });
+    }
+
+    if (width, height) == (new_width, new_height) {
+        return Ok(pixels.to_vec());
+    }
+
+    let decode = srgb_to_linear_table();
+    let row_len = width as usize * 4;
+    Ok(resample_premultiplied(
+        (width, height),
+        (new_width, new_height),
+        filter,
+        |y, row| {
+            let source = &pixels[y * row_len..(y + 1) * row_len];
+            for (px, out) in source.chunks_exact(4).zip(row.chunks_exact_mut(4)) {
+                let alpha = px[3] as f32 / 255.0;
+                out[0] = decode[px[0] as usize] * alpha;
+                out[1] = decode[px[1] as usize] * alpha;
+                out[2] = decode[px[2] as usize] * alpha;
+                out[3] = alpha;
+            }
+        },
+    ))
+}
+
+/// `resize_rgba8` on an RGBA16 sRGB buffer, decoding every sample exactly
+/// instead of through the 8-bit table.
+pub fn resize_rgba16(
+    pixels: &[u16],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+    filter: ResampleFilter,
+) -> Result<Vec<u16>, ProcessingError> {
+    validate_rgba16_input(pixels, width, height)?;
+    if new_width == 0 || new_height == 0 {
+        return Err(ProcessingError::InvalidDimensions {
+            width: new_width,
+            height: new_height,
+        });
+    }
+
+    if (width, height) == (new_width, new_height) {
+        return Ok(pixels.to_vec());
+    }
+
+    let row_len = width as usize * 4;
+    Ok(resample_premultiplied(
+        (width, height),
+        (new_width, new_height),
+        filter,
+        |y, row| {
+            let source = &pixels[y * row_len..(y + 1) * row_len];
+            for (px, out) in source.chunks_exact(4).zip(row.chunks_exact_mut(4)) {
+                let alpha = px[3] as f32 / 65_535.0;
+                out[0] = srgb_decode(px[0] as f32 / 65_535.0) * alpha;
+                out[1] = srgb_decode(px[1] as f32 / 65_535.0) * alpha;
+                out[2] = srgb_decode(px[2] as f32 / 65_535.0) * alpha;
+                out[3] = alpha;
+            }
+        },
+    ))
+}
+
+/// Filters premultiplied linear RGBA horizontally, then vertically, and
+/// re-encodes the result.
+///
+/// `decode_row(y, row)` fills `row` with source row `y` in premultiplied
+/// linear light. Rows are decoded and filtered horizontally only while an
+/// output row needs them, so memory stays at a window of filtered rows plus
+/// the output instead of a full-resolution float copy of the source.
+fn resample_premultiplied<S: Sample + Default>(
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+    filter: ResampleFilter,
+    mut decode_row: impl FnMut(usize, &mut [f32]),
+) -> Vec<S> {
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    let horizontal = contributions(width, new_width, filter);
+    let vertical = contributions(height, new_height, filter);
+
+    let mut source_row = vec![0.0_f32; width * 4];
+    // Horizontally filtered source rows `window_start..window_start + len`.
+    let mut window: VecDeque<Vec<f32>> = VecDeque::new();
+    let mut spare: Vec<Vec<f32>> = Vec::new();
+    let mut window_start = 0;
+    let mut sums = vec![0.0_f32; new_width * 4];
+    let mut output = Vec::with_capacity(new_width * new_height * 4);
+
+    for contribution in &vertical {
+        let needed = contribution.start..contribution.start + contribution.weights.len();
+        if needed.start < window_start {
+            spare.extend(window.drain(..));
+            window_start = needed.start;
+        }
+        while window_start < needed.start {
+            spare.extend(window.pop_front());
+            window_start += 1;
+        }
+        while window_start + window.len() < needed.end {
+            decode_row(window_start + window.len(), &mut source_row);
+            let mut filtered = spare.pop().unwrap_or_else(|| vec![0.0_f32; new_width * 4]);
+            resample_row(&source_row, &horizontal, &mut filtered);
+            window.push_back(filtered);
+        }
+
+        sums.fill(0.0);
+        for (row, weight) in window.iter().zip(&contribution.weights) {
+            for (sum, sample) in sums.iter_mut().zip(row) {
+                *sum += sample * weight;
+            }
+        }
+        output.extend(sums.chunks_exact(4).flat_map(unpremultiply::<S>));
+    }
+
+    output
+}
+
+/// Divides colour by alpha and re-encodes it to sRGB samples.
+fn unpremultiply<S: Sample + Default>(px: &[f32]) -> [S; 4] {
+    let alpha = px[3].clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return [S::default(); 4];
+    }
+    [
+        S::from_f32(srgb_encode(px[0] / alpha) * S::FULL_SCALE),
+        S::from_f32(srgb_encode(px[1] / alpha) * S::FULL_SCALE),
+        S::from_f32(srgb_encode(px[2] / alpha) * S::FULL_SCALE),
+        S::from_f32(alpha * S::FULL_SCALE),
+    ]
+}
+
+/// Weights of the source samples contributing to one output sample.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn contributions(
+    source_len: usize,
+    output_len: usize,
+    filter: ResampleFilter,
+) -> Vec<Contribution> {
+    let ratio = source_len as f32 / output_len as f32;
+    let filter_scale = ratio.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..output_len)
+        .map(|index| {
+            let center = (index as f32 + 0.5) * ratio;
+            let start = ((center - support).floor().max(0.0) as usize).min(source_len - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, source_len);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|source| filter.weight((source as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+
+            if sum.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|weight| *weight /= sum);
+                Contribution { start, weights }
+            } else {
+                let nearest = (center as usize).min(source_len - 1);
+                Contribution {
+                    start: nearest,
+                    weights: vec![1.0],
+                }
+            }
+        })
+        .collect()
+}
+
+fn resample_row(row: &[f32], contributions: &[Contribution], output: &mut [f32]) {
+    for (contribution, out) in contributions.iter().zip(output.chunks_exact_mut(4)) {
+        let mut sum = [0.0_f32; 4];
+        for (offset, weight) in contribution.weights.iter().enumerate() {
+            let source = (contribution.start + offset) * 4;
+            for channel in 0..4 {
+                sum[channel] += row[source + channel] * weight;
+            }
+        }
+        out.copy_from_slice(&sum);
+    }
+}
+
+fn srgb_to_linear_table() -> [f32; 256] {
+    let mut table = [0.0_f32; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = srgb_decode(value as f32 / 255.0);
+    }
+    table
+}
+
+fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light to sRGB in [0, 1].
+fn srgb_encode(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [ResampleFilter; 3] = [
+        ResampleFilter::Box,
+        ResampleFilter::Mitchell,
+        ResampleFilter::Lanczos3,
+    ];
+
+    fn solid(width: u32, height: u32, px: [u8; 4]) -> Vec<u8> {
+        px.repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn srgb_round_trip_is_exact_for_all_levels() {
+        let decode = srgb_to_linear_table();
+
+        for value in 0..=255_u8 {
+            assert_eq!(
+                u8::from_f32(srgb_encode(decode[value as usize]) * 255.0),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn solid_colour_survives_every_filter() {
+        let source = solid(9, 7, [37, 180, 220, 255]);
+
+        for filter in FILTERS {
+            let resized = resize_rgba8(&source, 9, 7, 4, 3, filter).unwrap();
+            assert_eq!(resized, solid(4, 3, [37, 180, 220, 255]), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn box_downscale_averages_in_linear_light() {
+        let source = vec![0, 0, 0, 255, 255, 255, 255, 255];
+
+        let resized = resize_rgba8(&source, 2, 1, 1, 1, ResampleFilter::Box).unwrap();
+
+        // Linear midpoint of black and white is sRGB 188, not 128.
+        assert_eq!(resized, vec![188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn transparent_pixels_do_not_bleed_colour() {
+        let source = vec![255, 0, 0, 0, 0, 0, 255, 255];
+
+        let resized = resize_rgba8(&source, 2, 1, 1, 1, ResampleFilter::Box).unwrap();
+
+        assert_eq!(&resized[..3], &[0, 0, 255]);
+        assert_eq!(resized[3], 128);
+    }
+
+    #[test]
+    fn rgba16_downscale_tracks_rgba8_and_keeps_fine_levels() {
+        let source8: Vec<u8> = (0..8 * 6)
+            .flat_map(|index| [(index * 5) as u8, 200 - index as u8, 90, 255])
+            .collect();
+        let source16: Vec<u16> = source8.iter().map(|&v| v as u16 * 257).collect();
+
+        let resized8 = resize_rgba8(&source8, 8, 6, 3, 2, ResampleFilter::Mitchell).unwrap();
+        let resized16 = resize_rgba16(&source16, 8, 6, 3, 2, ResampleFilter::Mitchell).unwrap();
+        for (&narrow, &wide) in resized8.iter().zip(&resized16) {
+            assert!((narrow as f32 - wide as f32 / 257.0).abs() <= 0.5 + 1e-3);
+        }
+
+        // A level between two 8-bit steps survives a 16-bit resize.
+        let fine = [12_345_u16, 40_001, 777, 65_535].repeat(16);
+        let resized = resize_rgba16(&fine, 4, 4, 2, 2, ResampleFilter::Lanczos3).unwrap();
+        for px in resized.chunks_exact(4) {
+            for (&got, &want) in px.iter().zip(&fine[..4]) {
+                assert!(got.abs_diff(want) <= 2, "{got} vs {want}");
+            }
+        }
+    }
+
+    #[test]
+    fn upscale_produces_requested_dimensions() {
+        let source = vec![10, 20, 30, 255, 200, 100, 50, 255];
+
+        for filter in FILTERS {
+            let resized = resize_rgba8(&source, 2, 1, 5, 3, filter).unwrap();
+            assert_eq!(resized.len(), 5 * 3 * 4);
+        }
+    }
+
+    #[test]
+    fn same_size_is_identity() {
+        let source = vec![1, 2, 3, 4, 5, 6, 7, 8];
+
+        let resized = resize_rgba8(&source, 2, 1, 2, 1, ResampleFilter::Lanczos3).unwrap();
+
+        assert_eq!(resized, source);
+    }
+
+    #[test]
+    fn rejects_zero_target_dimensions() {
+        let source = solid(2, 2, [0, 0, 0, 255]);
+
+        let result = resize_rgba8(&source, 2, 2, 0, 1, ResampleFilter::Mitchell);
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::InvalidDimensions {
+                width: 0,
+                height: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn long_edge_dimensions_preserve_aspect_ratio() {
+        assert_eq!(long_edge_dimensions(6000, 4000, 1500), (1500, 1000));
+        assert_eq!(long_edge_dimensions(4000, 6000, 240), (160, 240));
+        assert_eq!(long_edge_dimensions(10000, 10, 100), (100, 1));
+    }
+
+    #[test]
+    fn filter_names_parse() {
+        for filter in FILTERS {
+            assert_eq!(ResampleFilter::try_from(filter.as_str()).unwrap(), filter);
+        }
+        assert!(ResampleFilter::try_from("bicubic").is_err());
+    }
+}
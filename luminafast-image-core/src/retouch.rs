@@ -0,0 +1,591 @@
+//! Spot healing and clone stamp retouching.
+//!
+//! A `RetouchSpot` pairs a target area (one point for a circular spot, a
+//! polyline for a brushed stroke) with the source position it is painted from.
+//! Coordinates are normalized to the image size and the radius to its longest
+//! side, so the same spots replay on a downscaled preview and a full-size
+//! export.
+//!
+//! - Clone copies source pixels straight onto the target.
+//! - Heal solves a Poisson equation inside the target: it keeps the source
+//!   texture (its Laplacian) while matching the colour of the target's
+//!   surroundings at the boundary, using a fixed number of Gauss-Seidel
+//!   iterations so results are deterministic on every platform.
+//!
+//! Spots are applied in order and each one sees the result of the previous.
+//! Coordinates refer to the full frame, so the step is meant for full-frame
+//! renders rather than `render_region` crops.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ProcessingError;
+use crate::pipeline::{validate_rgba_input, ImagePipelineStep};
+use crate::rgba16::{validate_rgba16_input, Sample};
+
+/// Gauss-Seidel iterations used to solve the heal equation.
+const HEAL_ITERATIONS: usize = 120;
+/// Largest accepted radius, as a fraction of the longest image side.
+pub const MAX_RETOUCH_RADIUS: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetouchMode {
+    Heal,
+    Clone,
+}
+
+/// Point in normalized image coordinates (`0.0..=1.0` on each axis).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetouchPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetouchSpot {
+    pub mode: RetouchMode,
+    /// Target centre line; a single point is a circular spot.
+    pub target: Vec<RetouchPoint>,
+    /// Source position matching the first target point; the whole stroke is
+    /// sampled with the same offset.
+    pub source: RetouchPoint,
+    /// Brush radius as a fraction of the longest image side.
+    pub radius: f32,
+    /// Fraction of the radius used for the soft edge, in [0.0, 1.0].
+    #[serde(default)]
+    pub feather: f32,
+    /// Blend strength in [0.0, 1.0].
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+impl RetouchSpot {
+    pub fn validate(&self) -> Result<(), ProcessingError> {
+        if self.target.is_empty() {
+            return Err(invalid_retouch("target must contain at least one point"));
+        }
+
+        let coordinates = self
+            .target
+            .iter()
+            .chain(std::iter::once(&self.source))
+            .flat_map(|point| [point.x, point.y]);
+        if coordinates.into_iter().any(|value| !value.is_finite()) {
+            return Err(invalid_retouch("coordinates must be finite"));
+        }
+
+        if !self.radius.is_finite() || self.radius <= 0.0 || self.radius > MAX_RETOUCH_RADIUS {
+            return Err(invalid_retouch(&format!(
+                "radius {} outside (0, {}]",
+                self.radius, MAX_RETOUCH_RADIUS
+            )));
+        }
+
+        for (name, value) in [("feather", self.feather), ("opacity", self.opacity)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(invalid_retouch(&format!("{name} {value} outside [0, 1]")));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_retouch(message: &str) -> ProcessingError {
+    ProcessingError::InvalidRetouch {
+        message: message.to_string(),
+    }
+}
+
+/// Pipeline step replaying retouch spots in order.
+pub struct RetouchStep {
+    spots: Vec<RetouchSpot>,
+    cache_key: u64,
+}
+
+impl RetouchStep {
+    pub fn new(spots: Vec<RetouchSpot>) -> Result<Self, ProcessingError> {
+        for spot in &spots {
+            spot.validate()?;
+        }
+
+        let serialized =
+            serde_json::to_string(&spots).map_err(|e| ProcessingError::InvalidRetouch {
+                message: e.to_string(),
+            })?;
+        let mut hasher = DefaultHasher::new();
+        "retouch".hash(&mut hasher);
+        serialized.hash(&mut hasher);
+
+        Ok(Self {
+            spots,
+            cache_key: hasher.finish(),
+        })
+    }
+}
+
+impl ImagePipelineStep for RetouchStep {
+    fn apply(&self, pixels: &mut [u8], width: u32, height: u32) -> Result<(), ProcessingError> {
+        apply_retouch(pixels, width, height, &self.spots)
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        Some(self.cache_key)
+    }
+}
+
+/// Applies `spots` in order to an RGBA buffer, preserving alpha.
+pub fn apply_retouch(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    spots: &[RetouchSpot],
+) -> Result<(), ProcessingError> {
+    validate_rgba_input(pixels, width, height)?;
+
+    for spot in spots {
+        spot.validate()?;
+        apply_spot(pixels, width as usize, height as usize, spot);
+    }
+
+    Ok(())
+}
+
+/// `apply_retouch` on an RGBA16 buffer; spots solve in 16-bit sample units.
+pub fn apply_retouch_rgba16(
+    pixels: &mut [u16],
+    width: u32,
+    height: u32,
+    spots: &[RetouchSpot],
+) -> Result<(), ProcessingError> {
+    validate_rgba16_input(pixels, width, height)?;
+
+    for spot in spots {
+        spot.validate()?;
+        apply_spot(pixels, width as usize, height as usize, spot);
+    }
+
+    Ok(())
+}
+
+/// Target area of one spot in pixel coordinates.
+struct SpotGeometry {
+    segments: Vec<([f32; 2], [f32; 2])>,
+    radius: f32,
+    inner_radius: f32,
+    offset: [isize; 2],
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl SpotGeometry {
+    fn new(spot: &RetouchSpot, width: usize, height: usize) -> Self {
+        let to_pixels = |point: &RetouchPoint| [point.x * width as f32, point.y * height as f32];
+        let points: Vec<[f32; 2]> = spot.target.iter().map(to_pixels).collect();
+        let segments = if points.len() == 1 {
+            vec![(points[0], points[0])]
+        } else {
+            points.windows(2).map(|pair| (pair[0], pair[1])).collect()
+        };
+
+        let radius = (spot.radius * width.max(height) as f32).max(0.5);
+        let source = to_pixels(&spot.source);
+        let offset = [
+            (source[0] - points[0][0]).round() as isize,
+            (source[1] - points[0][1]).round() as isize,
+        ];
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for [x, y] in &points {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+
+        // One extra pixel so every masked pixel has its neighbours in the box.
+        let clamp_x = |value: f32| value.clamp(0.0, width as f32) as usize;
+        let clamp_y = |value: f32| value.clamp(0.0, height as f32) as usize;
+
+        Self {
+            segments,
+            radius,
+            inner_radius: radius * (1.0 - spot.feather),
+            offset,
+            x0: clamp_x((min_x - radius).floor() - 1.0),
+            y0: clamp_y((min_y - radius).floor() - 1.0),
+            x1: clamp_x((max_x + radius).ceil() + 1.0),
+            y1: clamp_y((max_y + radius).ceil() + 1.0),
+        }
+    }
+
+    fn box_width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    fn box_height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    /// Coverage in [0, 1] of the pixel centred at (`x + 0.5`, `y + 0.5`).
+    fn coverage(&self, x: usize, y: usize) -> f32 {
+        let point = [x as f32 + 0.5, y as f32 + 0.5];
+        let distance = self
+            .segments
+            .iter()
+            .map(|(a, b)| distance_to_segment(point, *a, *b))
+            .fold(f32::MAX, f32::min);
+
+        if distance <= self.inner_radius {
+            1.0
+        } else if distance >= self.radius {
+            0.0
+        } else {
+            (self.radius - distance) / (self.radius - self.inner_radius)
+        }
+    }
+}
+
+fn distance_to_segment(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [point[0] - a[0], point[1] - a[1]];
+    let length_sq = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if length_sq > 0.0 {
+        ((ap[0] * ab[0] + ap[1] * ab[1]) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let dx = ap[0] - ab[0] * t;
+    let dy = ap[1] - ab[1] * t;
+    (dx * dx + dy * dy).sqrt()
+}
+
+fn apply_spot<S: Sample>(pixels: &mut [S], width: usize, height: usize, spot: &RetouchSpot) {
+    let geometry = SpotGeometry::new(spot, width, height);
+    let box_width = geometry.box_width();
+    let box_height = geometry.box_height();
+    if box_width == 0 || box_height == 0 {
+        return;
+    }
+
+    let len = box_width * box_height;
+    let mut mask = vec![0.0_f32; len];
+    let mut target = vec![[0.0_f32; 3]; len];
+    let mut source = vec![[0.0_f32; 3]; len];
+
+    for by in 0..box_height {
+        for bx in 0..box_width {
+            let x = geometry.x0 + bx;
+            let y = geometry.y0 + by;
+            let index = by * box_width + bx;
+
+            mask[index] = geometry.coverage(x, y);
+            target[index] = read_rgb(pixels, width, x, y);
+
+            let sx = (x as isize + geometry.offset[0]).clamp(0, width as isize - 1) as usize;
+            let sy = (y as isize + geometry.offset[1]).clamp(0, height as isize - 1) as usize;
+            source[index] = read_rgb(pixels, width, sx, sy);
+        }
+    }
+
+    let patch = match spot.mode {
+        RetouchMode::Clone => source,
+        RetouchMode::Heal => solve_heal(&mask, &target, &source, box_width, box_height),
+    };
+
+    for by in 0..box_height {
+        for bx in 0..box_width {
+            let index = by * box_width + bx;
+            let weight = mask[index] * spot.opacity;
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let offset = ((geometry.y0 + by) * width + geometry.x0 + bx) * 4;
+            for channel in 0..3 {
+                let original = target[index][channel];
+                let value = original + (patch[index][channel] - original) * weight;
+                pixels[offset + channel] = S::from_f32(value);
+            }
+        }
+    }
+}
+
+fn read_rgb<S: Sample>(pixels: &[S], width: usize, x: usize, y: usize) -> [f32; 3] {
+    let offset = (y * width + x) * 4;
+    [
+        pixels[offset].to_f32(),
+        pixels[offset + 1].to_f32(),
+        pixels[offset + 2].to_f32(),
+    ]
+}
+
+/// Solves `laplacian(f) = laplacian(source)` inside the mask with `f = target`
+/// on the boundary, starting from the source shifted to the boundary mean.
+fn solve_heal(
+    mask: &[f32],
+    target: &[[f32; 3]],
+    source: &[[f32; 3]],
+    width: usize,
+    height: usize,
+) -> Vec<[f32; 3]> {
+    let inside = |index: usize| mask[index] > 0.0;
+    let neighbours = |index: usize| {
+        let x = index % width;
+        let y = index / width;
+        [
+            (x > 0).then(|| index - 1),
+            (x + 1 < width).then(|| index + 1),
+            (y > 0).then(|| index - width),
+            (y + 1 < height).then(|| index + width),
+        ]
+    };
+
+    let mut boundary_target = [0.0_f32; 3];
+    let mut boundary_source = [0.0_f32; 3];
+    let mut boundary_count = 0_usize;
+    for index in (0..mask.len()).filter(|&index| !inside(index)) {
+        if neighbours(index).into_iter().flatten().any(inside) {
+            for channel in 0..3 {
+                boundary_target[channel] += target[index][channel];
+                boundary_source[channel] += source[index][channel];
+            }
+            boundary_count += 1;
+        }
+    }
+
+    let shift: [f32; 3] = if boundary_count > 0 {
+        std::array::from_fn(|channel| {
+            (boundary_target[channel] - boundary_source[channel]) / boundary_count as f32
+        })
+    } else {
+        [0.0; 3]
+    };
+
+    let mut solution: Vec<[f32; 3]> = (0..mask.len())
+        .map(|index| {
+            if inside(index) {
+                std::array::from_fn(|channel| source[index][channel] + shift[channel])
+            } else {
+                target[index]
+            }
+        })
+        .collect();
+
+    for _ in 0..HEAL_ITERATIONS {
+        for index in (0..mask.len()).filter(|&index| inside(index)) {
+            let mut sum = [0.0_f32; 3];
+            let mut count = 0.0_f32;
+
+            for neighbour in neighbours(index).into_iter().flatten() {
+                for channel in 0..3 {
+                    sum[channel] += solution[neighbour][channel] + source[index][channel]
+                        - source[neighbour][channel];
+                }
+                count += 1.0;
+            }
+
+            if count > 0.0 {
+                solution[index] = std::array::from_fn(|channel| sum[channel] / count);
+            }
+        }
+    }
+
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_image(width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        for _y in 0..height {
+            for x in 0..width {
+                let value = if x < width / 2 { 40 } else { 200 };
+                pixels.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        pixels
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * width + x) * 4) as usize;
+        [
+            pixels[offset],
+            pixels[offset + 1],
+            pixels[offset + 2],
+            pixels[offset + 3],
+        ]
+    }
+
+    fn spot(
+        mode: RetouchMode,
+        target: &[(f32, f32)],
+        source: (f32, f32),
+        radius: f32,
+    ) -> RetouchSpot {
+        RetouchSpot {
+            mode,
+            target: target.iter().map(|&(x, y)| RetouchPoint { x, y }).collect(),
+            source: RetouchPoint {
+                x: source.0,
+                y: source.1,
+            },
+            radius,
+            feather: 0.0,
+            opacity: 1.0,
+        }
+    }
+
+    #[test]
+    fn clone_copies_source_pixels_onto_target() {
+        let mut pixels = split_image(20, 10);
+        let spots = [spot(RetouchMode::Clone, &[(0.25, 0.5)], (0.75, 0.5), 0.1)];
+
+        apply_retouch(&mut pixels, 20, 10, &spots).unwrap();
+
+        assert_eq!(pixel(&pixels, 20, 5, 5), [200, 200, 200, 255]);
+        assert_eq!(pixel(&pixels, 20, 0, 0), [40, 40, 40, 255]);
+    }
+
+    #[test]
+    fn heal_removes_dust_spot_and_matches_surroundings() {
+        let mut pixels = vec![0_u8; 24 * 24 * 4];
+        for y in 0..24 {
+            for x in 0..24 {
+                let offset = ((y * 24 + x) * 4) as usize;
+                let value = 100 + (x as u8 % 4);
+                let left_half = if x < 12 { value } else { value + 60 };
+                pixels[offset..offset + 4].copy_from_slice(&[left_half, left_half, left_half, 255]);
+            }
+        }
+        // Dark dust speck on the left half.
+        for y in 5..8 {
+            for x in 5..8 {
+                let offset = ((y * 24 + x) * 4) as usize;
+                pixels[offset..offset + 3].copy_from_slice(&[10, 10, 10]);
+            }
+        }
+
+        // Texture is taken from the brighter right half but must adopt the left half's tone.
+        let spots = [spot(
+            RetouchMode::Heal,
+            &[(6.5 / 24.0, 6.5 / 24.0)],
+            (18.5 / 24.0, 6.5 / 24.0),
+            3.0 / 24.0,
+        )];
+        apply_retouch(&mut pixels, 24, 24, &spots).unwrap();
+
+        let healed = pixel(&pixels, 24, 6, 6);
+        assert!(
+            (95..=110).contains(&healed[0]),
+            "healed value {}",
+            healed[0]
+        );
+        assert_eq!(healed[3], 255);
+    }
+
+    #[test]
+    fn brushed_stroke_covers_the_whole_polyline() {
+        let mut pixels = split_image(40, 10);
+        let spots = [spot(
+            RetouchMode::Clone,
+            &[(0.05, 0.2), (0.4, 0.2)],
+            (0.6, 0.2),
+            0.03,
+        )];
+
+        apply_retouch(&mut pixels, 40, 10, &spots).unwrap();
+
+        for x in [2, 8, 15] {
+            assert_eq!(pixel(&pixels, 40, x, 2)[0], 200, "x = {}", x);
+        }
+        assert_eq!(pixel(&pixels, 40, 8, 8)[0], 40);
+    }
+
+    #[test]
+    fn opacity_and_feather_blend_partially() {
+        let mut pixels = split_image(20, 10);
+        let mut half = spot(RetouchMode::Clone, &[(0.25, 0.5)], (0.75, 0.5), 0.1);
+        half.opacity = 0.5;
+
+        apply_retouch(&mut pixels, 20, 10, &[half]).unwrap();
+
+        assert_eq!(pixel(&pixels, 20, 5, 5)[0], 120);
+    }
+
+    #[test]
+    fn retouch_is_deterministic_and_cache_keyed_by_spots() {
+        let spots = vec![spot(RetouchMode::Heal, &[(0.3, 0.3)], (0.7, 0.6), 0.1)];
+        let step = RetouchStep::new(spots.clone()).unwrap();
+
+        let mut first = split_image(16, 16);
+        step.apply(&mut first, 16, 16).unwrap();
+        let mut second = split_image(16, 16);
+        RetouchStep::new(spots)
+            .unwrap()
+            .apply(&mut second, 16, 16)
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(
+            step.cache_key(),
+            RetouchStep::new(vec![spot(
+                RetouchMode::Heal,
+                &[(0.3, 0.3)],
+                (0.7, 0.6),
+                0.1
+            )])
+            .unwrap()
+            .cache_key()
+        );
+        assert_ne!(
+            step.cache_key(),
+            RetouchStep::new(vec![spot(
+                RetouchMode::Clone,
+                &[(0.3, 0.3)],
+                (0.7, 0.6),
+                0.1
+            )])
+            .unwrap()
+            .cache_key()
+        );
+    }
+
+    #[test]
+    fn spots_deserialize_from_camel_case_json() {
+        let parsed: RetouchSpot = serde_json::from_str(
+            r#"{"mode":"heal","target":[{"x":0.1,"y":0.2}],"source":{"x":0.4,"y":0.2},"radius":0.02}"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.mode, RetouchMode::Heal);
+        assert_eq!(parsed.feather, 0.0);
+        assert_eq!(parsed.opacity, 1.0);
+    }
+
+    #[test]
+    fn invalid_spots_are_rejected() {
+        let mut pixels = split_image(4, 4);
+        let mut empty = spot(RetouchMode::Clone, &[], (0.5, 0.5), 0.1);
+        assert!(apply_retouch(&mut pixels, 4, 4, std::slice::from_ref(&empty)).is_err());
+
+        empty.target.push(RetouchPoint { x: 0.2, y: 0.2 });
+        empty.radius = 0.0;
+        assert!(matches!(
+            RetouchStep::new(vec![empty]),
+            Err(ProcessingError::InvalidRetouch { .. })
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+//! 16-bit RGBA rendering for high-precision exports.
+//!
+//! A 16-bit sample `v` maps to the 8-bit domain as `v / 257`, so the filter
+//! chain, retouch spots and resampling are the same algorithms as the RGBA8
+//! path evaluated on finer input. Filters run per pixel rather than through
+//! the LUT compile stage so the extra precision survives to the output.
+
+use crate::errors::ProcessingError;
+use crate::filters::{transform_rgb, PixelFilters};
+use crate::raw_decoder::{LinearImage, RawDecoder};
+
+/// Scale between a 16-bit sample and the 8-bit domain of the filter chain.
+const U16_PER_U8: f32 = 257.0;
+
+/// Integer channel sample shared by the RGBA8 and RGBA16 paths.
+pub(crate) trait Sample: Copy {
+    /// Largest sample value, also used for opaque alpha.
+    const FULL_SCALE: f32;
+
+    fn to_f32(self) -> f32;
+
+    /// Rounds and clamps `value` to `[0, FULL_SCALE]`.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for u8 {
+    const FULL_SCALE: f32 = 255.0;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, Self::FULL_SCALE) as u8
+    }
+}
+
+impl Sample for u16 {
+    const FULL_SCALE: f32 = 65_535.0;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, Self::FULL_SCALE) as u16
+    }
+}
+
+pub fn validate_rgba16_input(
+    pixels: &[u16],
+    width: u32,
+    height: u32,
+) -> Result<(), ProcessingError> {
+    if width == 0 || height == 0 {
+        return Err(ProcessingError::InvalidDimensions { width, height });
+    }
+
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|px| px.checked_mul(4))
+        .ok_or(ProcessingError::InvalidDimensions { width, height })?;
+
+    if pixels.len() != expected {
+        return Err(ProcessingError::InvalidPixelCount {
+            expected,
+            got: pixels.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Applies the global filter chain to an RGBA16 buffer in place, preserving
+/// alpha.
+pub fn apply_filters_rgba16(
+    pixels: &mut [u16],
+    width: u32,
+    height: u32,
+    filters: &PixelFilters,
+) -> Result<(), ProcessingError> {
+    validate_rgba16_input(pixels, width, height)?;
+
+    for chunk in pixels.chunks_exact_mut(4) {
+        let rgb = [chunk[0], chunk[1], chunk[2]].map(|sample| sample as f32 / U16_PER_U8);
+        let transformed = transform_rgb(rgb, filters);
+        for (sample, value) in chunk.iter_mut().zip(transformed) {
+            *sample = u16::from_f32(value * U16_PER_U8);
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes RAW bytes to opaque RGBA16, keeping the decoder's linear samples
+/// at 16-bit precision.
+pub fn decode_raw_to_rgba16(
+    decoder: &dyn RawDecoder,
+    input: &[u8],
+) -> Result<(Vec<u16>, u32, u32), ProcessingError> {
+    let linear_image = decoder.decode_to_linear_rgb(input)?;
+    let rgba = linear_image_to_rgba16(&linear_image)?;
+    Ok((rgba, linear_image.width, linear_image.height))
+}
+
+/// Converts `linear_image` to opaque RGBA16 with the same mapping as
+/// `write_linear_image_rgba8`.
+pub fn linear_image_to_rgba16(linear_image: &LinearImage) -> Result<Vec<u16>, ProcessingError> {
+    let width = linear_image.width;
+    let height = linear_image.height;
+
+    if width == 0 || height == 0 {
+        return Err(ProcessingError::InvalidDimensions { width, height });
+    }
+
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or(ProcessingError::InvalidDimensions { width, height })?;
+    let expected_rgb = pixel_count
+        .checked_mul(3)
+        .ok_or(ProcessingError::InvalidDimensions { width, height })?;
+
+    if linear_image.pixels_rgb_f32.len() != expected_rgb {
+        return Err(ProcessingError::InvalidPixelCount {
+            expected: expected_rgb,
+            got: linear_image.pixels_rgb_f32.len(),
+        });
+    }
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for (index, rgb) in linear_image.pixels_rgb_f32.chunks_exact(3).enumerate() {
+        for (channel, sample) in rgb.iter().enumerate() {
+            if !sample.is_finite() {
+                return Err(ProcessingError::InvalidFilterValue {
+                    field: format!("pixels_rgb_f32[{}]", index * 3 + channel),
+                    value: *sample,
+                });
+            }
+            rgba.push(u16::from_f32(sample.clamp(0.0, 1.0) * u16::FULL_SCALE));
+        }
+
+        rgba.push(u16::MAX);
+    }
+
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_samples_keep_16_bit_precision() {
+        let linear =
+            LinearImage::new(2, 1, vec![0.0, 0.25, 1.0, 0.5, 1.0 / 65_535.0, 2.0]).unwrap();
+
+        let rgba = linear_image_to_rgba16(&linear).unwrap();
+
+        assert_eq!(
+            rgba,
+            vec![0, 16_384, 65_535, 65_535, 32_768, 1, 65_535, 65_535]
+        );
+    }
+
+    #[test]
+    fn filters_preserve_alpha_and_keep_identity_exact() {
+        let mut pixels = vec![12_345_u16, 40_001, 777, 1_000];
+        apply_filters_rgba16(&mut pixels, 1, 1, &PixelFilters::default()).unwrap();
+        assert_eq!(pixels, vec![12_345, 40_001, 777, 1_000]);
+
+        let brighter = PixelFilters {
+            exposure: 0.5,
+            ..PixelFilters::default()
+        };
+        apply_filters_rgba16(&mut pixels, 1, 1, &brighter).unwrap();
+        assert!(pixels[0] > 12_345 && pixels[1] > 40_001);
+        assert_eq!(pixels[3], 1_000);
+    }
+
+    #[test]
+    fn rejects_mismatched_buffers() {
+        let mut pixels = vec![0_u16; 7];
+        assert!(matches!(
+            apply_filters_rgba16(&mut pixels, 1, 2, &PixelFilters::default()),
+            Err(ProcessingError::InvalidPixelCount {
+                expected: 8,
+                got: 7
+            })
+        ));
+    }
+}
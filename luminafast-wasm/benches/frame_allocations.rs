@@ -0,0 +1,153 @@
+//! Allocations par frame du rendu preview.
+//!
+//! Compte les allocations (nombre et octets) d'un tick de slider pour :
+//! - `PixelFiltersWasm::apply_filters` (API historique, copie + nouveau buffer)
+//! - `PipelineBuilder::render` avec la recette inchangée
+//! - `PipelineBuilder::set_recipe` + `render` (tick de slider réel)
+//!
+//! Usage : `cargo bench --bench frame_allocations [-- <largeur> <hauteur>]`
+//! (défaut : 6000x4000, soit 24 MP). Échoue si le rendu via `PipelineBuilder`
+//! alloue un buffer de la taille de l'image en régime établi.
+
+use luminafast_wasm::{PipelineBuilder, PixelFiltersWasm};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn record(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed);
+    LARGEST_ALLOCATION.fetch_max(size, Ordering::Relaxed);
+}
+
+struct FrameStats {
+    allocations: f64,
+    bytes: f64,
+    largest: usize,
+    millis: f64,
+}
+
+fn measure(frames: usize, mut frame: impl FnMut(usize)) -> FrameStats {
+    // Première frame hors mesure : dimensionne les buffers réutilisés.
+    frame(0);
+
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    ALLOCATED_BYTES.store(0, Ordering::Relaxed);
+    LARGEST_ALLOCATION.store(0, Ordering::Relaxed);
+    let start = Instant::now();
+    for index in 1..=frames {
+        frame(index);
+    }
+
+    FrameStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) as f64 / frames as f64,
+        bytes: ALLOCATED_BYTES.load(Ordering::Relaxed) as f64 / frames as f64,
+        largest: LARGEST_ALLOCATION.load(Ordering::Relaxed),
+        millis: start.elapsed().as_secs_f64() * 1000.0 / frames as f64,
+    }
+}
+
+fn report(label: &str, stats: &FrameStats) {
+    println!(
+        "{:<28} {:>8.1} alloc/frame {:>14.0} B/frame  max {:>11} B  {:>8.2} ms/frame",
+        label, stats.allocations, stats.bytes, stats.largest, stats.millis
+    );
+}
+
+fn recipe_json(exposure: usize) -> String {
+    format!(
+        r#"{{"schemaVersion":1,"processVersion":1,"adjustments":{{"exposure":{},"contrast":12,"saturation":8}}}}"#,
+        exposure % 40
+    )
+}
+
+fn main() {
+    let mut args = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<u32>().ok());
+    let width = args.next().unwrap_or(6000);
+    let height = args.next().unwrap_or(4000);
+    let frames = 10;
+    let frame_bytes = width as usize * height as usize * 4;
+
+    let pixels: Vec<u8> = (0..frame_bytes).map(|index| (index % 251) as u8).collect();
+    println!(
+        "frame_allocations: {}x{} ({} B/frame)",
+        width, height, frame_bytes
+    );
+
+    let legacy = measure(frames, |index| {
+        let filters = PixelFiltersWasm::new(
+            (index % 40) as f32 / 50.0,
+            0.24,
+            1.08,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            5500.0,
+            0.0,
+        );
+        let output = filters
+            .apply_filters(&pixels, width, height)
+            .unwrap_or_else(|_| panic!("apply_filters failed"));
+        std::hint::black_box(output);
+    });
+    report("apply_filters (legacy)", &legacy);
+
+    let mut builder = PipelineBuilder::from_recipe(&recipe_json(0))
+        .unwrap_or_else(|_| panic!("invalid benchmark recipe"));
+    builder
+        .set_input(&pixels, width, height)
+        .unwrap_or_else(|_| panic!("invalid benchmark input"));
+
+    let render_only = measure(frames, |_| {
+        builder.render().unwrap_or_else(|_| panic!("render failed"));
+        std::hint::black_box(builder.output_ptr());
+    });
+    report("PipelineBuilder::render", &render_only);
+
+    let slider_tick = measure(frames, |index| {
+        builder
+            .set_recipe(&recipe_json(index))
+            .unwrap_or_else(|_| panic!("invalid benchmark recipe"));
+        builder.render().unwrap_or_else(|_| panic!("render failed"));
+        std::hint::black_box(builder.output_ptr());
+    });
+    report("set_recipe + render", &slider_tick);
+
+    assert!(
+        render_only.largest < frame_bytes && slider_tick.largest < frame_bytes,
+        "PipelineBuilder allocated a frame-sized buffer in steady state"
+    );
+}
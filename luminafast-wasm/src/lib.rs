@@ -7,9 +7,19 @@
 
 // Réexports publics depuis le core partagé (contrat unifié backend/WASM)
 pub use luminafast_image_core::{
-    apply_filters, compute_histogram_from_pixels, PixelFilters, ProcessingError,
+    apply_filters, apply_retouch, compute_histogram_from_pixels, PixelFilters, ProcessingError,
+    RetouchSpot,
 };
 
+mod pipeline;
+
+pub use pipeline::PipelineBuilder;
+
+/// Build multi-thread : `initThreadPool(navigator.hardwareConcurrency)` doit
+/// être appelé (et attendu) côté JS avant le premier rendu.
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
 use wasm_bindgen::prelude::*;
 
 /// Wrapper WASM pour PixelFilters
@@ -79,6 +89,20 @@ impl PixelFiltersWasm {
     }
 }
 
+/// `true` si le module a été compilé avec la feature `threads` (rendu et
+/// histogramme répartis en bandes sur le pool de Web Workers).
+#[wasm_bindgen]
+pub fn threads_enabled() -> bool {
+    cfg!(feature = "threads")
+}
+
+/// Mémoire linéaire du module, pour créer des vues sur les buffers exposés
+/// par `PipelineBuilder::prepare_input` / `output_ptr`.
+#[wasm_bindgen]
+pub fn wasm_memory() -> JsValue {
+    wasm_bindgen::memory()
+}
+
 /// Calcule l'histogramme RGB d'une image RGBA.
 ///
 /// Retourne un Uint32Array de 768 valeurs : r[0..256] ++ g[0..256] ++ b[0..256].
@@ -89,8 +113,33 @@ impl PixelFiltersWasm {
 /// @param height - Hauteur en pixels
 #[wasm_bindgen]
 pub fn compute_histogram(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u32>, JsValue> {
-    compute_histogram_from_pixels(pixels, width, height)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+    #[cfg(feature = "threads")]
+    let histogram = luminafast_image_core::compute_histogram_parallel(pixels, width, height);
+    #[cfg(not(feature = "threads"))]
+    let histogram = compute_histogram_from_pixels(pixels, width, height);
+
+    histogram.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Rejoue les retouches (heal/clone) d'une recette sur une image RGBA.
+///
+/// `spots_json` est le tableau `retouch` de la recette d'édition, avec des
+/// coordonnées normalisées : le même tableau donne le même résultat en preview
+/// et à l'export.
+#[wasm_bindgen]
+pub fn apply_retouch_spots(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    spots_json: &str,
+) -> Result<Vec<u8>, JsValue> {
+    let spots: Vec<RetouchSpot> =
+        serde_json::from_str(spots_json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let mut result = pixels.to_vec();
+    apply_retouch(&mut result, width, height, &spots)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -121,4 +170,15 @@ mod tests {
         assert_eq!(histogram[256], 1);
         assert_eq!(histogram[512], 1);
     }
+
+    #[test]
+    fn apply_retouch_spots_wrapper_clones_source_pixels() {
+        let pixels = vec![10_u8, 10, 10, 255, 250, 250, 250, 255];
+        let spots = r#"[{"mode":"clone","target":[{"x":0.25,"y":0.5}],"source":{"x":0.75,"y":0.5},"radius":0.2}]"#;
+
+        let result = apply_retouch_spots(&pixels, 2, 1, spots)
+            .expect("WASM wrapper should replay retouch spots");
+
+        assert_eq!(&result[..4], &[250, 250, 250, 255]);
+    }
 }
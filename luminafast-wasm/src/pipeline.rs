@@ -0,0 +1,393 @@
+//! `PipelineBuilder` : pipeline complet du core construit depuis une recette
+//! d'édition sérialisée.
+//!
+//! Les buffers d'entrée et de sortie sont conservés entre deux rendus : une
+//! fois l'image chargée, un tick de slider ne fait que remplacer la recette
+//! et relancer `render`, sans réallouer de buffer de la taille de l'image.
+//!
+//! Rendu sans copie : `prepare_input` et `output_ptr` exposent les buffers
+//! dans la mémoire linéaire WASM (`wasm_memory()`), que la page lit et écrit
+//! via des vues `Uint8ClampedArray`. `render_into` copie le rendu dans un
+//! buffer fourni par l'appelant (par ex. `ImageData.data`) sans allocation.
+//! Les vues sont invalidées si la mémoire WASM grandit : les recréer après
+//! chaque `prepare_input`.
+//!
+//! Avec la feature `threads`, `render` répartit les étapes pixel à pixel en
+//! bandes de lignes sur le pool rayon (`ImagePipeline::execute_parallel`) ;
+//! le résultat est identique au rendu mono-thread.
+//!
+//! Chaque rendu mémorise la sortie des étapes amont dans un `PipelineCache`
+//! borné (`set_cache_budget`) : quand seuls les réglages changent, les
+//! retouches sont reprises du cache au lieu d'être recalculées. Toute
+//! nouvelle entrée (`set_input`, `prepare_input`, `set_linear_input`) vide le
+//! cache ; la page ne doit donc rappeler `prepare_input` que si la source
+//! change.
+
+use luminafast_image_core::pipeline::{validate_rgba_input, write_linear_image_rgba8};
+use luminafast_image_core::{
+    CachedExecution, EditRecipe, ImagePipeline, LinearImage, PipelineCache, ProcessingError,
+};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct PipelineBuilder {
+    pipeline: ImagePipeline,
+    width: u32,
+    height: u32,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    cache: PipelineCache,
+    cached_steps: u32,
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl PipelineBuilder {
+    /// Pipeline vide (identité) ; appeler `set_recipe` puis `set_input`.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PipelineBuilder {
+        PipelineBuilder {
+            pipeline: ImagePipeline::new(),
+            width: 0,
+            height: 0,
+            input: Vec::new(),
+            output: Vec::new(),
+            cache: PipelineCache::default(),
+            cached_steps: 0,
+        }
+    }
+
+    /// Construit directement depuis une recette JSON (`EditRecipe`, toute
+    /// version de schéma connue).
+    pub fn from_recipe(recipe_json: &str) -> Result<PipelineBuilder, JsValue> {
+        let mut builder = PipelineBuilder::new();
+        builder.set_recipe(recipe_json)?;
+        Ok(builder)
+    }
+
+    /// Remplace les étapes du pipeline par celles de la recette. Les buffers
+    /// d'image sont conservés.
+    pub fn set_recipe(&mut self, recipe_json: &str) -> Result<(), JsValue> {
+        let recipe = EditRecipe::from_json(recipe_json).map_err(js_error)?;
+        self.pipeline = recipe.build_pipeline().map_err(js_error)?;
+        Ok(())
+    }
+
+    /// Copie une image RGBA8 dans le buffer d'entrée (capacité réutilisée).
+    pub fn set_input(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), JsValue> {
+        validate_rgba_input(pixels, width, height).map_err(js_error)?;
+
+        self.input.clear();
+        self.input.extend_from_slice(pixels);
+        self.width = width;
+        self.height = height;
+        self.cache.clear();
+        Ok(())
+    }
+
+    /// Dimensionne le buffer d'entrée pour une image `width`x`height` et
+    /// retourne son adresse dans la mémoire WASM, pour que la page y écrive
+    /// directement les pixels RGBA8.
+    pub fn prepare_input(&mut self, width: u32, height: u32) -> Result<*mut u8, JsValue> {
+        let len = rgba_len(width, height)?;
+
+        self.input.resize(len, 0);
+        self.width = width;
+        self.height = height;
+        self.cache.clear();
+        Ok(self.input.as_mut_ptr())
+    }
+
+    /// Charge une image linéaire RGB f32 (0..1), convertie en RGBA8 dans le
+    /// buffer d'entrée.
+    pub fn set_linear_input(
+        &mut self,
+        samples: Vec<f32>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), JsValue> {
+        let linear_image = LinearImage::new(width, height, samples).map_err(js_error)?;
+        if let Err(error) = write_linear_image_rgba8(&linear_image, &mut self.input) {
+            self.clear_input();
+            return Err(js_error(error));
+        }
+
+        self.width = width;
+        self.height = height;
+        self.cache.clear();
+        Ok(())
+    }
+
+    /// Rend l'entrée courante dans le buffer de sortie, en reprenant du
+    /// cache les étapes amont inchangées.
+    pub fn render(&mut self) -> Result<(), JsValue> {
+        if self.input.is_empty() {
+            return Err(JsValue::from_str("PipelineBuilder: no input image loaded"));
+        }
+
+        self.output.clear();
+        self.output.extend_from_slice(&self.input);
+
+        // Le buffer de sortie porte déjà le rendu final : seules les étapes
+        // amont sont mémorisées, un tick de slider n'alloue rien.
+        let execution = CachedExecution {
+            parallel: cfg!(feature = "threads"),
+            skip_output_stage: true,
+        };
+        let result = self.pipeline.execute_cached_with(
+            &mut self.cache,
+            0,
+            &mut self.output,
+            self.width,
+            self.height,
+            execution,
+        );
+
+        self.cached_steps = result.map_err(js_error)? as u32;
+        Ok(())
+    }
+
+    /// Nombre d'étapes reprises du cache lors du dernier `render`.
+    #[wasm_bindgen(getter)]
+    pub fn cached_steps(&self) -> u32 {
+        self.cached_steps
+    }
+
+    /// Borne mémoire du cache d'étapes, en octets (0 désactive le cache).
+    pub fn set_cache_budget(&mut self, budget_bytes: usize) {
+        self.cache.set_budget_bytes(budget_bytes);
+    }
+
+    pub fn cache_used_bytes(&self) -> usize {
+        self.cache.used_bytes()
+    }
+
+    /// Copie du dernier rendu (vide avant le premier `render`).
+    pub fn output(&self) -> Vec<u8> {
+        self.output.clone()
+    }
+
+    /// Adresse du dernier rendu dans la mémoire WASM (`output_len` octets).
+    pub fn output_ptr(&self) -> *const u8 {
+        self.output.as_ptr()
+    }
+
+    pub fn output_len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Rend l'entrée courante puis la copie dans `target` (même taille que
+    /// l'image RGBA), sans allocation côté WASM.
+    pub fn render_into(&mut self, target: &js_sys::Uint8ClampedArray) -> Result<(), JsValue> {
+        let expected = rgba_len(self.width, self.height)?;
+        if target.length() as usize != expected {
+            return Err(JsValue::from_str(&format!(
+                "PipelineBuilder: target holds {} bytes, expected {}",
+                target.length(),
+                expected
+            )));
+        }
+
+        self.render()?;
+        target.copy_from(&self.output);
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn clear_input(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.width = 0;
+        self.height = 0;
+        self.cache.clear();
+        self.cached_steps = 0;
+    }
+}
+
+fn rgba_len(width: u32, height: u32) -> Result<usize, JsValue> {
+    if width == 0 || height == 0 {
+        return Err(js_error(ProcessingError::InvalidDimensions {
+            width,
+            height,
+        }));
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|px| px.checked_mul(4))
+        .ok_or_else(|| js_error(ProcessingError::InvalidDimensions { width, height }))
+}
+
+fn js_error(error: impl std::fmt::Display) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use luminafast_image_core::apply_filters;
+
+    const RECIPE: &str = r#"{"schemaVersion":1,"processVersion":1,"adjustments":{"exposure":20,"contrast":10,"saturation":15}}"#;
+
+    #[test]
+    fn builder_renders_recipe_like_core_pipeline() {
+        let pixels = vec![40_u8, 90, 160, 255, 200, 120, 30, 255];
+        let mut builder = PipelineBuilder::from_recipe(RECIPE).expect("parse recipe");
+        builder.set_input(&pixels, 2, 1).expect("load input");
+
+        builder.render().expect("render recipe");
+
+        let filters = EditRecipe::from_json(RECIPE)
+            .and_then(|recipe| recipe.to_pixel_filters())
+            .expect("recipe filters");
+        let expected = apply_filters(&pixels, 2, 1, &filters).expect("core filters");
+        assert_eq!(builder.output(), expected);
+    }
+
+    #[test]
+    fn builder_reuses_buffers_between_renders() {
+        let pixels = vec![128_u8; 64 * 64 * 4];
+        let mut builder = PipelineBuilder::from_recipe(RECIPE).expect("parse recipe");
+        builder.set_input(&pixels, 64, 64).expect("load input");
+        builder.render().expect("first render");
+        let input_ptr = builder.input.as_ptr();
+        let output_ptr = builder.output.as_ptr();
+
+        builder
+            .set_recipe(r#"{"schemaVersion":1,"processVersion":1,"adjustments":{"exposure":-30}}"#)
+            .expect("replace recipe");
+        builder.set_input(&pixels, 64, 64).expect("reload input");
+        builder.render().expect("second render");
+
+        assert_eq!(builder.input.as_ptr(), input_ptr);
+        assert_eq!(builder.output.as_ptr(), output_ptr);
+    }
+
+    #[test]
+    fn builder_renders_from_prepared_linear_memory() {
+        let pixels = [40_u8, 90, 160, 255, 200, 120, 30, 255];
+        let mut builder = PipelineBuilder::from_recipe(RECIPE).expect("parse recipe");
+
+        let input_ptr = builder.prepare_input(2, 1).expect("prepare input");
+        // La page écrit ici via une vue sur la mémoire WASM.
+        unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), input_ptr, pixels.len()) };
+        builder.render().expect("render prepared input");
+
+        let rendered =
+            unsafe { std::slice::from_raw_parts(builder.output_ptr(), builder.output_len()) };
+        let mut reference = PipelineBuilder::from_recipe(RECIPE).expect("parse recipe");
+        reference.set_input(&pixels, 2, 1).expect("load input");
+        reference.render().expect("render reference");
+        assert_eq!(rendered, reference.output().as_slice());
+    }
+
+    #[test]
+    fn builder_converts_linear_input() {
+        let mut builder = PipelineBuilder::new();
+        builder
+            .set_linear_input(vec![1.0, 0.5, 0.0], 1, 1)
+            .expect("load linear input");
+
+        builder.render().expect("render identity");
+
+        assert_eq!((builder.width(), builder.height()), (1, 1));
+        assert_eq!(builder.output(), vec![255, 128, 0, 255]);
+    }
+
+    const RETOUCH_RECIPE: &str = r#"{"schemaVersion":1,"processVersion":1,
+        "adjustments":{"exposure":20},
+        "retouch":[{"mode":"heal","target":[{"x":0.25,"y":0.25}],"source":{"x":0.75,"y":0.75},"radius":0.1}]}"#;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height * 4)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn builder_reuses_cached_upstream_steps() {
+        let pixels = gradient(32, 32);
+        let mut builder = PipelineBuilder::from_recipe(RETOUCH_RECIPE).expect("parse recipe");
+        builder.set_input(&pixels, 32, 32).expect("load input");
+        builder.render().expect("first render");
+        assert_eq!(builder.cached_steps(), 0);
+
+        // Mêmes retouches, réglages différents : seule l'étape de filtres est recalculée.
+        let edited = RETOUCH_RECIPE.replace(r#""exposure":20"#, r#""exposure":-35"#);
+        builder.set_recipe(&edited).expect("replace recipe");
+        builder.render().expect("second render");
+        assert_eq!(builder.cached_steps(), 1);
+
+        let mut reference = PipelineBuilder::from_recipe(&edited).expect("parse recipe");
+        reference.set_input(&pixels, 32, 32).expect("load input");
+        reference.render().expect("render reference");
+        assert_eq!(builder.output(), reference.output());
+
+        builder.render().expect("unchanged render");
+        assert_eq!(builder.cached_steps(), 1);
+        assert_eq!(builder.output(), reference.output());
+
+        builder.set_input(&pixels, 32, 32).expect("reload input");
+        builder.render().expect("render new input");
+        assert_eq!(builder.cached_steps(), 0);
+    }
+
+    #[test]
+    fn builder_cache_respects_budget() {
+        let pixels = gradient(16, 16);
+        let mut builder = PipelineBuilder::from_recipe(RETOUCH_RECIPE).expect("parse recipe");
+        builder.set_input(&pixels, 16, 16).expect("load input");
+        builder.render().expect("render");
+        assert_eq!(builder.cache_used_bytes(), pixels.len());
+
+        builder.set_cache_budget(pixels.len() - 1);
+        assert_eq!(builder.cache_used_bytes(), 0);
+        builder.render().expect("render without cache");
+        assert_eq!(builder.cached_steps(), 0);
+        assert_eq!(builder.cache_used_bytes(), 0);
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn threaded_render_matches_single_thread_pipeline() {
+        let recipe = r#"{"schemaVersion":1,"processVersion":1,
+            "adjustments":{"exposure":-18,"contrast":22,"shadows":40,"vibrance":15,"colorTemp":4800},
+            "retouch":[{"mode":"clone","target":[{"x":0.2,"y":0.2}],"source":{"x":0.8,"y":0.7},"radius":0.05}]}"#;
+        let (width, height) = (120_u32, 211_u32);
+        let pixels: Vec<u8> = (0..width * height * 4)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 9) as u8)
+            .collect();
+        let mut builder = PipelineBuilder::from_recipe(recipe).expect("parse recipe");
+        builder
+            .set_input(&pixels, width, height)
+            .expect("load input");
+
+        builder.render().expect("threaded render");
+
+        let mut single_thread = pixels.clone();
+        EditRecipe::from_json(recipe)
+            .and_then(|recipe| recipe.build_pipeline())
+            .and_then(|pipeline| pipeline.execute(&mut single_thread, width, height))
+            .expect("single-thread render");
+        assert_eq!(builder.output(), single_thread);
+        assert_eq!(
+            crate::compute_histogram(&builder.output(), width, height).expect("histogram"),
+            luminafast_image_core::compute_histogram_from_pixels(&single_thread, width, height)
+                .expect("single-thread histogram")
+        );
+    }
+}
@@ -0,0 +1,17 @@
+-- Migration 009: Process version pinning for edit recipes
+-- Each edited image is pinned to the process version (core algorithms + UI
+-- scale factors) it was edited with, so algorithm upgrades never change the
+-- rendering of an existing catalog.
+
+CREATE TABLE IF NOT EXISTS image_process_versions (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    process_version INTEGER NOT NULL CHECK(process_version >= 1),
+    pinned_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Backfill: every image edited before this migration was rendered with process version 1
+INSERT OR IGNORE INTO image_process_versions (image_id, process_version)
+SELECT DISTINCT e.target_id, 1
+FROM events e
+WHERE e.event_type = '"edit_applied"'
+  AND e.target_id IN (SELECT id FROM images);
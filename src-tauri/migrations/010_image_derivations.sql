@@ -0,0 +1,16 @@
+-- Migration 010: Derived images
+-- Images produced inside the app from other catalog images (HDR merges) are
+-- ingested like any other file and linked back to the frames they were
+-- built from, in capture order.
+
+CREATE TABLE IF NOT EXISTS image_derivations (
+    derived_image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    source_image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    derivation TEXT NOT NULL,            -- 'hdr_merge'
+    source_order INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (derived_image_id, source_image_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_derivations_source
+    ON image_derivations(source_image_id);
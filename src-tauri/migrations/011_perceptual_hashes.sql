@@ -0,0 +1,11 @@
+-- Migration 011: Perceptual hashes
+-- dHash/pHash fingerprints computed from the thumbnail preview, used to find
+-- near-duplicates that BLAKE3 cannot match (RAW + JPEG, re-saved files).
+-- 64-bit hashes are stored bit-for-bit as signed INTEGER.
+
+CREATE TABLE IF NOT EXISTS image_perceptual_hashes (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    dhash INTEGER NOT NULL,
+    phash INTEGER NOT NULL,
+    computed_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
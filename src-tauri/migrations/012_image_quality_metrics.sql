@@ -0,0 +1,15 @@
+-- Migration 012: Image quality metrics
+-- Sharpness (variance of Laplacian) and exposure metrics computed from the
+-- standard preview, used to cull bursts from search and smart collections.
+
+CREATE TABLE IF NOT EXISTS image_quality_metrics (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    sharpness REAL NOT NULL,             -- variance of Laplacian, 8-bit luma scale
+    mean_luminance REAL NOT NULL,        -- 0.0-1.0, gamma-encoded
+    highlights_clipped REAL NOT NULL,    -- fraction of pixels with a clipped channel
+    shadows_clipped REAL NOT NULL,       -- fraction of pixels crushed to black
+    computed_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_quality_metrics_sharpness
+    ON image_quality_metrics(sharpness);
@@ -0,0 +1,27 @@
+-- Migration 013: Image stacks
+-- Bursts and exposure brackets are grouped into stacks shown as a single
+-- cover image when collapsed. Detection needs the body serial and exposure
+-- bias, which were not stored until now.
+
+ALTER TABLE exif_metadata ADD COLUMN body_serial TEXT;
+ALTER TABLE exif_metadata ADD COLUMN exposure_bias REAL;  -- EV
+
+-- Deleting the cover image dissolves the stack.
+CREATE TABLE IF NOT EXISTS image_stacks (
+    id INTEGER PRIMARY KEY,
+    cover_image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK(kind IN ('burst', 'bracket', 'manual')) DEFAULT 'manual',
+    collapsed INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- An image belongs to at most one stack.
+CREATE TABLE IF NOT EXISTS image_stack_members (
+    stack_id INTEGER NOT NULL REFERENCES image_stacks(id) ON DELETE CASCADE,
+    image_id INTEGER NOT NULL UNIQUE REFERENCES images(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (stack_id, image_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_stack_members_stack
+    ON image_stack_members(stack_id, position);
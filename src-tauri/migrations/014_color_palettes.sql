@@ -0,0 +1,14 @@
+-- Migration 014: Dominant colour palettes
+-- k-means clusters of the thumbnail colours in CIELAB, most dominant first,
+-- used to search images by colour distance (ΔE76).
+
+CREATE TABLE IF NOT EXISTS image_color_palettes (
+    image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,           -- 0 = most dominant
+    lab_l REAL NOT NULL,
+    lab_a REAL NOT NULL,
+    lab_b REAL NOT NULL,
+    weight REAL NOT NULL,                -- fraction of thumbnail pixels, 0.0-1.0
+    hex TEXT NOT NULL,                   -- sRGB #rrggbb, for display
+    PRIMARY KEY (image_id, position)
+);
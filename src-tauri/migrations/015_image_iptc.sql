@@ -0,0 +1,11 @@
+-- Migration 015: IPTC fields per image
+-- Creator, caption and copyright entered in the catalogue, embedded into
+-- exported files. Keywords are not stored here: they are the image tags.
+
+CREATE TABLE IF NOT EXISTS image_iptc (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    author TEXT,
+    description TEXT,
+    copyright TEXT,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,11 @@
+-- Migration 016: Named export presets
+-- Format, sizing, quality, colour space and output naming, stored as the
+-- JSON of `ExportPresetSettings` so presets can be shared between catalogues.
+
+CREATE TABLE IF NOT EXISTS export_presets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    settings TEXT NOT NULL,              -- JSON ExportPresetSettings
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
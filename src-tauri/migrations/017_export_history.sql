@@ -0,0 +1,18 @@
+-- Migration 017: Export history
+-- One row per written file. `recipe_hash` identifies the edits that were
+-- rendered, so a delivered file is stale once the image hashes differently.
+-- `settings` is the JSON of `ExportHistorySettings`, enough to render again.
+
+CREATE TABLE IF NOT EXISTS export_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    output_path TEXT NOT NULL,
+    preset_id INTEGER REFERENCES export_presets(id) ON DELETE SET NULL,
+    preset_name TEXT,                    -- kept when the preset is deleted
+    recipe_hash TEXT NOT NULL,
+    settings TEXT NOT NULL,              -- JSON ExportHistorySettings
+    exported_at TEXT NOT NULL            -- RFC 3339
+);
+
+CREATE INDEX IF NOT EXISTS idx_export_history_image ON export_history(image_id);
+CREATE INDEX IF NOT EXISTS idx_export_history_output ON export_history(output_path);
@@ -0,0 +1,6 @@
+-- Migration 018: Events by target
+-- Replaying the edits of one image (exports, stale export detection) reads
+-- its events only, instead of scanning the whole events table.
+
+CREATE INDEX IF NOT EXISTS idx_events_target
+    ON events(target_type, target_id, event_type, timestamp);
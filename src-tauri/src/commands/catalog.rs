@@ -142,6 +142,10 @@ pub async fn get_all_images(
             params.push(search_pattern);
         }
 
+        if f.stack_tops_only.unwrap_or(false) {
+            conditions.push(crate::services::stacking::STACK_TOPS_CONDITION);
+        }
+
         if !conditions.is_empty() {
             query.push_str(" WHERE ");
             query.push_str(&conditions.join(" AND "));
@@ -289,6 +293,38 @@ pub async fn get_image_exif(id: u32, state: State<'_, AppState>) -> CommandResul
     Ok(result)
 }
 
+/// Get sharpness and exposure metrics for a single image
+/// Returns None until the standard preview has been generated.
+#[tauri::command]
+pub async fn get_image_quality_metrics(
+    id: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<Option<crate::services::quality_metrics::QualityMetrics>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    crate::services::quality_metrics::get_quality_metrics(db.connection(), id)
+        .map_err(|e| format!("Failed to load quality metrics for image {}: {}", id, e))
+}
+
+/// Get the dominant colour palette of a single image, most dominant first
+/// Empty until the thumbnail has been generated.
+#[tauri::command]
+pub async fn get_image_color_palette(
+    id: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<crate::services::color_palette::PaletteColor>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    crate::services::color_palette::get_palette(db.connection(), id)
+        .map_err(|e| format!("Failed to load colour palette for image {}: {}", id, e))
+}
+
 #[cfg(test)]
 mod get_image_exif_tests {
     use crate::database::Database;
@@ -972,6 +1008,8 @@ pub async fn get_smart_collection_results(
     // Parse the smart query to SQL
     let where_clause = crate::services::smart_query_parser::parse_smart_query(&smart_query)
         .map_err(|e| format!("Failed to parse smart query: {}", e))?;
+    let order_clause = crate::services::smart_query_parser::parse_smart_query_order(&smart_query)
+        .map_err(|e| format!("Failed to parse smart query sort: {}", e))?;
 
     // Build the SQL query dynamically (sans alias pour compatibilité parser)
     let query_str = format!(
@@ -984,9 +1022,10 @@ pub async fn get_smart_collection_results(
          FROM images
          LEFT JOIN image_state ON images.id = image_state.image_id
          LEFT JOIN exif_metadata ON images.id = exif_metadata.image_id
+         LEFT JOIN image_quality_metrics ON images.id = image_quality_metrics.image_id
          WHERE {}
-         ORDER BY images.imported_at DESC",
-        where_clause
+         ORDER BY {}",
+        where_clause, order_clause
     );
 
     let mut stmt = db
@@ -1095,6 +1134,7 @@ fn get_smart_collection_image_count(
         "SELECT COUNT(*) FROM images
          LEFT JOIN image_state ON images.id = image_state.image_id
          LEFT JOIN exif_metadata ON images.id = exif_metadata.image_id
+         LEFT JOIN image_quality_metrics ON images.id = image_quality_metrics.image_id
          WHERE {}",
         where_clause
     );
@@ -1,11 +1,12 @@
 use crate::models::discovery::{
     BatchIngestionRequest, BatchIngestionResult, DiscoveredFile, DiscoveryConfig, DiscoverySession,
-    IngestionResult,
+    ImportCopyOptions, IngestionResult,
 };
 use crate::services::blake3::Blake3Service;
 use crate::services::db_repository::{SqliteDbRepository, SqlitePoolConfig};
 use crate::services::discovery::DiscoveryService;
 use crate::services::ingestion::IngestionService;
+use crate::services::naming::NamePreview;
 use crate::services::security::{get_runtime_whitelist, validate_path};
 use crate::types::db_context::DBContext;
 use std::path::PathBuf;
@@ -44,7 +45,7 @@ fn get_discovery_service() -> Arc<DiscoveryService> {
 }
 
 /// Get the global ingestion service
-fn get_ingestion_service() -> Arc<IngestionService> {
+pub(crate) fn get_ingestion_service() -> Arc<IngestionService> {
     INGESTION_SERVICE
         .get_or_init(|| {
             // Create BLAKE3 service for ingestion
@@ -108,12 +109,41 @@ pub async fn batch_ingest(
     app_handle: AppHandle,
     request: BatchIngestionRequest,
 ) -> Result<BatchIngestionResult, String> {
-    // Validate that all files were part of a discovery session
-    let discovery_service = get_discovery_service();
+    if let Some(copy_to) = &request.copy_to {
+        validate_copy_destination(copy_to)?;
+    }
+    ensure_session_files(request.session_id, &request.file_paths).await?;
 
-    // Get discovered files for the session
-    let discovered_files = discovery_service
-        .get_session_files(request.session_id)
+    // Proceed with ingestion only if all files are validated
+    let ingestion_service = get_ingestion_service();
+    let result = ingestion_service
+        .batch_ingest(&request, Some(app_handle))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result)
+}
+
+/// Resolve the destination of each file of a copying import without copying anything
+#[tauri::command]
+pub async fn preview_import_names(
+    #[allow(non_snake_case)] sessionId: Uuid,
+    file_paths: Vec<PathBuf>,
+    copy_to: ImportCopyOptions,
+) -> Result<Vec<NamePreview>, String> {
+    validate_copy_destination(&copy_to)?;
+    ensure_session_files(sessionId, &file_paths).await?;
+
+    get_ingestion_service()
+        .preview_import_names(&file_paths, &copy_to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Reject files that were not found by the given discovery session
+async fn ensure_session_files(session_id: Uuid, file_paths: &[PathBuf]) -> Result<(), String> {
+    let discovered_files = get_discovery_service()
+        .get_session_files(session_id)
         .await
         .map_err(|e| format!("Failed to get session files: {}", e))?;
 
@@ -121,8 +151,7 @@ pub async fn batch_ingest(
     let valid_paths: std::collections::HashSet<_> =
         discovered_files.iter().map(|f| f.path.clone()).collect();
 
-    // Validate that all requested files are in the discovery session
-    for file_path in &request.file_paths {
+    for file_path in file_paths {
         if !valid_paths.contains(file_path) {
             return Err(format!(
                 "File not found in discovery session: {}. All files must be from a valid discovery session.",
@@ -131,14 +160,14 @@ pub async fn batch_ingest(
         }
     }
 
-    // Proceed with ingestion only if all files are validated
-    let ingestion_service = get_ingestion_service();
-    let result = ingestion_service
-        .batch_ingest(&request, Some(app_handle))
-        .await
-        .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    Ok(result)
+/// Reject copy destinations outside the whitelisted directories
+fn validate_copy_destination(copy_to: &ImportCopyOptions) -> Result<(), String> {
+    let whitelist = get_runtime_whitelist();
+    let requested = copy_to.destination_dir.to_string_lossy();
+    validate_path(requested.as_ref(), &whitelist).map_err(|e| e.to_string())
 }
 
 /// Start a new discovery session
@@ -394,6 +423,8 @@ mod tests {
                 gps_lat REAL,
                 gps_lon REAL,
                 color_space TEXT,
+                body_serial TEXT,
+                exposure_bias REAL,
                 FOREIGN KEY (image_id) REFERENCES images (id)
             );",
         )
@@ -477,6 +508,49 @@ mod tests {
         assert_eq!(table_exists, 1, "ingestion_sessions table must exist");
     }
 
+    fn copy_options(destination_dir: PathBuf) -> ImportCopyOptions {
+        ImportCopyOptions {
+            destination_dir,
+            naming: crate::services::naming::NamingOptions {
+                template: "{filename}".to_string(),
+                custom_text: String::new(),
+                sequence_start: 1,
+                collision: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_copy_destination_must_be_whitelisted() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert!(validate_copy_destination(&copy_options(temp_dir.path().to_path_buf())).is_ok());
+        assert!(
+            validate_copy_destination(&copy_options(PathBuf::from("/non/existent/library")))
+                .is_err()
+        );
+        assert!(
+            validate_copy_destination(&copy_options(temp_dir.path().join("../escape"))).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_import_names_requires_discovery_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside = temp_dir.path().join("IMG_0001.CR3");
+        std::fs::write(&outside, b"not discovered").unwrap();
+
+        let result = preview_import_names(
+            Uuid::new_v4(),
+            vec![outside],
+            copy_options(temp_dir.path().to_path_buf()),
+        )
+        .await;
+
+        let error = result.expect_err("unknown session must be rejected");
+        assert!(error.contains("Failed to get session files"), "{}", error);
+    }
+
     #[tokio::test]
     async fn test_get_default_discovery_config() {
         let config = get_default_discovery_config().await.unwrap();
@@ -1,5 +1,6 @@
 use crate::commands::catalog::AppState;
-use crate::models::event::Event;
+use crate::models::event::{Event, EventType, TargetType};
+use crate::services::edit_recipe::pin_process_version;
 use crate::services::event_sourcing::{EventStore, EventStoreError};
 use serde::{Deserialize, Serialize};
 use tauri::State;
@@ -66,6 +67,16 @@ pub fn append_event(event: EventDTO, state: State<AppState>) -> Result<(), Comma
     store
         .append_event(&event_to_store)
         .map_err(CommandError::from)?;
+
+    // Pin the image to the current process version on its first edit
+    if event_to_store.event_type == EventType::EditApplied
+        && event_to_store.target_type == TargetType::Image
+    {
+        pin_process_version(conn, event_to_store.target_id).map_err(|e| CommandError {
+            message: format!("Process version pin failed: {}", e),
+        })?;
+    }
+
     Ok(())
 }
 
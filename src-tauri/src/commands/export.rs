@@ -1,68 +1,443 @@
 use crate::commands::catalog::AppState;
-use crate::models::dto::{CommandResult, ExportResultDTO};
+use crate::models::dto::{
+    BatchExportFailureDTO, BatchExportRequestDTO, BatchExportResultDTO, CommandResult,
+    ExportResultDTO,
+};
+use crate::services::export_batch::{
+    preview_batch_names, run_batch_export, run_export_requests, BatchExportRequest,
+    BatchExportResult, CancellationToken, ExportProgress, DEFAULT_EXPORT_WORKERS,
+    DEFAULT_FILE_NAME_TEMPLATE, EXPORT_PROGRESS_EVENT,
+};
+use crate::services::export_encoding::ExportFormatOptions;
+use crate::services::export_history::{self, ExportHistoryEntry, ExportHistorySettings};
+use crate::services::export_metadata::ExportMetadataOptions;
 use crate::services::export_pipeline::{
-    export_image_with_edits, export_raw_image_with_edits, ExportFormat, ExportRequest,
+    prepare_export, render_prepared_export, resolve_edit_recipe, ExportFormat, ExportPipelineError,
+    ExportRequest, ExportResult, ExportTarget, RsRawDecoder,
 };
-use std::path::PathBuf;
-use tauri::State;
+use crate::services::export_sizing::ExportResize;
+use crate::services::naming::{NamePreview, NamingOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, State};
+use uuid::Uuid;
+
+/// Cancellation tokens of the batch exports currently running
+static EXPORT_JOBS: OnceLock<Mutex<HashMap<Uuid, CancellationToken>>> = OnceLock::new();
+
+fn export_jobs() -> &'static Mutex<HashMap<Uuid, CancellationToken>> {
+    EXPORT_JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn export_image_edited(
     image_id: String,
     output_path: String,
     format: String,
+    resize: Option<ExportResize>,
+    options: Option<ExportFormatOptions>,
+    metadata: Option<ExportMetadataOptions>,
+    target: Option<ExportTarget>,
     state: State<'_, AppState>,
 ) -> CommandResult<ExportResultDTO> {
-    run_export_command(image_id, output_path, format, state, false)
+    let request = build_export_request(
+        image_id,
+        output_path,
+        format,
+        resize.unwrap_or_default(),
+        options.unwrap_or_default(),
+        metadata.unwrap_or_default(),
+        target.unwrap_or_default(),
+    )?;
+    run_export_command(request, state, false).await
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn export_raw_edited(
     image_id: String,
     output_path: String,
     format: String,
+    resize: Option<ExportResize>,
+    options: Option<ExportFormatOptions>,
+    metadata: Option<ExportMetadataOptions>,
+    target: Option<ExportTarget>,
     state: State<'_, AppState>,
 ) -> CommandResult<ExportResultDTO> {
-    run_export_command(image_id, output_path, format, state, true)
+    let request = build_export_request(
+        image_id,
+        output_path,
+        format,
+        resize.unwrap_or_default(),
+        options.unwrap_or_default(),
+        metadata.unwrap_or_default(),
+        target.unwrap_or_default(),
+    )?;
+    run_export_command(request, state, true).await
+}
+
+/// Exports a list of images or a collection on a worker pool, emitting
+/// `export-progress` events. The first event carries the job ID accepted by
+/// `cancel_batch_export`.
+#[tauri::command]
+pub async fn batch_export(
+    app_handle: AppHandle,
+    request: BatchExportRequestDTO,
+    state: State<'_, AppState>,
+) -> CommandResult<BatchExportResultDTO> {
+    let batch = build_batch_request(request)?;
+    run_batch_job(app_handle, batch, None, state).await
+}
+
+/// Resolves the output path and collision action of every image of a batch
+/// export without writing anything.
+#[tauri::command]
+pub async fn preview_export_names(
+    request: BatchExportRequestDTO,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<NamePreview>> {
+    let batch = build_batch_request(request)?;
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    preview_batch_names(db.connection(), &batch).map_err(|e| e.to_string())
+}
+
+fn build_batch_request(request: BatchExportRequestDTO) -> CommandResult<BatchExportRequest> {
+    Ok(BatchExportRequest {
+        source: request.source,
+        output_dir: PathBuf::from(request.output_dir),
+        naming: NamingOptions {
+            template: request
+                .file_name_template
+                .unwrap_or_else(|| DEFAULT_FILE_NAME_TEMPLATE.to_string()),
+            custom_text: request.custom_text,
+            sequence_start: request.sequence_start.unwrap_or(1),
+            collision: request.collision,
+        },
+        format: ExportFormat::try_from(request.format.as_str()).map_err(|e| e.to_string())?,
+        resize: request.resize,
+        options: request.options,
+        metadata: request.metadata,
+        raw_only: request.raw_only,
+        max_workers: request.max_workers.unwrap_or(DEFAULT_EXPORT_WORKERS),
+        watermark: request.watermark,
+    })
+}
+
+/// Runs `batch` off the async runtime, registered for `cancel_batch_export`,
+/// and records the written files in the export history under `preset`.
+pub(crate) async fn run_batch_job(
+    app_handle: AppHandle,
+    batch: BatchExportRequest,
+    preset: Option<(i64, String)>,
+    state: State<'_, AppState>,
+) -> CommandResult<BatchExportResultDTO> {
+    let settings = ExportHistorySettings::for_batch(&batch);
+    let result = run_export_job(
+        app_handle,
+        &state,
+        move |db_path, job_id, cancel, on_progress| {
+            run_batch_export(db_path, job_id, &batch, cancel, on_progress)
+        },
+    )
+    .await?;
+
+    let (preset_id, preset_name) = preset.unzip();
+    record_export_history(
+        &state,
+        result
+            .exported
+            .iter()
+            .map(|exported| (exported, &settings, preset_id, preset_name.as_deref())),
+    )?;
+    Ok(batch_result_dto(result))
+}
+
+/// Export history, newest first; only the exports of `image_id` when given.
+#[tauri::command]
+pub async fn list_export_history(
+    image_id: Option<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ExportHistoryEntry>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_history::list_export_history(db.connection(), image_id).map_err(|e| e.to_string())
+}
+
+/// Delivered files whose image was edited since they were exported.
+#[tauri::command]
+pub async fn list_stale_exports(
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ExportHistoryEntry>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_history::find_stale_exports(db.connection()).map_err(|e| e.to_string())
+}
+
+/// Renders stale exports again over their previous files with their
+/// recorded settings: all of them, or the history entries `entry_ids`.
+/// Emits `export-progress` events and can be cancelled like `batch_export`.
+#[tauri::command]
+pub async fn reexport_stale(
+    app_handle: AppHandle,
+    entry_ids: Option<Vec<i64>>,
+    state: State<'_, AppState>,
+) -> CommandResult<BatchExportResultDTO> {
+    let stale = {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        export_history::find_stale_exports(db.connection()).map_err(|e| e.to_string())?
+    };
+    let stale: Vec<ExportHistoryEntry> = match entry_ids {
+        Some(ids) => stale
+            .into_iter()
+            .filter(|entry| ids.contains(&entry.id))
+            .collect(),
+        None => stale,
+    };
+
+    let requests = stale
+        .iter()
+        .map(ExportHistoryEntry::reexport_request)
+        .collect();
+    let result = run_export_job(
+        app_handle,
+        &state,
+        move |db_path, job_id, cancel, on_progress| {
+            Ok(run_export_requests(
+                db_path,
+                job_id,
+                requests,
+                DEFAULT_EXPORT_WORKERS,
+                cancel,
+                on_progress,
+            ))
+        },
+    )
+    .await?;
+
+    let entries: HashMap<&str, &ExportHistoryEntry> = stale
+        .iter()
+        .map(|entry| (entry.output_path.as_str(), entry))
+        .collect();
+    record_export_history(
+        &state,
+        result.exported.iter().filter_map(|exported| {
+            let entry = entries.get(exported.output_path.as_str())?;
+            Some((
+                exported,
+                &entry.settings,
+                entry.preset_id,
+                entry.preset_name.as_deref(),
+            ))
+        }),
+    )?;
+    Ok(batch_result_dto(result))
 }
 
-fn run_export_command(
+/// Runs `job` on a blocking thread with the catalog path, a cancellation
+/// token registered for `cancel_batch_export` and a progress emitter.
+async fn run_export_job<F>(
+    app_handle: AppHandle,
+    state: &State<'_, AppState>,
+    job: F,
+) -> CommandResult<BatchExportResult>
+where
+    F: FnOnce(
+            &Path,
+            Uuid,
+            &CancellationToken,
+            &(dyn Fn(&ExportProgress) + Sync),
+        ) -> Result<BatchExportResult, ExportPipelineError>
+        + Send
+        + 'static,
+{
+    // Workers open their own connections; the shared one is only needed for its path.
+    let db_path = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?
+        .get_db_path()
+        .to_path_buf();
+
+    let job_id = Uuid::new_v4();
+    let cancel = CancellationToken::default();
+    export_jobs()
+        .lock()
+        .map_err(|e| format!("Export jobs lock poisoned: {}", e))?
+        .insert(job_id, cancel.clone());
+
+    let outcome = tokio::task::spawn_blocking(move || {
+        job(&db_path, job_id, &cancel, &|progress| {
+            let _ = app_handle.emit(EXPORT_PROGRESS_EVENT, progress);
+        })
+    })
+    .await;
+
+    if let Ok(mut jobs) = export_jobs().lock() {
+        jobs.remove(&job_id);
+    }
+
+    outcome
+        .map_err(|e| format!("Batch export task failed: {}", e))?
+        .map_err(|e| e.to_string())
+}
+
+/// Records written files in one transaction, with their settings and preset.
+fn record_export_history<'a>(
+    state: &State<'_, AppState>,
+    exports: impl Iterator<
+        Item = (
+            &'a ExportResult,
+            &'a ExportHistorySettings,
+            Option<i64>,
+            Option<&'a str>,
+        ),
+    >,
+) -> CommandResult<()> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    let tx = db
+        .connection()
+        .transaction()
+        .map_err(|e| format!("Export history not recorded: {}", e))?;
+    for (result, settings, preset_id, preset_name) in exports {
+        export_history::record_export(&tx, result, settings, preset_id, preset_name)
+            .map_err(|e| format!("Export history not recorded: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Export history not recorded: {}", e))
+}
+
+fn batch_result_dto(result: BatchExportResult) -> BatchExportResultDTO {
+    BatchExportResultDTO {
+        job_id: result.job_id.to_string(),
+        total: result.total,
+        exported: result.exported.into_iter().map(Into::into).collect(),
+        failed: result
+            .failed
+            .into_iter()
+            .map(|failure| BatchExportFailureDTO {
+                image_id: failure.image_id,
+                error: failure.error,
+            })
+            .collect(),
+        cancelled_image_ids: result.cancelled,
+        skipped_image_ids: result.skipped,
+    }
+}
+
+/// Requests cancellation of a running batch export. Images already rendering
+/// finish; returns false when no such job is running.
+#[tauri::command]
+pub async fn cancel_batch_export(job_id: String) -> CommandResult<bool> {
+    let job_id =
+        Uuid::parse_str(&job_id).map_err(|e| format!("Invalid job_id '{}': {}", job_id, e))?;
+    let jobs = export_jobs()
+        .lock()
+        .map_err(|e| format!("Export jobs lock poisoned: {}", e))?;
+
+    Ok(match jobs.get(&job_id) {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Returns the serialized edit recipe (process version + adjustments) of an image.
+#[tauri::command]
+pub async fn get_edit_recipe(
+    image_id: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<serde_json::Value> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    let recipe = resolve_edit_recipe(db.connection(), image_id).map_err(|e| e.to_string())?;
+    serde_json::to_value(recipe).map_err(|e| format!("Recipe serialization failed: {}", e))
+}
+
+fn build_export_request(
     image_id: String,
     output_path: String,
     format: String,
-    state: State<'_, AppState>,
-    raw_only: bool,
-) -> CommandResult<ExportResultDTO> {
+    resize: ExportResize,
+    options: ExportFormatOptions,
+    metadata: ExportMetadataOptions,
+    target: ExportTarget,
+) -> CommandResult<ExportRequest> {
     let parsed_image_id = image_id
         .parse::<i64>()
         .map_err(|e| format!("Invalid image_id '{}': {}", image_id, e))?;
 
     let export_format = ExportFormat::try_from(format.as_str()).map_err(|e| e.to_string())?;
 
-    let request = ExportRequest {
+    Ok(ExportRequest {
         image_id: parsed_image_id,
         output_path: PathBuf::from(output_path),
         format: export_format,
+        resize,
+        options,
+        metadata,
+        watermark: None,
+        target,
+    })
+}
+
+async fn run_export_command(
+    request: ExportRequest,
+    state: State<'_, AppState>,
+    raw_only: bool,
+) -> CommandResult<ExportResultDTO> {
+    // Only the catalog reads hold the lock; decoding, rendering and encoding
+    // run on a blocking thread so other commands keep access to the database.
+    let prepared = {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        prepare_export(db.connection(), &request, raw_only).map_err(|e| e.to_string())?
     };
 
+    let render_request = request.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        render_prepared_export(prepared, &render_request, &RsRawDecoder)
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))?
+    .map_err(|e| e.to_string())?;
+
     let mut db = state
         .db
         .lock()
         .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    export_history::record_export(
+        db.connection(),
+        &result,
+        &ExportHistorySettings::for_request(&request, raw_only),
+        None,
+        None,
+    )
+    .map_err(|e| format!("Export history not recorded: {}", e))?;
 
-    let result = if raw_only {
-        export_raw_image_with_edits(db.connection(), &request).map_err(|e| e.to_string())?
-    } else {
-        export_image_with_edits(db.connection(), &request).map_err(|e| e.to_string())?
-    };
-
-    Ok(ExportResultDTO {
-        image_id: result.image_id,
-        output_path: result.output_path,
-        format: result.format,
-        width: result.width,
-        height: result.height,
-        applied_edit_events: result.applied_edit_events,
-        used_snapshot: result.used_snapshot,
-    })
+    Ok(result.into())
 }
@@ -0,0 +1,130 @@
+use crate::commands::catalog::AppState;
+use crate::commands::export::run_batch_job;
+use crate::models::dto::{BatchExportResultDTO, CommandResult};
+use crate::services::export_batch::{
+    BatchExportRequest, BatchExportSource, DEFAULT_EXPORT_WORKERS,
+};
+use crate::services::export_presets::{self, ExportPreset, ExportPresetSettings};
+use std::path::PathBuf;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub async fn list_export_presets(state: State<'_, AppState>) -> CommandResult<Vec<ExportPreset>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::list_export_presets(db.connection()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_export_preset(
+    name: String,
+    settings: ExportPresetSettings,
+    state: State<'_, AppState>,
+) -> CommandResult<ExportPreset> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::create_export_preset(db.connection(), &name, &settings)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_export_preset(
+    preset_id: i64,
+    name: String,
+    settings: ExportPresetSettings,
+    state: State<'_, AppState>,
+) -> CommandResult<ExportPreset> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::update_export_preset(db.connection(), preset_id, &name, &settings)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_export_preset(preset_id: i64, state: State<'_, AppState>) -> CommandResult<()> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::delete_export_preset(db.connection(), preset_id).map_err(|e| e.to_string())
+}
+
+/// Returns the presets `preset_ids` (all when omitted) as a shareable JSON document.
+#[tauri::command]
+pub async fn export_presets_to_json(
+    preset_ids: Option<Vec<i64>>,
+    state: State<'_, AppState>,
+) -> CommandResult<String> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::export_presets_to_json(db.connection(), preset_ids.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Imports a shared preset document; presets with an existing name are replaced.
+#[tauri::command]
+pub async fn import_presets_from_json(
+    json: String,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ExportPreset>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    export_presets::import_presets_from_json(db.connection(), &json).map_err(|e| e.to_string())
+}
+
+/// Batch-exports `source` with a stored preset, emitting `export-progress`
+/// events. `output_dir` overrides the preset directory and is required when
+/// the preset has none.
+#[tauri::command]
+pub async fn export_with_preset(
+    app_handle: AppHandle,
+    preset_id: i64,
+    source: BatchExportSource,
+    output_dir: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<BatchExportResultDTO> {
+    let preset = {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        export_presets::get_export_preset(db.connection(), preset_id).map_err(|e| e.to_string())?
+    };
+
+    let settings = preset.settings;
+    let naming = settings.naming();
+    let output_dir = output_dir
+        .or(settings.output_dir)
+        .ok_or_else(|| format!("Export preset '{}' has no output directory", preset.name))?;
+
+    let batch = BatchExportRequest {
+        source,
+        output_dir: PathBuf::from(output_dir),
+        naming,
+        format: settings.format,
+        resize: settings.resize,
+        options: settings.options,
+        metadata: settings.metadata,
+        watermark: settings.watermark,
+        raw_only: false,
+        max_workers: DEFAULT_EXPORT_WORKERS,
+    };
+
+    run_batch_job(app_handle, batch, Some((preset.id, preset.name)), state).await
+}
@@ -1,8 +1,12 @@
+use crate::commands::catalog::AppState;
 use crate::models::hashing::*;
 use crate::services::blake3::Blake3Service;
+use crate::services::perceptual_hash::{
+    self, PerceptualHashAlgorithm, SimilarImage, SimilarImageGroup, DEFAULT_SIMILARITY_THRESHOLD,
+};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
 
 /// État global pour le service de hachage
 pub struct HashingState {
@@ -68,6 +72,60 @@ pub async fn detect_duplicates(
         .map_err(|e| e.to_string())
 }
 
+/// Recherche les images visuellement proches d'une image du catalogue
+/// Complète detect_duplicates (octets identiques) via les hashes perceptuels
+#[tauri::command]
+pub async fn find_similar_images(
+    image_id: i64,
+    max_distance: Option<u32>,
+    algorithm: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SimilarImage>, String> {
+    let algorithm = parse_similarity_algorithm(algorithm)?;
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    perceptual_hash::find_similar_images(
+        db.connection(),
+        image_id,
+        algorithm,
+        max_distance.unwrap_or(DEFAULT_SIMILARITY_THRESHOLD),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Regroupe les images quasi identiques de tout le catalogue
+#[tauri::command]
+pub async fn group_similar_images(
+    max_distance: Option<u32>,
+    algorithm: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SimilarImageGroup>, String> {
+    let algorithm = parse_similarity_algorithm(algorithm)?;
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    perceptual_hash::group_similar_images(
+        db.connection(),
+        algorithm,
+        max_distance.unwrap_or(DEFAULT_SIMILARITY_THRESHOLD),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// pHash par défaut : plus robuste au redimensionnement et à la compression
+fn parse_similarity_algorithm(
+    algorithm: Option<String>,
+) -> Result<PerceptualHashAlgorithm, String> {
+    algorithm
+        .map(|name| PerceptualHashAlgorithm::try_from(name.as_str()).map_err(|e| e.to_string()))
+        .unwrap_or(Ok(PerceptualHashAlgorithm::PHash))
+}
+
 /// Scan un répertoire et détecte les doublons
 #[tauri::command]
 pub async fn scan_directory_for_duplicates(
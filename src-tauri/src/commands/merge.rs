@@ -0,0 +1,167 @@
+use crate::commands::catalog::AppState;
+use crate::commands::discovery::get_ingestion_service;
+use crate::models::dto::{CommandResult, HdrMergeResultDTO, PanoramaResultDTO};
+use crate::services::derived_images::{
+    get_derivation_sources as load_derivation_sources, ingest_derived_file, link_derived_image,
+    DerivationKind, DerivationSource,
+};
+use crate::services::hdr_merge::{
+    prepare_hdr_merge, render_hdr_merge, HdrMergeRequest, HdrOutputFormat,
+};
+use crate::services::panorama::{prepare_panorama, render_panorama, PanoramaRequest};
+use luminafast_image_core::{PanoramaProjection, DEFAULT_MAX_ALIGNMENT_SHIFT};
+use std::path::PathBuf;
+use tauri::State;
+
+/// Merges bracketed RAW exposures into a float DNG/TIFF and ingests the
+/// result as a new catalog image linked to its sources.
+#[tauri::command]
+pub async fn merge_hdr(
+    image_ids: Vec<i64>,
+    output_path: String,
+    format: String,
+    max_shift: Option<u32>,
+    state: State<'_, AppState>,
+) -> CommandResult<HdrMergeResultDTO> {
+    let request = HdrMergeRequest {
+        image_ids: image_ids.clone(),
+        output_path: PathBuf::from(output_path),
+        format: HdrOutputFormat::try_from(format.as_str()).map_err(|e| e.to_string())?,
+        max_shift: max_shift.unwrap_or(DEFAULT_MAX_ALIGNMENT_SHIFT),
+    };
+
+    // Only the catalog reads hold the lock; decoding and merging run on a
+    // blocking thread so other commands keep access to the database.
+    let prepared = {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        prepare_hdr_merge(db.connection(), &request).map_err(|e| e.to_string())?
+    };
+
+    let result = tauri::async_runtime::spawn_blocking(move || render_hdr_merge(prepared, &request))
+        .await
+        .map_err(|e| format!("HDR merge task failed: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    let derived = ingest_derived_file(
+        &get_ingestion_service(),
+        &result.output_path,
+        result.format.raw_format(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        link_derived_image(
+            db.connection(),
+            &derived,
+            DerivationKind::HdrMerge,
+            &image_ids,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(HdrMergeResultDTO {
+        image_id: derived.image_id,
+        output_path: result.output_path.to_string_lossy().to_string(),
+        format: result.format.as_str().to_string(),
+        width: result.width,
+        height: result.height,
+        source_image_ids: image_ids,
+        reference_image_id: result.reference_image_id,
+        offsets: result.offsets.iter().map(|&(dx, dy)| [dx, dy]).collect(),
+    })
+}
+
+/// Stitches overlapping images into a cylindrical or spherical panorama,
+/// written as a float DNG/TIFF and ingested as a new linked catalog image.
+#[tauri::command]
+pub async fn merge_panorama(
+    image_ids: Vec<i64>,
+    output_path: String,
+    format: String,
+    projection: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<PanoramaResultDTO> {
+    let projection = match projection {
+        Some(projection) => {
+            PanoramaProjection::try_from(projection.as_str()).map_err(|e| e.to_string())?
+        }
+        None => PanoramaProjection::Cylindrical,
+    };
+    let request = PanoramaRequest {
+        image_ids: image_ids.clone(),
+        output_path: PathBuf::from(output_path),
+        format: HdrOutputFormat::try_from(format.as_str()).map_err(|e| e.to_string())?,
+        projection,
+    };
+
+    // Only the catalog reads hold the lock; stitching runs on a blocking
+    // thread so other commands keep access to the database.
+    let prepared = {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        prepare_panorama(db.connection(), &request).map_err(|e| e.to_string())?
+    };
+
+    let result = tauri::async_runtime::spawn_blocking(move || render_panorama(prepared, &request))
+        .await
+        .map_err(|e| format!("Panorama task failed: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    let derived = ingest_derived_file(
+        &get_ingestion_service(),
+        &result.output_path,
+        result.format.raw_format(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    {
+        let mut db = state
+            .db
+            .lock()
+            .map_err(|e| format!("Database lock poisoned: {}", e))?;
+        link_derived_image(
+            db.connection(),
+            &derived,
+            DerivationKind::Panorama,
+            &image_ids,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(PanoramaResultDTO {
+        image_id: derived.image_id,
+        output_path: result.output_path.to_string_lossy().to_string(),
+        format: result.format.as_str().to_string(),
+        projection: result.projection.as_str().to_string(),
+        width: result.width,
+        height: result.height,
+        source_image_ids: image_ids,
+        reference_image_id: result.reference_image_id,
+        focal_length: result.focal_length,
+    })
+}
+
+/// Returns the images a derived image (HDR merge, panorama) was built from.
+#[tauri::command]
+pub async fn get_derivation_sources(
+    image_id: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<DerivationSource>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    load_derivation_sources(db.connection(), image_id).map_err(|e| e.to_string())
+}
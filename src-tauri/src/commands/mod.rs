@@ -3,12 +3,15 @@ pub mod discovery;
 pub mod event_sourcing;
 pub mod exif;
 pub mod export;
+pub mod export_presets;
 pub mod filesystem;
 pub mod hashing;
+pub mod merge;
 pub mod metrics;
 pub mod preview;
 pub mod search;
 pub mod settings;
 pub mod snapshots;
+pub mod stacking;
 pub mod tags;
 pub mod xmp;
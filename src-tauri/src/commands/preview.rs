@@ -1,8 +1,11 @@
 use crate::commands::catalog::AppState;
 use crate::models::preview::*;
+use crate::services::color_palette::{extract_palette, replace_palette, PALETTE_SIZE};
+use crate::services::perceptual_hash::{compute_perceptual_hashes, upsert_perceptual_hashes};
 use crate::services::preview::PreviewService;
 use crate::services::preview_db::PreviewDbService;
-use std::path::PathBuf;
+use crate::services::quality_metrics::{compute_quality_metrics_from_file, upsert_quality_metrics};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, State};
 use tokio::sync::OnceCell;
@@ -67,6 +70,67 @@ fn get_preview_db_service() -> Result<Arc<PreviewDbService>, String> {
         .cloned()
 }
 
+/// Calcule les hashes perceptuels (dHash/pHash) et la palette de couleurs dominantes
+/// depuis le thumbnail, décodé une seule fois, et les persiste
+/// Non bloquant : un échec est journalisé sans faire échouer la génération
+fn persist_thumbnail_analysis(db_service: &PreviewDbService, image_id: i64, thumbnail_path: &Path) {
+    let thumbnail = match image::open(thumbnail_path) {
+        Ok(thumbnail) => thumbnail,
+        Err(e) => {
+            log::warn!(
+                "Failed to decode thumbnail of image {} for analysis: {}",
+                image_id,
+                e
+            );
+            return;
+        }
+    };
+    let hashes = compute_perceptual_hashes(&thumbnail);
+    let palette = extract_palette(&thumbnail, PALETTE_SIZE);
+
+    if let Err(e) =
+        db_service.with_db_conn(|conn| upsert_perceptual_hashes(conn, image_id, &hashes))
+    {
+        log::warn!(
+            "Failed to persist perceptual hashes for image {}: {}",
+            image_id,
+            e
+        );
+    }
+    if let Err(e) = db_service.with_db_conn(|conn| replace_palette(conn, image_id, &palette)) {
+        log::warn!(
+            "Failed to persist colour palette for image {}: {}",
+            image_id,
+            e
+        );
+    }
+}
+
+/// Calcule la netteté et les métriques d'exposition depuis la preview standard
+/// Non bloquant : un échec est journalisé sans faire échouer la génération
+fn persist_quality_metrics(db_service: &PreviewDbService, image_id: i64, preview_path: &Path) {
+    let metrics = match compute_quality_metrics_from_file(preview_path) {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            log::warn!(
+                "Failed to compute quality metrics for image {}: {}",
+                image_id,
+                e
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = db_service.with_db_conn(|conn| upsert_quality_metrics(conn, image_id, &metrics))
+    {
+        log::warn!(
+            "Failed to persist quality metrics for image {}: {}",
+            image_id,
+            e
+        );
+    }
+}
+
 /// Génère une preview pour un fichier
 #[tauri::command]
 pub async fn generate_preview(
@@ -112,6 +176,15 @@ pub async fn generate_preview(
                 log::warn!("Failed to persist preview to database: {}", e);
                 // Non-blocking: continue even if DB persistence fails
             }
+            match preview_type {
+                PreviewType::Thumbnail => {
+                    persist_thumbnail_analysis(&db_service, image_id, &result.path)
+                }
+                PreviewType::Standard => {
+                    persist_quality_metrics(&db_service, image_id, &result.path)
+                }
+                PreviewType::OneToOne => {}
+            }
         } else {
             log::warn!(
                 "No image found in DB for hash {}: preview not persisted",
@@ -194,6 +267,15 @@ pub async fn generate_preview_pyramid(
                     log::warn!("Failed to persist preview to database: {}", e);
                     // Non-blocking: continue even if DB persistence fails
                 }
+                match preview_type {
+                    PreviewType::Thumbnail => {
+                        persist_thumbnail_analysis(db_svc, image_id, &result.path)
+                    }
+                    PreviewType::Standard => {
+                        persist_quality_metrics(db_svc, image_id, &result.path)
+                    }
+                    PreviewType::OneToOne => {}
+                }
             } else {
                 log::warn!(
                     "No image found in DB for hash {}: preview not persisted",
@@ -3,12 +3,14 @@ use serde_json::Value;
 use tauri::State;
 
 use crate::commands::catalog::AppState;
-use crate::services::search::SearchService;
+use crate::services::search::{SearchService, SearchSort};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchRequest {
     pub text: String,
     pub filters: Vec<Value>,
+    #[serde(default)]
+    pub sort: Option<SearchSort>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,7 +30,7 @@ pub struct SearchResponseDTO {
 
 #[tauri::command]
 pub async fn search_images(
-    request: SearchRequest,
+    query: SearchRequest,
     state: State<'_, AppState>,
 ) -> Result<SearchResponseDTO, String> {
     let mut db = state
@@ -36,7 +38,8 @@ pub async fn search_images(
         .lock()
         .map_err(|e| format!("Database lock poisoned: {}", e))?;
 
-    let search_results = SearchService::search(&mut db, &request.text, &request.filters)?;
+    let search_results =
+        SearchService::search(&mut db, &query.text, &query.filters, query.sort.as_ref())?;
 
     let total = search_results.len();
     let results: Vec<SearchResultDTO> = search_results
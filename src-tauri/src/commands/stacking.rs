@@ -0,0 +1,104 @@
+use crate::commands::catalog::AppState;
+use crate::models::dto::CommandResult;
+use crate::services::stacking::{self, ImageStack, StackingOptions, DEFAULT_MAX_GAP_SECONDS};
+use tauri::State;
+
+/// Groups unstacked images into burst and bracket stacks from their capture
+/// time, camera serial and exposure, and returns the new stacks.
+#[tauri::command]
+pub async fn auto_stack_images(
+    max_gap_seconds: Option<f64>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ImageStack>> {
+    let options = StackingOptions {
+        max_gap_seconds: max_gap_seconds.unwrap_or(DEFAULT_MAX_GAP_SECONDS),
+    };
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::auto_stack_images(db.connection(), &options).map_err(|e| e.to_string())
+}
+
+/// Stacks the images in the given order; the cover defaults to the first.
+#[tauri::command]
+pub async fn stack_images(
+    image_ids: Vec<i64>,
+    cover_image_id: Option<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<ImageStack> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::create_stack(db.connection(), &image_ids, cover_image_id).map_err(|e| e.to_string())
+}
+
+/// Removes images from a stack, or dissolves it when `image_ids` is omitted.
+/// Returns the remaining stack, or null once it has been dissolved.
+#[tauri::command]
+pub async fn unstack_images(
+    stack_id: i64,
+    image_ids: Option<Vec<i64>>,
+    state: State<'_, AppState>,
+) -> CommandResult<Option<ImageStack>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    match image_ids {
+        Some(image_ids) => stacking::remove_from_stack(db.connection(), stack_id, &image_ids),
+        None => stacking::dissolve_stack(db.connection(), stack_id).map(|()| None),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Sets the display order of a stack; every member must be listed once.
+#[tauri::command]
+pub async fn reorder_stack(
+    stack_id: i64,
+    image_ids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<ImageStack> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::reorder_stack(db.connection(), stack_id, &image_ids).map_err(|e| e.to_string())
+}
+
+/// Collapses a stack to its cover, or expands it.
+#[tauri::command]
+pub async fn set_stack_collapsed(
+    stack_id: i64,
+    collapsed: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<ImageStack> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::set_stack_collapsed(db.connection(), stack_id, collapsed).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_stack_cover(
+    stack_id: i64,
+    image_id: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<ImageStack> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::set_stack_cover(db.connection(), stack_id, image_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_image_stacks(state: State<'_, AppState>) -> CommandResult<Vec<ImageStack>> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+    stacking::list_stacks(db.connection()).map_err(|e| e.to_string())
+}
@@ -5,6 +5,7 @@
 
 use crate::commands::catalog::AppState;
 use crate::models::dto::CommandResult;
+use crate::services::iptc::{self, IptcMetadata};
 use crate::services::xmp::{self, XmpData};
 use serde::{Deserialize, Serialize};
 use tauri::State;
@@ -35,6 +36,18 @@ pub struct XmpImportResultDTO {
     pub tags_imported: u32,
 }
 
+/// Champs IPTC saisis dans le catalogue pour une image (intégrés aux exports)
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIptcDTO {
+    /// Auteur (dc:creator, IPTC By-line, EXIF Artist)
+    pub author: Option<String>,
+    /// Légende (dc:description, IPTC Caption)
+    pub description: Option<String>,
+    /// Mention de copyright (dc:rights, IPTC Copyright Notice, EXIF Copyright)
+    pub copyright: Option<String>,
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Helpers privés
 // ─────────────────────────────────────────────────────────────────────────────
@@ -42,87 +55,12 @@ pub struct XmpImportResultDTO {
 /// Construit la liste de tags plats + hiérarchiques pour une image à partir de la DB.
 ///
 /// Retourne (flat_tags, hierarchical_subjects).
-/// `hierarchical_subjects` suit la convention Lightroom : "Parent/Enfant/SousEnfant".
 fn build_tags_for_image(
     db: &mut crate::database::Database,
     image_id: u32,
 ) -> Result<(Vec<String>, Vec<String>), String> {
-    // Charger tous les tags avec leur parent_id
-    let mut stmt = db
-        .connection()
-        .prepare("SELECT id, name, parent_id FROM tags")
-        .map_err(|e| format!("Prepare all_tags: {}", e))?;
-
-    let all_tags: Vec<(u32, String, Option<u32>)> = stmt
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-        .map_err(|e| format!("Query all_tags: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Row all_tags: {}", e))?;
-
-    drop(stmt); // Libère l'emprunt mutable sur db avant la prochaine requête
-
-    // Charger les tag ids de l'image
-    let mut stmt2 = db
-        .connection()
-        .prepare("SELECT tag_id FROM image_tags WHERE image_id = ?")
-        .map_err(|e| format!("Prepare image_tags: {}", e))?;
-
-    let image_tag_ids: Vec<u32> = stmt2
-        .query_map([image_id], |row| row.get(0))
-        .map_err(|e| format!("Query image_tags: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Row image_tags: {}", e))?;
-
-    drop(stmt2);
-
-    // Build lookup: id → (name, parent_id)
-    let lookup: std::collections::HashMap<u32, (String, Option<u32>)> = all_tags
-        .into_iter()
-        .map(|(id, name, parent_id)| (id, (name, parent_id)))
-        .collect();
-
-    let mut flat_tags = Vec::new();
-    let mut hierarchical_subjects = Vec::new();
-
-    for tag_id in &image_tag_ids {
-        if let Some((name, _)) = lookup.get(tag_id) {
-            flat_tags.push(name.clone());
-
-            // Construire le chemin hiérarchique complet : "Racine/Parent/Enfant"
-            let path = build_tag_path(*tag_id, &lookup);
-            if path.contains('/') {
-                // Uniquement si hiérarchique (au moins 2 niveaux)
-                hierarchical_subjects.push(path);
-            }
-        }
-    }
-
-    Ok((flat_tags, hierarchical_subjects))
-}
-
-/// Remonte la chaîne parent jusqu'à la racine et construit le chemin slash-séparé.
-fn build_tag_path(
-    tag_id: u32,
-    lookup: &std::collections::HashMap<u32, (String, Option<u32>)>,
-) -> String {
-    let mut parts = Vec::new();
-    let mut current_id = tag_id;
-
-    // Protection contre les cycles (max 20 niveaux)
-    for _ in 0..20 {
-        if let Some((name, parent_id)) = lookup.get(&current_id) {
-            parts.push(name.clone());
-            match parent_id {
-                Some(pid) => current_id = *pid,
-                None => break,
-            }
-        } else {
-            break;
-        }
-    }
-
-    parts.reverse();
-    parts.join("/")
+    xmp::load_image_tags(db.connection(), image_id as i64)
+        .map_err(|e| format!("Load image tags: {}", e))
 }
 
 /// Récupère ou crée un tag par son nom dans la DB.
@@ -365,3 +303,45 @@ pub async fn get_xmp_status(
         xmp_path: xmp_path.to_string_lossy().to_string(),
     })
 }
+
+/// Retourne les champs IPTC d'une image (vides si jamais saisis).
+#[tauri::command]
+pub async fn get_image_iptc(
+    image_id: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<ImageIptcDTO> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    let stored = iptc::load_image_iptc(db.connection(), image_id as i64)
+        .map_err(|e| format!("Load IPTC error: {}", e))?;
+    Ok(ImageIptcDTO {
+        author: stored.author,
+        description: stored.description,
+        copyright: stored.copyright,
+    })
+}
+
+/// Enregistre les champs IPTC d'une image. Les mots-clés restent gérés par les tags.
+#[tauri::command]
+pub async fn set_image_iptc(
+    image_id: u32,
+    iptc: ImageIptcDTO,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let mut db = state
+        .db
+        .lock()
+        .map_err(|e| format!("Database lock poisoned: {}", e))?;
+
+    let metadata = IptcMetadata {
+        copyright: iptc.copyright,
+        keywords: vec![],
+        description: iptc.description,
+        author: iptc.author,
+    };
+    iptc::save_image_iptc(db.connection(), image_id as i64, &metadata)
+        .map_err(|e| format!("Save IPTC error: {}", e))
+}
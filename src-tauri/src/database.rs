@@ -113,6 +113,36 @@ impl Database {
         // Run app settings persistence migration (Phase 6.0.1)
         self.run_migration("008_app_settings_table")?;
 
+        // Run process version pinning migration (edit recipes)
+        self.run_migration("009_process_versions")?;
+
+        // Run derived images migration (HDR merge)
+        self.run_migration("010_image_derivations")?;
+
+        // Run perceptual hashes migration (similar image search)
+        self.run_migration("011_perceptual_hashes")?;
+
+        // Run image quality metrics migration (culling)
+        self.run_migration("012_image_quality_metrics")?;
+
+        // Run image stacks migration (bursts and brackets)
+        self.run_migration("013_image_stacks")?;
+
+        // Run colour palettes migration (search by colour)
+        self.run_migration("014_color_palettes")?;
+
+        // Run IPTC fields migration (metadata embedded into exports)
+        self.run_migration("015_image_iptc")?;
+
+        // Run export presets migration
+        self.run_migration("016_export_presets")?;
+
+        // Run export history migration (stale delivered files)
+        self.run_migration("017_export_history")?;
+
+        // Run events target index migration (per-image edit replay)
+        self.run_migration("018_events_target_index")?;
+
         Ok(())
     }
 
@@ -161,6 +191,20 @@ impl Database {
             "006_snapshots" => include_str!("../migrations/006_snapshots.sql"),
             "007_fix_previews_schema" => include_str!("../migrations/007_fix_previews_schema.sql"),
             "008_app_settings_table" => include_str!("../migrations/008_app_settings_table.sql"),
+            "009_process_versions" => include_str!("../migrations/009_process_versions.sql"),
+            "010_image_derivations" => include_str!("../migrations/010_image_derivations.sql"),
+            "011_perceptual_hashes" => include_str!("../migrations/011_perceptual_hashes.sql"),
+            "012_image_quality_metrics" => {
+                include_str!("../migrations/012_image_quality_metrics.sql")
+            }
+            "013_image_stacks" => include_str!("../migrations/013_image_stacks.sql"),
+            "014_color_palettes" => include_str!("../migrations/014_color_palettes.sql"),
+            "015_image_iptc" => include_str!("../migrations/015_image_iptc.sql"),
+            "016_export_presets" => include_str!("../migrations/016_export_presets.sql"),
+            "017_export_history" => include_str!("../migrations/017_export_history.sql"),
+            "018_events_target_index" => {
+                include_str!("../migrations/018_events_target_index.sql")
+            }
             _ => {
                 return Err(DatabaseError::MigrationFailed(format!(
                     "Unknown migration version: {}",
@@ -364,15 +408,18 @@ mod tests {
         db.initialize()?;
         db.initialize()?;
 
-        // 8 migrations: 001_initial, 002_ingestion_sessions, 003_previews,
+        // 18 migrations: 001_initial, 002_ingestion_sessions, 003_previews,
         // 004_add_folder_online_status, 005_event_sourcing, 006_snapshots,
-        // 007_fix_previews_schema, 008_app_settings_table
+        // 007_fix_previews_schema, 008_app_settings_table, 009_process_versions,
+        // 010_image_derivations, 011_perceptual_hashes, 012_image_quality_metrics,
+        // 013_image_stacks, 014_color_palettes, 015_image_iptc, 016_export_presets,
+        // 017_export_history, 018_events_target_index
         let migration_count: i64 = db
             .connection()
             .prepare("SELECT COUNT(*) FROM migrations")?
             .query_row([], |row| row.get(0))?;
 
-        assert_eq!(migration_count, 8);
+        assert_eq!(migration_count, 18);
 
         Ok(())
     }
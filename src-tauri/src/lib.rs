@@ -104,6 +104,8 @@ pub fn run() {
             commands::catalog::get_folder_images,
             commands::catalog::update_volume_status,
             commands::catalog::get_image_exif,
+            commands::catalog::get_image_quality_metrics,
+            commands::catalog::get_image_color_palette,
             // EXIF commands
             commands::exif::extract_exif,
             commands::exif::extract_exif_batch,
@@ -116,6 +118,8 @@ pub fn run() {
             commands::hashing::get_hash_cache_stats,
             commands::hashing::benchmark_hashing,
             commands::hashing::scan_directory_for_duplicates,
+            commands::hashing::find_similar_images,
+            commands::hashing::group_similar_images,
             // Filesystem commands
             commands::filesystem::start_watcher,
             commands::filesystem::stop_watcher,
@@ -143,6 +147,7 @@ pub fn run() {
             commands::discovery::get_discovered_files,
             commands::discovery::ingest_file,
             commands::discovery::batch_ingest,
+            commands::discovery::preview_import_names,
             commands::discovery::create_discovery_config,
             commands::discovery::get_supported_formats,
             commands::discovery::validate_discovery_path,
@@ -175,6 +180,32 @@ pub fn run() {
             // Export commands (M3.2)
             commands::export::export_image_edited,
             commands::export::export_raw_edited,
+            commands::export::batch_export,
+            commands::export::cancel_batch_export,
+            commands::export::preview_export_names,
+            commands::export::list_export_history,
+            commands::export::list_stale_exports,
+            commands::export::reexport_stale,
+            commands::export_presets::list_export_presets,
+            commands::export_presets::create_export_preset,
+            commands::export_presets::update_export_preset,
+            commands::export_presets::delete_export_preset,
+            commands::export_presets::export_presets_to_json,
+            commands::export_presets::import_presets_from_json,
+            commands::export_presets::export_with_preset,
+            commands::export::get_edit_recipe,
+            // Merge commands (HDR, panorama)
+            commands::merge::merge_hdr,
+            commands::merge::merge_panorama,
+            commands::merge::get_derivation_sources,
+            // Stacking commands (bursts, brackets)
+            commands::stacking::auto_stack_images,
+            commands::stacking::stack_images,
+            commands::stacking::unstack_images,
+            commands::stacking::reorder_stack,
+            commands::stacking::set_stack_collapsed,
+            commands::stacking::set_stack_cover,
+            commands::stacking::get_image_stacks,
             // Snapshot commands (Phase 4.3)
             commands::snapshots::create_snapshot,
             commands::snapshots::get_snapshots,
@@ -193,6 +224,8 @@ pub fn run() {
             commands::xmp::export_image_xmp,
             commands::xmp::import_image_xmp,
             commands::xmp::get_xmp_status,
+            commands::xmp::get_image_iptc,
+            commands::xmp::set_image_iptc,
             // Metrics commands (Phase M.1.1a)
             commands::metrics::get_threadpool_metrics,
             commands::metrics::simulate_threadpool_load,
@@ -138,6 +138,8 @@ pub struct NewExifMetadata {
     pub gps_lat: Option<f64>,
     pub gps_lon: Option<f64>,
     pub color_space: Option<String>,
+    pub body_serial: Option<String>,
+    pub exposure_bias: Option<f64>,
 }
 
 #[cfg(test)]
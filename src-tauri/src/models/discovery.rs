@@ -81,6 +81,9 @@ pub enum RawFormat {
     /// WebP (Google)
     #[serde(rename = "webp")]
     WEBP,
+    /// Tagged Image File Format (floating-point HDR merges)
+    #[serde(rename = "tiff")]
+    TIFF,
 }
 
 impl RawFormat {
@@ -100,6 +103,7 @@ impl RawFormat {
             RawFormat::JPEG => "jpeg",
             RawFormat::PNG => "png",
             RawFormat::WEBP => "webp",
+            RawFormat::TIFF => "tiff",
         }
     }
 
@@ -107,7 +111,7 @@ impl RawFormat {
     pub fn is_raw(&self) -> bool {
         !matches!(
             self,
-            RawFormat::JPG | RawFormat::JPEG | RawFormat::PNG | RawFormat::WEBP
+            RawFormat::JPG | RawFormat::JPEG | RawFormat::PNG | RawFormat::WEBP | RawFormat::TIFF
         )
     }
 
@@ -128,6 +132,7 @@ impl RawFormat {
             RawFormat::JPG | RawFormat::JPEG => "image/jpeg",
             RawFormat::PNG => "image/png",
             RawFormat::WEBP => "image/webp",
+            RawFormat::TIFF => "image/tiff",
         }
     }
 
@@ -148,6 +153,7 @@ impl RawFormat {
             RawFormat::JPG | RawFormat::JPEG => "JPEG",
             RawFormat::PNG => "PNG",
             RawFormat::WEBP => "WebP",
+            RawFormat::TIFF => "TIFF",
         }
     }
 
@@ -172,6 +178,7 @@ impl RawFormat {
             RawFormat::JPG | RawFormat::JPEG => &[0xFF, 0xD8, 0xFF],
             RawFormat::PNG => &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A],
             RawFormat::WEBP => &[0x52, 0x49, 0x46, 0x46], // RIFF
+            RawFormat::TIFF => &[0x49, 0x49, 0x2A, 0x00], // TIFF LE
         }
     }
 
@@ -561,6 +568,19 @@ pub struct BatchIngestionRequest {
     pub skip_existing: bool,
     /// Maximum number of files to process in this batch
     pub max_files: Option<usize>,
+    /// Copy files into a renamed library folder and ingest the copies
+    #[serde(default)]
+    pub copy_to: Option<ImportCopyOptions>,
+}
+
+/// Destination and naming of files copied during ingestion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCopyOptions {
+    /// Root folder of the copies
+    pub destination_dir: PathBuf,
+    /// Path of each copy under `destination_dir`, without extension
+    pub naming: crate::services::naming::NamingOptions,
 }
 
 /// Batch ingestion results
@@ -66,6 +66,112 @@ pub struct ExportResultDTO {
     pub height: u32,
     pub applied_edit_events: usize,
     pub used_snapshot: bool,
+    pub edit_state: crate::services::export_pipeline::ExportEditState,
+    pub recipe_hash: String,
+    pub quality: Option<u8>,
+    pub file_size_bytes: u64,
+}
+
+impl From<crate::services::export_pipeline::ExportResult> for ExportResultDTO {
+    fn from(result: crate::services::export_pipeline::ExportResult) -> Self {
+        Self {
+            image_id: result.image_id,
+            output_path: result.output_path,
+            format: result.format,
+            width: result.width,
+            height: result.height,
+            applied_edit_events: result.applied_edit_events,
+            used_snapshot: result.used_snapshot,
+            edit_state: result.edit_state,
+            recipe_hash: result.recipe_hash,
+            quality: result.quality,
+            file_size_bytes: result.file_size_bytes,
+        }
+    }
+}
+
+/// Request of the batch export command; omitted settings use their defaults
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchExportRequestDTO {
+    pub source: crate::services::export_batch::BatchExportSource,
+    pub output_dir: String,
+    /// Output path without extension, see `services::naming`; `{filename}_edited` when omitted
+    pub file_name_template: Option<String>,
+    /// Value of the `{text}` naming token
+    #[serde(default)]
+    pub custom_text: String,
+    /// `{seq}` of the first image; 1 when omitted
+    pub sequence_start: Option<u32>,
+    #[serde(default)]
+    pub collision: crate::services::naming::CollisionPolicy,
+    pub format: String,
+    #[serde(default)]
+    pub resize: crate::services::export_sizing::ExportResize,
+    #[serde(default)]
+    pub options: crate::services::export_encoding::ExportFormatOptions,
+    #[serde(default)]
+    pub metadata: crate::services::export_metadata::ExportMetadataOptions,
+    /// Drawn after resizing; none when omitted
+    #[serde(default)]
+    pub watermark: Option<crate::services::export_watermark::Watermark>,
+    #[serde(default)]
+    pub raw_only: bool,
+    pub max_workers: Option<usize>,
+}
+
+/// DTO returned by the batch export command
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchExportResultDTO {
+    pub job_id: String,
+    pub total: usize,
+    pub exported: Vec<ExportResultDTO>,
+    pub failed: Vec<BatchExportFailureDTO>,
+    /// Images never started because the job was cancelled
+    pub cancelled_image_ids: Vec<i64>,
+    /// Images whose output file already existed under the `skip` collision policy
+    pub skipped_image_ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchExportFailureDTO {
+    pub image_id: i64,
+    pub error: String,
+}
+
+/// DTO returned by the HDR merge command
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HdrMergeResultDTO {
+    /// Catalog ID of the ingested HDR image
+    pub image_id: i64,
+    pub output_path: String,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub source_image_ids: Vec<i64>,
+    pub reference_image_id: i64,
+    /// `[dx, dy]` alignment of each source, in `source_image_ids` order
+    pub offsets: Vec<[i32; 2]>,
+}
+
+/// DTO returned by the panorama merge command
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PanoramaResultDTO {
+    /// Catalog ID of the ingested panorama
+    pub image_id: i64,
+    pub output_path: String,
+    pub format: String,
+    pub projection: String,
+    pub width: u32,
+    pub height: u32,
+    pub source_image_ids: Vec<i64>,
+    /// Source the projection is centred on
+    pub reference_image_id: i64,
+    pub focal_length: f64,
 }
 
 /// DTO for collection responses
@@ -95,6 +201,8 @@ pub struct ImageFilter {
     pub flag: Option<String>,
     pub folder_id: Option<u32>,
     pub search_text: Option<String>,
+    /// Hide non-cover members of collapsed stacks
+    pub stack_tops_only: Option<bool>,
 }
 
 /// Standard result type for Tauri commands
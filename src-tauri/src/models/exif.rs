@@ -1,8 +1,9 @@
 //! Modèle EXIF pour LuminaFast
-//! Correspond exactement au schéma SQL de la table exif_metadata (migration 001_initial.sql)
+//! Correspond exactement au schéma SQL de la table exif_metadata (migrations 001_initial.sql, 013_image_stacks.sql)
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExifMetadata {
     pub iso: Option<u32>,
     pub aperture: Option<f32>, // f-number
@@ -16,6 +17,13 @@ pub struct ExifMetadata {
     pub gps_lat: Option<f64>,
     pub gps_lon: Option<f64>,
     pub color_space: Option<String>,
+    /// Numéro de série du boîtier (BodySerialNumber), clé de regroupement des stacks
+    pub body_serial: Option<String>,
+    /// Correction d'exposition en EV (ExposureBiasValue), signature des bracketings
+    pub exposure_bias: Option<f64>,
+    /// DateTimeOriginal + SubSecTimeOriginal, heure locale du boîtier.
+    /// Stocké dans `images.captured_at`, pas dans `exif_metadata`.
+    pub captured_at: Option<NaiveDateTime>,
 }
 
 #[cfg(test)]
@@ -35,6 +43,9 @@ mod tests {
             gps_lat: Some(48.8566),
             gps_lon: Some(2.3522),
             color_space: Some("sRGB".to_string()),
+            body_serial: Some("012345".to_string()),
+            exposure_bias: Some(-1.0),
+            captured_at: None,
         };
         assert_eq!(exif.iso, Some(100));
         assert_eq!(exif.camera_model.as_deref(), Some("MockCam X"));
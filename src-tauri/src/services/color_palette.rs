@@ -0,0 +1,560 @@
+//! Dominant colour palettes for search by colour.
+//!
+//! The palette is extracted from the thumbnail by k-means clustering in
+//! CIELAB, where Euclidean distance (ΔE76) approximates perceived colour
+//! difference. Each entry keeps the share of pixels it stands for, so a
+//! query can ask for "lots of teal" rather than any teal pixel.
+
+use image::DynamicImage;
+use luminafast_image_core::srgb8_to_lab;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use thiserror::Error;
+
+/// Number of colours kept per image.
+pub const PALETTE_SIZE: usize = 5;
+
+/// ΔE76 radius within which a palette colour matches the queried colour.
+pub const DEFAULT_COLOR_DISTANCE: f64 = 20.0;
+
+/// Share of the image the matching palette colour must cover.
+pub const DEFAULT_COLOR_WEIGHT: f64 = 0.1;
+
+/// Pixels are sampled on at most this many rows and columns.
+const SAMPLE_GRID: u32 = 64;
+const MAX_ITERATIONS: usize = 20;
+
+/// CSS colour names accepted by colour filters, besides `#rrggbb` / `#rgb`.
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0x00, 0x00, 0x00]),
+    ("white", [0xff, 0xff, 0xff]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("grey", [0x80, 0x80, 0x80]),
+    ("silver", [0xc0, 0xc0, 0xc0]),
+    ("red", [0xff, 0x00, 0x00]),
+    ("maroon", [0x80, 0x00, 0x00]),
+    ("orange", [0xff, 0xa5, 0x00]),
+    ("gold", [0xff, 0xd7, 0x00]),
+    ("yellow", [0xff, 0xff, 0x00]),
+    ("beige", [0xf5, 0xf5, 0xdc]),
+    ("brown", [0xa5, 0x2a, 0x2a]),
+    ("olive", [0x80, 0x80, 0x00]),
+    ("lime", [0x00, 0xff, 0x00]),
+    ("green", [0x00, 0x80, 0x00]),
+    ("teal", [0x00, 0x80, 0x80]),
+    ("turquoise", [0x40, 0xe0, 0xd0]),
+    ("cyan", [0x00, 0xff, 0xff]),
+    ("blue", [0x00, 0x00, 0xff]),
+    ("navy", [0x00, 0x00, 0x80]),
+    ("indigo", [0x4b, 0x00, 0x82]),
+    ("purple", [0x80, 0x00, 0x80]),
+    ("violet", [0xee, 0x82, 0xee]),
+    ("magenta", [0xff, 0x00, 0xff]),
+    ("pink", [0xff, 0xc0, 0xcb]),
+];
+
+// D65 reference white.
+const WHITE_X: f64 = 0.95047;
+const WHITE_Y: f64 = 1.0;
+const WHITE_Z: f64 = 1.08883;
+
+#[derive(Debug, Error)]
+pub enum ColorPaletteError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Image decode error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Unknown colour: {0} (use a colour name or #rrggbb)")]
+    UnknownColor(String),
+
+    #[error("Invalid colour filter: {0}")]
+    InvalidFilter(String),
+}
+
+/// CIELAB colour (D65).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl Lab {
+    pub fn from_srgb8(rgb: [u8; 3]) -> Self {
+        let [l, a, b] = srgb8_to_lab(rgb);
+        Self { l, a, b }
+    }
+
+    /// Nearest sRGB colour, clipped to the gamut.
+    pub fn to_srgb8(self) -> [u8; 3] {
+        let fy = (self.l + 16.0) / 116.0;
+        let x = lab_f_inv(fy + self.a / 500.0) * WHITE_X;
+        let y = lab_f_inv(fy) * WHITE_Y;
+        let z = lab_f_inv(fy - self.b / 200.0) * WHITE_Z;
+        [
+            3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+            -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+            0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+        ]
+        .map(|linear| (linear_to_srgb(linear.clamp(0.0, 1.0)) * 255.0).round() as u8)
+    }
+
+    pub fn distance_squared(self, other: Lab) -> f64 {
+        (self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn lab_f_inv(t: f64) -> f64 {
+    const DELTA: f64 = 6.0 / 29.0;
+    if t > DELTA {
+        t * t * t
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+/// Parses a CSS colour name, `#rrggbb` or `#rgb`.
+pub fn parse_color(text: &str) -> Result<Lab, ColorPaletteError> {
+    let normalized = text.trim().to_lowercase();
+    if let Some((_, rgb)) = NAMED_COLORS.iter().find(|(name, _)| *name == normalized) {
+        return Ok(Lab::from_srgb8(*rgb));
+    }
+
+    let hex = normalized.strip_prefix('#').unwrap_or(&normalized);
+    // Byte slicing below relies on single-byte digits.
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ColorPaletteError::UnknownColor(text.to_string()));
+    }
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|digit| [digit, digit]).collect(),
+        6 => hex.to_string(),
+        _ => return Err(ColorPaletteError::UnknownColor(text.to_string())),
+    };
+    let channel = |index: usize| {
+        u8::from_str_radix(&expanded[index..index + 2], 16)
+            .map_err(|_| ColorPaletteError::UnknownColor(text.to_string()))
+    };
+    Ok(Lab::from_srgb8([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// One dominant colour of an image.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteColor {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+    /// Fraction of sampled pixels in this cluster, 0.0-1.0.
+    pub weight: f64,
+    /// sRGB `#rrggbb`, for display.
+    pub hex: String,
+}
+
+impl PaletteColor {
+    fn new(lab: Lab, weight: f64) -> Self {
+        let [r, g, b] = lab.to_srgb8();
+        Self {
+            l: lab.l,
+            a: lab.a,
+            b: lab.b,
+            weight,
+            hex: format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    pub fn lab(&self) -> Lab {
+        Lab {
+            l: self.l,
+            a: self.a,
+            b: self.b,
+        }
+    }
+}
+
+/// Clusters the image colours into at most `size` entries, most dominant
+/// first. Deterministic: centres are seeded by farthest-point selection.
+pub fn extract_palette(image: &DynamicImage, size: usize) -> Vec<PaletteColor> {
+    let rgb = image.to_rgb8();
+    let step_x = (rgb.width() / SAMPLE_GRID).max(1);
+    let step_y = (rgb.height() / SAMPLE_GRID).max(1);
+    let samples: Vec<Lab> = (0..rgb.height())
+        .step_by(step_y as usize)
+        .flat_map(|y| {
+            (0..rgb.width())
+                .step_by(step_x as usize)
+                .map(move |x| (x, y))
+        })
+        .map(|(x, y)| Lab::from_srgb8(rgb.get_pixel(x, y).0))
+        .collect();
+    if samples.is_empty() || size == 0 {
+        return Vec::new();
+    }
+
+    let mut centres = seed_centres(&samples, size);
+    let mut assignments = vec![usize::MAX; samples.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (sample, assignment) in samples.iter().zip(assignments.iter_mut()) {
+            let nearest = nearest_centre(&centres, *sample);
+            if nearest != *assignment {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![(0.0, 0.0, 0.0, 0_usize); centres.len()];
+        for (sample, &assignment) in samples.iter().zip(&assignments) {
+            let sum = &mut sums[assignment];
+            sum.0 += sample.l;
+            sum.1 += sample.a;
+            sum.2 += sample.b;
+            sum.3 += 1;
+        }
+        for (centre, &(l, a, b, count)) in centres.iter_mut().zip(&sums) {
+            if count > 0 {
+                let count = count as f64;
+                *centre = Lab {
+                    l: l / count,
+                    a: a / count,
+                    b: b / count,
+                };
+            }
+        }
+    }
+
+    let mut counts = vec![0_usize; centres.len()];
+    for &assignment in &assignments {
+        counts[assignment] += 1;
+    }
+    let total = samples.len() as f64;
+    let mut palette: Vec<PaletteColor> = centres
+        .into_iter()
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .map(|(centre, count)| PaletteColor::new(centre, count as f64 / total))
+        .collect();
+    palette.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    palette
+}
+
+/// First centre nearest the mean colour, then repeatedly the sample farthest
+/// from every chosen centre. Stops early on images with fewer colours.
+fn seed_centres(samples: &[Lab], size: usize) -> Vec<Lab> {
+    let count = samples.len() as f64;
+    let mean = Lab {
+        l: samples.iter().map(|s| s.l).sum::<f64>() / count,
+        a: samples.iter().map(|s| s.a).sum::<f64>() / count,
+        b: samples.iter().map(|s| s.b).sum::<f64>() / count,
+    };
+    let first = samples[nearest_centre(samples, mean)];
+
+    let mut centres = vec![first];
+    let mut nearest: Vec<f64> = samples
+        .iter()
+        .map(|sample| sample.distance_squared(first))
+        .collect();
+    while centres.len() < size {
+        let (index, &distance) = nearest
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .expect("samples are not empty");
+        if distance < 1e-6 {
+            break;
+        }
+        let centre = samples[index];
+        centres.push(centre);
+        for (sample, best) in samples.iter().zip(nearest.iter_mut()) {
+            *best = best.min(sample.distance_squared(centre));
+        }
+    }
+    centres
+}
+
+fn nearest_centre(centres: &[Lab], sample: Lab) -> usize {
+    centres
+        .iter()
+        .enumerate()
+        .min_by(|a, b| {
+            a.1.distance_squared(sample)
+                .total_cmp(&b.1.distance_squared(sample))
+        })
+        .map(|(index, _)| index)
+        .expect("at least one centre")
+}
+
+/// Decodes `path` (typically the thumbnail JPEG) and extracts its palette.
+pub fn extract_palette_from_file(path: &Path) -> Result<Vec<PaletteColor>, ColorPaletteError> {
+    Ok(extract_palette(&image::open(path)?, PALETTE_SIZE))
+}
+
+/// Replaces the stored palette of an image, all or nothing.
+pub fn replace_palette(
+    conn: &Connection,
+    image_id: i64,
+    palette: &[PaletteColor],
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM image_color_palettes WHERE image_id = ?1",
+        [image_id],
+    )?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO image_color_palettes (image_id, position, lab_l, lab_a, lab_b, weight, hex)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    for (position, color) in palette.iter().enumerate() {
+        stmt.execute(params![
+            image_id,
+            position as i64,
+            color.l,
+            color.a,
+            color.b,
+            color.weight,
+            color.hex
+        ])?;
+    }
+    drop(stmt);
+    tx.commit()
+}
+
+/// Stored palette, most dominant first; empty until the thumbnail exists.
+pub fn get_palette(conn: &Connection, image_id: i64) -> rusqlite::Result<Vec<PaletteColor>> {
+    let mut stmt = conn.prepare(
+        "SELECT lab_l, lab_a, lab_b, weight, hex FROM image_color_palettes
+         WHERE image_id = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map([image_id], |row| {
+        Ok(PaletteColor {
+            l: row.get(0)?,
+            a: row.get(1)?,
+            b: row.get(2)?,
+            weight: row.get(3)?,
+            hex: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Colour criterion of a search or smart collection filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorQuery {
+    pub target: Lab,
+    /// ΔE76.
+    pub max_distance: f64,
+    /// Minimum share of the image, 0.0-1.0.
+    pub min_weight: f64,
+}
+
+impl ColorQuery {
+    /// Parses a filter value: a colour string (`"teal"`, `"#008080"`), or an
+    /// object `{"color": "teal", "distance": 15, "weight": 0.3}` where
+    /// `distance` and `weight` are optional.
+    pub fn from_value(value: &Value) -> Result<Self, ColorPaletteError> {
+        let (color, distance, weight) = match value {
+            Value::String(color) => (color.as_str(), None, None),
+            Value::Object(object) => (
+                object
+                    .get("color")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| ColorPaletteError::InvalidFilter(value.to_string()))?,
+                object.get("distance").and_then(Value::as_f64),
+                object.get("weight").and_then(Value::as_f64),
+            ),
+            _ => return Err(ColorPaletteError::InvalidFilter(value.to_string())),
+        };
+
+        let max_distance = distance.unwrap_or(DEFAULT_COLOR_DISTANCE);
+        let min_weight = weight.unwrap_or(DEFAULT_COLOR_WEIGHT);
+        if max_distance < 0.0 || !(0.0..=1.0).contains(&min_weight) {
+            return Err(ColorPaletteError::InvalidFilter(value.to_string()));
+        }
+
+        Ok(Self {
+            target: parse_color(color)?,
+            max_distance,
+            min_weight,
+        })
+    }
+
+    /// SQL condition, true when the image whose id is in `image_id_column`
+    /// has a palette colour within the distance and covering the weight.
+    pub fn sql_condition(&self, image_id_column: &str) -> String {
+        let Lab { l, a, b } = self.target;
+        format!(
+            "EXISTS (SELECT 1 FROM image_color_palettes cp
+                WHERE cp.image_id = {column} AND cp.weight >= ({weight})
+                AND (cp.lab_l - ({l})) * (cp.lab_l - ({l}))
+                  + (cp.lab_a - ({a})) * (cp.lab_a - ({a}))
+                  + (cp.lab_b - ({b})) * (cp.lab_b - ({b})) <= ({distance_squared}))",
+            column = image_id_column,
+            weight = self.min_weight,
+            distance_squared = self.max_distance * self.max_distance,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use serde_json::json;
+
+    #[test]
+    fn test_lab_conversion_reference_values() {
+        let white = Lab::from_srgb8([255, 255, 255]);
+        assert!((white.l - 100.0).abs() < 0.01);
+        assert!(white.a.abs() < 0.01 && white.b.abs() < 0.01);
+
+        // sRGB red is L*a*b* (53.24, 80.09, 67.20).
+        let red = Lab::from_srgb8([255, 0, 0]);
+        assert!((red.l - 53.24).abs() < 0.05);
+        assert!((red.a - 80.09).abs() < 0.05);
+        assert!((red.b - 67.20).abs() < 0.05);
+
+        for rgb in [[0, 128, 128], [255, 165, 0], [12, 34, 56]] {
+            assert_eq!(Lab::from_srgb8(rgb).to_srgb8(), rgb);
+        }
+    }
+
+    #[test]
+    fn test_parse_color_names_and_hex() {
+        let teal = parse_color("Teal").unwrap();
+        assert_eq!(parse_color("#008080").unwrap(), teal);
+        assert_eq!(parse_color("#fff").unwrap(), parse_color("white").unwrap());
+        assert!(matches!(
+            parse_color("tealish"),
+            Err(ColorPaletteError::UnknownColor(_))
+        ));
+        assert!(parse_color("#12345g").is_err());
+    }
+
+    #[test]
+    fn test_parse_color_rejects_multibyte_input() {
+        for text in ["#€", "aébcd", "€€", "#ff€"] {
+            assert!(matches!(
+                parse_color(text),
+                Err(ColorPaletteError::UnknownColor(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_extract_palette_finds_dominant_colours() {
+        // 3/4 teal, 1/4 orange.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(80, 40, |x, _| {
+            if x < 60 {
+                Rgb([0, 128, 128])
+            } else {
+                Rgb([255, 165, 0])
+            }
+        }));
+
+        let palette = extract_palette(&image, PALETTE_SIZE);
+
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette[0].hex, "#008080");
+        assert!((palette[0].weight - 0.75).abs() < 0.05);
+        assert_eq!(palette[1].hex, "#ffa500");
+        assert!((palette.iter().map(|c| c.weight).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_color_query_from_value() {
+        let simple = ColorQuery::from_value(&json!("teal")).unwrap();
+        assert_eq!(simple.max_distance, DEFAULT_COLOR_DISTANCE);
+        assert_eq!(simple.min_weight, DEFAULT_COLOR_WEIGHT);
+
+        let lots = ColorQuery::from_value(&json!({"color": "#008080", "weight": 0.4})).unwrap();
+        assert_eq!(lots.target, simple.target);
+        assert_eq!(lots.min_weight, 0.4);
+
+        assert!(ColorQuery::from_value(&json!(42)).is_err());
+        assert!(ColorQuery::from_value(&json!({"color": "teal", "weight": 2})).is_err());
+    }
+
+    #[test]
+    fn test_failed_replace_keeps_previous_palette() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE images (id INTEGER PRIMARY KEY);
+             INSERT INTO images (id) VALUES (1);",
+        )
+        .unwrap();
+        conn.execute_batch(include_str!("../../migrations/014_color_palettes.sql"))
+            .unwrap();
+        let previous = vec![PaletteColor::new(Lab::from_srgb8([10, 125, 130]), 1.0)];
+        replace_palette(&conn, 1, &previous).unwrap();
+
+        // NaN is stored as NULL, which the second insert's NOT NULL rejects.
+        let valid = PaletteColor::new(Lab::from_srgb8([0, 128, 0]), 0.6);
+        let invalid = PaletteColor::new(Lab::from_srgb8([0, 0, 255]), f64::NAN);
+        assert!(replace_palette(&conn, 1, &[valid, invalid]).is_err());
+
+        assert_eq!(get_palette(&conn, 1).unwrap(), previous);
+    }
+
+    #[test]
+    fn test_sql_condition_matches_by_distance_and_weight() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE images (id INTEGER PRIMARY KEY);
+             INSERT INTO images (id) VALUES (1), (2), (3);",
+        )
+        .unwrap();
+        conn.execute_batch(include_str!("../../migrations/014_color_palettes.sql"))
+            .unwrap();
+        let teal = |weight| PaletteColor::new(Lab::from_srgb8([10, 125, 130]), weight);
+        let green = PaletteColor::new(Lab::from_srgb8([0, 128, 0]), 0.6);
+        replace_palette(&conn, 1, &[teal(0.6)]).unwrap();
+        replace_palette(&conn, 2, &[green, teal(0.05)]).unwrap();
+        replace_palette(
+            &conn,
+            3,
+            &[PaletteColor::new(Lab::from_srgb8([0, 0, 255]), 1.0)],
+        )
+        .unwrap();
+
+        let matching = |query: ColorQuery| -> Vec<i64> {
+            let sql = format!(
+                "SELECT i.id FROM images i WHERE {} ORDER BY i.id",
+                query.sql_condition("i.id")
+            );
+            let mut stmt = conn.prepare(&sql).unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<i64>>>()
+                .unwrap()
+        };
+
+        assert_eq!(
+            matching(ColorQuery::from_value(&json!("teal")).unwrap()),
+            vec![1]
+        );
+        assert_eq!(
+            matching(ColorQuery::from_value(&json!({"color": "teal", "weight": 0.01})).unwrap()),
+            vec![1, 2]
+        );
+        assert_eq!(
+            matching(ColorQuery::from_value(&json!("navy")).unwrap()),
+            Vec::<i64>::new()
+        );
+        assert_eq!(get_palette(&conn, 2).unwrap().len(), 2);
+        assert!(get_palette(&conn, 9).unwrap().is_empty());
+    }
+}
@@ -532,6 +532,8 @@ impl DBContext for SqliteDbRepository {
                     gps_lat: real.gps_lat,
                     gps_lon: real.gps_lon,
                     color_space: real.color_space.clone(),
+                    body_serial: real.body_serial.clone(),
+                    exposure_bias: real.exposure_bias,
                 }
             } else {
                 NewExifMetadata {
@@ -549,14 +551,17 @@ impl DBContext for SqliteDbRepository {
                     gps_lat: None,
                     gps_lon: None,
                     color_space: None,
+                    body_serial: None,
+                    exposure_bias: None,
                 }
             };
 
             transaction.execute(
                 "INSERT INTO exif_metadata (
                     image_id, iso, aperture, shutter_speed, focal_length,
-                    lens, camera_make, camera_model, gps_lat, gps_lon, color_space
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    lens, camera_make, camera_model, gps_lat, gps_lon, color_space,
+                    body_serial, exposure_bias
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 rusqlite::params![
                     new_exif.image_id,
                     new_exif.iso,
@@ -569,6 +574,8 @@ impl DBContext for SqliteDbRepository {
                     new_exif.gps_lat,
                     new_exif.gps_lon,
                     new_exif.color_space,
+                    new_exif.body_serial,
+                    new_exif.exposure_bias,
                 ],
             )?;
 
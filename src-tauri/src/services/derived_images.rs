@@ -0,0 +1,238 @@
+//! Derived images: files rendered by the app from other catalog images.
+//!
+//! A derived file is ingested through `IngestionService` like any imported
+//! file, its path is recorded in `ingestion_file_status` so previews and
+//! exports can resolve it, and `image_derivations` links it back to the
+//! images it was built from.
+
+use crate::models::discovery::{DiscoveredFile, DiscoveryError, RawFormat};
+use crate::services::ingestion::IngestionService;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// How a derived image was produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivationKind {
+    HdrMerge,
+    Panorama,
+}
+
+impl DerivationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::HdrMerge => "hdr_merge",
+            Self::Panorama => "panorama",
+        }
+    }
+}
+
+/// Catalog record of an ingested derived file.
+#[derive(Debug, Clone)]
+pub struct DerivedImage {
+    pub image_id: i64,
+    pub blake3_hash: String,
+    pub session_id: Uuid,
+    pub file_path: PathBuf,
+}
+
+/// One source of a derived image, in `source_order`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivationSource {
+    pub source_image_id: i64,
+    pub derivation: String,
+    pub source_order: i64,
+}
+
+#[derive(Debug, Error)]
+pub enum DerivedImageError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Ingestion error: {0}")]
+    Discovery(#[from] DiscoveryError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Derived file {0} was not ingested: {1}")]
+    NotIngested(String, String),
+}
+
+/// Ingests a rendered file in its own ingestion session.
+pub async fn ingest_derived_file(
+    ingestion: &IngestionService,
+    path: &Path,
+    format: RawFormat,
+) -> Result<DerivedImage, DerivedImageError> {
+    let metadata = std::fs::metadata(path)?;
+    let session_id = Uuid::new_v4();
+    ingestion.create_ingestion_session(session_id).await?;
+
+    let file = DiscoveredFile::new(
+        session_id,
+        path.to_path_buf(),
+        format,
+        metadata.len(),
+        chrono::DateTime::<chrono::Utc>::from(metadata.modified()?),
+    );
+    let result = ingestion.ingest_file(&file).await?;
+    ingestion.complete_session(session_id).await?;
+
+    match (result.database_id, result.metadata) {
+        (Some(image_id), Some(metadata)) if result.success => Ok(DerivedImage {
+            image_id,
+            blake3_hash: metadata.blake3_hash,
+            session_id,
+            file_path: path.to_path_buf(),
+        }),
+        _ => Err(DerivedImageError::NotIngested(
+            path.display().to_string(),
+            result.error.unwrap_or_else(|| "unknown error".to_string()),
+        )),
+    }
+}
+
+/// Records the file path of a derived image and links it to its sources.
+pub fn link_derived_image(
+    conn: &mut Connection,
+    derived: &DerivedImage,
+    kind: DerivationKind,
+    source_image_ids: &[i64],
+) -> Result<(), DerivedImageError> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "INSERT INTO ingestion_file_status (session_id, file_path, blake3_hash, status)
+         VALUES (?1, ?2, ?3, 'ingested')",
+        params![
+            derived.session_id.to_string(),
+            derived.file_path.to_string_lossy(),
+            derived.blake3_hash
+        ],
+    )?;
+
+    for (order, source_image_id) in source_image_ids.iter().enumerate() {
+        tx.execute(
+            "INSERT OR REPLACE INTO image_derivations
+                (derived_image_id, source_image_id, derivation, source_order)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                derived.image_id,
+                source_image_id,
+                kind.as_str(),
+                order as i64
+            ],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Returns the sources of a derived image (empty for regular imports).
+pub fn get_derivation_sources(
+    conn: &Connection,
+    derived_image_id: i64,
+) -> rusqlite::Result<Vec<DerivationSource>> {
+    let mut stmt = conn.prepare(
+        "SELECT source_image_id, derivation, source_order
+         FROM image_derivations
+         WHERE derived_image_id = ?1
+         ORDER BY source_order",
+    )?;
+
+    let sources = stmt
+        .query_map([derived_image_id], |row| {
+            Ok(DerivationSource {
+                source_image_id: row.get(0)?,
+                derivation: row.get(1)?,
+                source_order: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::hashing::HashConfig;
+    use crate::services::blake3::Blake3Service;
+    use crate::services::db_repository::SqliteDbRepository;
+    use crate::services::hdr_merge::{encode_float_image, HdrOutputFormat};
+    use crate::types::db_context::DBContext;
+    use luminafast_image_core::LinearImage;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_ingested_derived_file_is_linked_to_sources() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("catalog.db");
+        let mut db = Database::new(&db_path).unwrap();
+        db.initialize().unwrap();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO images (id, blake3_hash, filename, extension)
+                 VALUES (1, 'a', 'a.arw', 'arw'), (2, 'b', 'b.arw', 'arw');",
+            )
+            .unwrap();
+
+        let output = dir.path().join("merged.tiff");
+        let image = LinearImage::new(1, 1, vec![0.5, 1.5, 2.5]).unwrap();
+        std::fs::write(&output, encode_float_image(&image, HdrOutputFormat::Tiff)).unwrap();
+
+        let repository = SqliteDbRepository::from_db_path(&db_path).unwrap();
+        let db_context: Arc<dyn DBContext> = Arc::new(repository);
+        let ingestion = IngestionService::with_context(
+            Arc::new(Blake3Service::new(HashConfig::default())),
+            db_context,
+        );
+
+        let derived = ingest_derived_file(&ingestion, &output, RawFormat::TIFF)
+            .await
+            .unwrap();
+        link_derived_image(db.connection(), &derived, DerivationKind::HdrMerge, &[2, 1]).unwrap();
+
+        let sources = get_derivation_sources(db.connection(), derived.image_id).unwrap();
+        assert_eq!(
+            sources,
+            vec![
+                DerivationSource {
+                    source_image_id: 2,
+                    derivation: "hdr_merge".to_string(),
+                    source_order: 0,
+                },
+                DerivationSource {
+                    source_image_id: 1,
+                    derivation: "hdr_merge".to_string(),
+                    source_order: 1,
+                },
+            ]
+        );
+
+        let path = crate::services::export_pipeline::resolve_source_image_path(
+            db.connection(),
+            derived.image_id,
+        )
+        .unwrap();
+        assert_eq!(path, output);
+    }
+
+    #[test]
+    fn test_regular_import_has_no_sources() {
+        let dir = TempDir::new().unwrap();
+        let mut db = Database::new(dir.path().join("catalog.db")).unwrap();
+        db.initialize().unwrap();
+
+        assert!(get_derivation_sources(db.connection(), 42)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Process version pinning for edit recipes.
+//!
+//! An image is pinned to a process version the first time it is edited. The
+//! export pipeline reads the pin back so a recipe is always rendered with the
+//! algorithms it was created with, whatever `CURRENT_PROCESS_VERSION` is.
+
+use luminafast_image_core::CURRENT_PROCESS_VERSION;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Returns the process version pinned for an image, or the current version
+/// for images that were never edited.
+pub fn load_process_version(conn: &Connection, image_id: i64) -> rusqlite::Result<u32> {
+    let pinned = conn
+        .query_row(
+            "SELECT process_version FROM image_process_versions WHERE image_id = ?1",
+            [image_id],
+            |row| row.get::<_, u32>(0),
+        )
+        .optional()?;
+
+    Ok(pinned.unwrap_or(CURRENT_PROCESS_VERSION))
+}
+
+/// Pins an image to the current process version unless it is already pinned.
+pub fn pin_process_version(conn: &Connection, image_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO image_process_versions (image_id, process_version)
+         SELECT id, ?2 FROM images WHERE id = ?1",
+        params![image_id, CURRENT_PROCESS_VERSION],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE images (id INTEGER PRIMARY KEY);
+            CREATE TABLE image_process_versions (
+                image_id INTEGER PRIMARY KEY,
+                process_version INTEGER NOT NULL,
+                pinned_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO images (id) VALUES (1);
+        "#,
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_unpinned_image_uses_current_process_version() {
+        let conn = setup_db();
+
+        assert_eq!(
+            load_process_version(&conn, 1).unwrap(),
+            CURRENT_PROCESS_VERSION
+        );
+    }
+
+    #[test]
+    fn test_pin_keeps_existing_version() {
+        let conn = setup_db();
+        conn.execute(
+            "INSERT INTO image_process_versions (image_id, process_version) VALUES (1, 7)",
+            [],
+        )
+        .unwrap();
+
+        pin_process_version(&conn, 1).unwrap();
+
+        assert_eq!(load_process_version(&conn, 1).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_pin_ignores_unknown_image() {
+        let conn = setup_db();
+
+        pin_process_version(&conn, 42).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM image_process_versions", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
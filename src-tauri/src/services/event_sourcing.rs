@@ -1,6 +1,6 @@
-use crate::models::event::Event;
+use crate::models::event::{Event, EventType, TargetType};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, Params};
 use std::fmt;
 
 #[derive(Debug)]
@@ -72,8 +72,32 @@ impl<'a> EventStore<'a> {
     }
 
     pub fn get_events(&self) -> Result<Vec<Event>, EventStoreError> {
-        let mut stmt = self.conn.prepare("SELECT id, timestamp, event_type, payload, target_type, target_id, user_id, created_at FROM events ORDER BY timestamp ASC")?;
-        let rows = stmt.query_map([], |row| {
+        self.query_events(
+            "SELECT id, timestamp, event_type, payload, target_type, target_id, user_id, created_at FROM events ORDER BY timestamp ASC",
+            [],
+        )
+    }
+
+    /// Events of one type on one target, oldest first, filtered in SQL.
+    pub fn get_target_events(
+        &self,
+        target_type: TargetType,
+        target_id: i64,
+        event_type: EventType,
+    ) -> Result<Vec<Event>, EventStoreError> {
+        self.query_events(
+            "SELECT id, timestamp, event_type, payload, target_type, target_id, user_id, created_at FROM events WHERE target_type = ?1 AND target_id = ?2 AND event_type = ?3 ORDER BY timestamp ASC",
+            params![
+                serde_json::to_string(&target_type)?,
+                target_id,
+                serde_json::to_string(&event_type)?,
+            ],
+        )
+    }
+
+    fn query_events(&self, sql: &str, params: impl Params) -> Result<Vec<Event>, EventStoreError> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| {
             let event_type: String = row.get(2)?;
             let payload: String = row.get(3)?;
             let target_type: String = row.get(4)?;
@@ -103,11 +127,11 @@ impl<'a> EventStore<'a> {
                 user_id,
             ) = row_result?;
 
-            let event_type: crate::models::event::EventType =
+            let event_type: EventType =
                 serde_json::from_str(&event_type_str).map_err(EventStoreError::Deserialization)?;
             let payload: crate::models::event::EventPayload =
                 serde_json::from_str(&payload_str).map_err(EventStoreError::Deserialization)?;
-            let target_type: crate::models::event::TargetType =
+            let target_type: TargetType =
                 serde_json::from_str(&target_type_str).map_err(EventStoreError::Deserialization)?;
             let created_at = DateTime::parse_from_rfc3339(&created_at_str)
                 .map_err(EventStoreError::DateParsing)?
@@ -132,7 +156,7 @@ impl<'a> EventStore<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::event::{Event, EventPayload, EventType, ImageAddedPayload, TargetType};
+    use crate::models::event::{EditAppliedPayload, EventPayload, ImageAddedPayload};
     use uuid::Uuid;
 
     fn setup_db() -> Connection {
@@ -155,6 +179,53 @@ mod tests {
         conn
     }
 
+    fn edit_event(target_type: TargetType, target_id: i64, timestamp: i64) -> Event {
+        Event {
+            id: Uuid::new_v4().to_string(),
+            timestamp,
+            event_type: EventType::EditApplied,
+            payload: EventPayload::EditApplied(EditAppliedPayload {
+                image_id: target_id,
+                edit_type: "exposure".to_string(),
+                old_value: None,
+                new_value: serde_json::json!(timestamp),
+            }),
+            target_type,
+            target_id,
+            user_id: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_get_target_events_filters_in_sql() {
+        let conn = setup_db();
+        let store = EventStore::new(&conn);
+        store
+            .append_event(&edit_event(TargetType::Image, 1, 20))
+            .unwrap();
+        store
+            .append_event(&edit_event(TargetType::Image, 2, 15))
+            .unwrap();
+        store
+            .append_event(&edit_event(TargetType::Collection, 1, 30))
+            .unwrap();
+        store
+            .append_event(&edit_event(TargetType::Image, 1, 10))
+            .unwrap();
+
+        let events = store
+            .get_target_events(TargetType::Image, 1, EventType::EditApplied)
+            .unwrap();
+
+        let timestamps: Vec<_> = events.iter().map(|event| event.timestamp).collect();
+        assert_eq!(timestamps, vec![10, 20]);
+        assert!(store
+            .get_target_events(TargetType::Image, 1, EventType::RatingChanged)
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn test_append_and_get_event() {
         let conn = setup_db();
@@ -3,7 +3,8 @@
 //! Conforme aux spécifications de Phase 2.2 du plan de développement
 
 use crate::models::exif::ExifMetadata;
-use exif::{Exif, In, Reader, Tag, Value};
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::{DateTime, Exif, In, Reader, Tag, Value};
 use std::fs::File;
 use std::io::BufReader;
 
@@ -44,6 +45,9 @@ pub fn extract_exif_metadata(path: &str) -> Result<ExifMetadata, String> {
         gps_lat: get_gps_latitude(&exif),
         gps_lon: get_gps_longitude(&exif),
         color_space: get_color_space(&exif),
+        body_serial: get_field_string(&exif, Tag::BodySerialNumber),
+        exposure_bias: get_exposure_bias(&exif),
+        captured_at: get_capture_datetime(&exif),
     })
 }
 
@@ -198,6 +202,53 @@ fn get_color_space(exif: &Exif) -> Option<String> {
         })
 }
 
+/// Extrait la correction d'exposition en EV
+fn get_exposure_bias(exif: &Exif) -> Option<f64> {
+    exif.get_field(Tag::ExposureBiasValue, In::PRIMARY)
+        .and_then(|field| match field.value {
+            Value::SRational(ref v) if !v.is_empty() && v[0].denom != 0 => {
+                Some(v[0].num as f64 / v[0].denom as f64)
+            }
+            _ => None,
+        })
+}
+
+/// Extrait la date de prise de vue (DateTimeOriginal), avec les sous-secondes
+/// quand le boîtier les fournit — indispensable pour séparer les rafales
+fn get_capture_datetime(exif: &Exif) -> Option<NaiveDateTime> {
+    let mut datetime = exif
+        .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .and_then(|field| match field.value {
+            Value::Ascii(ref v) if !v.is_empty() => DateTime::from_ascii(&v[0]).ok(),
+            _ => None,
+        })?;
+
+    if let Some(field) = exif.get_field(Tag::SubSecTimeOriginal, In::PRIMARY) {
+        if let Value::Ascii(ref v) = field.value {
+            if let Some(subsec) = v.first() {
+                let _ = datetime.parse_subsec(subsec);
+            }
+        }
+    }
+
+    exif_datetime_to_naive(&datetime)
+}
+
+/// Convertit une date EXIF en `NaiveDateTime` (None si hors plage)
+fn exif_datetime_to_naive(datetime: &DateTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(
+        datetime.year as i32,
+        datetime.month as u32,
+        datetime.day as u32,
+    )?
+    .and_hms_nano_opt(
+        datetime.hour as u32,
+        datetime.minute as u32,
+        datetime.second as u32,
+        datetime.nanosecond.unwrap_or(0),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,6 +278,18 @@ mod tests {
         assert_eq!(shutter_speed_to_log2(1, 0), 0.0);
     }
 
+    #[test]
+    fn test_exif_datetime_to_naive_keeps_subseconds() {
+        let mut datetime = DateTime::from_ascii(b"2024:05:01 10:00:03").unwrap();
+        datetime.parse_subsec(b"25").unwrap();
+
+        let naive = exif_datetime_to_naive(&datetime).expect("valid date");
+        assert_eq!(naive.to_string(), "2024-05-01 10:00:03.250");
+
+        let invalid = DateTime::from_ascii(b"2024:13:01 10:00:03").unwrap();
+        assert!(exif_datetime_to_naive(&invalid).is_none());
+    }
+
     #[test]
     fn test_extract_exif_from_nonexistent_file() {
         let result = extract_exif_metadata("/nonexistent/file.jpg");
@@ -0,0 +1,828 @@
+//! Batch export of catalog images on a bounded worker pool.
+//!
+//! Workers never go through the shared `AppState` connection: each image
+//! opens its own read-only connection, resolves its recipe and metadata, and
+//! closes it before decoding, so the catalog stays usable while a batch
+//! renders. Cancellation is checked before each image; renders already in
+//! flight finish and the remaining images are reported as cancelled.
+//!
+//! Output paths come from a `naming` template resolved for the whole batch
+//! before the first render, so `preview_batch_names` shows exactly what a run
+//! would write.
+
+use crate::services::export_encoding::ExportFormatOptions;
+use crate::services::export_metadata::{parse_capture_date, ExportMetadataOptions};
+use crate::services::export_pipeline::{
+    prepare_export, render_prepared_export, ExportFormat, ExportPipelineError, ExportRequest,
+    ExportResult, ExportTarget, RsRawDecoder,
+};
+use crate::services::export_sizing::ExportResize;
+use crate::services::export_watermark::Watermark;
+use crate::services::naming::{
+    NameAction, NamePlanner, NamePreview, NamingContext, NamingOptions, NamingTemplate,
+};
+use crate::services::smart_query_parser::{parse_smart_query, parse_smart_query_order};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Tauri event carrying an `ExportProgress`, the export counterpart of
+/// `ingestion-progress`.
+pub const EXPORT_PROGRESS_EVENT: &str = "export-progress";
+
+/// Worker count when the request does not set one.
+pub const DEFAULT_EXPORT_WORKERS: usize = 4;
+
+/// Each worker holds a full-resolution render in memory.
+pub const MAX_EXPORT_WORKERS: usize = 8;
+
+/// Output name of each image, without extension; see `services::naming` for
+/// the available tokens.
+pub const DEFAULT_FILE_NAME_TEMPLATE: &str = "{filename}_edited";
+
+/// How long a worker waits for a writer to release the catalog.
+const CATALOG_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Images exported by a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum BatchExportSource {
+    #[serde(rename_all = "camelCase")]
+    Images { image_ids: Vec<i64> },
+    /// Members of a static collection, or the results of a smart one.
+    #[serde(rename_all = "camelCase")]
+    Collection { collection_id: i64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchExportRequest {
+    pub source: BatchExportSource,
+    pub output_dir: PathBuf,
+    /// Output path under `output_dir`, without extension, and collision policy.
+    pub naming: NamingOptions,
+    pub format: ExportFormat,
+    pub resize: ExportResize,
+    pub options: ExportFormatOptions,
+    pub metadata: ExportMetadataOptions,
+    pub watermark: Option<Watermark>,
+    /// Fails non-RAW sources, like `export_raw_edited`.
+    pub raw_only: bool,
+    /// Clamped to `1..=MAX_EXPORT_WORKERS`.
+    pub max_workers: usize,
+}
+
+/// Batch export progress event (emitted after each image)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub job_id: Uuid,
+    pub total: usize,
+    /// Images finished so far, whatever their outcome
+    pub processed: usize,
+    pub successful: usize,
+    pub failed: usize,
+    /// Images skipped after cancellation
+    pub cancelled: usize,
+    /// Images skipped because their output file already existed
+    pub skipped: usize,
+    /// Source file name of the last finished image
+    pub current_file: Option<String>,
+    /// Progress percentage (0.0 - 1.0)
+    pub percentage: f32,
+}
+
+impl ExportProgress {
+    fn new(job_id: Uuid, total: usize) -> Self {
+        Self {
+            job_id,
+            total,
+            processed: 0,
+            successful: 0,
+            failed: 0,
+            cancelled: 0,
+            skipped: 0,
+            current_file: None,
+            percentage: if total == 0 { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BatchExportFailure {
+    pub image_id: i64,
+    pub error: String,
+}
+
+/// Outcome of a batch, each list in source order.
+#[derive(Debug)]
+pub struct BatchExportResult {
+    pub job_id: Uuid,
+    pub total: usize,
+    pub exported: Vec<ExportResult>,
+    pub failed: Vec<BatchExportFailure>,
+    /// Images never started because the job was cancelled.
+    pub cancelled: Vec<i64>,
+    /// Images whose output already existed under the `Skip` collision policy.
+    pub skipped: Vec<i64>,
+}
+
+/// Cancellation flag shared between a running job and its caller.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct PlannedExport {
+    request: ExportRequest,
+    raw_only: bool,
+    source_name: String,
+    action: NameAction,
+}
+
+enum ExportOutcome {
+    Exported(ExportResult),
+    Failed(String),
+    Cancelled,
+    Skipped,
+}
+
+/// Exports every image of `request.source` with the catalog at `db_path`.
+///
+/// `on_progress` receives an event before the first image and after each
+/// one. Per-image errors are collected in the result; only invalid options,
+/// an unknown collection or an unusable output directory fail the job.
+pub fn run_batch_export(
+    db_path: &Path,
+    job_id: Uuid,
+    request: &BatchExportRequest,
+    cancel: &CancellationToken,
+    on_progress: &(dyn Fn(&ExportProgress) + Sync),
+) -> Result<BatchExportResult, ExportPipelineError> {
+    request.options.validate(request.format)?;
+    request.resize.validate()?;
+    if let Some(watermark) = &request.watermark {
+        watermark.validate()?;
+    }
+    parse_file_name_template(&request.naming.template)?;
+    fs::create_dir_all(&request.output_dir)?;
+
+    let planned = {
+        let conn = open_catalog_connection(db_path)?;
+        plan_batch(&conn, request)?
+    };
+
+    Ok(run_planned_exports(
+        db_path,
+        job_id,
+        &planned,
+        request.max_workers,
+        cancel,
+        on_progress,
+    ))
+}
+
+/// Renders each `(request, raw_only)` pair again at its own output path,
+/// overwriting what is there, on the same worker pool as `run_batch_export`.
+pub fn run_export_requests(
+    db_path: &Path,
+    job_id: Uuid,
+    requests: Vec<(ExportRequest, bool)>,
+    max_workers: usize,
+    cancel: &CancellationToken,
+    on_progress: &(dyn Fn(&ExportProgress) + Sync),
+) -> BatchExportResult {
+    let planned: Vec<PlannedExport> = requests
+        .into_iter()
+        .map(|(request, raw_only)| PlannedExport {
+            source_name: request
+                .output_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            request,
+            raw_only,
+            action: NameAction::Overwrite,
+        })
+        .collect();
+
+    run_planned_exports(db_path, job_id, &planned, max_workers, cancel, on_progress)
+}
+
+fn run_planned_exports(
+    db_path: &Path,
+    job_id: Uuid,
+    planned: &[PlannedExport],
+    max_workers: usize,
+    cancel: &CancellationToken,
+    on_progress: &(dyn Fn(&ExportProgress) + Sync),
+) -> BatchExportResult {
+    let total = planned.len();
+    let progress = Mutex::new(ExportProgress::new(job_id, total));
+    on_progress(&progress.lock().expect("export progress lock poisoned"));
+
+    let outcomes: Vec<Mutex<Option<ExportOutcome>>> =
+        planned.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    let workers = max_workers.clamp(1, MAX_EXPORT_WORKERS).min(total);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(plan) = planned.get(index) else {
+                    break;
+                };
+
+                let outcome = if cancel.is_cancelled() {
+                    ExportOutcome::Cancelled
+                } else if plan.action == NameAction::Skip {
+                    ExportOutcome::Skipped
+                } else {
+                    match export_one(db_path, &plan.request, plan.raw_only) {
+                        Ok(result) => ExportOutcome::Exported(result),
+                        Err(error) => ExportOutcome::Failed(error.to_string()),
+                    }
+                };
+
+                // Reported under the lock so events arrive with increasing counts.
+                let mut progress = progress.lock().expect("export progress lock poisoned");
+                match &outcome {
+                    ExportOutcome::Exported(_) => progress.successful += 1,
+                    ExportOutcome::Failed(_) => progress.failed += 1,
+                    ExportOutcome::Cancelled => progress.cancelled += 1,
+                    ExportOutcome::Skipped => progress.skipped += 1,
+                }
+                progress.processed += 1;
+                progress.current_file = Some(plan.source_name.clone());
+                progress.percentage = progress.processed as f32 / total as f32;
+                on_progress(&progress);
+                drop(progress);
+
+                *outcomes[index]
+                    .lock()
+                    .expect("export outcome lock poisoned") = Some(outcome);
+            });
+        }
+    });
+
+    let mut result = BatchExportResult {
+        job_id,
+        total,
+        exported: Vec::new(),
+        failed: Vec::new(),
+        cancelled: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (plan, outcome) in planned.iter().zip(outcomes) {
+        let image_id = plan.request.image_id;
+        match outcome.into_inner().expect("export outcome lock poisoned") {
+            Some(ExportOutcome::Exported(exported)) => result.exported.push(exported),
+            Some(ExportOutcome::Failed(error)) => {
+                result.failed.push(BatchExportFailure { image_id, error })
+            }
+            Some(ExportOutcome::Skipped) => result.skipped.push(image_id),
+            Some(ExportOutcome::Cancelled) | None => result.cancelled.push(image_id),
+        }
+    }
+
+    result
+}
+
+/// Reads the catalog state of one image through its own connection, then
+/// renders with the connection already closed.
+fn export_one(
+    db_path: &Path,
+    request: &ExportRequest,
+    raw_only: bool,
+) -> Result<ExportResult, ExportPipelineError> {
+    let prepared = {
+        let conn = open_catalog_connection(db_path)?;
+        prepare_export(&conn, request, raw_only)?
+    };
+    if let Some(parent) = request.output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    render_prepared_export(prepared, request, &RsRawDecoder)
+}
+
+fn open_catalog_connection(db_path: &Path) -> Result<Connection, ExportPipelineError> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(CATALOG_BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Resolves the output path of every image of `request` without writing
+/// anything.
+pub fn preview_batch_names(
+    conn: &Connection,
+    request: &BatchExportRequest,
+) -> Result<Vec<NamePreview>, ExportPipelineError> {
+    Ok(plan_batch(conn, request)?
+        .into_iter()
+        .map(|plan| NamePreview {
+            image_id: Some(plan.request.image_id),
+            source: plan.source_name,
+            target: plan.request.output_path.to_string_lossy().to_string(),
+            action: plan.action,
+        })
+        .collect())
+}
+
+fn plan_batch(
+    conn: &Connection,
+    request: &BatchExportRequest,
+) -> Result<Vec<PlannedExport>, ExportPipelineError> {
+    let template = parse_file_name_template(&request.naming.template)?;
+    let image_ids = resolve_source_image_ids(conn, &request.source)?;
+    let collection = match request.source {
+        BatchExportSource::Collection { collection_id } => conn
+            .query_row(
+                "SELECT name FROM collections WHERE id = ?1",
+                [collection_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?,
+        BatchExportSource::Images { .. } => None,
+    };
+    let extension = request.format.extension();
+    let mut planner = NamePlanner::new(&request.output_dir, request.naming.collision);
+
+    image_ids
+        .into_iter()
+        .zip(request.naming.sequence_start..)
+        .map(|(image_id, sequence)| {
+            let mut context = naming_context(conn, image_id)?;
+            context.collection = collection.clone();
+            let source_name = if context.filename.is_empty() {
+                format!("image_{image_id}")
+            } else {
+                context.filename.clone()
+            };
+
+            let relative = template.render(&context, sequence, &request.naming.custom_text);
+            let planned = planner.claim(&relative, extension);
+
+            Ok(PlannedExport {
+                request: ExportRequest {
+                    image_id,
+                    output_path: planned.path,
+                    format: request.format,
+                    resize: request.resize,
+                    options: request.options,
+                    metadata: request.metadata,
+                    watermark: request.watermark.clone(),
+                    target: ExportTarget::Current,
+                },
+                raw_only: request.raw_only,
+                source_name,
+                action: planned.action,
+            })
+        })
+        .collect()
+}
+
+/// Catalog values available to naming tokens; empty for unknown images,
+/// which then fail at export.
+fn naming_context(conn: &Connection, image_id: i64) -> Result<NamingContext, ExportPipelineError> {
+    let context = conn
+        .query_row(
+            "SELECT i.filename, i.blake3_hash, i.captured_at, e.camera_model, s.rating
+             FROM images i
+             LEFT JOIN exif_metadata e ON e.image_id = i.id
+             LEFT JOIN image_state s ON s.image_id = i.id
+             WHERE i.id = ?1",
+            [image_id],
+            |row| {
+                Ok(NamingContext {
+                    filename: row.get(0)?,
+                    blake3_hash: row.get(1)?,
+                    captured_at: row
+                        .get::<_, Option<String>>(2)?
+                        .and_then(|value| parse_capture_date(&value)),
+                    camera_model: row.get(3)?,
+                    rating: row.get(4)?,
+                    collection: None,
+                })
+            },
+        )
+        .optional()?;
+    Ok(context.unwrap_or_default())
+}
+
+/// Parses an output naming template, rejecting absolute paths, `..` and
+/// unknown tokens.
+pub fn parse_file_name_template(template: &str) -> Result<NamingTemplate, ExportPipelineError> {
+    NamingTemplate::parse(template).map_err(|error| {
+        ExportPipelineError::InvalidFileNameTemplate(format!("'{template}': {error}"))
+    })
+}
+
+/// Image IDs of `source`, without duplicates, in display order.
+fn resolve_source_image_ids(
+    conn: &Connection,
+    source: &BatchExportSource,
+) -> Result<Vec<i64>, ExportPipelineError> {
+    let image_ids = match source {
+        BatchExportSource::Images { image_ids } => image_ids.clone(),
+        BatchExportSource::Collection { collection_id } => {
+            collection_image_ids(conn, *collection_id)?
+        }
+    };
+
+    let mut seen = HashSet::new();
+    Ok(image_ids
+        .into_iter()
+        .filter(|image_id| seen.insert(*image_id))
+        .collect())
+}
+
+fn collection_image_ids(
+    conn: &Connection,
+    collection_id: i64,
+) -> Result<Vec<i64>, ExportPipelineError> {
+    let invalid = |reason: String| ExportPipelineError::InvalidCollection {
+        id: collection_id,
+        reason,
+    };
+
+    let (collection_type, smart_query) = conn
+        .query_row(
+            "SELECT type, smart_query FROM collections WHERE id = ?1",
+            [collection_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| invalid("not found".to_string()))?;
+
+    if collection_type != "smart" {
+        let mut stmt = conn.prepare(
+            "SELECT i.id
+             FROM images i
+             INNER JOIN collection_images ci ON i.id = ci.image_id
+             WHERE ci.collection_id = ?1
+             ORDER BY ci.sort_order ASC, i.imported_at DESC",
+        )?;
+        let image_ids = stmt
+            .query_map([collection_id], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(image_ids);
+    }
+
+    let smart_query = smart_query.ok_or_else(|| invalid("no smart query".to_string()))?;
+    let where_clause = parse_smart_query(&smart_query).map_err(|e| invalid(e.to_string()))?;
+    let order_clause = parse_smart_query_order(&smart_query).map_err(|e| invalid(e.to_string()))?;
+    // Same joins as `get_smart_collection_results`, which the parser targets.
+    let mut stmt = conn.prepare(&format!(
+        "SELECT images.id
+         FROM images
+         LEFT JOIN image_state ON images.id = image_state.image_id
+         LEFT JOIN exif_metadata ON images.id = exif_metadata.image_id
+         LEFT JOIN image_quality_metrics ON images.id = image_quality_metrics.image_id
+         WHERE {where_clause}
+         ORDER BY {order_clause}"
+    ))?;
+    let image_ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(image_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::services::export_watermark::{WatermarkAnchor, WatermarkContent, WatermarkOffset};
+    use crate::services::naming::CollisionPolicy;
+    use image::{Rgba, RgbaImage};
+    use tempfile::TempDir;
+
+    struct Catalog {
+        dir: TempDir,
+        db_path: PathBuf,
+    }
+
+    /// Migrated catalog whose images are 8x6 PNG files named after `filenames`.
+    fn catalog_with_images(filenames: &[&str]) -> Catalog {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("catalog.db");
+        let mut db = Database::new(&db_path).unwrap();
+        db.initialize().unwrap();
+
+        let conn = db.connection();
+        conn.execute("INSERT INTO ingestion_sessions (id) VALUES ('session')", [])
+            .unwrap();
+        for (index, filename) in filenames.iter().enumerate() {
+            let image_id = index as i64 + 1;
+            let source_dir = dir.path().join(format!("card{image_id}"));
+            fs::create_dir_all(&source_dir).unwrap();
+            let source_path = source_dir.join(filename);
+            RgbaImage::from_pixel(8, 6, Rgba([90, 120, 150, 255]))
+                .save(&source_path)
+                .unwrap();
+
+            let hash = format!("hash-{image_id}");
+            conn.execute(
+                "INSERT INTO images (id, blake3_hash, filename, extension)
+                 VALUES (?1, ?2, ?3, 'png')",
+                rusqlite::params![image_id, hash, filename],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO ingestion_file_status (session_id, file_path, blake3_hash)
+                 VALUES ('session', ?1, ?2)",
+                rusqlite::params![source_path.to_string_lossy(), hash],
+            )
+            .unwrap();
+        }
+
+        Catalog { dir, db_path }
+    }
+
+    fn batch_request(catalog: &Catalog, source: BatchExportSource) -> BatchExportRequest {
+        BatchExportRequest {
+            source,
+            output_dir: catalog.dir.path().join("out"),
+            format: ExportFormat::Jpeg,
+            resize: ExportResize::default(),
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            naming: NamingOptions::new(DEFAULT_FILE_NAME_TEMPLATE),
+            raw_only: false,
+            max_workers: 2,
+        }
+    }
+
+    fn run(
+        catalog: &Catalog,
+        request: &BatchExportRequest,
+        cancel: &CancellationToken,
+    ) -> (BatchExportResult, Vec<ExportProgress>) {
+        let events = Mutex::new(Vec::new());
+        let result = run_batch_export(
+            &catalog.db_path,
+            Uuid::new_v4(),
+            request,
+            cancel,
+            &|progress| events.lock().unwrap().push(progress.clone()),
+        )
+        .unwrap();
+        (result, events.into_inner().unwrap())
+    }
+
+    #[test]
+    fn exports_listed_images_and_reports_progress() {
+        let catalog = catalog_with_images(&["first.png", "second.png"]);
+        let request = batch_request(
+            &catalog,
+            BatchExportSource::Images {
+                image_ids: vec![2, 1, 2, 42],
+            },
+        );
+
+        let (result, events) = run(&catalog, &request, &CancellationToken::default());
+
+        assert_eq!(result.total, 3);
+        let exported: Vec<i64> = result.exported.iter().map(|r| r.image_id).collect();
+        assert_eq!(exported, vec![2, 1]);
+        for export in &result.exported {
+            assert_eq!((export.width, export.height), (8, 6));
+            assert!(Path::new(&export.output_path).exists());
+        }
+        assert!(result.exported[0]
+            .output_path
+            .ends_with("second_edited.jpg"));
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(result.failed[0].image_id, 42);
+        assert!(result.cancelled.is_empty());
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].processed, 0);
+        let processed: Vec<usize> = events.iter().map(|e| e.processed).collect();
+        assert_eq!(processed, vec![0, 1, 2, 3]);
+        let last = events.last().expect("final progress event");
+        assert_eq!((last.successful, last.failed, last.cancelled), (2, 1, 0));
+        assert_eq!(last.percentage, 1.0);
+    }
+
+    #[test]
+    fn collection_members_get_distinct_file_names() {
+        let catalog = catalog_with_images(&["dup.png", "dup.png", "other.png"]);
+        {
+            let mut db = Database::new(&catalog.db_path).unwrap();
+            db.connection()
+                .execute_batch(
+                    "INSERT INTO collections (id, name, type) VALUES (7, 'Picks', 'static');
+                     INSERT INTO collection_images (collection_id, image_id, sort_order)
+                     VALUES (7, 2, 0), (7, 1, 1);",
+                )
+                .unwrap();
+        }
+        let request = batch_request(&catalog, BatchExportSource::Collection { collection_id: 7 });
+
+        let (result, _) = run(&catalog, &request, &CancellationToken::default());
+
+        let paths: Vec<String> = result
+            .exported
+            .iter()
+            .map(|r| {
+                Path::new(&r.output_path)
+                    .file_name()
+                    .expect("output file name")
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(paths, vec!["dup_edited.jpg", "dup_edited_1.jpg"]);
+    }
+
+    #[test]
+    fn cancelled_job_skips_remaining_images() {
+        let catalog = catalog_with_images(&["a.png", "b.png", "c.png"]);
+        let request = batch_request(
+            &catalog,
+            BatchExportSource::Images {
+                image_ids: vec![1, 2, 3],
+            },
+        );
+        let cancel = CancellationToken::default();
+        cancel.cancel();
+
+        let (result, events) = run(&catalog, &request, &cancel);
+
+        assert!(result.exported.is_empty());
+        assert_eq!(result.cancelled, vec![1, 2, 3]);
+        assert_eq!(events.last().expect("final progress event").cancelled, 3);
+        assert!(!catalog.dir.path().join("out/a_edited.jpg").exists());
+    }
+
+    #[test]
+    fn unknown_collection_fails_the_job() {
+        let catalog = catalog_with_images(&[]);
+        let request = batch_request(&catalog, BatchExportSource::Collection { collection_id: 9 });
+
+        let error = run_batch_export(
+            &catalog.db_path,
+            Uuid::new_v4(),
+            &request,
+            &CancellationToken::default(),
+            &|_| {},
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            ExportPipelineError::InvalidCollection { id: 9, .. }
+        ));
+    }
+
+    #[test]
+    fn naming_template_names_outputs_from_catalog_values() {
+        let catalog = catalog_with_images(&["IMG_0001.png", "IMG_0002.png"]);
+        {
+            let mut db = Database::new(&catalog.db_path).unwrap();
+            db.connection()
+                .execute_batch(
+                    "UPDATE images SET captured_at = '2026-03-07T09:05:30Z' WHERE id = 1;
+                     INSERT INTO exif_metadata (image_id, camera_model) VALUES (1, 'X-T5');
+                     INSERT INTO image_state (image_id, rating) VALUES (1, 5);
+                     INSERT INTO collections (id, name, type) VALUES (3, 'Client Work', 'static');
+                     INSERT INTO collection_images (collection_id, image_id, sort_order)
+                     VALUES (3, 1, 0), (3, 2, 1);",
+                )
+                .unwrap();
+        }
+        let mut request =
+            batch_request(&catalog, BatchExportSource::Collection { collection_id: 3 });
+        request.naming = NamingOptions {
+            template: "{collection}/{yyyy}-{mm}-{dd}/{text}_{camera}_{rating}_{seq:3}_{hash:4}"
+                .to_string(),
+            custom_text: "web".to_string(),
+            sequence_start: 10,
+            collision: CollisionPolicy::Suffix,
+        };
+
+        let (result, _) = run(&catalog, &request, &CancellationToken::default());
+
+        let out = catalog.dir.path().join("out/Client Work");
+        let paths: Vec<PathBuf> = result
+            .exported
+            .iter()
+            .map(|r| PathBuf::from(&r.output_path))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                out.join("2026-03-07/web_X-T5_5_010_hash.jpg"),
+                out.join("unknown-unknown-unknown/web_unknown_0_011_hash.jpg"),
+            ]
+        );
+        assert!(paths.iter().all(|path| path.exists()));
+    }
+
+    #[test]
+    fn preview_and_skip_policy_follow_existing_outputs() {
+        let catalog = catalog_with_images(&["a.png", "b.png"]);
+        let mut request = batch_request(
+            &catalog,
+            BatchExportSource::Images {
+                image_ids: vec![1, 2],
+            },
+        );
+        request.naming.collision = CollisionPolicy::Skip;
+        let out = catalog.dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        fs::write(out.join("a_edited.jpg"), b"keep").unwrap();
+
+        let previews = {
+            let conn = open_catalog_connection(&catalog.db_path).unwrap();
+            preview_batch_names(&conn, &request).unwrap()
+        };
+        let actions: Vec<(Option<i64>, NameAction)> =
+            previews.iter().map(|p| (p.image_id, p.action)).collect();
+        assert_eq!(
+            actions,
+            vec![(Some(1), NameAction::Skip), (Some(2), NameAction::Create)]
+        );
+        assert_eq!(previews[1].source, "b.png");
+        assert!(previews[1].target.ends_with("b_edited.jpg"));
+
+        let (result, events) = run(&catalog, &request, &CancellationToken::default());
+
+        assert_eq!(result.skipped, vec![1]);
+        assert_eq!(result.exported.len(), 1);
+        assert_eq!(fs::read(out.join("a_edited.jpg")).unwrap(), b"keep");
+        assert_eq!(events.last().expect("final progress event").skipped, 1);
+
+        for template in ["", "  ", "../{filename}", "/abs/{filename}", "{unknown}"] {
+            assert!(parse_file_name_template(template).is_err(), "{template:?}");
+        }
+    }
+
+    #[test]
+    fn watermark_is_drawn_on_the_resized_output() {
+        let catalog = catalog_with_images(&["logo_me.png"]);
+        let logo_path = catalog.dir.path().join("logo.png");
+        RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+            .save(&logo_path)
+            .unwrap();
+        let mut request = batch_request(&catalog, BatchExportSource::Images { image_ids: vec![1] });
+        request.format = ExportFormat::Png;
+        request.resize = ExportResize::Exact {
+            width: 4,
+            height: 3,
+        };
+        request.watermark = Some(Watermark {
+            content: WatermarkContent::Image {
+                path: logo_path,
+                scale: 0.5,
+            },
+            opacity: 1.0,
+            anchor: WatermarkAnchor::TopLeft,
+            offset: WatermarkOffset { x: 0.0, y: 0.0 },
+        });
+
+        let (result, _) = run(&catalog, &request, &CancellationToken::default());
+
+        let output = image::open(&result.exported[0].output_path)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(output.dimensions(), (4, 3));
+        assert_eq!(output.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_ne!(output.get_pixel(3, 2), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn source_deserializes_from_tagged_json() {
+        let source: BatchExportSource =
+            serde_json::from_str(r#"{"kind":"images","imageIds":[3,4]}"#).unwrap();
+        assert_eq!(
+            source,
+            BatchExportSource::Images {
+                image_ids: vec![3, 4]
+            }
+        );
+
+        let source: BatchExportSource =
+            serde_json::from_str(r#"{"kind":"collection","collectionId":5}"#).unwrap();
+        assert_eq!(source, BatchExportSource::Collection { collection_id: 5 });
+    }
+}
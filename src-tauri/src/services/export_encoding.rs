@@ -0,0 +1,966 @@
+//! Export file encoders and their per-format options.
+//!
+//! Rendered pixels arrive as RGBA8 or RGBA16 (16-bit keeps the precision of
+//! linear RAW data through to PNG and TIFF). Each format validates the options
+//! it understands before the source is decoded, so an unsupported combination
+//! (16-bit JPEG, lossless AVIF...) fails fast instead of after a full render.
+//!
+//! Files are encoded in memory and written once, which lets a file-size cap
+//! binary-search the quality of lossy formats without touching the disk.
+
+use crate::services::export_metadata::{EmbeddedMetadata, TiffSubDirectories, PNG_XMP_KEYWORD};
+use crate::services::export_pipeline::{ExportFormat, ExportPipelineError};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use jpeg_encoder::{Density, SamplingFactor};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+use tiff::encoder::{colortype, Compression, DeflateLevel, Rational, TiffEncoder};
+use tiff::tags::ResolutionUnit;
+
+/// Lossy quality used when `ExportFormatOptions::quality` is unset.
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+pub const DEFAULT_WEBP_QUALITY: u8 = 90;
+pub const DEFAULT_AVIF_QUALITY: u8 = 80;
+/// AVIF encoder speed (1 slowest/smallest to 10 fastest) when unset.
+pub const DEFAULT_AVIF_SPEED: u8 = 4;
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Rendered export pixels, straight (non-premultiplied) sRGB RGBA.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportPixels {
+    Rgba8(Vec<u8>),
+    Rgba16(Vec<u16>),
+}
+
+impl ExportPixels {
+    fn sample_count(&self) -> usize {
+        match self {
+            Self::Rgba8(samples) => samples.len(),
+            Self::Rgba16(samples) => samples.len(),
+        }
+    }
+
+    fn has_transparency(&self) -> bool {
+        match self {
+            Self::Rgba8(samples) => samples.chunks_exact(4).any(|px| px[3] < u8::MAX),
+            Self::Rgba16(samples) => samples.chunks_exact(4).any(|px| px[3] < u16::MAX),
+        }
+    }
+}
+
+/// What happens to the alpha channel of the render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaHandling {
+    /// Keep alpha only when some pixel is not fully opaque.
+    #[default]
+    Auto,
+    Keep,
+    Discard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngCompression {
+    None,
+    #[default]
+    Fast,
+    Default,
+    Best,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiffCompression {
+    #[default]
+    None,
+    Lzw,
+    Deflate,
+    PackBits,
+}
+
+/// JPEG chroma subsampling; 4:2:0 gives the smallest files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ChromaSubsampling {
+    #[default]
+    #[serde(rename = "4:4:4")]
+    Yuv444,
+    #[serde(rename = "4:2:2")]
+    Yuv422,
+    #[serde(rename = "4:2:0")]
+    Yuv420,
+}
+
+/// Format-specific encoder settings carried by an `ExportRequest`. Options a
+/// format does not use are ignored, except those that would silently change
+/// the result (see `validate`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportFormatOptions {
+    /// Bits per channel: 8, or 16 for PNG and TIFF.
+    pub bit_depth: u8,
+    pub alpha: AlphaHandling,
+    /// Lossy quality in 1..=100 for JPEG, WebP and AVIF. With
+    /// `max_file_size_kb` it is the highest quality tried.
+    pub quality: Option<u8>,
+    /// WebP only: encode losslessly instead of with `quality`.
+    pub lossless: bool,
+    /// AVIF encoder speed in 1..=10.
+    pub avif_speed: Option<u8>,
+    pub png_compression: PngCompression,
+    pub tiff_compression: TiffCompression,
+    pub chroma_subsampling: ChromaSubsampling,
+    /// Resolution written to JPEG (JFIF), PNG (pHYs) and TIFF headers. WebP
+    /// and AVIF have no resolution field and ignore it.
+    pub dpi: Option<u16>,
+    /// Lossy formats only: highest quality whose file fits this many KiB.
+    pub max_file_size_kb: Option<u32>,
+}
+
+impl Default for ExportFormatOptions {
+    fn default() -> Self {
+        Self {
+            bit_depth: 8,
+            alpha: AlphaHandling::Auto,
+            quality: None,
+            lossless: false,
+            avif_speed: None,
+            png_compression: PngCompression::Fast,
+            tiff_compression: TiffCompression::None,
+            chroma_subsampling: ChromaSubsampling::Yuv444,
+            dpi: None,
+            max_file_size_kb: None,
+        }
+    }
+}
+
+impl ExportFormatOptions {
+    pub fn is_16_bit(&self) -> bool {
+        self.bit_depth == 16
+    }
+
+    /// Rejects options `format` cannot honour.
+    pub fn validate(&self, format: ExportFormat) -> Result<(), ExportPipelineError> {
+        match self.bit_depth {
+            8 => {}
+            16 if matches!(format, ExportFormat::Png | ExportFormat::Tiff) => {}
+            16 => {
+                return Err(invalid_options(format!(
+                    "16-bit output is only available for PNG and TIFF, not {}",
+                    format.as_str()
+                )))
+            }
+            other => return Err(invalid_options(format!("unsupported bit depth {other}"))),
+        }
+
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                return Err(invalid_options(format!(
+                    "quality {quality} outside 1..=100"
+                )));
+            }
+        }
+        if let Some(speed) = self.avif_speed {
+            if !(1..=10).contains(&speed) {
+                return Err(invalid_options(format!(
+                    "AVIF speed {speed} outside 1..=10"
+                )));
+            }
+        }
+        if self.dpi == Some(0) {
+            return Err(invalid_options("dpi must be positive".into()));
+        }
+
+        if self.lossless && format != ExportFormat::Webp {
+            return Err(invalid_options(format!(
+                "lossless mode is only available for WebP, not {}",
+                format.as_str()
+            )));
+        }
+        if self.alpha == AlphaHandling::Keep && format == ExportFormat::Jpeg {
+            return Err(invalid_options("JPEG cannot store an alpha channel".into()));
+        }
+        if let Some(limit) = self.max_file_size_kb {
+            if limit == 0 {
+                return Err(invalid_options("file size limit must be positive".into()));
+            }
+            if self.lossy_quality(format).is_none() {
+                return Err(invalid_options(format!(
+                    "a file size limit needs a lossy format, not {}{}",
+                    format.as_str(),
+                    if self.lossless { " (lossless)" } else { "" }
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Quality used for `format`, or `None` when it is lossless.
+    fn lossy_quality(&self, format: ExportFormat) -> Option<u8> {
+        let default = match format {
+            ExportFormat::Jpeg => DEFAULT_JPEG_QUALITY,
+            ExportFormat::Webp if !self.lossless => DEFAULT_WEBP_QUALITY,
+            ExportFormat::Avif => DEFAULT_AVIF_QUALITY,
+            _ => return None,
+        };
+        Some(self.quality.unwrap_or(default))
+    }
+}
+
+fn invalid_options(message: String) -> ExportPipelineError {
+    ExportPipelineError::InvalidFormatOptions(message)
+}
+
+/// What `write_export_image` put on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrittenExport {
+    /// Quality the file was encoded with; `None` for lossless output.
+    pub quality: Option<u8>,
+    pub file_size_bytes: u64,
+}
+
+/// Encodes and writes `pixels`. `metadata` is embedded into JPEG, PNG and
+/// TIFF files and ignored for WebP and AVIF.
+pub(crate) fn write_export_image(
+    pixels: &ExportPixels,
+    width: u32,
+    height: u32,
+    output_path: &Path,
+    format: ExportFormat,
+    options: &ExportFormatOptions,
+    metadata: Option<&EmbeddedMetadata>,
+) -> Result<WrittenExport, ExportPipelineError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|px| px.checked_mul(4))
+        .ok_or(ExportPipelineError::InvalidPixelBuffer {
+            expected: usize::MAX,
+            got: pixels.sample_count(),
+            width,
+            height,
+        })?;
+
+    if pixels.sample_count() != expected {
+        return Err(ExportPipelineError::InvalidPixelBuffer {
+            expected,
+            got: pixels.sample_count(),
+            width,
+            height,
+        });
+    }
+    options.validate(format)?;
+
+    let keep_alpha = match options.alpha {
+        _ if format == ExportFormat::Jpeg => false,
+        AlphaHandling::Auto => pixels.has_transparency(),
+        AlphaHandling::Keep => true,
+        AlphaHandling::Discard => false,
+    };
+    let image = EncoderInput {
+        pixels,
+        width,
+        height,
+        keep_alpha,
+        metadata,
+    };
+
+    let (bytes, quality) = match options.max_file_size_kb {
+        Some(limit_kb) => encode_within_size(&image, format, options, limit_kb)?,
+        None => {
+            let quality = options.lossy_quality(format);
+            (encode_image(&image, format, options, quality)?, quality)
+        }
+    };
+
+    if let Some(parent) = output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::write(output_path, &bytes)?;
+
+    Ok(WrittenExport {
+        quality,
+        file_size_bytes: bytes.len() as u64,
+    })
+}
+
+struct EncoderInput<'a> {
+    pixels: &'a ExportPixels,
+    width: u32,
+    height: u32,
+    keep_alpha: bool,
+    metadata: Option<&'a EmbeddedMetadata>,
+}
+
+/// Binary-searches the highest quality (up to `options.quality`) whose
+/// encoding fits `limit_kb`. File size is not strictly monotonic in quality,
+/// so the result is the best fit found, and it always respects the limit.
+fn encode_within_size(
+    image: &EncoderInput,
+    format: ExportFormat,
+    options: &ExportFormatOptions,
+    limit_kb: u32,
+) -> Result<(Vec<u8>, Option<u8>), ExportPipelineError> {
+    let limit_bytes = limit_kb as usize * 1024;
+    let mut high = options
+        .lossy_quality(format)
+        .ok_or_else(|| invalid_options("a file size limit needs a lossy format".into()))?;
+    let mut low = 1_u8;
+    let mut best = None;
+    let mut smallest = usize::MAX;
+
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let bytes = encode_image(image, format, options, Some(quality))?;
+        smallest = smallest.min(bytes.len());
+
+        if bytes.len() <= limit_bytes {
+            best = Some((bytes, Some(quality)));
+            low = quality + 1;
+        } else if quality == 1 {
+            break;
+        } else {
+            high = quality - 1;
+        }
+    }
+
+    best.ok_or(ExportPipelineError::FileSizeLimitUnreachable {
+        limit_kb,
+        smallest_kb: smallest.div_ceil(1024) as u64,
+    })
+}
+
+fn encode_image(
+    image: &EncoderInput,
+    format: ExportFormat,
+    options: &ExportFormatOptions,
+    quality: Option<u8>,
+) -> Result<Vec<u8>, ExportPipelineError> {
+    let EncoderInput {
+        pixels,
+        width,
+        height,
+        keep_alpha,
+        metadata,
+    } = *image;
+    let mut bytes = Vec::new();
+
+    match (format, pixels) {
+        (ExportFormat::Jpeg, ExportPixels::Rgba8(rgba)) => {
+            let mut encoder =
+                jpeg_encoder::Encoder::new(&mut bytes, quality.unwrap_or(DEFAULT_JPEG_QUALITY));
+            encoder.set_sampling_factor(match options.chroma_subsampling {
+                ChromaSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+                ChromaSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+                ChromaSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+            });
+            if let Some(dpi) = options.dpi {
+                encoder.set_density(Density::Inch { x: dpi, y: dpi });
+            }
+            if let Some(metadata) = metadata {
+                for (marker, data) in metadata.jpeg_app_segments()? {
+                    encoder
+                        .add_app_segment(marker, &data)
+                        .map_err(encoder_error)?;
+                }
+            }
+            let (width, height) = jpeg_dimensions(width, height)?;
+            encoder
+                .encode(
+                    &drop_alpha(rgba),
+                    width,
+                    height,
+                    jpeg_encoder::ColorType::Rgb,
+                )
+                .map_err(encoder_error)?;
+        }
+        (ExportFormat::Png, _) => encode_png(image, options, &mut bytes)?,
+        (ExportFormat::Tiff, _) => encode_tiff(image, options, &mut bytes)?,
+        (ExportFormat::Webp, ExportPixels::Rgba8(rgba)) => {
+            let (samples, color_type) = rgb8_samples(rgba, keep_alpha);
+            if options.lossless {
+                WebPEncoder::new_lossless(&mut bytes)
+                    .write_image(&samples, width, height, color_type)?;
+            } else {
+                let encoder = if keep_alpha {
+                    webp::Encoder::from_rgba(&samples, width, height)
+                } else {
+                    webp::Encoder::from_rgb(&samples, width, height)
+                };
+                let quality = quality.unwrap_or(DEFAULT_WEBP_QUALITY);
+                let encoded = encoder
+                    .encode_simple(false, quality as f32)
+                    .map_err(|error| encoder_error(format!("{error:?}")))?;
+                bytes.extend_from_slice(&encoded);
+            }
+        }
+        (ExportFormat::Avif, ExportPixels::Rgba8(rgba)) => {
+            let (samples, color_type) = rgb8_samples(rgba, keep_alpha);
+            AvifEncoder::new_with_speed_quality(
+                &mut bytes,
+                options.avif_speed.unwrap_or(DEFAULT_AVIF_SPEED),
+                quality.unwrap_or(DEFAULT_AVIF_QUALITY),
+            )
+            .write_image(&samples, width, height, color_type)?;
+        }
+        (ExportFormat::Jpeg | ExportFormat::Webp | ExportFormat::Avif, ExportPixels::Rgba16(_)) => {
+            return Err(invalid_options(format!(
+                "{} cannot encode 16-bit pixels",
+                format.as_str()
+            )));
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn jpeg_dimensions(width: u32, height: u32) -> Result<(u16, u16), ExportPipelineError> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(invalid_options(format!(
+            "JPEG is limited to 65535 pixels per side, got {width}x{height}"
+        ))),
+    }
+}
+
+fn encode_png(
+    image: &EncoderInput,
+    options: &ExportFormatOptions,
+    bytes: &mut Vec<u8>,
+) -> Result<(), ExportPipelineError> {
+    let mut info = png::Info::with_size(image.width, image.height);
+    if let Some(metadata) = image.metadata {
+        info.exif_metadata = metadata.exif_tiff()?.map(Into::into);
+    }
+    let mut encoder = png::Encoder::with_info(&mut *bytes, info).map_err(encoder_error)?;
+    if let Some(xmp) = image.metadata.and_then(EmbeddedMetadata::xmp_packet) {
+        encoder
+            .add_itxt_chunk(PNG_XMP_KEYWORD.to_string(), xmp.to_string())
+            .map_err(encoder_error)?;
+    }
+    encoder.set_color(if image.keep_alpha {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_compression(match options.png_compression {
+        PngCompression::None => png::Compression::NoCompression,
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Balanced,
+        PngCompression::Best => png::Compression::High,
+    });
+    if let Some(dpi) = options.dpi {
+        let pixels_per_meter = (dpi as f64 / METERS_PER_INCH).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: pixels_per_meter,
+            yppu: pixels_per_meter,
+            unit: png::Unit::Meter,
+        }));
+    }
+
+    let data = match image.pixels {
+        ExportPixels::Rgba8(rgba) => {
+            encoder.set_depth(png::BitDepth::Eight);
+            if image.keep_alpha {
+                rgba.clone()
+            } else {
+                drop_alpha(rgba)
+            }
+        }
+        ExportPixels::Rgba16(rgba) => {
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let samples = if image.keep_alpha {
+                rgba.clone()
+            } else {
+                drop_alpha(rgba)
+            };
+            samples
+                .iter()
+                .flat_map(|sample| sample.to_be_bytes())
+                .collect()
+        }
+    };
+
+    let mut writer = encoder.write_header().map_err(encoder_error)?;
+    writer.write_image_data(&data).map_err(encoder_error)?;
+    writer.finish().map_err(encoder_error)
+}
+
+fn encode_tiff(
+    image: &EncoderInput,
+    options: &ExportFormatOptions,
+    bytes: &mut Vec<u8>,
+) -> Result<(), ExportPipelineError> {
+    let compression = match options.tiff_compression {
+        TiffCompression::None => Compression::Uncompressed,
+        TiffCompression::Lzw => Compression::Lzw,
+        TiffCompression::Deflate => Compression::Deflate(DeflateLevel::Balanced),
+        TiffCompression::PackBits => Compression::Packbits,
+    };
+    let mut encoder = TiffEncoder::new(Cursor::new(bytes))
+        .map_err(encoder_error)?
+        .with_compression(compression);
+    let sub_directories = match image.metadata {
+        Some(metadata) => Some(
+            metadata
+                .write_tiff_sub_directories(&mut encoder)
+                .map_err(encoder_error)?,
+        ),
+        None => None,
+    };
+    let page = TiffPage {
+        width: image.width,
+        height: image.height,
+        dpi: options.dpi,
+        metadata: image.metadata.zip(sub_directories.as_ref()),
+    };
+
+    match (image.pixels, image.keep_alpha) {
+        (ExportPixels::Rgba8(rgba), true) => {
+            write_tiff_image::<colortype::RGBA8, _>(&mut encoder, &page, rgba)
+        }
+        (ExportPixels::Rgba8(rgba), false) => {
+            write_tiff_image::<colortype::RGB8, _>(&mut encoder, &page, &drop_alpha(rgba))
+        }
+        (ExportPixels::Rgba16(rgba), true) => {
+            write_tiff_image::<colortype::RGBA16, _>(&mut encoder, &page, rgba)
+        }
+        (ExportPixels::Rgba16(rgba), false) => {
+            write_tiff_image::<colortype::RGB16, _>(&mut encoder, &page, &drop_alpha(rgba))
+        }
+    }
+}
+
+/// Everything about the TIFF image directory except its samples.
+struct TiffPage<'a> {
+    width: u32,
+    height: u32,
+    dpi: Option<u16>,
+    metadata: Option<(&'a EmbeddedMetadata, &'a TiffSubDirectories)>,
+}
+
+fn write_tiff_image<C, W>(
+    encoder: &mut TiffEncoder<W>,
+    page: &TiffPage,
+    samples: &[C::Inner],
+) -> Result<(), ExportPipelineError>
+where
+    C: colortype::ColorType,
+    W: std::io::Write + std::io::Seek,
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    let mut tiff_image = encoder
+        .new_image::<C>(page.width, page.height)
+        .map_err(encoder_error)?;
+    if let Some((metadata, sub_directories)) = page.metadata {
+        metadata
+            .write_tiff_image_tags(tiff_image.encoder(), sub_directories)
+            .map_err(encoder_error)?;
+    }
+    if let Some(dpi) = page.dpi {
+        tiff_image.resolution(
+            ResolutionUnit::Inch,
+            Rational {
+                n: dpi as u32,
+                d: 1,
+            },
+        );
+    }
+    tiff_image.write_data(samples).map_err(encoder_error)
+}
+
+fn encoder_error(error: impl std::fmt::Display) -> ExportPipelineError {
+    ExportPipelineError::Image(image::ImageError::IoError(std::io::Error::other(
+        error.to_string(),
+    )))
+}
+
+fn drop_alpha<T: Copy>(rgba: &[T]) -> Vec<T> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| [px[0], px[1], px[2]])
+        .collect()
+}
+
+/// 8-bit samples with or without alpha, as passed to `image` encoders.
+fn rgb8_samples(rgba: &[u8], keep_alpha: bool) -> (Vec<u8>, ExtendedColorType) {
+    if keep_alpha {
+        (rgba.to_vec(), ExtendedColorType::Rgba8)
+    } else {
+        (drop_alpha(rgba), ExtendedColorType::Rgb8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn gradient8(width: u32, height: u32, alpha: u8) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|index| [(index * 9) as u8, (index * 5) as u8, 200, alpha])
+            .collect()
+    }
+
+    #[test]
+    fn png_16_bit_round_trips_samples() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("out.png");
+        let samples = vec![1_u16, 32_769, 65_535, 65_535, 12_345, 40_001, 777, 65_535];
+
+        let options = ExportFormatOptions {
+            bit_depth: 16,
+            png_compression: PngCompression::Best,
+            ..ExportFormatOptions::default()
+        };
+        write_export_image(
+            &ExportPixels::Rgba16(samples),
+            2,
+            1,
+            &path,
+            ExportFormat::Png,
+            &options,
+            None,
+        )
+        .unwrap();
+
+        let decoded = image::open(&path).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::Rgb16);
+        assert_eq!(
+            decoded.to_rgb16().into_raw(),
+            vec![1, 32_769, 65_535, 12_345, 40_001, 777]
+        );
+    }
+
+    #[test]
+    fn auto_alpha_keeps_transparency_only_when_present() {
+        let temp = tempdir().unwrap();
+        let opaque = temp.path().join("opaque.tiff");
+        let translucent = temp.path().join("translucent.tiff");
+        let options = ExportFormatOptions::default();
+
+        for (path, alpha) in [(&opaque, 255), (&translucent, 128)] {
+            write_export_image(
+                &ExportPixels::Rgba8(gradient8(3, 2, alpha)),
+                3,
+                2,
+                path,
+                ExportFormat::Tiff,
+                &options,
+                None,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            image::open(&opaque).unwrap().color(),
+            image::ColorType::Rgb8
+        );
+        let decoded = image::open(&translucent).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::Rgba8);
+        assert_eq!(decoded.to_rgba8().into_raw(), gradient8(3, 2, 128));
+    }
+
+    #[test]
+    fn webp_lossless_is_exact_and_lossy_is_smaller() {
+        let temp = tempdir().unwrap();
+        let lossless_path = temp.path().join("lossless.webp");
+        let lossy_path = temp.path().join("lossy.webp");
+        let noise: Vec<u8> = (0..32 * 32 * 4_u32)
+            .map(|index| {
+                if index % 4 == 3 {
+                    255
+                } else {
+                    (index.wrapping_mul(2_654_435_761) >> 13) as u8
+                }
+            })
+            .collect();
+        let pixels = ExportPixels::Rgba8(noise.clone());
+
+        let lossless = ExportFormatOptions {
+            lossless: true,
+            ..ExportFormatOptions::default()
+        };
+        write_export_image(
+            &pixels,
+            32,
+            32,
+            &lossless_path,
+            ExportFormat::Webp,
+            &lossless,
+            None,
+        )
+        .unwrap();
+        let lossy = ExportFormatOptions {
+            quality: Some(40),
+            ..ExportFormatOptions::default()
+        };
+        write_export_image(
+            &pixels,
+            32,
+            32,
+            &lossy_path,
+            ExportFormat::Webp,
+            &lossy,
+            None,
+        )
+        .unwrap();
+
+        let decoded = image::open(&lossless_path).unwrap().to_rgba8().into_raw();
+        assert_eq!(decoded, noise);
+        let lossy_decoded = image::open(&lossy_path).unwrap();
+        assert_eq!((lossy_decoded.width(), lossy_decoded.height()), (32, 32));
+        assert!(
+            std::fs::metadata(&lossy_path).unwrap().len()
+                < std::fs::metadata(&lossless_path).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn avif_writes_an_avif_container() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("out.avif");
+        let options = ExportFormatOptions {
+            quality: Some(60),
+            avif_speed: Some(10),
+            ..ExportFormatOptions::default()
+        };
+
+        write_export_image(
+            &ExportPixels::Rgba8(gradient8(16, 8, 255)),
+            16,
+            8,
+            &path,
+            ExportFormat::Avif,
+            &options,
+            None,
+        )
+        .unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[4..12], b"ftypavif");
+    }
+
+    #[test]
+    fn options_reject_unsupported_combinations() {
+        let sixteen_bit = ExportFormatOptions {
+            bit_depth: 16,
+            ..ExportFormatOptions::default()
+        };
+        assert!(sixteen_bit.validate(ExportFormat::Tiff).is_ok());
+        assert!(sixteen_bit.validate(ExportFormat::Avif).is_err());
+
+        let cases = [
+            (
+                ExportFormat::Png,
+                ExportFormatOptions {
+                    bit_depth: 12,
+                    ..Default::default()
+                },
+            ),
+            (
+                ExportFormat::Webp,
+                ExportFormatOptions {
+                    quality: Some(0),
+                    ..Default::default()
+                },
+            ),
+            (
+                ExportFormat::Avif,
+                ExportFormatOptions {
+                    avif_speed: Some(11),
+                    ..Default::default()
+                },
+            ),
+            (
+                ExportFormat::Avif,
+                ExportFormatOptions {
+                    lossless: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                ExportFormat::Jpeg,
+                ExportFormatOptions {
+                    alpha: AlphaHandling::Keep,
+                    ..Default::default()
+                },
+            ),
+        ];
+        for (format, options) in cases {
+            assert!(matches!(
+                options.validate(format),
+                Err(ExportPipelineError::InvalidFormatOptions(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn options_deserialize_from_camel_case_with_defaults() {
+        let options: ExportFormatOptions = serde_json::from_value(serde_json::json!({
+            "bitDepth": 16,
+            "alpha": "discard",
+            "tiffCompression": "deflate"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            options,
+            ExportFormatOptions {
+                bit_depth: 16,
+                alpha: AlphaHandling::Discard,
+                tiff_compression: TiffCompression::Deflate,
+                ..ExportFormatOptions::default()
+            }
+        );
+    }
+
+    fn noise8(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height * 4)
+            .map(|index| {
+                if index % 4 == 3 {
+                    255
+                } else {
+                    // Smooth gradient plus noise so quality visibly changes size.
+                    let base = (index / 4 % width) * 200 / width;
+                    (base + (index.wrapping_mul(2_654_435_761) >> 27)) as u8
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jpeg_quality_and_subsampling_shrink_files() {
+        let temp = tempdir().unwrap();
+        let pixels = ExportPixels::Rgba8(noise8(64, 64));
+        let mut sizes = Vec::new();
+
+        for (name, quality, chroma_subsampling) in [
+            ("high.jpg", 95, ChromaSubsampling::Yuv444),
+            ("low.jpg", 40, ChromaSubsampling::Yuv444),
+            ("low-420.jpg", 40, ChromaSubsampling::Yuv420),
+        ] {
+            let options = ExportFormatOptions {
+                quality: Some(quality),
+                chroma_subsampling,
+                ..ExportFormatOptions::default()
+            };
+            let path = temp.path().join(name);
+            let written =
+                write_export_image(&pixels, 64, 64, &path, ExportFormat::Jpeg, &options, None)
+                    .unwrap();
+            assert_eq!(written.quality, Some(quality));
+            assert_eq!(
+                written.file_size_bytes,
+                std::fs::metadata(&path).unwrap().len()
+            );
+            assert_eq!(image::open(&path).unwrap().width(), 64);
+            sizes.push(written.file_size_bytes);
+        }
+
+        assert!(sizes[0] > sizes[1] && sizes[1] > sizes[2], "{sizes:?}");
+    }
+
+    #[test]
+    fn dpi_is_written_to_jpeg_png_and_tiff_headers() {
+        let temp = tempdir().unwrap();
+        let pixels = ExportPixels::Rgba8(gradient8(4, 4, 255));
+        let options = ExportFormatOptions {
+            dpi: Some(300),
+            ..ExportFormatOptions::default()
+        };
+        let write = |name: &str, format| {
+            let path = temp.path().join(name);
+            write_export_image(&pixels, 4, 4, &path, format, &options, None).unwrap();
+            std::fs::read(path).unwrap()
+        };
+
+        let jpeg = write("dpi.jpg", ExportFormat::Jpeg);
+        let jfif = jpeg
+            .windows(5)
+            .position(|w| w == b"JFIF\0")
+            .expect("JFIF header");
+        // Version (2 bytes), unit 1 = dots per inch, then X and Y density.
+        assert_eq!(&jpeg[jfif + 7..jfif + 12], &[1, 1, 44, 1, 44]);
+
+        let png = write("dpi.png", ExportFormat::Png);
+        let reader = png::Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let dims = reader.info().pixel_dims.expect("pHYs chunk");
+        assert_eq!((dims.xppu, dims.unit), (11_811, png::Unit::Meter));
+
+        let tiff = write("dpi.tiff", ExportFormat::Tiff);
+        let mut decoder = tiff::decoder::Decoder::new(Cursor::new(tiff)).unwrap();
+        assert_eq!(
+            decoder.get_tag(tiff::tags::Tag::XResolution).unwrap(),
+            tiff::decoder::ifd::Value::Rational(300, 1)
+        );
+    }
+
+    #[test]
+    fn file_size_limit_searches_the_highest_fitting_quality() {
+        let temp = tempdir().unwrap();
+        let pixels = ExportPixels::Rgba8(noise8(96, 96));
+        let path = temp.path().join("capped.jpg");
+
+        let unlimited = ExportFormatOptions {
+            quality: Some(100),
+            ..ExportFormatOptions::default()
+        };
+        let full = write_export_image(&pixels, 96, 96, &path, ExportFormat::Jpeg, &unlimited, None)
+            .unwrap()
+            .file_size_bytes;
+        let limit_kb = (full / 1024 / 2).max(1) as u32;
+
+        let capped = ExportFormatOptions {
+            max_file_size_kb: Some(limit_kb),
+            ..unlimited
+        };
+        let written =
+            write_export_image(&pixels, 96, 96, &path, ExportFormat::Jpeg, &capped, None).unwrap();
+
+        let quality = written.quality.expect("lossy quality");
+        assert!(written.file_size_bytes <= limit_kb as u64 * 1024);
+        assert!((1..100).contains(&quality));
+        // One step up no longer fits, so the search did not settle too low.
+        let above = ExportFormatOptions {
+            quality: Some(quality + 1),
+            ..ExportFormatOptions::default()
+        };
+        let next = write_export_image(&pixels, 96, 96, &path, ExportFormat::Jpeg, &above, None)
+            .unwrap()
+            .file_size_bytes;
+        assert!(next > limit_kb as u64 * 1024);
+    }
+
+    #[test]
+    fn file_size_limit_reports_unreachable_caps_and_lossless_formats() {
+        let temp = tempdir().unwrap();
+        let pixels = ExportPixels::Rgba8(noise8(256, 256));
+        let path = temp.path().join("tiny.jpg");
+        let tiny = ExportFormatOptions {
+            max_file_size_kb: Some(1),
+            ..ExportFormatOptions::default()
+        };
+
+        let result = write_export_image(&pixels, 256, 256, &path, ExportFormat::Jpeg, &tiny, None);
+        assert!(matches!(
+            result,
+            Err(ExportPipelineError::FileSizeLimitUnreachable { limit_kb: 1, .. })
+        ));
+        assert!(!path.exists());
+
+        assert!(matches!(
+            tiny.validate(ExportFormat::Png),
+            Err(ExportPipelineError::InvalidFormatOptions(_))
+        ));
+        let lossless_webp = ExportFormatOptions {
+            lossless: true,
+            ..tiny
+        };
+        assert!(lossless_webp.validate(ExportFormat::Webp).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//! History of written exports and detection of stale deliveries.
+//!
+//! Every exported file is recorded with the hash of the edit recipe it was
+//! rendered from. When the image is retouched later its recipe hashes
+//! differently, so the file is stale; its recorded settings are enough to
+//! render it again at the same path.
+
+use crate::services::export_batch::BatchExportRequest;
+use crate::services::export_encoding::ExportFormatOptions;
+use crate::services::export_metadata::ExportMetadataOptions;
+use crate::services::export_pipeline::{
+    resolve_recipe_hash, ExportFormat, ExportPipelineError, ExportRequest, ExportResult,
+    ExportTarget,
+};
+use crate::services::export_sizing::ExportResize;
+use crate::services::export_watermark::Watermark;
+use chrono::Utc;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Render settings of a recorded export, everything but the image and path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHistorySettings {
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub resize: ExportResize,
+    #[serde(default)]
+    pub options: ExportFormatOptions,
+    #[serde(default)]
+    pub metadata: ExportMetadataOptions,
+    #[serde(default)]
+    pub watermark: Option<Watermark>,
+    #[serde(default)]
+    pub target: ExportTarget,
+    #[serde(default)]
+    pub raw_only: bool,
+}
+
+impl ExportHistorySettings {
+    pub fn for_request(request: &ExportRequest, raw_only: bool) -> Self {
+        Self {
+            format: request.format,
+            resize: request.resize,
+            options: request.options,
+            metadata: request.metadata,
+            watermark: request.watermark.clone(),
+            target: request.target,
+            raw_only,
+        }
+    }
+
+    /// Batch exports always render the current edit state.
+    pub fn for_batch(request: &BatchExportRequest) -> Self {
+        Self {
+            format: request.format,
+            resize: request.resize,
+            options: request.options,
+            metadata: request.metadata,
+            watermark: request.watermark.clone(),
+            target: ExportTarget::Current,
+            raw_only: request.raw_only,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHistoryEntry {
+    pub id: i64,
+    pub image_id: i64,
+    pub output_path: String,
+    /// `None` for one-off exports, and once the preset is deleted.
+    pub preset_id: Option<i64>,
+    pub preset_name: Option<String>,
+    pub recipe_hash: String,
+    pub settings: ExportHistorySettings,
+    /// RFC 3339.
+    pub exported_at: String,
+}
+
+impl ExportHistoryEntry {
+    /// Request rendering this entry again at the same path, and whether it
+    /// is RAW-only.
+    pub fn reexport_request(&self) -> (ExportRequest, bool) {
+        let settings = &self.settings;
+        let request = ExportRequest {
+            image_id: self.image_id,
+            output_path: PathBuf::from(&self.output_path),
+            format: settings.format,
+            resize: settings.resize,
+            options: settings.options,
+            metadata: settings.metadata,
+            watermark: settings.watermark.clone(),
+            target: settings.target,
+        };
+        (request, settings.raw_only)
+    }
+}
+
+/// Records one written export; returns the ID of the new entry.
+pub fn record_export(
+    conn: &Connection,
+    result: &ExportResult,
+    settings: &ExportHistorySettings,
+    preset_id: Option<i64>,
+    preset_name: Option<&str>,
+) -> Result<i64, ExportPipelineError> {
+    conn.execute(
+        "INSERT INTO export_history
+             (image_id, output_path, preset_id, preset_name, recipe_hash, settings, exported_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            result.image_id,
+            result.output_path,
+            preset_id,
+            preset_name,
+            result.recipe_hash,
+            serde_json::to_string(settings)?,
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Every recorded export, newest first; only those of `image_id` when given.
+pub fn list_export_history(
+    conn: &Connection,
+    image_id: Option<i64>,
+) -> Result<Vec<ExportHistoryEntry>, ExportPipelineError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, image_id, output_path, preset_id, preset_name, recipe_hash, settings,
+                exported_at
+         FROM export_history
+         WHERE ?1 IS NULL OR image_id = ?1
+         ORDER BY id DESC",
+    )?;
+    let rows = stmt
+        .query_map([image_id], read_history_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter().map(parse_history_row).collect()
+}
+
+/// Latest export of each output path whose image now resolves to another
+/// recipe than the one rendered, oldest first.
+///
+/// Files exported from a snapshot only go stale when that snapshot changes.
+/// Entries whose snapshot was deleted cannot be rendered again and are left
+/// out.
+pub fn find_stale_exports(
+    conn: &Connection,
+) -> Result<Vec<ExportHistoryEntry>, ExportPipelineError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, image_id, output_path, preset_id, preset_name, recipe_hash, settings,
+                exported_at
+         FROM export_history
+         WHERE id IN (SELECT MAX(id) FROM export_history GROUP BY output_path)
+         ORDER BY id ASC",
+    )?;
+    let rows = stmt
+        .query_map([], read_history_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut current_hashes: HashMap<(i64, String), String> = HashMap::new();
+    let mut stale = Vec::new();
+    for row in rows {
+        let entry = parse_history_row(row)?;
+        let key = (
+            entry.image_id,
+            serde_json::to_string(&entry.settings.target)?,
+        );
+        let current = match current_hashes.get(&key) {
+            Some(hash) => hash.clone(),
+            None => match resolve_recipe_hash(conn, entry.image_id, &entry.settings.target) {
+                Ok(hash) => {
+                    current_hashes.insert(key, hash.clone());
+                    hash
+                }
+                Err(ExportPipelineError::SnapshotNotFound { .. }) => continue,
+                Err(error) => return Err(error),
+            },
+        };
+        if current != entry.recipe_hash {
+            stale.push(entry);
+        }
+    }
+    Ok(stale)
+}
+
+type HistoryRow = (
+    i64,
+    i64,
+    String,
+    Option<i64>,
+    Option<String>,
+    String,
+    String,
+    String,
+);
+
+fn read_history_row(row: &Row) -> rusqlite::Result<HistoryRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+    ))
+}
+
+fn parse_history_row(row: HistoryRow) -> Result<ExportHistoryEntry, ExportPipelineError> {
+    let (id, image_id, output_path, preset_id, preset_name, recipe_hash, settings, exported_at) =
+        row;
+    Ok(ExportHistoryEntry {
+        id,
+        image_id,
+        output_path,
+        preset_id,
+        preset_name,
+        recipe_hash,
+        settings: serde_json::from_str(&settings)?,
+        exported_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::event::{Event, EventPayload, EventType, TargetType};
+    use crate::services::event_sourcing::EventStore;
+    use crate::services::export_batch::{run_export_requests, CancellationToken};
+    use crate::services::export_pipeline::export_image_with_edits;
+    use image::{Rgba, RgbaImage};
+    use std::path::Path;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    /// Migrated catalog with one 8x6 PNG image, ID 1.
+    fn catalog() -> (TempDir, PathBuf, Database) {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("catalog.db");
+        let mut db = Database::new(&db_path).unwrap();
+        db.initialize().unwrap();
+
+        let source_path = dir.path().join("portrait.png");
+        RgbaImage::from_pixel(8, 6, Rgba([90, 120, 150, 255]))
+            .save(&source_path)
+            .unwrap();
+        db.connection()
+            .execute_batch(&format!(
+                "INSERT INTO ingestion_sessions (id) VALUES ('session');
+                 INSERT INTO images (id, blake3_hash, filename, extension)
+                 VALUES (1, 'hash-1', 'portrait.png', 'png');
+                 INSERT INTO ingestion_file_status (session_id, file_path, blake3_hash)
+                 VALUES ('session', '{}', 'hash-1');",
+                source_path.to_string_lossy()
+            ))
+            .unwrap();
+
+        (dir, db_path, db)
+    }
+
+    fn edit(conn: &Connection, event_id: &str, exposure: f64) {
+        EventStore::new(conn)
+            .append_event(&Event {
+                id: event_id.to_string(),
+                timestamp: Utc::now().timestamp_millis(),
+                event_type: EventType::EditApplied,
+                payload: EventPayload::Generic(
+                    serde_json::json!({ "edits": { "exposure": exposure } }),
+                ),
+                target_type: TargetType::Image,
+                target_id: 1,
+                user_id: None,
+                created_at: Utc::now(),
+            })
+            .unwrap();
+    }
+
+    fn export_and_record(
+        conn: &Connection,
+        output_path: &Path,
+        target: ExportTarget,
+        preset: Option<(i64, &str)>,
+    ) -> ExportHistoryEntry {
+        let request = ExportRequest {
+            image_id: 1,
+            output_path: output_path.to_path_buf(),
+            format: ExportFormat::Png,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target,
+        };
+        let result = export_image_with_edits(conn, &request).unwrap();
+        let id = record_export(
+            conn,
+            &result,
+            &ExportHistorySettings::for_request(&request, false),
+            preset.map(|(id, _)| id),
+            preset.map(|(_, name)| name),
+        )
+        .unwrap();
+        list_export_history(conn, Some(1))
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .expect("recorded entry")
+    }
+
+    #[test]
+    fn edits_after_export_make_the_latest_delivery_stale() {
+        let (dir, _, mut db) = catalog();
+        let conn = db.connection();
+        conn.execute(
+            "INSERT INTO export_presets (id, name, settings) VALUES (3, 'Client web', '{}')",
+            [],
+        )
+        .unwrap();
+        edit(conn, "evt-1", 10.0);
+
+        let gallery = dir.path().join("gallery.png");
+        let first = export_and_record(
+            conn,
+            &gallery,
+            ExportTarget::Current,
+            Some((3, "Client web")),
+        );
+        assert_eq!(first.preset_name.as_deref(), Some("Client web"));
+        assert_eq!(first.settings.format, ExportFormat::Png);
+        assert!(find_stale_exports(conn).unwrap().is_empty());
+
+        edit(conn, "evt-2", 25.0);
+        let stale = find_stale_exports(conn).unwrap();
+        assert_eq!(stale, vec![first]);
+
+        // Exporting the same path again supersedes the stale entry.
+        export_and_record(conn, &gallery, ExportTarget::Current, None);
+        assert!(find_stale_exports(conn).unwrap().is_empty());
+        assert_eq!(list_export_history(conn, None).unwrap().len(), 2);
+
+        // Deleting the preset keeps its name on the recorded exports.
+        conn.execute("DELETE FROM export_presets WHERE id = 3", [])
+            .unwrap();
+        let oldest = list_export_history(conn, Some(1))
+            .unwrap()
+            .pop()
+            .expect("oldest");
+        assert_eq!(oldest.preset_id, None);
+        assert_eq!(oldest.preset_name.as_deref(), Some("Client web"));
+    }
+
+    #[test]
+    fn snapshot_exports_ignore_later_edits() {
+        let (dir, _, mut db) = catalog();
+        let conn = db.connection();
+        edit(conn, "evt-1", 10.0);
+        conn.execute(
+            "INSERT INTO edit_snapshots (id, image_id, name, snapshot_data, event_ids, created_at)
+             VALUES (5, 1, 'Client v2', ?1, '[\"evt-1\"]', ?2)",
+            params![
+                serde_json::json!([
+                    { "eventType": "edit_applied", "payload": { "edits": { "exposure": 10.0 } } }
+                ])
+                .to_string(),
+                Utc::now().to_rfc3339(),
+            ],
+        )
+        .unwrap();
+
+        export_and_record(
+            conn,
+            &dir.path().join("v2.png"),
+            ExportTarget::Snapshot { snapshot_id: 5 },
+            None,
+        );
+        edit(conn, "evt-2", 40.0);
+        assert!(find_stale_exports(conn).unwrap().is_empty());
+
+        conn.execute("DELETE FROM edit_snapshots WHERE id = 5", [])
+            .unwrap();
+        assert!(find_stale_exports(conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reexported_stale_files_are_current_again() {
+        let (dir, db_path, mut db) = catalog();
+        let conn = db.connection();
+        let output = dir.path().join("out").join("gallery.png");
+        std::fs::create_dir_all(dir.path().join("out")).unwrap();
+        export_and_record(conn, &output, ExportTarget::Current, None);
+        let before = std::fs::read(&output).unwrap();
+        edit(conn, "evt-1", 60.0);
+
+        let stale = find_stale_exports(conn).unwrap();
+        let requests = stale
+            .iter()
+            .map(ExportHistoryEntry::reexport_request)
+            .collect();
+        let result = run_export_requests(
+            &db_path,
+            Uuid::new_v4(),
+            requests,
+            2,
+            &CancellationToken::default(),
+            &|_| {},
+        );
+
+        assert_eq!(result.exported.len(), 1);
+        assert_ne!(std::fs::read(&output).unwrap(), before);
+        record_export(
+            conn,
+            &result.exported[0],
+            &stale[0].settings,
+            stale[0].preset_id,
+            stale[0].preset_name.as_deref(),
+        )
+        .unwrap();
+        assert!(find_stale_exports(conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1067 @@
+//! Catalog metadata embedded into exported files.
+//!
+//! EXIF is rebuilt from the catalog (the `exif_metadata` row, the capture
+//! date and the IPTC creator and copyright) instead of being copied from the
+//! source file, so an export can drop the GPS position, the body serial
+//! number or everything but the copyright. Tags, rating, IPTC core fields
+//! and the optional edit history go into an XMP packet; creator, caption,
+//! copyright and keywords are also written as IPTC-IIM for older readers.
+//!
+//! JPEG gets APP1 EXIF and XMP segments plus an APP13 IIM block, PNG an
+//! `eXIf` chunk and an `XML:com.adobe.xmp` iTXt chunk, TIFF the EXIF and GPS
+//! IFDs with XMP (tag 700) and IIM (tag 33723). A JPEG XMP segment keeps
+//! only the most recent history steps that fit its 64 KB limit. WebP and AVIF
+//! exports carry no metadata. Nothing here depends on the clock, so the same
+//! catalog state always produces the same bytes.
+
+use crate::models::exif::ExifMetadata;
+use crate::services::export_pipeline::ExportPipelineError;
+use crate::services::iptc::{load_image_iptc, IptcMetadata};
+use crate::services::xmp::load_image_tags;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use exif::{Context, Field, In, Tag, Value};
+use quick_xml::events::BytesText;
+use quick_xml::writer::Writer;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Cursor, Seek, Write};
+use tiff::encoder::{
+    DirectoryEncoder, Rational as TiffRational, SRational as TiffSRational, TiffEncoder,
+    TiffKindStandard, TiffValue,
+};
+use tiff::tags::{Tag as TiffTag, Type as TiffType};
+use tiff::TiffResult;
+
+const SOFTWARE: &str = "LuminaFast";
+const EXIF_VERSION: &[u8] = b"0232";
+/// `ColorSpace` value for sRGB, the colour space of every export.
+const EXIF_COLOR_SPACE_SRGB: u16 = 1;
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const JPEG_PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+/// Largest JPEG APPn payload: the 16-bit segment length counts its own two bytes.
+const JPEG_MAX_SEGMENT_PAYLOAD: usize = 65_533;
+/// Photoshop image resource holding IPTC-IIM datasets.
+const PHOTOSHOP_IPTC_RESOURCE: u16 = 0x0404;
+pub(crate) const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const TIFF_XMP_TAG: u16 = 700;
+const TIFF_IPTC_TAG: u16 = 33_723;
+
+/// What catalog metadata an export carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportMetadataOptions {
+    /// Embed catalog metadata at all.
+    pub embed: bool,
+    pub strip_gps: bool,
+    /// Drops the camera body serial number.
+    pub strip_camera_serials: bool,
+    /// Keeps only the copyright notice; wins over every other option.
+    pub copyright_only: bool,
+    /// Adds the applied edits as an XMP `xmpMM:History`.
+    pub include_history: bool,
+}
+
+impl Default for ExportMetadataOptions {
+    fn default() -> Self {
+        Self {
+            embed: true,
+            strip_gps: false,
+            strip_camera_serials: false,
+            copyright_only: false,
+            include_history: false,
+        }
+    }
+}
+
+/// One applied edit, in replay order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingStep {
+    /// `None` for edits folded into a snapshot.
+    pub when: Option<DateTime<Utc>>,
+    /// Recipe fields the edit changed, e.g. `exposure`.
+    pub parameters: Vec<String>,
+}
+
+/// Catalog metadata of one image, before the export options apply.
+#[derive(Debug, Clone, Default)]
+pub struct CatalogMetadata {
+    pub exif: Option<ExifMetadata>,
+    /// 1-5 stars; unrated images have none.
+    pub rating: Option<u8>,
+    /// Creator, caption and copyright; `keywords` holds the flat tags.
+    pub iptc: IptcMetadata,
+    /// Lightroom tag paths such as `Places/France/Paris`.
+    pub hierarchical_subjects: Vec<String>,
+    pub history: Vec<ProcessingStep>,
+}
+
+/// Loads everything an export may embed except the edit history, which
+/// comes from the recipe replay.
+pub fn load_catalog_metadata(
+    conn: &Connection,
+    image_id: i64,
+) -> Result<CatalogMetadata, ExportPipelineError> {
+    let mut exif = conn
+        .query_row(
+            "SELECT iso, aperture, shutter_speed, focal_length, lens, camera_make,
+                    camera_model, gps_lat, gps_lon, color_space, body_serial, exposure_bias
+             FROM exif_metadata
+             WHERE image_id = ?1",
+            [image_id],
+            |row| {
+                Ok(ExifMetadata {
+                    iso: row.get(0)?,
+                    aperture: row.get(1)?,
+                    shutter_speed: row.get(2)?,
+                    focal_length: row.get(3)?,
+                    lens: row.get(4)?,
+                    camera_make: row.get(5)?,
+                    camera_model: row.get(6)?,
+                    gps_lat: row.get(7)?,
+                    gps_lon: row.get(8)?,
+                    color_space: row.get(9)?,
+                    body_serial: row.get(10)?,
+                    exposure_bias: row.get(11)?,
+                    captured_at: None,
+                })
+            },
+        )
+        .optional()?;
+
+    let captured_at = conn
+        .query_row(
+            "SELECT captured_at FROM images WHERE id = ?1",
+            [image_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten()
+        .and_then(|value| parse_capture_date(&value));
+    if let Some(captured_at) = captured_at {
+        exif.get_or_insert_with(ExifMetadata::default).captured_at = Some(captured_at);
+    }
+
+    let rating = conn
+        .query_row(
+            "SELECT rating FROM image_state WHERE image_id = ?1",
+            [image_id],
+            |row| row.get::<_, Option<u8>>(0),
+        )
+        .optional()?
+        .flatten()
+        .filter(|rating| *rating > 0);
+
+    let (keywords, hierarchical_subjects) = load_image_tags(conn, image_id)?;
+    let iptc = IptcMetadata {
+        keywords,
+        ..load_image_iptc(conn, image_id)?
+    };
+
+    Ok(CatalogMetadata {
+        exif,
+        rating,
+        iptc,
+        hierarchical_subjects,
+        history: Vec::new(),
+    })
+}
+
+/// `images.captured_at` holds the camera's local time tagged as UTC.
+pub(crate) fn parse_capture_date(value: &str) -> Option<NaiveDateTime> {
+    value
+        .parse::<DateTime<Utc>>()
+        .map(|date| date.naive_utc())
+        .or_else(|_| value.parse::<NaiveDateTime>())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+}
+
+impl CatalogMetadata {
+    /// The subset of the catalog `options` lets through.
+    fn filtered(&self, options: &ExportMetadataOptions) -> Self {
+        if options.copyright_only {
+            return Self {
+                iptc: IptcMetadata {
+                    copyright: self.iptc.copyright.clone(),
+                    ..IptcMetadata::default()
+                },
+                ..Self::default()
+            };
+        }
+
+        let mut filtered = self.clone();
+        if let Some(exif) = filtered.exif.as_mut() {
+            if options.strip_gps {
+                exif.gps_lat = None;
+                exif.gps_lon = None;
+            }
+            if options.strip_camera_serials {
+                exif.body_serial = None;
+            }
+        }
+        if !options.include_history {
+            filtered.history.clear();
+        }
+        filtered
+    }
+}
+
+/// Metadata ready to be written into an export container.
+#[derive(Debug, Clone)]
+pub struct EmbeddedMetadata {
+    exif_fields: Vec<Field>,
+    xmp_packet: Option<String>,
+    /// `xmp_packet` trimmed to fit a single JPEG APP1 segment.
+    jpeg_xmp_packet: Option<String>,
+    iptc_iim: Vec<u8>,
+}
+
+impl EmbeddedMetadata {
+    /// Applies `options` to `catalog`; `None` when nothing is left to embed.
+    pub fn from_catalog(
+        catalog: &CatalogMetadata,
+        options: &ExportMetadataOptions,
+    ) -> Result<Option<Self>, ExportPipelineError> {
+        if !options.embed {
+            return Ok(None);
+        }
+
+        let catalog = catalog.filtered(options);
+        let name_software = !options.copyright_only;
+        let xmp_packet = xmp_packet(&catalog, name_software)?;
+        let embedded = Self {
+            exif_fields: exif_fields(&catalog, name_software),
+            jpeg_xmp_packet: jpeg_xmp_packet(&catalog, name_software, xmp_packet.as_deref())?,
+            xmp_packet,
+            iptc_iim: iptc_iim(&catalog.iptc),
+        };
+
+        if embedded.exif_fields.is_empty()
+            && embedded.xmp_packet.is_none()
+            && embedded.iptc_iim.is_empty()
+        {
+            return Ok(None);
+        }
+        Ok(Some(embedded))
+    }
+
+    pub fn xmp_packet(&self) -> Option<&str> {
+        self.xmp_packet.as_deref()
+    }
+
+    /// EXIF as a little-endian TIFF structure: the payload of a PNG `eXIf`
+    /// chunk, and of a JPEG APP1 segment after its `Exif` header.
+    pub fn exif_tiff(&self) -> Result<Option<Vec<u8>>, ExportPipelineError> {
+        if self.exif_fields.is_empty() {
+            return Ok(None);
+        }
+
+        let mut writer = exif::experimental::Writer::new();
+        for field in &self.exif_fields {
+            writer.push_field(field);
+        }
+        let mut bytes = Cursor::new(Vec::new());
+        writer
+            .write(&mut bytes, true)
+            .map_err(|error| ExportPipelineError::Io(std::io::Error::other(error.to_string())))?;
+        Ok(Some(bytes.into_inner()))
+    }
+
+    /// `(marker number, payload)` of the JPEG APPn segments to write.
+    ///
+    /// The XMP history is trimmed to fit one segment; a segment still over
+    /// the limit is left out rather than failing the export.
+    pub(crate) fn jpeg_app_segments(&self) -> Result<Vec<(u8, Vec<u8>)>, ExportPipelineError> {
+        let mut segments = Vec::new();
+        if let Some(exif) = self.exif_tiff()? {
+            segments.push((1, [JPEG_EXIF_HEADER, &exif].concat()));
+        }
+        if let Some(xmp) = &self.jpeg_xmp_packet {
+            segments.push((1, [JPEG_XMP_HEADER, xmp.as_bytes()].concat()));
+        }
+        if !self.iptc_iim.is_empty() {
+            segments.push((13, photoshop_iptc_resource(&self.iptc_iim)));
+        }
+        segments.retain(|(marker, payload)| {
+            let fits = payload.len() <= JPEG_MAX_SEGMENT_PAYLOAD;
+            if !fits {
+                log::warn!(
+                    "Skipping JPEG APP{} metadata segment of {} bytes (limit {})",
+                    marker,
+                    payload.len(),
+                    JPEG_MAX_SEGMENT_PAYLOAD
+                );
+            }
+            fits
+        });
+        Ok(segments)
+    }
+
+    /// Writes the EXIF and GPS sub-IFDs, which must precede the image
+    /// directory pointing at them.
+    pub(crate) fn write_tiff_sub_directories<W: Write + Seek>(
+        &self,
+        encoder: &mut TiffEncoder<W>,
+    ) -> TiffResult<TiffSubDirectories> {
+        let mut sub_directories = TiffSubDirectories::default();
+        for (context, offset) in [
+            (Context::Exif, &mut sub_directories.exif),
+            (Context::Gps, &mut sub_directories.gps),
+        ] {
+            let mut fields = self.fields_in(context).peekable();
+            if fields.peek().is_none() {
+                continue;
+            }
+
+            let mut directory = encoder.extra_directory()?;
+            for field in fields {
+                write_tiff_field(&mut directory, field)?;
+            }
+            *offset = Some(directory.finish_with_offsets()?.offset);
+        }
+        Ok(sub_directories)
+    }
+
+    /// Adds the primary IFD tags, sub-IFD pointers, XMP and IPTC to the
+    /// image directory.
+    pub(crate) fn write_tiff_image_tags<W: Write + Seek>(
+        &self,
+        directory: &mut DirectoryEncoder<'_, W, TiffKindStandard>,
+        sub_directories: &TiffSubDirectories,
+    ) -> TiffResult<()> {
+        for field in self.fields_in(Context::Tiff) {
+            write_tiff_field(directory, field)?;
+        }
+        if let Some(offset) = sub_directories.exif {
+            directory.write_tag(TiffTag::ExifDirectory, offset)?;
+        }
+        if let Some(offset) = sub_directories.gps {
+            directory.write_tag(TiffTag::GpsDirectory, offset)?;
+        }
+        if let Some(xmp) = &self.xmp_packet {
+            directory.write_tag(TiffTag::Unknown(TIFF_XMP_TAG), xmp.as_bytes())?;
+        }
+        if !self.iptc_iim.is_empty() {
+            directory.write_tag(
+                TiffTag::Unknown(TIFF_IPTC_TAG),
+                TiffUndefined(&self.iptc_iim),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn fields_in(&self, context: Context) -> impl Iterator<Item = &Field> {
+        self.exif_fields
+            .iter()
+            .filter(move |field| field.tag.context() == context)
+    }
+}
+
+/// Offsets of the sub-IFDs written by `write_tiff_sub_directories`.
+#[derive(Debug, Default)]
+pub(crate) struct TiffSubDirectories {
+    exif: Option<u32>,
+    gps: Option<u32>,
+}
+
+fn exif_fields(catalog: &CatalogMetadata, name_software: bool) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let iptc = &catalog.iptc;
+
+    if let Some(exif) = &catalog.exif {
+        push_ascii(&mut fields, Tag::Make, exif.camera_make.as_deref());
+        push_ascii(&mut fields, Tag::Model, exif.camera_model.as_deref());
+    }
+    push_ascii(
+        &mut fields,
+        Tag::ImageDescription,
+        iptc.description.as_deref(),
+    );
+    if name_software {
+        push_ascii(&mut fields, Tag::Software, Some(SOFTWARE));
+    }
+    push_ascii(&mut fields, Tag::Artist, iptc.author.as_deref());
+    push_ascii(&mut fields, Tag::Copyright, iptc.copyright.as_deref());
+
+    let Some(exif) = &catalog.exif else {
+        return fields;
+    };
+
+    push(
+        &mut fields,
+        Tag::ExifVersion,
+        Value::Undefined(EXIF_VERSION.to_vec(), 0),
+    );
+    push(
+        &mut fields,
+        Tag::ColorSpace,
+        Value::Short(vec![EXIF_COLOR_SPACE_SRGB]),
+    );
+    let date_taken = exif
+        .captured_at
+        .map(|taken| taken.format("%Y:%m:%d %H:%M:%S").to_string());
+    push_ascii(&mut fields, Tag::DateTimeOriginal, date_taken.as_deref());
+    if let Some(log2_seconds) = exif.shutter_speed {
+        push(
+            &mut fields,
+            Tag::ExposureTime,
+            Value::Rational(vec![exposure_time(log2_seconds)]),
+        );
+    }
+    if let Some(aperture) = exif.aperture {
+        push(&mut fields, Tag::FNumber, rational(aperture as f64, 10));
+    }
+    if let Some(iso) = exif.iso {
+        push(
+            &mut fields,
+            Tag::PhotographicSensitivity,
+            Value::Short(vec![iso.min(u16::MAX as u32) as u16]),
+        );
+    }
+    if let Some(bias) = exif.exposure_bias {
+        push(
+            &mut fields,
+            Tag::ExposureBiasValue,
+            Value::SRational(vec![exif::SRational {
+                num: (bias * 100.0).round() as i32,
+                denom: 100,
+            }]),
+        );
+    }
+    if let Some(focal_length) = exif.focal_length {
+        push(
+            &mut fields,
+            Tag::FocalLength,
+            rational(focal_length as f64, 10),
+        );
+    }
+    push_ascii(&mut fields, Tag::LensModel, exif.lens.as_deref());
+    push_ascii(
+        &mut fields,
+        Tag::BodySerialNumber,
+        exif.body_serial.as_deref(),
+    );
+
+    if let (Some(lat), Some(lon)) = (exif.gps_lat, exif.gps_lon) {
+        push(
+            &mut fields,
+            Tag::GPSVersionID,
+            Value::Byte(vec![2, 3, 0, 0]),
+        );
+        push_ascii(
+            &mut fields,
+            Tag::GPSLatitudeRef,
+            Some(if lat < 0.0 { "S" } else { "N" }),
+        );
+        push(&mut fields, Tag::GPSLatitude, degrees_minutes_seconds(lat));
+        push_ascii(
+            &mut fields,
+            Tag::GPSLongitudeRef,
+            Some(if lon < 0.0 { "W" } else { "E" }),
+        );
+        push(&mut fields, Tag::GPSLongitude, degrees_minutes_seconds(lon));
+    }
+
+    fields
+}
+
+fn push(fields: &mut Vec<Field>, tag: Tag, value: Value) {
+    fields.push(Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value,
+    });
+}
+
+fn push_ascii(fields: &mut Vec<Field>, tag: Tag, text: Option<&str>) {
+    if let Some(text) = text.map(str::trim).filter(|text| !text.is_empty()) {
+        push(fields, tag, Value::Ascii(vec![text.as_bytes().to_vec()]));
+    }
+}
+
+fn rational(value: f64, denom: u32) -> Value {
+    Value::Rational(vec![exif::Rational {
+        num: (value.max(0.0) * denom as f64).round() as u32,
+        denom,
+    }])
+}
+
+/// Catalog shutter speeds are log2(seconds); fractions of a second are
+/// written as `1/n` the way cameras do.
+fn exposure_time(log2_seconds: f32) -> exif::Rational {
+    let seconds = 2_f64.powf(log2_seconds as f64);
+    if seconds < 1.0 {
+        exif::Rational {
+            num: 1,
+            denom: (1.0 / seconds).round().max(1.0) as u32,
+        }
+    } else {
+        exif::Rational {
+            num: (seconds * 10.0).round() as u32,
+            denom: 10,
+        }
+    }
+}
+
+fn degrees_minutes_seconds(coordinate: f64) -> Value {
+    let coordinate = coordinate.abs();
+    let degrees = coordinate.trunc();
+    let minutes = ((coordinate - degrees) * 60.0).trunc();
+    let seconds = ((coordinate - degrees) * 60.0 - minutes) * 60.0;
+    Value::Rational(vec![
+        exif::Rational {
+            num: degrees as u32,
+            denom: 1,
+        },
+        exif::Rational {
+            num: minutes as u32,
+            denom: 1,
+        },
+        exif::Rational {
+            num: (seconds * 1000.0).round() as u32,
+            denom: 1000,
+        },
+    ])
+}
+
+fn xmp_packet(
+    catalog: &CatalogMetadata,
+    name_software: bool,
+) -> Result<Option<String>, ExportPipelineError> {
+    let iptc = &catalog.iptc;
+    let has_content = catalog.rating.is_some()
+        || !iptc.keywords.is_empty()
+        || !catalog.hierarchical_subjects.is_empty()
+        || iptc.author.is_some()
+        || iptc.description.is_some()
+        || iptc.copyright.is_some()
+        || !catalog.history.is_empty();
+    if !has_content {
+        return Ok(None);
+    }
+
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 1);
+    writer
+        .create_element("x:xmpmeta")
+        .with_attribute(("xmlns:x", "adobe:ns:meta/"))
+        .with_attribute(("x:xmptk", "LuminaFast 1.0"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("rdf:RDF")
+                .with_attribute(("xmlns:rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"))
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("rdf:Description")
+                        .with_attributes([
+                            ("rdf:about", ""),
+                            ("xmlns:xmp", "http://ns.adobe.com/xap/1.0/"),
+                            ("xmlns:dc", "http://purl.org/dc/elements/1.1/"),
+                            ("xmlns:lr", "http://ns.adobe.com/lightroom/1.0/"),
+                            ("xmlns:xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+                            ("xmlns:xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+                            (
+                                "xmlns:stEvt",
+                                "http://ns.adobe.com/xap/1.0/sType/ResourceEvent#",
+                            ),
+                        ])
+                        .write_inner_content(|writer| {
+                            write_xmp_description(writer, catalog, name_software)
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+
+    let body = String::from_utf8(writer.into_inner().into_inner())
+        .map_err(|error| ExportPipelineError::Io(std::io::Error::other(error)))?;
+    Ok(Some(format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n{body}\n<?xpacket end=\"w\"?>"
+    )))
+}
+
+/// XMP packet for a JPEG APP1 segment: drops the oldest history steps until
+/// the packet fits, and the whole history if even one step is too much.
+fn jpeg_xmp_packet(
+    catalog: &CatalogMetadata,
+    name_software: bool,
+    full_packet: Option<&str>,
+) -> Result<Option<String>, ExportPipelineError> {
+    let fits = |packet: &str| JPEG_XMP_HEADER.len() + packet.len() <= JPEG_MAX_SEGMENT_PAYLOAD;
+    let Some(full_packet) = full_packet else {
+        return Ok(None);
+    };
+    if fits(full_packet) || catalog.history.is_empty() {
+        return Ok(Some(full_packet.to_string()));
+    }
+
+    let with_recent_steps = |kept: usize| {
+        let mut trimmed = catalog.clone();
+        trimmed.history.drain(..catalog.history.len() - kept);
+        xmp_packet(&trimmed, name_software)
+    };
+
+    // Largest number of most recent steps that still fits.
+    let (mut low, mut high) = (0, catalog.history.len() - 1);
+    while low < high {
+        let middle = (low + high).div_ceil(2);
+        match with_recent_steps(middle)? {
+            Some(packet) if fits(&packet) => low = middle,
+            _ => high = middle - 1,
+        }
+    }
+    with_recent_steps(low)
+}
+
+type XmpWriter = Writer<Cursor<Vec<u8>>>;
+
+fn write_xmp_description(
+    writer: &mut XmpWriter,
+    catalog: &CatalogMetadata,
+    name_software: bool,
+) -> std::io::Result<()> {
+    let iptc = &catalog.iptc;
+
+    if name_software {
+        write_text(writer, "xmp:CreatorTool", SOFTWARE)?;
+    }
+    if let Some(rating) = catalog.rating {
+        write_text(writer, "xmp:Rating", &rating.to_string())?;
+    }
+    write_array(writer, "dc:subject", "rdf:Bag", &iptc.keywords)?;
+    write_array(
+        writer,
+        "lr:hierarchicalSubject",
+        "rdf:Bag",
+        &catalog.hierarchical_subjects,
+    )?;
+    if let Some(author) = &iptc.author {
+        write_array(
+            writer,
+            "dc:creator",
+            "rdf:Seq",
+            std::slice::from_ref(author),
+        )?;
+    }
+    if let Some(description) = &iptc.description {
+        write_lang_alt(writer, "dc:description", description)?;
+    }
+    if let Some(copyright) = &iptc.copyright {
+        write_lang_alt(writer, "dc:rights", copyright)?;
+        write_text(writer, "xmpRights:Marked", "True")?;
+    }
+
+    if !catalog.history.is_empty() {
+        writer
+            .create_element("xmpMM:History")
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("rdf:Seq")
+                    .write_inner_content(|writer| {
+                        for step in &catalog.history {
+                            writer
+                                .create_element("rdf:li")
+                                .with_attribute(("rdf:parseType", "Resource"))
+                                .write_inner_content(|writer| write_history_step(writer, step))?;
+                        }
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+    }
+
+    Ok(())
+}
+
+fn write_history_step(writer: &mut XmpWriter, step: &ProcessingStep) -> std::io::Result<()> {
+    write_text(writer, "stEvt:action", "edited")?;
+    if let Some(when) = step.when {
+        write_text(
+            writer,
+            "stEvt:when",
+            &when.to_rfc3339_opts(SecondsFormat::Secs, true),
+        )?;
+    }
+    write_text(writer, "stEvt:softwareAgent", SOFTWARE)?;
+    write_text(writer, "stEvt:parameters", &step.parameters.join(", "))
+}
+
+fn write_text(writer: &mut XmpWriter, name: &str, text: &str) -> std::io::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+fn write_array(
+    writer: &mut XmpWriter,
+    name: &str,
+    kind: &str,
+    items: &[String],
+) -> std::io::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    writer.create_element(name).write_inner_content(|writer| {
+        writer.create_element(kind).write_inner_content(|writer| {
+            for item in items {
+                write_text(writer, "rdf:li", item)?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn write_lang_alt(writer: &mut XmpWriter, name: &str, text: &str) -> std::io::Result<()> {
+    writer.create_element(name).write_inner_content(|writer| {
+        writer
+            .create_element("rdf:Alt")
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("rdf:li")
+                    .with_attribute(("xml:lang", "x-default"))
+                    .write_text_content(BytesText::new(text))?;
+                Ok(())
+            })?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// IPTC-IIM datasets (record 2, UTF-8); empty when there is nothing to say.
+fn iptc_iim(iptc: &IptcMetadata) -> Vec<u8> {
+    let mut application = Vec::new();
+    for keyword in &iptc.keywords {
+        push_iim_dataset(&mut application, 2, 25, truncate_utf8(keyword, 64));
+    }
+    let fields = [
+        (80, iptc.author.as_deref(), 32),
+        (116, iptc.copyright.as_deref(), 128),
+        (120, iptc.description.as_deref(), 2000),
+    ];
+    for (dataset, text, max_len) in fields {
+        if let Some(text) = text {
+            push_iim_dataset(&mut application, 2, dataset, truncate_utf8(text, max_len));
+        }
+    }
+    if application.is_empty() {
+        return application;
+    }
+
+    let mut iim = Vec::new();
+    // Coded character set: UTF-8.
+    push_iim_dataset(&mut iim, 1, 90, b"\x1b%G");
+    // Record version 4.
+    push_iim_dataset(&mut iim, 2, 0, &[0, 4]);
+    iim.extend_from_slice(&application);
+    iim
+}
+
+fn push_iim_dataset(iim: &mut Vec<u8>, record: u8, dataset: u8, data: &[u8]) {
+    iim.extend_from_slice(&[0x1c, record, dataset]);
+    iim.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    iim.extend_from_slice(data);
+}
+
+/// The longest prefix of `text` that fits `max_len` bytes without splitting
+/// a character.
+fn truncate_utf8(text: &str, max_len: usize) -> &[u8] {
+    let mut end = text.len().min(max_len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text.as_bytes()[..end]
+}
+
+/// Wraps IIM datasets in the Photoshop resource block of a JPEG APP13.
+fn photoshop_iptc_resource(iim: &[u8]) -> Vec<u8> {
+    let mut segment = JPEG_PHOTOSHOP_HEADER.to_vec();
+    segment.extend_from_slice(b"8BIM");
+    segment.extend_from_slice(&PHOTOSHOP_IPTC_RESOURCE.to_be_bytes());
+    // Empty Pascal-string name, padded to an even length.
+    segment.extend_from_slice(&[0, 0]);
+    segment.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+    segment.extend_from_slice(iim);
+    if iim.len() % 2 == 1 {
+        segment.push(0);
+    }
+    segment
+}
+
+/// EXIF text written as TIFF ASCII. `tiff` only accepts 7-bit `str` there,
+/// while EXIF text in the wild (and ours) is UTF-8.
+struct TiffAscii<'a>(&'a [u8]);
+
+impl TiffValue for TiffAscii<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: TiffType = TiffType::ASCII;
+
+    fn count(&self) -> usize {
+        self.0.len() + 1
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Owned([self.0, &[0]].concat())
+    }
+}
+
+struct TiffUndefined<'a>(&'a [u8]);
+
+impl TiffValue for TiffUndefined<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: TiffType = TiffType::UNDEFINED;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0)
+    }
+}
+
+fn write_tiff_field<W: Write + Seek>(
+    directory: &mut DirectoryEncoder<'_, W, TiffKindStandard>,
+    field: &Field,
+) -> TiffResult<()> {
+    let tag = TiffTag::from_u16_exhaustive(field.tag.number());
+    match &field.value {
+        Value::Ascii(strings) => {
+            let text = strings.first().map(Vec::as_slice).unwrap_or_default();
+            directory.write_tag(tag, TiffAscii(text))
+        }
+        Value::Undefined(bytes, _) => directory.write_tag(tag, TiffUndefined(bytes)),
+        Value::Byte(bytes) => directory.write_tag(tag, bytes.as_slice()),
+        Value::Short(values) => directory.write_tag(tag, values.as_slice()),
+        Value::Rational(values) => {
+            let values: Vec<TiffRational> = values
+                .iter()
+                .map(|value| TiffRational {
+                    n: value.num,
+                    d: value.denom,
+                })
+                .collect();
+            directory.write_tag(tag, values.as_slice())
+        }
+        Value::SRational(values) => {
+            let values: Vec<TiffSRational> = values
+                .iter()
+                .map(|value| TiffSRational {
+                    n: value.num,
+                    d: value.denom,
+                })
+                .collect();
+            directory.write_tag(tag, values.as_slice())
+        }
+        // `exif_fields` produces no other value types.
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn sample_catalog() -> CatalogMetadata {
+        CatalogMetadata {
+            exif: Some(ExifMetadata {
+                iso: Some(400),
+                aperture: Some(2.8),
+                shutter_speed: Some((1.0_f32 / 250.0).log2()),
+                focal_length: Some(35.0),
+                lens: Some("XF35mmF1.4 R".to_string()),
+                camera_make: Some("FUJIFILM".to_string()),
+                camera_model: Some("X-T5".to_string()),
+                gps_lat: Some(48.858_37),
+                gps_lon: Some(-2.294_481),
+                color_space: Some("sRGB".to_string()),
+                body_serial: Some("SN-0042".to_string()),
+                exposure_bias: Some(-0.33),
+                captured_at: NaiveDate::from_ymd_opt(2026, 5, 14)
+                    .and_then(|date| date.and_hms_opt(18, 30, 5)),
+            }),
+            rating: Some(4),
+            iptc: IptcMetadata {
+                copyright: Some("© 2026 Jeanne Durand".to_string()),
+                keywords: vec!["Paris".to_string(), "Tour Eiffel".to_string()],
+                description: Some("Coucher de soleil".to_string()),
+                author: Some("Jeanne Durand".to_string()),
+            },
+            hierarchical_subjects: vec!["Lieux/France/Paris".to_string()],
+            history: vec![ProcessingStep {
+                when: DateTime::from_timestamp(1_780_000_000, 0),
+                parameters: vec!["contrast".to_string(), "exposure".to_string()],
+            }],
+        }
+    }
+
+    fn embedded(options: ExportMetadataOptions) -> EmbeddedMetadata {
+        EmbeddedMetadata::from_catalog(&sample_catalog(), &options)
+            .unwrap()
+            .expect("sample catalog has metadata")
+    }
+
+    fn parse_exif(embedded: &EmbeddedMetadata) -> exif::Exif {
+        let tiff = embedded.exif_tiff().unwrap().expect("EXIF fields");
+        exif::Reader::new().read_raw(tiff).unwrap()
+    }
+
+    #[test]
+    fn exif_round_trips_catalog_fields() {
+        let exif = parse_exif(&embedded(ExportMetadataOptions::default()));
+
+        let text = |tag| {
+            exif.get_field(tag, In::PRIMARY)
+                .map(|field| field.display_value().to_string())
+        };
+        assert_eq!(text(Tag::Model).as_deref(), Some("\"X-T5\""));
+        assert_eq!(text(Tag::ExposureTime).as_deref(), Some("1/250"));
+        assert_eq!(text(Tag::FNumber).as_deref(), Some("2.8"));
+        assert_eq!(text(Tag::PhotographicSensitivity).as_deref(), Some("400"));
+        assert_eq!(
+            text(Tag::DateTimeOriginal).as_deref(),
+            Some("2026-05-14 18:30:05")
+        );
+        assert_eq!(text(Tag::GPSLongitudeRef).as_deref(), Some("W"));
+        assert!(text(Tag::BodySerialNumber).is_some());
+        let copyright = exif
+            .get_field(Tag::Copyright, In::PRIMARY)
+            .expect("copyright field");
+        assert!(matches!(
+            &copyright.value,
+            Value::Ascii(text) if text[0] == "© 2026 Jeanne Durand".as_bytes()
+        ));
+    }
+
+    #[test]
+    fn strip_options_drop_gps_and_serials() {
+        let exif = parse_exif(&embedded(ExportMetadataOptions {
+            strip_gps: true,
+            strip_camera_serials: true,
+            ..ExportMetadataOptions::default()
+        }));
+
+        assert!(exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_none());
+        assert!(exif.get_field(Tag::BodySerialNumber, In::PRIMARY).is_none());
+        assert!(exif.get_field(Tag::LensModel, In::PRIMARY).is_some());
+    }
+
+    #[test]
+    fn copyright_only_keeps_nothing_else() {
+        let metadata = embedded(ExportMetadataOptions {
+            copyright_only: true,
+            include_history: true,
+            ..ExportMetadataOptions::default()
+        });
+
+        let exif = parse_exif(&metadata);
+        assert_eq!(exif.fields().count(), 1);
+        assert!(exif.get_field(Tag::Copyright, In::PRIMARY).is_some());
+
+        let xmp = metadata.xmp_packet().expect("rights packet");
+        assert!(xmp.contains("Jeanne Durand</rdf:li>"));
+        assert!(!xmp.contains("Paris"));
+        assert!(!xmp.contains("xmp:Rating"));
+        assert!(!xmp.contains("xmpMM:History"));
+        assert!(!xmp.contains("xmp:CreatorTool"));
+    }
+
+    #[test]
+    fn xmp_carries_tags_rating_iptc_and_optional_history() {
+        let without_history = embedded(ExportMetadataOptions::default());
+        let xmp = without_history.xmp_packet().expect("xmp packet");
+        assert!(xmp.starts_with("<?xpacket begin="));
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(xmp.contains("<rdf:li>Tour Eiffel</rdf:li>"));
+        assert!(xmp.contains("<rdf:li>Lieux/France/Paris</rdf:li>"));
+        assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Coucher de soleil</rdf:li>"));
+        assert!(!xmp.contains("xmpMM:History"));
+
+        let with_history = embedded(ExportMetadataOptions {
+            include_history: true,
+            ..ExportMetadataOptions::default()
+        });
+        let xmp = with_history.xmp_packet().expect("xmp packet");
+        assert!(xmp.contains("<stEvt:parameters>contrast, exposure</stEvt:parameters>"));
+        assert!(xmp.contains("<stEvt:when>2026-05-28T20:26:40Z</stEvt:when>"));
+    }
+
+    #[test]
+    fn jpeg_xmp_keeps_the_most_recent_history_that_fits_one_segment() {
+        let mut catalog = sample_catalog();
+        catalog.history = (0..5_000)
+            .map(|index| ProcessingStep {
+                when: DateTime::from_timestamp(1_780_000_000 + index, 0),
+                parameters: vec![format!("step-{index}"), "exposure".to_string()],
+            })
+            .collect();
+        let options = ExportMetadataOptions {
+            include_history: true,
+            ..ExportMetadataOptions::default()
+        };
+        let embedded = EmbeddedMetadata::from_catalog(&catalog, &options)
+            .unwrap()
+            .expect("metadata");
+
+        // Other containers keep the whole history.
+        let full = embedded.xmp_packet().expect("xmp packet");
+        assert!(full.len() > JPEG_MAX_SEGMENT_PAYLOAD);
+        assert!(full.contains("step-0,"));
+
+        let segments = embedded.jpeg_app_segments().unwrap();
+        assert!(segments
+            .iter()
+            .all(|(_, payload)| payload.len() <= JPEG_MAX_SEGMENT_PAYLOAD));
+        let xmp = segments
+            .iter()
+            .find(|(_, payload)| payload.starts_with(JPEG_XMP_HEADER))
+            .map(|(_, payload)| String::from_utf8_lossy(&payload[JPEG_XMP_HEADER.len()..]))
+            .expect("xmp segment");
+        assert!(xmp.contains("<stEvt:parameters>step-4999, exposure</stEvt:parameters>"));
+        assert!(!xmp.contains("<stEvt:parameters>step-0, exposure</stEvt:parameters>"));
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(segments
+            .iter()
+            .any(|(_, payload)| payload.starts_with(JPEG_EXIF_HEADER)));
+        assert!(segments.iter().any(|(marker, _)| *marker == 13));
+    }
+
+    #[test]
+    fn nothing_is_embedded_when_disabled_or_empty() {
+        let disabled = ExportMetadataOptions {
+            embed: false,
+            ..ExportMetadataOptions::default()
+        };
+        assert!(EmbeddedMetadata::from_catalog(&sample_catalog(), &disabled)
+            .unwrap()
+            .is_none());
+
+        let copyright_only = ExportMetadataOptions {
+            copyright_only: true,
+            ..ExportMetadataOptions::default()
+        };
+        assert!(
+            EmbeddedMetadata::from_catalog(&CatalogMetadata::default(), &copyright_only)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn iptc_iim_truncates_on_character_boundaries() {
+        let iim = iptc_iim(&IptcMetadata {
+            author: Some("é".repeat(20)),
+            ..IptcMetadata::default()
+        });
+
+        // 1:90, 2:0, then 2:80 capped at 32 bytes = 16 two-byte characters.
+        let by_line = &iim[iim.len() - 32 - 5..];
+        assert_eq!(&by_line[..5], &[0x1c, 2, 80, 0, 32]);
+        assert_eq!(by_line[5..], *"é".repeat(16).as_bytes());
+    }
+}
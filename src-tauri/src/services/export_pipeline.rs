@@ -1,10 +1,22 @@
-use crate::models::event::{EventPayload, EventType};
+use crate::models::event::{EventPayload, EventType, TargetType};
+use crate::services::edit_recipe::load_process_version;
 use crate::services::event_sourcing::{EventStore, EventStoreError};
-use crate::services::export_rendering::render_pixels_for_export;
-use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use crate::services::export_encoding::{write_export_image, ExportFormatOptions, ExportPixels};
+use crate::services::export_metadata::{
+    load_catalog_metadata, EmbeddedMetadata, ExportMetadataOptions, ProcessingStep,
+};
+use crate::services::export_rendering::{
+    render_recipe_for_export, render_recipe_rgba16_for_export,
+};
+use crate::services::export_sizing::{resize_for_export, ExportResize};
+use crate::services::export_watermark::{apply_watermark, Watermark};
+use chrono::{DateTime, Utc};
 use luminafast_image_core::pipeline::decode_raw_to_rgba8;
-use luminafast_image_core::{LinearImage, PixelFilters, ProcessingError, RawDecoder};
+use luminafast_image_core::{
+    decode_raw_to_rgba16, EditRecipe, LinearImage, ProcessingError, RawDecoder,
+};
 use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use std::collections::HashSet;
 use std::fs;
@@ -16,17 +28,42 @@ const KNOWN_RAW_EXTENSIONS: &[&str] = &[
 ];
 const PILOT_RAW_EXTENSIONS: &[&str] = &["arw", "raf", "dng"];
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExportFormat {
+    #[serde(alias = "jpg")]
     Jpeg,
+    #[serde(alias = "tif")]
     Tiff,
+    Png,
+    Webp,
+    Avif,
 }
 
 impl ExportFormat {
+    /// Whether the container can carry embedded EXIF/XMP/IPTC metadata.
+    pub fn supports_metadata(self) -> bool {
+        matches!(self, Self::Jpeg | Self::Tiff | Self::Png)
+    }
+
     pub fn as_str(self) -> &'static str {
         match self {
             Self::Jpeg => "jpeg",
             Self::Tiff => "tiff",
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+        }
+    }
+
+    /// File extension written by batch exports.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Tiff => "tiff",
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
         }
     }
 }
@@ -38,6 +75,9 @@ impl TryFrom<&str> for ExportFormat {
         match value.to_lowercase().as_str() {
             "jpeg" | "jpg" => Ok(Self::Jpeg),
             "tiff" | "tif" => Ok(Self::Tiff),
+            "png" => Ok(Self::Png),
+            "webp" => Ok(Self::Webp),
+            "avif" => Ok(Self::Avif),
             unsupported => Err(ExportPipelineError::InvalidOutputFormat(
                 unsupported.to_string(),
             )),
@@ -50,6 +90,44 @@ pub struct ExportRequest {
     pub image_id: i64,
     pub output_path: PathBuf,
     pub format: ExportFormat,
+    /// Output size; see `ExportResize` for the upscaling rules.
+    pub resize: ExportResize,
+    /// Bit depth, alpha handling and compression of the written file.
+    pub options: ExportFormatOptions,
+    /// Catalog metadata embedded into JPEG, PNG and TIFF output.
+    pub metadata: ExportMetadataOptions,
+    /// Drawn on the resized render.
+    pub watermark: Option<Watermark>,
+    /// Edit state to render.
+    pub target: ExportTarget,
+}
+
+/// Which edit state of an image an export renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExportTarget {
+    /// The newest snapshot plus every edit made after it.
+    #[default]
+    Current,
+    /// Exactly the state saved in one of the image's snapshots.
+    #[serde(rename_all = "camelCase")]
+    Snapshot { snapshot_id: i64 },
+    /// The state as of `at`: the newest snapshot taken by then plus the
+    /// edits recorded up to and including `at`.
+    #[serde(rename_all = "camelCase")]
+    At { at: DateTime<Utc> },
+}
+
+/// Edit state an export actually rendered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportEditState {
+    pub target: ExportTarget,
+    /// Snapshot the recipe started from, if any.
+    pub snapshot_id: Option<i64>,
+    pub snapshot_name: Option<String>,
+    /// Newest edit event replayed on top of the snapshot.
+    pub last_event_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +139,14 @@ pub struct ExportResult {
     pub height: u32,
     pub applied_edit_events: usize,
     pub used_snapshot: bool,
+    /// Target of the request and the snapshot and edits it resolved to.
+    pub edit_state: ExportEditState,
+    /// BLAKE3 of the rendered edit recipe, see `recipe_hash`.
+    pub recipe_hash: String,
+    /// Encoder quality, as searched when a file size limit was set; `None`
+    /// for lossless formats.
+    pub quality: Option<u8>,
+    pub file_size_bytes: u64,
 }
 
 #[derive(Debug, Error)]
@@ -86,12 +172,30 @@ pub enum ExportPipelineError {
     #[error("Image {0} not found in catalog")]
     ImageNotFound(i64),
 
+    #[error("Snapshot {snapshot_id} not found for image {image_id}")]
+    SnapshotNotFound { image_id: i64, snapshot_id: i64 },
+
+    #[error("Invalid output file name template {0}")]
+    InvalidFileNameTemplate(String),
+
+    #[error("Invalid watermark: {0}")]
+    InvalidWatermark(String),
+
+    #[error("Collection {id} cannot be exported: {reason}")]
+    InvalidCollection { id: i64, reason: String },
+
     #[error("Image {0} has no known source file path")]
     SourcePathUnavailable(i64),
 
     #[error("Unsupported export format: {0}")]
     InvalidOutputFormat(String),
 
+    #[error("Unsupported export options: {0}")]
+    InvalidFormatOptions(String),
+
+    #[error("Cannot fit export under {limit_kb} KB: the smallest encoding is {smallest_kb} KB")]
+    FileSizeLimitUnreachable { limit_kb: u32, smallest_kb: u64 },
+
     #[error("RAW export command requires a RAW source file, got extension: {0}")]
     RawSourceRequired(String),
 
@@ -107,79 +211,23 @@ pub enum ExportPipelineError {
     },
 }
 
-#[derive(Debug, Clone)]
-struct EditStateAccumulator {
-    exposure: f64,
-    contrast: f64,
-    highlights: f64,
-    shadows: f64,
-    temp: f64,
-    tint: f64,
-    vibrance: f64,
-    saturation: f64,
-    clarity: f64,
-}
-
-impl Default for EditStateAccumulator {
-    fn default() -> Self {
-        Self {
-            exposure: 0.0,
-            contrast: 0.0,
-            highlights: 0.0,
-            shadows: 0.0,
-            temp: 5500.0,
-            tint: 0.0,
-            vibrance: 0.0,
-            saturation: 0.0,
-            clarity: 0.0,
-        }
-    }
-}
-
-impl EditStateAccumulator {
-    fn apply_patch(&mut self, patch: &Map<String, Value>) {
-        for (key, value) in patch {
-            let Some(v) = value_to_f64(value) else {
-                continue;
-            };
-
-            match key.as_str() {
-                "exposure" => self.exposure = v,
-                "contrast" => self.contrast = v,
-                "highlights" => self.highlights = v,
-                "shadows" => self.shadows = v,
-                "temp" | "colorTemp" | "color_temp" => self.temp = v,
-                "tint" => self.tint = v,
-                "vibrance" => self.vibrance = v,
-                "saturation" => self.saturation = v,
-                "clarity" => self.clarity = v,
-                _ => {}
-            }
-        }
-    }
-
-    fn to_pixel_filters(&self) -> PixelFilters {
-        PixelFilters {
-            exposure: (self.exposure / 50.0) as f32,
-            contrast: (self.contrast / 50.0) as f32,
-            saturation: (1.0 + self.saturation / 100.0) as f32,
-            highlights: (self.highlights / 100.0) as f32,
-            shadows: (self.shadows / 100.0) as f32,
-            clarity: (self.clarity / 100.0) as f32,
-            vibrance: (self.vibrance / 100.0) as f32,
-            color_temp: self.temp as f32,
-            tint: (self.tint / 2.0) as f32,
-        }
-    }
+/// Edit recipe replayed from the snapshot seed and the edit events.
+struct ResolvedRecipe {
+    recipe: EditRecipe,
+    applied_edit_events: usize,
+    edit_state: ExportEditState,
+    history: Vec<ProcessingStep>,
 }
 
 struct SnapshotSeed {
+    id: i64,
+    name: String,
     event_ids: HashSet<String>,
     patches: Vec<Map<String, Value>>,
 }
 
 #[derive(Debug, Default)]
-struct RsRawDecoder;
+pub(crate) struct RsRawDecoder;
 
 impl RawDecoder for RsRawDecoder {
     fn decode_to_linear_rgb(&self, input: &[u8]) -> Result<LinearImage, ProcessingError> {
@@ -239,6 +287,27 @@ fn export_image_with_edits_internal(
     require_raw_source: bool,
     raw_decoder: &dyn RawDecoder,
 ) -> Result<ExportResult, ExportPipelineError> {
+    let prepared = prepare_export(conn, request, require_raw_source)?;
+    render_prepared_export(prepared, request, raw_decoder)
+}
+
+/// Catalog state an export needs, read up front so rendering runs without a
+/// database connection.
+pub(crate) struct PreparedExport {
+    source_path: PathBuf,
+    recipe: EditRecipe,
+    applied_edit_events: usize,
+    edit_state: ExportEditState,
+    metadata: Option<EmbeddedMetadata>,
+}
+
+/// Resolves the source file, edit recipe and embedded metadata of `request`
+/// and validates its options.
+pub(crate) fn prepare_export(
+    conn: &Connection,
+    request: &ExportRequest,
+    require_raw_source: bool,
+) -> Result<PreparedExport, ExportPipelineError> {
     let source_path = resolve_source_image_path(conn, request.image_id)?;
 
     if require_raw_source && !is_known_raw_extension(&source_path) {
@@ -247,20 +316,73 @@ fn export_image_with_edits_internal(
         ));
     }
 
-    let (filters, applied_edit_events, used_snapshot) =
-        resolve_filters_from_history(conn, request.image_id)?;
+    let ResolvedRecipe {
+        recipe,
+        applied_edit_events,
+        edit_state,
+        history,
+    } = resolve_recipe_from_history(conn, request.image_id, &request.target)?;
+    // Fail before decoding when the pinned process version cannot be rendered.
+    recipe.step_order()?;
+    request.options.validate(request.format)?;
+    request.resize.validate()?;
+
+    let metadata = if request.format.supports_metadata() && request.metadata.embed {
+        let mut catalog = load_catalog_metadata(conn, request.image_id)?;
+        catalog.history = history;
+        EmbeddedMetadata::from_catalog(&catalog, &request.metadata)?
+    } else {
+        None
+    };
+
+    Ok(PreparedExport {
+        source_path,
+        recipe,
+        applied_edit_events,
+        edit_state,
+        metadata,
+    })
+}
+
+/// Decodes, renders, resizes and writes a prepared export.
+pub(crate) fn render_prepared_export(
+    prepared: PreparedExport,
+    request: &ExportRequest,
+    raw_decoder: &dyn RawDecoder,
+) -> Result<ExportResult, ExportPipelineError> {
+    let PreparedExport {
+        source_path,
+        recipe,
+        applied_edit_events,
+        edit_state,
+        metadata,
+    } = prepared;
 
     let (source_pixels, width, height) =
-        decode_source_pixels_for_export(&source_path, raw_decoder)?;
+        decode_source_pixels_for_export(&source_path, raw_decoder, &request.options)?;
 
-    let processed_pixels = render_pixels_for_export(&source_pixels, width, height, &filters)?;
+    let processed_pixels = match source_pixels {
+        ExportPixels::Rgba8(pixels) => {
+            ExportPixels::Rgba8(render_recipe_for_export(&pixels, width, height, &recipe)?)
+        }
+        ExportPixels::Rgba16(pixels) => ExportPixels::Rgba16(render_recipe_rgba16_for_export(
+            &pixels, width, height, &recipe,
+        )?),
+    };
+    let (mut processed_pixels, width, height) =
+        resize_for_export(processed_pixels, width, height, &request.resize)?;
+    if let Some(watermark) = &request.watermark {
+        apply_watermark(&mut processed_pixels, width, height, watermark)?;
+    }
 
-    write_export_image(
+    let written = write_export_image(
         &processed_pixels,
         width,
         height,
         &request.output_path,
         request.format,
+        &request.options,
+        metadata.as_ref(),
     )?;
 
     Ok(ExportResult {
@@ -270,14 +392,21 @@ fn export_image_with_edits_internal(
         width,
         height,
         applied_edit_events,
-        used_snapshot,
+        used_snapshot: edit_state.snapshot_id.is_some(),
+        edit_state,
+        recipe_hash: recipe_hash(&recipe)?,
+        quality: written.quality,
+        file_size_bytes: written.file_size_bytes,
     })
 }
 
+/// Decodes the source at the precision of the requested output: 16-bit
+/// exports keep RAW linear samples and 16-bit sources without an 8-bit pass.
 fn decode_source_pixels_for_export(
     source_path: &Path,
     raw_decoder: &dyn RawDecoder,
-) -> Result<(Vec<u8>, u32, u32), ExportPipelineError> {
+    options: &ExportFormatOptions,
+) -> Result<(ExportPixels, u32, u32), ExportPipelineError> {
     if let Some(ext) = source_extension(source_path) {
         if KNOWN_RAW_EXTENSIONS.contains(&ext.as_str()) {
             if !PILOT_RAW_EXTENSIONS.contains(&ext.as_str()) {
@@ -285,15 +414,29 @@ fn decode_source_pixels_for_export(
             }
 
             let raw_bytes = fs::read(source_path)?;
-            return decode_raw_to_rgba8(raw_decoder, &raw_bytes)
-                .map_err(ExportPipelineError::Processing);
+            if options.is_16_bit() {
+                let (pixels, width, height) = decode_raw_to_rgba16(raw_decoder, &raw_bytes)?;
+                return Ok((ExportPixels::Rgba16(pixels), width, height));
+            }
+            let (pixels, width, height) = decode_raw_to_rgba8(raw_decoder, &raw_bytes)?;
+            return Ok((ExportPixels::Rgba8(pixels), width, height));
         }
     }
 
     let decoded = image::open(source_path)?;
-    let rgba = decoded.to_rgba8();
-    let (width, height) = rgba.dimensions();
-    Ok((rgba.into_raw(), width, height))
+    let (width, height) = (decoded.width(), decoded.height());
+    if options.is_16_bit() {
+        return Ok((
+            ExportPixels::Rgba16(decoded.to_rgba16().into_raw()),
+            width,
+            height,
+        ));
+    }
+    Ok((
+        ExportPixels::Rgba8(decoded.to_rgba8().into_raw()),
+        width,
+        height,
+    ))
 }
 
 fn source_extension(path: &Path) -> Option<String> {
@@ -302,17 +445,17 @@ fn source_extension(path: &Path) -> Option<String> {
         .map(|ext| ext.to_ascii_lowercase())
 }
 
-fn source_extension_or_unknown(path: &Path) -> String {
+pub(crate) fn source_extension_or_unknown(path: &Path) -> String {
     source_extension(path).unwrap_or_else(|| "<none>".to_string())
 }
 
-fn is_known_raw_extension(path: &Path) -> bool {
+pub(crate) fn is_known_raw_extension(path: &Path) -> bool {
     source_extension(path)
         .map(|ext| KNOWN_RAW_EXTENSIONS.contains(&ext.as_str()))
         .unwrap_or(false)
 }
 
-fn resolve_source_image_path(
+pub(crate) fn resolve_source_image_path(
     conn: &Connection,
     image_id: i64,
 ) -> Result<PathBuf, ExportPipelineError> {
@@ -345,67 +488,147 @@ fn resolve_source_image_path(
     }
 }
 
-fn resolve_filters_from_history(
+/// Resolves the edit recipe of an image from its snapshot seed and edit events.
+pub fn resolve_edit_recipe(
     conn: &Connection,
     image_id: i64,
-) -> Result<(PixelFilters, usize, bool), ExportPipelineError> {
-    let mut accumulator = EditStateAccumulator::default();
-    let mut applied_count = 0_usize;
-    let mut used_snapshot = false;
+) -> Result<EditRecipe, ExportPipelineError> {
+    resolve_recipe_from_history(conn, image_id, &ExportTarget::Current)
+        .map(|resolved| resolved.recipe)
+}
+
+/// Hash of the recipe `target` resolves to now; an export whose recorded
+/// hash differs was rendered from other edits.
+pub(crate) fn resolve_recipe_hash(
+    conn: &Connection,
+    image_id: i64,
+    target: &ExportTarget,
+) -> Result<String, ExportPipelineError> {
+    recipe_hash(&resolve_recipe_from_history(conn, image_id, target)?.recipe)
+}
+
+/// BLAKE3 (hex) of the recipe JSON: process version and every adjustment.
+fn recipe_hash(recipe: &EditRecipe) -> Result<String, ExportPipelineError> {
+    Ok(blake3::hash(&serde_json::to_vec(recipe)?)
+        .to_hex()
+        .to_string())
+}
+
+fn resolve_recipe_from_history(
+    conn: &Connection,
+    image_id: i64,
+    target: &ExportTarget,
+) -> Result<ResolvedRecipe, ExportPipelineError> {
+    let mut recipe = EditRecipe::new(load_process_version(conn, image_id)?);
+    let mut history = Vec::new();
+    let mut edit_state = ExportEditState {
+        target: *target,
+        snapshot_id: None,
+        snapshot_name: None,
+        last_event_at: None,
+    };
     let mut snapshot_event_ids = HashSet::new();
 
-    if let Some(seed) = load_latest_snapshot_seed(conn, image_id)? {
-        used_snapshot = true;
+    if let Some(seed) = load_snapshot_seed(conn, image_id, target)? {
+        edit_state.snapshot_id = Some(seed.id);
+        edit_state.snapshot_name = Some(seed.name);
         snapshot_event_ids = seed.event_ids;
 
         for patch in seed.patches {
-            accumulator.apply_patch(&patch);
-            applied_count += 1;
+            recipe.apply_patch(&patch);
+            history.push(ProcessingStep {
+                when: None,
+                parameters: patch.keys().cloned().collect(),
+            });
         }
     }
 
+    // A snapshot target is the saved state alone; later edits are not replayed.
+    let replay_until = match target {
+        ExportTarget::Current => None,
+        ExportTarget::At { at } => Some(*at),
+        ExportTarget::Snapshot { .. } => {
+            return Ok(ResolvedRecipe {
+                recipe,
+                applied_edit_events: history.len(),
+                edit_state,
+                history,
+            });
+        }
+    };
+
     let store = EventStore::new(conn);
-    let events = store.get_events()?;
+    let events = store.get_target_events(TargetType::Image, image_id, EventType::EditApplied)?;
 
     for event in events {
-        if event.target_id != image_id || event.event_type != EventType::EditApplied {
+        if snapshot_event_ids.contains(&event.id) {
             continue;
         }
 
-        if snapshot_event_ids.contains(&event.id) {
+        if replay_until.is_some_and(|until| event.created_at > until) {
             continue;
         }
 
         if let Some(patch) = extract_patch_from_event_payload(&event.payload) {
-            accumulator.apply_patch(&patch);
-            applied_count += 1;
+            recipe.apply_patch(&patch);
+            edit_state.last_event_at = edit_state.last_event_at.max(Some(event.created_at));
+            history.push(ProcessingStep {
+                when: Some(event.created_at),
+                parameters: patch.keys().cloned().collect(),
+            });
         }
     }
 
-    Ok((accumulator.to_pixel_filters(), applied_count, used_snapshot))
+    Ok(ResolvedRecipe {
+        recipe,
+        applied_edit_events: history.len(),
+        edit_state,
+        history,
+    })
 }
 
-fn load_latest_snapshot_seed(
+/// Snapshot `target` starts from: the newest one, the newest taken by a
+/// point in time, or the requested one, which must exist.
+fn load_snapshot_seed(
     conn: &Connection,
     image_id: i64,
+    target: &ExportTarget,
 ) -> Result<Option<SnapshotSeed>, ExportPipelineError> {
-    let row = conn
-        .query_row(
-            "SELECT snapshot_data, event_ids
-             FROM edit_snapshots
-             WHERE image_id = ?1
-             ORDER BY created_at DESC
-             LIMIT 1",
-            [image_id],
-            |row| {
-                let snapshot_data: String = row.get(0)?;
-                let event_ids_json: String = row.get(1)?;
-                Ok((snapshot_data, event_ids_json))
-            },
-        )
-        .optional()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, snapshot_data, event_ids, created_at
+         FROM edit_snapshots
+         WHERE image_id = ?1
+         ORDER BY created_at DESC",
+    )?;
+    let rows = stmt
+        .query_map([image_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let selected = match target {
+        ExportTarget::Current => rows.into_iter().next(),
+        ExportTarget::Snapshot { snapshot_id } => {
+            Some(rows.into_iter().find(|row| row.0 == *snapshot_id).ok_or(
+                ExportPipelineError::SnapshotNotFound {
+                    image_id,
+                    snapshot_id: *snapshot_id,
+                },
+            )?)
+        }
+        ExportTarget::At { at } => rows.into_iter().find(|row| {
+            DateTime::parse_from_rfc3339(&row.4)
+                .is_ok_and(|created_at| created_at.with_timezone(&Utc) <= *at)
+        }),
+    };
 
-    let Some((snapshot_data, event_ids_json)) = row else {
+    let Some((id, name, snapshot_data, event_ids_json, _)) = selected else {
         return Ok(None);
     };
 
@@ -436,7 +659,12 @@ fn load_latest_snapshot_seed(
         }
     }
 
-    Ok(Some(SnapshotSeed { event_ids, patches }))
+    Ok(Some(SnapshotSeed {
+        id,
+        name,
+        event_ids,
+        patches,
+    }))
 }
 
 fn extract_patch_from_event_payload(payload: &EventPayload) -> Option<Map<String, Value>> {
@@ -491,79 +719,13 @@ fn normalize_event_type(raw: &str) -> String {
     normalized
 }
 
-fn write_export_image(
-    rgba_pixels: &[u8],
-    width: u32,
-    height: u32,
-    output_path: &Path,
-    format: ExportFormat,
-) -> Result<(), ExportPipelineError> {
-    let expected = (width as usize)
-        .checked_mul(height as usize)
-        .and_then(|px| px.checked_mul(4))
-        .ok_or(ExportPipelineError::InvalidPixelBuffer {
-            expected: usize::MAX,
-            got: rgba_pixels.len(),
-            width,
-            height,
-        })?;
-
-    if rgba_pixels.len() != expected {
-        return Err(ExportPipelineError::InvalidPixelBuffer {
-            expected,
-            got: rgba_pixels.len(),
-            width,
-            height,
-        });
-    }
-
-    if let Some(parent) = output_path.parent() {
-        if !parent.as_os_str().is_empty() {
-            std::fs::create_dir_all(parent)?;
-        }
-    }
-
-    match format {
-        ExportFormat::Jpeg => {
-            let rgba = RgbaImage::from_raw(width, height, rgba_pixels.to_vec()).ok_or(
-                ExportPipelineError::InvalidPixelBuffer {
-                    expected,
-                    got: rgba_pixels.len(),
-                    width,
-                    height,
-                },
-            )?;
-            let rgb: RgbImage = DynamicImage::ImageRgba8(rgba).to_rgb8();
-            rgb.save_with_format(output_path, ImageFormat::Jpeg)?;
-        }
-        ExportFormat::Tiff => {
-            let rgba = RgbaImage::from_raw(width, height, rgba_pixels.to_vec()).ok_or(
-                ExportPipelineError::InvalidPixelBuffer {
-                    expected,
-                    got: rgba_pixels.len(),
-                    width,
-                    height,
-                },
-            )?;
-            DynamicImage::ImageRgba8(rgba).save_with_format(output_path, ImageFormat::Tiff)?;
-        }
-    }
-
-    Ok(())
-}
-
-fn value_to_f64(value: &Value) -> Option<f64> {
-    value
-        .as_f64()
-        .or_else(|| value.as_i64().map(|v| v as f64))
-        .or_else(|| value.as_u64().map(|v| v as f64))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::event::{Event, EventPayload, TargetType};
+    use crate::models::event::{Event, EventPayload};
+    use crate::services::export_encoding::TiffCompression;
     use chrono::Utc;
+    use image::RgbaImage;
     use std::path::Path;
     use tempfile::tempdir;
 
@@ -582,7 +744,51 @@ mod tests {
                 r#"
             CREATE TABLE images (
                 id INTEGER PRIMARY KEY,
-                blake3_hash TEXT NOT NULL UNIQUE
+                blake3_hash TEXT NOT NULL UNIQUE,
+                captured_at TEXT
+            );
+
+            CREATE TABLE exif_metadata (
+                image_id INTEGER PRIMARY KEY,
+                iso INTEGER,
+                aperture REAL,
+                shutter_speed REAL,
+                focal_length REAL,
+                lens TEXT,
+                camera_make TEXT,
+                camera_model TEXT,
+                gps_lat REAL,
+                gps_lon REAL,
+                color_space TEXT,
+                body_serial TEXT,
+                exposure_bias REAL
+            );
+
+            CREATE TABLE image_state (
+                image_id INTEGER PRIMARY KEY,
+                rating INTEGER DEFAULT 0,
+                flag TEXT,
+                color_label TEXT
+            );
+
+            CREATE TABLE tags (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                parent_id INTEGER
+            );
+
+            CREATE TABLE image_tags (
+                image_id INTEGER,
+                tag_id INTEGER,
+                PRIMARY KEY (image_id, tag_id)
+            );
+
+            CREATE TABLE image_iptc (
+                image_id INTEGER PRIMARY KEY,
+                author TEXT,
+                description TEXT,
+                copyright TEXT,
+                updated_at TEXT
             );
 
             CREATE TABLE ingestion_file_status (
@@ -611,6 +817,12 @@ mod tests {
                 event_ids TEXT NOT NULL,
                 created_at TEXT NOT NULL
             );
+
+            CREATE TABLE image_process_versions (
+                image_id INTEGER PRIMARY KEY,
+                process_version INTEGER NOT NULL,
+                pinned_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
             "#,
             ),
             "create export pipeline test schema",
@@ -657,15 +869,25 @@ mod tests {
     }
 
     fn append_edit_event(conn: &Connection, event_id: &str, image_id: i64, edits: Value) {
+        append_edit_event_at(conn, event_id, image_id, edits, Utc::now());
+    }
+
+    fn append_edit_event_at(
+        conn: &Connection,
+        event_id: &str,
+        image_id: i64,
+        edits: Value,
+        created_at: DateTime<Utc>,
+    ) {
         let event = Event {
             id: event_id.to_string(),
-            timestamp: Utc::now().timestamp_millis(),
+            timestamp: created_at.timestamp_millis(),
             event_type: EventType::EditApplied,
             payload: EventPayload::Generic(serde_json::json!({ "edits": edits })),
             target_type: TargetType::Image,
             target_id: image_id,
             user_id: None,
-            created_at: Utc::now(),
+            created_at,
         };
 
         must_ok(
@@ -694,10 +916,10 @@ mod tests {
 
     #[test]
     fn test_export_format_parser_rejects_unsupported() {
-        let result = ExportFormat::try_from("png");
+        let result = ExportFormat::try_from("bmp");
         assert!(matches!(
             result,
-            Err(ExportPipelineError::InvalidOutputFormat(ref fmt)) if fmt == "png"
+            Err(ExportPipelineError::InvalidOutputFormat(ref fmt)) if fmt == "bmp"
         ));
     }
 
@@ -717,6 +939,11 @@ mod tests {
             image_id: 1,
             output_path: output_path.clone(),
             format: ExportFormat::Jpeg,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = must_ok(
@@ -733,6 +960,198 @@ mod tests {
         assert!(exported.get_pixel(0, 0)[0] > 100);
     }
 
+    #[test]
+    fn test_export_pipeline_embeds_catalog_metadata() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("tagged.png");
+        create_source_image(&source_path, [100, 100, 100, 255]);
+        insert_image_with_path(&conn, 10, "hash-metadata", &source_path);
+        must_ok(
+            conn.execute_batch(
+                "UPDATE images SET captured_at = '2026-05-14 18:30:05+00:00' WHERE id = 10;
+                 INSERT INTO exif_metadata (image_id, iso, camera_make, camera_model,
+                                            gps_lat, gps_lon, body_serial)
+                 VALUES (10, 200, 'FUJIFILM', 'X-T5', 48.85, 2.29, 'SN-0042');
+                 INSERT INTO image_state (image_id, rating) VALUES (10, 5);
+                 INSERT INTO tags (id, name, parent_id) VALUES (1, 'France', NULL), (2, 'Paris', 1);
+                 INSERT INTO image_tags (image_id, tag_id) VALUES (10, 2);
+                 INSERT INTO image_iptc (image_id, author, copyright)
+                 VALUES (10, 'Jeanne Durand', '© 2026 Jeanne Durand');",
+            ),
+            "seed catalog metadata",
+        );
+        append_edit_event(
+            &conn,
+            "evt-meta",
+            10,
+            serde_json::json!({ "exposure": 5.0 }),
+        );
+
+        for (format, file_name) in [
+            (ExportFormat::Jpeg, "meta.jpg"),
+            (ExportFormat::Png, "meta.png"),
+            (ExportFormat::Tiff, "meta.tif"),
+        ] {
+            let output_path = temp.path().join(file_name);
+            let request = ExportRequest {
+                image_id: 10,
+                output_path: output_path.clone(),
+                format,
+                resize: ExportResize::Original,
+                options: ExportFormatOptions::default(),
+                metadata: ExportMetadataOptions {
+                    strip_gps: true,
+                    include_history: true,
+                    ..ExportMetadataOptions::default()
+                },
+                watermark: None,
+                target: ExportTarget::Current,
+            };
+            must_ok(
+                export_image_with_edits(&conn, &request),
+                "run metadata export",
+            );
+
+            let bytes = must_ok(std::fs::read(&output_path), "read exported file");
+            let exif = must_ok(
+                exif::Reader::new().read_from_container(&mut std::io::Cursor::new(&bytes)),
+                "parse exported EXIF",
+            );
+            let field = |tag| exif.get_field(tag, exif::In::PRIMARY);
+            assert!(field(exif::Tag::Copyright).is_some(), "{file_name}");
+            assert!(field(exif::Tag::BodySerialNumber).is_some(), "{file_name}");
+            assert!(field(exif::Tag::GPSLatitude).is_none(), "{file_name}");
+            assert!(field(exif::Tag::DateTimeOriginal).is_some(), "{file_name}");
+
+            let text = String::from_utf8_lossy(&bytes);
+            assert!(
+                text.contains("<rdf:li>France/Paris</rdf:li>"),
+                "{file_name}"
+            );
+            assert!(text.contains("<xmp:Rating>5</xmp:Rating>"), "{file_name}");
+            assert!(
+                text.contains("<stEvt:parameters>exposure</stEvt:parameters>"),
+                "{file_name}"
+            );
+            must_ok(image::open(&output_path), "decode exported image");
+        }
+    }
+
+    #[test]
+    fn test_export_pipeline_downscales_to_long_edge() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("wide.png");
+        let output_path = temp.path().join("wide.tiff");
+        let source = RgbaImage::from_pixel(60, 30, image::Rgba([120, 80, 40, 255]));
+        must_ok(source.save(&source_path), "save wide source image");
+        insert_image_with_path(&conn, 4, "hash-long-edge", &source_path);
+
+        let request = ExportRequest {
+            image_id: 4,
+            output_path: output_path.clone(),
+            format: ExportFormat::Tiff,
+            resize: ExportResize::LongEdge { pixels: 20 },
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        let result = must_ok(
+            export_image_with_edits(&conn, &request),
+            "run long-edge export",
+        );
+
+        assert_eq!((result.width, result.height), (20, 10));
+        let exported = must_ok(image::open(&output_path), "open exported tiff").to_rgba8();
+        assert_eq!(exported.dimensions(), (20, 10));
+        assert_eq!(exported.get_pixel(10, 5).0, [120, 80, 40, 255]);
+    }
+
+    #[test]
+    fn test_export_pipeline_never_upscales_to_long_edge() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("small.png");
+        let output_path = temp.path().join("small.tiff");
+        create_source_image(&source_path, [10, 20, 30, 255]);
+        insert_image_with_path(&conn, 5, "hash-no-upscale", &source_path);
+
+        let request = ExportRequest {
+            image_id: 5,
+            output_path,
+            format: ExportFormat::Tiff,
+            resize: ExportResize::LongEdge { pixels: 2048 },
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        let result = must_ok(
+            export_image_with_edits(&conn, &request),
+            "run export without upscaling",
+        );
+
+        assert_eq!((result.width, result.height), (1, 1));
+    }
+
+    #[test]
+    fn test_export_pipeline_replays_retouch_events() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("dust.png");
+        let output_path = temp.path().join("dust.tiff");
+        let mut source = RgbaImage::from_pixel(20, 10, image::Rgba([60, 60, 60, 255]));
+        for y in 0..10 {
+            for x in 10..20 {
+                source.put_pixel(x, y, image::Rgba([180, 180, 180, 255]));
+            }
+        }
+        must_ok(source.save(&source_path), "save retouch source image");
+        insert_image_with_path(&conn, 6, "hash-retouch", &source_path);
+        append_edit_event(
+            &conn,
+            "evt-retouch",
+            6,
+            serde_json::json!({
+                "retouchSpot": {
+                    "mode": "clone",
+                    "target": [{ "x": 0.25, "y": 0.5 }],
+                    "source": { "x": 0.75, "y": 0.5 },
+                    "radius": 0.1
+                }
+            }),
+        );
+
+        let request = ExportRequest {
+            image_id: 6,
+            output_path: output_path.clone(),
+            format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        let result = must_ok(
+            export_image_with_edits(&conn, &request),
+            "run retouch export",
+        );
+
+        assert_eq!(result.applied_edit_events, 1);
+        let exported = must_ok(image::open(&output_path), "open exported tiff").to_rgba8();
+        assert_eq!(exported.get_pixel(5, 5).0, [180, 180, 180, 255]);
+        assert_eq!(exported.get_pixel(0, 0).0, [60, 60, 60, 255]);
+    }
+
     #[test]
     fn test_export_pipeline_uses_snapshot_and_writes_tiff() {
         let conn = setup_test_db();
@@ -782,6 +1201,11 @@ mod tests {
             image_id: 2,
             output_path: output_path.clone(),
             format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = must_ok(
@@ -795,6 +1219,254 @@ mod tests {
         assert_eq!(result.applied_edit_events, 2);
     }
 
+    /// Saves a snapshot of `edits`, composed of the single event `event_id`.
+    fn insert_snapshot(
+        conn: &Connection,
+        image_id: i64,
+        name: &str,
+        event_id: &str,
+        edits: Value,
+        created_at: DateTime<Utc>,
+    ) -> i64 {
+        must_ok(
+            conn.execute(
+                "INSERT INTO edit_snapshots (image_id, name, snapshot_data, event_ids, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    image_id,
+                    name,
+                    serde_json::json!([
+                        { "id": event_id, "eventType": "edit_applied", "payload": { "edits": edits } }
+                    ])
+                    .to_string(),
+                    serde_json::json!([event_id]).to_string(),
+                    created_at.to_rfc3339(),
+                ],
+            ),
+            "insert snapshot",
+        );
+        conn.last_insert_rowid()
+    }
+
+    /// Image 12: "Client v1" at t0 (exposure 10), "Client v2" at t2
+    /// (exposure 20), with edits at t1 and t3 after each of them.
+    fn setup_snapshot_history(conn: &Connection, source_path: &Path) -> (DateTime<Utc>, i64) {
+        let t0 = must_ok(
+            DateTime::parse_from_rfc3339("2026-03-01T10:00:00Z"),
+            "parse base time",
+        )
+        .with_timezone(&Utc);
+        let minutes = |n: i64| t0 + chrono::Duration::minutes(n);
+
+        create_source_image(source_path, [100, 100, 100, 255]);
+        insert_image_with_path(conn, 12, "hash-snapshots", source_path);
+
+        append_edit_event_at(
+            conn,
+            "evt-v1",
+            12,
+            serde_json::json!({ "exposure": 10.0 }),
+            t0,
+        );
+        insert_snapshot(
+            conn,
+            12,
+            "Client v1",
+            "evt-v1",
+            serde_json::json!({ "exposure": 10.0 }),
+            t0,
+        );
+        append_edit_event_at(
+            conn,
+            "evt-contrast",
+            12,
+            serde_json::json!({ "contrast": 15.0 }),
+            minutes(10),
+        );
+        append_edit_event_at(
+            conn,
+            "evt-v2",
+            12,
+            serde_json::json!({ "exposure": 20.0 }),
+            minutes(20),
+        );
+        let v2 = insert_snapshot(
+            conn,
+            12,
+            "Client v2",
+            "evt-v2",
+            serde_json::json!({ "exposure": 20.0 }),
+            minutes(20),
+        );
+        append_edit_event_at(
+            conn,
+            "evt-late",
+            12,
+            serde_json::json!({ "exposure": 35.0 }),
+            minutes(30),
+        );
+
+        (t0, v2)
+    }
+
+    #[test]
+    fn test_export_named_snapshot_ignores_later_edits() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+        let (_, v2) = setup_snapshot_history(&conn, &temp.path().join("snap.png"));
+
+        let target = ExportTarget::Snapshot { snapshot_id: v2 };
+        let resolved = must_ok(
+            resolve_recipe_from_history(&conn, 12, &target),
+            "resolve snapshot recipe",
+        );
+        assert_eq!(resolved.recipe.adjustments.exposure, 20.0);
+        assert_eq!(resolved.applied_edit_events, 1);
+
+        let request = ExportRequest {
+            image_id: 12,
+            output_path: temp.path().join("client-v2.png"),
+            format: ExportFormat::Png,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target,
+        };
+        let result = must_ok(
+            export_image_with_edits(&conn, &request),
+            "export named snapshot",
+        );
+
+        assert!(result.used_snapshot);
+        assert_eq!(
+            result.edit_state,
+            ExportEditState {
+                target,
+                snapshot_id: Some(v2),
+                snapshot_name: Some("Client v2".to_string()),
+                last_event_at: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_export_at_timestamp_replays_edits_up_to_that_point() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+        let (t0, _) = setup_snapshot_history(&conn, &temp.path().join("at.png"));
+        let at = t0 + chrono::Duration::minutes(15);
+
+        let resolved = must_ok(
+            resolve_recipe_from_history(&conn, 12, &ExportTarget::At { at }),
+            "resolve recipe at timestamp",
+        );
+
+        assert_eq!(resolved.recipe.adjustments.exposure, 10.0);
+        assert_eq!(resolved.recipe.adjustments.contrast, 15.0);
+        assert_eq!(
+            resolved.edit_state.snapshot_name.as_deref(),
+            Some("Client v1")
+        );
+        assert_eq!(
+            resolved.edit_state.last_event_at,
+            Some(t0 + chrono::Duration::minutes(10))
+        );
+
+        let current = must_ok(
+            resolve_recipe_from_history(&conn, 12, &ExportTarget::Current),
+            "resolve current recipe",
+        );
+        assert_eq!(current.recipe.adjustments.exposure, 35.0);
+        assert_eq!(
+            current.edit_state.snapshot_name.as_deref(),
+            Some("Client v2")
+        );
+    }
+
+    #[test]
+    fn test_export_rejects_snapshot_of_another_image() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+        let (t0, v2) = setup_snapshot_history(&conn, &temp.path().join("own.png"));
+        let foreign = insert_snapshot(
+            &conn,
+            13,
+            "Other image",
+            "evt-foreign",
+            serde_json::json!({ "exposure": 50.0 }),
+            t0,
+        );
+
+        for snapshot_id in [foreign, v2 + 100] {
+            let result =
+                resolve_recipe_from_history(&conn, 12, &ExportTarget::Snapshot { snapshot_id });
+            assert!(matches!(
+                result,
+                Err(ExportPipelineError::SnapshotNotFound { image_id: 12, snapshot_id: id })
+                    if id == snapshot_id
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolve_edit_recipe_merges_events_into_pinned_process_version() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+        let source_path = temp.path().join("recipe.png");
+
+        create_source_image(&source_path, [90, 90, 90, 255]);
+        insert_image_with_path(&conn, 8, "hash-recipe", &source_path);
+        append_edit_event(&conn, "evt-r1", 8, serde_json::json!({ "exposure": 10.0 }));
+        append_edit_event(&conn, "evt-r2", 8, serde_json::json!({ "temp": 6100.0 }));
+
+        let recipe = must_ok(resolve_edit_recipe(&conn, 8), "resolve edit recipe");
+
+        assert_eq!(
+            recipe.process_version,
+            luminafast_image_core::CURRENT_PROCESS_VERSION
+        );
+        assert_eq!(recipe.adjustments.exposure, 10.0);
+        assert_eq!(recipe.adjustments.color_temp, 6100.0);
+    }
+
+    #[test]
+    fn test_export_pipeline_rejects_unsupported_pinned_process_version() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+        let source_path = temp.path().join("future.png");
+
+        create_source_image(&source_path, [90, 90, 90, 255]);
+        insert_image_with_path(&conn, 9, "hash-future", &source_path);
+        must_ok(
+            conn.execute(
+                "INSERT INTO image_process_versions (image_id, process_version) VALUES (9, 99)",
+                [],
+            ),
+            "pin future process version",
+        );
+
+        let request = ExportRequest {
+            image_id: 9,
+            output_path: temp.path().join("future.jpg"),
+            format: ExportFormat::Jpeg,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        let result = export_image_with_edits(&conn, &request);
+
+        assert!(matches!(
+            result,
+            Err(ExportPipelineError::Processing(
+                ProcessingError::UnsupportedProcessVersion { version: 99 }
+            ))
+        ));
+    }
+
     #[test]
     fn test_export_pipeline_raw_pilot_writes_tiff_with_mock_decoder() {
         let conn = setup_test_db();
@@ -816,6 +1488,11 @@ mod tests {
             image_id: 3,
             output_path: output_path.clone(),
             format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = must_ok(
@@ -829,6 +1506,76 @@ mod tests {
         assert_eq!(result.height, 1);
     }
 
+    #[test]
+    fn test_export_raw_pilot_16_bit_tiff_keeps_linear_precision() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("pilot.dng");
+        let output_path = temp.path().join("export-raw-16.tif");
+        create_mock_raw_file(&source_path);
+        insert_image_with_path(&conn, 4, "hash-raw-16", &source_path);
+
+        let request = ExportRequest {
+            image_id: 4,
+            output_path: output_path.clone(),
+            format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions {
+                bit_depth: 16,
+                tiff_compression: TiffCompression::Lzw,
+                ..ExportFormatOptions::default()
+            },
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        must_ok(
+            export_image_with_edits_internal(&conn, &request, true, &MockPilotRawDecoder),
+            "run 16-bit raw export pipeline",
+        );
+
+        let exported = must_ok(image::open(&output_path), "open exported 16-bit tiff");
+        assert_eq!(exported.color(), image::ColorType::Rgb16);
+        // 0.2 and 0.7 fall between 8-bit levels; the 16-bit file keeps them.
+        assert_eq!(
+            exported.to_rgb16().into_raw(),
+            vec![13_107, 13_107, 13_107, 45_875, 45_875, 45_875]
+        );
+    }
+
+    #[test]
+    fn test_export_pipeline_rejects_16_bit_jpeg_before_decoding() {
+        let conn = setup_test_db();
+        let temp = must_ok(tempdir(), "create temp directory");
+
+        let source_path = temp.path().join("pilot.raf");
+        create_mock_raw_file(&source_path);
+        insert_image_with_path(&conn, 5, "hash-raw-jpeg16", &source_path);
+
+        let request = ExportRequest {
+            image_id: 5,
+            output_path: temp.path().join("export.jpg"),
+            format: ExportFormat::Jpeg,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions {
+                bit_depth: 16,
+                ..ExportFormatOptions::default()
+            },
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
+        };
+
+        let result =
+            export_image_with_edits_internal(&conn, &request, false, &MockFailingRawDecoder);
+        assert!(matches!(
+            result,
+            Err(ExportPipelineError::InvalidFormatOptions(_))
+        ));
+    }
+
     #[test]
     fn test_export_raw_pipeline_rejects_non_raw_source() {
         let conn = setup_test_db();
@@ -844,6 +1591,11 @@ mod tests {
             image_id: 4,
             output_path,
             format: ExportFormat::Jpeg,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = export_image_with_edits_internal(&conn, &request, true, &MockPilotRawDecoder);
@@ -869,6 +1621,11 @@ mod tests {
             image_id: 5,
             output_path,
             format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = export_image_with_edits_internal(&conn, &request, false, &MockPilotRawDecoder);
@@ -894,6 +1651,11 @@ mod tests {
             image_id: 6,
             output_path,
             format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result =
@@ -945,6 +1707,11 @@ mod tests {
             image_id: 7,
             output_path: output_path.clone(),
             format: ExportFormat::Tiff,
+            resize: ExportResize::Original,
+            options: ExportFormatOptions::default(),
+            metadata: ExportMetadataOptions::default(),
+            watermark: None,
+            target: ExportTarget::Current,
         };
 
         let result = export_raw_image_with_edits(&conn, &request);
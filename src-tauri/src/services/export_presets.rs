@@ -0,0 +1,525 @@
+//! Named export presets persisted in the catalog.
+//!
+//! A preset stores everything an export needs except the images: format,
+//! sizing, encoder quality, colour space, metadata options, watermark and
+//! where and how files are named. The colour space setting only accepts sRGB
+//! for now, as rendering is sRGB end to end. Presets travel between
+//! workstations as a versioned JSON document; importing a preset whose name
+//! already exists replaces it.
+
+use crate::services::export_batch::{parse_file_name_template, DEFAULT_FILE_NAME_TEMPLATE};
+use crate::services::export_encoding::ExportFormatOptions;
+use crate::services::export_metadata::ExportMetadataOptions;
+use crate::services::export_pipeline::{ExportFormat, ExportPipelineError};
+use crate::services::export_sizing::ExportResize;
+use crate::services::export_watermark::Watermark;
+use crate::services::naming::{CollisionPolicy, NamingOptions};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Version of the shared preset JSON document.
+pub const PRESET_FILE_VERSION: u32 = 1;
+
+const MAX_PRESET_NAME_LEN: usize = 255;
+
+#[derive(Debug, Error)]
+pub enum ExportPresetError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Invalid preset JSON: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Export preset {0} not found")]
+    NotFound(i64),
+
+    #[error("Export preset named '{0}' already exists")]
+    DuplicateName(String),
+
+    #[error("Invalid export preset name: {0}")]
+    InvalidName(String),
+
+    #[error("Invalid export preset '{name}': {source}")]
+    InvalidSettings {
+        name: String,
+        source: ExportPipelineError,
+    },
+
+    #[error("Unsupported preset file version {0} (expected {PRESET_FILE_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// Colour space of exported pixels. Rendering is sRGB end to end, so it is
+/// the only value accepted for now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportColorSpace {
+    #[default]
+    Srgb,
+}
+
+/// Everything a preset fixes about an export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPresetSettings {
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub resize: ExportResize,
+    /// Quality, bit depth, compression and DPI.
+    #[serde(default)]
+    pub options: ExportFormatOptions,
+    #[serde(default)]
+    pub metadata: ExportMetadataOptions,
+    /// Always sRGB until another colour space is implemented.
+    #[serde(default)]
+    pub color_space: ExportColorSpace,
+    /// Text or logo drawn after resizing.
+    #[serde(default)]
+    pub watermark: Option<Watermark>,
+    /// Used when the export call does not give a directory.
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    /// Output path under the directory, see `services::naming`.
+    #[serde(default = "default_file_name_template")]
+    pub file_name_template: String,
+    /// Value of the `{text}` token.
+    #[serde(default)]
+    pub custom_text: String,
+    /// `{seq}` of the first image.
+    #[serde(default = "default_sequence_start")]
+    pub sequence_start: u32,
+    #[serde(default)]
+    pub collision: CollisionPolicy,
+}
+
+fn default_file_name_template() -> String {
+    DEFAULT_FILE_NAME_TEMPLATE.to_string()
+}
+
+fn default_sequence_start() -> u32 {
+    1
+}
+
+impl ExportPresetSettings {
+    pub fn validate(&self) -> Result<(), ExportPipelineError> {
+        self.options.validate(self.format)?;
+        self.resize.validate()?;
+        if let Some(watermark) = &self.watermark {
+            watermark.validate()?;
+        }
+        parse_file_name_template(&self.file_name_template)?;
+        Ok(())
+    }
+
+    pub fn naming(&self) -> NamingOptions {
+        NamingOptions {
+            template: self.file_name_template.clone(),
+            custom_text: self.custom_text.clone(),
+            sequence_start: self.sequence_start,
+            collision: self.collision,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPreset {
+    pub id: i64,
+    pub name: String,
+    pub settings: ExportPresetSettings,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// A preset as shared between catalogs, without its local ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedExportPreset {
+    pub name: String,
+    pub settings: ExportPresetSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportPresetFile {
+    version: u32,
+    presets: Vec<SharedExportPreset>,
+}
+
+/// All presets, by name.
+pub fn list_export_presets(conn: &Connection) -> Result<Vec<ExportPreset>, ExportPresetError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, settings, created_at, updated_at
+         FROM export_presets
+         ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt
+        .query_map([], read_preset_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter().map(parse_preset_row).collect()
+}
+
+pub fn get_export_preset(conn: &Connection, id: i64) -> Result<ExportPreset, ExportPresetError> {
+    let row = conn
+        .query_row(
+            "SELECT id, name, settings, created_at, updated_at
+             FROM export_presets
+             WHERE id = ?1",
+            [id],
+            read_preset_row,
+        )
+        .optional()?
+        .ok_or(ExportPresetError::NotFound(id))?;
+    parse_preset_row(row)
+}
+
+pub fn create_export_preset(
+    conn: &Connection,
+    name: &str,
+    settings: &ExportPresetSettings,
+) -> Result<ExportPreset, ExportPresetError> {
+    let name = validate_preset(name, settings)?;
+    let result = conn.execute(
+        "INSERT INTO export_presets (name, settings) VALUES (?1, ?2)",
+        params![name, serde_json::to_string(settings)?],
+    );
+    map_duplicate_name(result, &name)?;
+    get_export_preset(conn, conn.last_insert_rowid())
+}
+
+pub fn update_export_preset(
+    conn: &Connection,
+    id: i64,
+    name: &str,
+    settings: &ExportPresetSettings,
+) -> Result<ExportPreset, ExportPresetError> {
+    let name = validate_preset(name, settings)?;
+    let result = conn.execute(
+        "UPDATE export_presets
+         SET name = ?1, settings = ?2, updated_at = datetime('now')
+         WHERE id = ?3",
+        params![name, serde_json::to_string(settings)?, id],
+    );
+    if map_duplicate_name(result, &name)? == 0 {
+        return Err(ExportPresetError::NotFound(id));
+    }
+    get_export_preset(conn, id)
+}
+
+pub fn delete_export_preset(conn: &Connection, id: i64) -> Result<(), ExportPresetError> {
+    if conn.execute("DELETE FROM export_presets WHERE id = ?1", [id])? == 0 {
+        return Err(ExportPresetError::NotFound(id));
+    }
+    Ok(())
+}
+
+/// Serializes the presets `ids` (all of them when `None`) as a shareable
+/// JSON document.
+pub fn export_presets_to_json(
+    conn: &Connection,
+    ids: Option<&[i64]>,
+) -> Result<String, ExportPresetError> {
+    let presets = match ids {
+        Some(ids) => ids
+            .iter()
+            .map(|&id| get_export_preset(conn, id))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => list_export_presets(conn)?,
+    };
+
+    let file = ExportPresetFile {
+        version: PRESET_FILE_VERSION,
+        presets: presets
+            .into_iter()
+            .map(|preset| SharedExportPreset {
+                name: preset.name,
+                settings: preset.settings,
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+/// Imports a document written by `export_presets_to_json`. Every preset is
+/// validated before any is written; existing presets with the same name are
+/// replaced.
+pub fn import_presets_from_json(
+    conn: &Connection,
+    json: &str,
+) -> Result<Vec<ExportPreset>, ExportPresetError> {
+    let file: ExportPresetFile = serde_json::from_str(json)?;
+    if file.version != PRESET_FILE_VERSION {
+        return Err(ExportPresetError::UnsupportedVersion(file.version));
+    }
+
+    let presets = file
+        .presets
+        .iter()
+        .map(|preset| Ok((validate_preset(&preset.name, &preset.settings)?, preset)))
+        .collect::<Result<Vec<_>, ExportPresetError>>()?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut ids = Vec::with_capacity(presets.len());
+    for (name, preset) in presets {
+        let id = tx.query_row(
+            "INSERT INTO export_presets (name, settings) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE
+             SET settings = excluded.settings, updated_at = datetime('now')
+             RETURNING id",
+            params![name, serde_json::to_string(&preset.settings)?],
+            |row| row.get::<_, i64>(0),
+        )?;
+        ids.push(id);
+    }
+    tx.commit()?;
+
+    ids.into_iter()
+        .map(|id| get_export_preset(conn, id))
+        .collect()
+}
+
+/// Returns the trimmed name once name and settings are valid.
+fn validate_preset(
+    name: &str,
+    settings: &ExportPresetSettings,
+) -> Result<String, ExportPresetError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ExportPresetError::InvalidName("name is empty".to_string()));
+    }
+    if name.chars().count() > MAX_PRESET_NAME_LEN {
+        return Err(ExportPresetError::InvalidName(format!(
+            "name is longer than {MAX_PRESET_NAME_LEN} characters"
+        )));
+    }
+
+    settings
+        .validate()
+        .map_err(|source| ExportPresetError::InvalidSettings {
+            name: name.to_string(),
+            source,
+        })?;
+    Ok(name.to_string())
+}
+
+fn map_duplicate_name(
+    result: rusqlite::Result<usize>,
+    name: &str,
+) -> Result<usize, ExportPresetError> {
+    match result {
+        Err(rusqlite::Error::SqliteFailure(error, _))
+            if error.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            Err(ExportPresetError::DuplicateName(name.to_string()))
+        }
+        other => Ok(other?),
+    }
+}
+
+type PresetRow = (i64, String, String, String, String);
+
+fn read_preset_row(row: &Row<'_>) -> rusqlite::Result<PresetRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn parse_preset_row(
+    (id, name, settings, created_at, updated_at): PresetRow,
+) -> Result<ExportPreset, ExportPresetError> {
+    Ok(ExportPreset {
+        id,
+        name,
+        settings: serde_json::from_str(&settings)?,
+        created_at,
+        updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::export_encoding::ChromaSubsampling;
+    use crate::services::export_watermark::{WatermarkAnchor, WatermarkContent, WatermarkOffset};
+
+    fn setup_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("../../migrations/016_export_presets.sql"))
+            .unwrap();
+        conn
+    }
+
+    fn web_settings() -> ExportPresetSettings {
+        ExportPresetSettings {
+            format: ExportFormat::Jpeg,
+            resize: ExportResize::LongEdge { pixels: 2048 },
+            options: ExportFormatOptions {
+                quality: Some(82),
+                chroma_subsampling: ChromaSubsampling::Yuv420,
+                ..ExportFormatOptions::default()
+            },
+            metadata: ExportMetadataOptions {
+                strip_gps: true,
+                ..ExportMetadataOptions::default()
+            },
+            color_space: ExportColorSpace::Srgb,
+            watermark: Some(Watermark {
+                content: WatermarkContent::Text {
+                    text: "© Studio".to_string(),
+                    font_path: "/fonts/Studio.ttf".into(),
+                    size: 0.04,
+                    color: [255, 255, 255],
+                },
+                opacity: 0.6,
+                anchor: WatermarkAnchor::BottomRight,
+                offset: WatermarkOffset { x: 0.02, y: 0.02 },
+            }),
+            output_dir: Some("/exports/web".to_string()),
+            file_name_template: "{filename}-web".to_string(),
+            custom_text: String::new(),
+            sequence_start: 1,
+            collision: CollisionPolicy::Overwrite,
+        }
+    }
+
+    #[test]
+    fn crud_round_trips_settings() {
+        let conn = setup_db();
+
+        let created = create_export_preset(&conn, "  Web 2048 ", &web_settings()).unwrap();
+        assert_eq!(created.name, "Web 2048");
+        assert_eq!(created.settings, web_settings());
+
+        let mut print = web_settings();
+        print.format = ExportFormat::Tiff;
+        print.options = ExportFormatOptions {
+            bit_depth: 16,
+            dpi: Some(300),
+            ..ExportFormatOptions::default()
+        };
+        let updated = update_export_preset(&conn, created.id, "Print", &print).unwrap();
+        assert_eq!(updated.name, "Print");
+        assert_eq!(
+            get_export_preset(&conn, created.id).unwrap().settings,
+            print
+        );
+
+        create_export_preset(&conn, "archive", &web_settings()).unwrap();
+        let names: Vec<String> = list_export_presets(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|preset| preset.name)
+            .collect();
+        assert_eq!(names, vec!["archive", "Print"]);
+
+        delete_export_preset(&conn, created.id).unwrap();
+        assert!(matches!(
+            get_export_preset(&conn, created.id),
+            Err(ExportPresetError::NotFound(_))
+        ));
+        assert!(matches!(
+            delete_export_preset(&conn, created.id),
+            Err(ExportPresetError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_names_and_invalid_settings() {
+        let conn = setup_db();
+        create_export_preset(&conn, "Web", &web_settings()).unwrap();
+
+        assert!(matches!(
+            create_export_preset(&conn, "Web", &web_settings()),
+            Err(ExportPresetError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            create_export_preset(&conn, " ", &web_settings()),
+            Err(ExportPresetError::InvalidName(_))
+        ));
+
+        let mut sixteen_bit_jpeg = web_settings();
+        sixteen_bit_jpeg.options.bit_depth = 16;
+        assert!(matches!(
+            create_export_preset(&conn, "Broken", &sixteen_bit_jpeg),
+            Err(ExportPresetError::InvalidSettings { .. })
+        ));
+
+        let mut opaque = web_settings();
+        if let Some(watermark) = opaque.watermark.as_mut() {
+            watermark.opacity = 2.0;
+        }
+        assert!(matches!(
+            create_export_preset(&conn, "Opaque", &opaque),
+            Err(ExportPresetError::InvalidSettings { .. })
+        ));
+
+        let mut escaping = web_settings();
+        escaping.file_name_template = "../{filename}".to_string();
+        assert!(matches!(
+            create_export_preset(&conn, "Escaping", &escaping),
+            Err(ExportPresetError::InvalidSettings { .. })
+        ));
+    }
+
+    #[test]
+    fn json_export_import_round_trip_replaces_by_name() {
+        let source = setup_db();
+        create_export_preset(&source, "Web", &web_settings()).unwrap();
+        let json = export_presets_to_json(&source, None).unwrap();
+
+        let target = setup_db();
+        let mut stale = web_settings();
+        stale.resize = ExportResize::Original;
+        create_export_preset(&target, "Web", &stale).unwrap();
+
+        let imported = import_presets_from_json(&target, &json).unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].settings, web_settings());
+        assert_eq!(list_export_presets(&target).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn import_validates_everything_before_writing() {
+        let conn = setup_db();
+        let json = r#"{
+            "version": 1,
+            "presets": [
+                { "name": "Minimal", "settings": { "format": "png" } },
+                { "name": "Bad", "settings": { "format": "jpeg", "options": { "lossless": true } } }
+            ]
+        }"#;
+
+        assert!(matches!(
+            import_presets_from_json(&conn, json),
+            Err(ExportPresetError::InvalidSettings { .. })
+        ));
+        assert!(list_export_presets(&conn).unwrap().is_empty());
+
+        let minimal = r#"{"version":1,"presets":[{"name":"Minimal","settings":{"format":"png"}}]}"#;
+        let imported = import_presets_from_json(&conn, minimal).unwrap();
+        assert_eq!(imported[0].settings.file_name_template, "{filename}_edited");
+        assert_eq!(imported[0].settings.color_space, ExportColorSpace::Srgb);
+        assert_eq!(
+            imported[0].settings.naming().collision,
+            CollisionPolicy::Suffix
+        );
+        assert_eq!(imported[0].settings.sequence_start, 1);
+
+        assert!(matches!(
+            import_presets_from_json(&conn, r#"{"version":2,"presets":[]}"#),
+            Err(ExportPresetError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            import_presets_from_json(
+                &conn,
+                r#"{"version":1,"presets":[{"name":"P3","settings":{"format":"jpeg","colorSpace":"displayP3"}}]}"#
+            ),
+            Err(ExportPresetError::Serialization(_))
+        ));
+    }
+}
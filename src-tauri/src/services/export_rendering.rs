@@ -3,7 +3,7 @@
 //! This module is the backend entry point for edited export rendering and
 //! delegates pixel algorithms to the shared core crate.
 
-use luminafast_image_core::{apply_filters, PixelFilters, ProcessingError};
+use luminafast_image_core::{apply_filters, EditRecipe, PixelFilters, ProcessingError};
 
 /// Renders an edited RGBA pixel buffer for export using the shared core engine.
 pub fn render_pixels_for_export(
@@ -15,9 +15,38 @@ pub fn render_pixels_for_export(
     apply_filters(pixels, width, height, filters)
 }
 
+/// Renders an RGBA pixel buffer through the full pipeline of an edit recipe
+/// (retouch spots, then global adjustments), as the WASM preview does.
+pub fn render_recipe_for_export(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    recipe: &EditRecipe,
+) -> Result<Vec<u8>, ProcessingError> {
+    let mut result = pixels.to_vec();
+    recipe
+        .build_pipeline()?
+        .execute(&mut result, width, height)?;
+    Ok(result)
+}
+
+/// 16-bit counterpart of `render_recipe_for_export`, used by PNG and TIFF
+/// exports that keep the precision of linear RAW data.
+pub fn render_recipe_rgba16_for_export(
+    pixels: &[u16],
+    width: u32,
+    height: u32,
+    recipe: &EditRecipe,
+) -> Result<Vec<u16>, ProcessingError> {
+    let mut result = pixels.to_vec();
+    recipe.render_rgba16(&mut result, width, height)?;
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_render_pixels_for_export_applies_filters() {
@@ -50,4 +79,38 @@ mod tests {
             })
         ));
     }
+
+    #[test]
+    fn test_render_recipe_for_export_matches_filter_path_without_retouch() {
+        let pixels = vec![90_u8, 120_u8, 150_u8, 255_u8];
+        let recipe = EditRecipe::from_value(json!({ "exposure": 40, "saturation": 20 })).unwrap();
+
+        let via_recipe = render_recipe_for_export(&pixels, 1, 1, &recipe).unwrap();
+        let via_filters =
+            render_pixels_for_export(&pixels, 1, 1, &recipe.to_pixel_filters().unwrap()).unwrap();
+
+        assert_eq!(via_recipe, via_filters);
+    }
+
+    #[test]
+    fn test_render_recipe_for_export_replays_retouch_spots() {
+        let mut pixels = vec![40_u8, 40, 40, 255, 40, 40, 40, 255];
+        pixels.extend_from_slice(&[220, 220, 220, 255, 220, 220, 220, 255]);
+        let recipe = EditRecipe::from_value(json!({
+            "schemaVersion": 1,
+            "processVersion": 1,
+            "retouch": [{
+                "mode": "clone",
+                "target": [{ "x": 0.125, "y": 0.5 }],
+                "source": { "x": 0.625, "y": 0.5 },
+                "radius": 0.1
+            }]
+        }))
+        .unwrap();
+
+        let result = render_recipe_for_export(&pixels, 4, 1, &recipe).unwrap();
+
+        assert_eq!(&result[..4], &[220, 220, 220, 255]);
+        assert_eq!(&result[4..8], &[40, 40, 40, 255]);
+    }
 }
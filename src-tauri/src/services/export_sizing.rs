@@ -0,0 +1,348 @@
+//! Output sizing modes for exports.
+//!
+//! Every mode except `Exact` only ever downscales: a render already smaller
+//! than the requested size is written at its own resolution. `Exact` always
+//! produces the requested pixel size by scaling the render to cover it and
+//! cropping the overflow evenly from both sides, so the image is never
+//! distorted. Both the requested size and that scaled intermediate are capped
+//! at `MAX_EXACT_PIXELS`. Resampling uses the shared linear-light Lanczos3
+//! filter.
+
+use crate::services::export_encoding::ExportPixels;
+use crate::services::export_pipeline::ExportPipelineError;
+use luminafast_image_core::{
+    long_edge_dimensions, resize_rgba16, resize_rgba8, ProcessingError, ResampleFilter,
+};
+use serde::{Deserialize, Serialize};
+
+const EXPORT_RESAMPLE_FILTER: ResampleFilter = ResampleFilter::Lanczos3;
+
+/// Largest pixel count an `Exact` export may request or scale through.
+pub const MAX_EXACT_PIXELS: u64 = 200_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum ExportResize {
+    /// Keep the render resolution.
+    #[default]
+    Original,
+    /// Longest side in pixels.
+    LongEdge { pixels: u32 },
+    /// Shortest side in pixels.
+    ShortEdge { pixels: u32 },
+    /// Total pixel count in millions, aspect ratio preserved.
+    Megapixels { megapixels: f64 },
+    /// Exact output size: scaled to cover, then centre-cropped.
+    Exact { width: u32, height: u32 },
+}
+
+impl ExportResize {
+    pub fn validate(&self) -> Result<(), ExportPipelineError> {
+        let invalid = |width: u32, height: u32| {
+            Err(ExportPipelineError::Processing(
+                ProcessingError::InvalidDimensions { width, height },
+            ))
+        };
+
+        match *self {
+            Self::Original => Ok(()),
+            Self::LongEdge { pixels } | Self::ShortEdge { pixels } if pixels == 0 => {
+                invalid(pixels, pixels)
+            }
+            Self::Megapixels { megapixels } if !megapixels.is_finite() || megapixels <= 0.0 => {
+                Err(ExportPipelineError::InvalidFormatOptions(format!(
+                    "megapixel target {megapixels} must be positive"
+                )))
+            }
+            Self::Exact { width, height } if width == 0 || height == 0 => invalid(width, height),
+            Self::Exact { width, height } if pixel_count(width, height) > MAX_EXACT_PIXELS => {
+                Err(ExportPipelineError::InvalidFormatOptions(format!(
+                    "exact size {width}x{height} exceeds {MAX_EXACT_PIXELS} pixels"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Scaled size of a `width`x`height` render before any crop.
+    fn scaled_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        match *self {
+            Self::Original => (width, height),
+            Self::LongEdge { pixels } => {
+                if width.max(height) <= pixels {
+                    (width, height)
+                } else {
+                    long_edge_dimensions(width, height, pixels)
+                }
+            }
+            Self::ShortEdge { pixels } => {
+                let short = width.min(height);
+                if short <= pixels {
+                    (width, height)
+                } else {
+                    scale_dimensions(width, height, pixels as f64 / short as f64)
+                }
+            }
+            Self::Megapixels { megapixels } => {
+                let target = megapixels * 1_000_000.0;
+                let current = width as f64 * height as f64;
+                if current <= target {
+                    (width, height)
+                } else {
+                    scale_dimensions(width, height, (target / current).sqrt())
+                }
+            }
+            Self::Exact {
+                width: target_width,
+                height: target_height,
+            } => {
+                let scale =
+                    (target_width as f64 / width as f64).max(target_height as f64 / height as f64);
+                let (scaled_width, scaled_height) = scale_dimensions(width, height, scale);
+                (
+                    scaled_width.max(target_width),
+                    scaled_height.max(target_height),
+                )
+            }
+        }
+    }
+
+    /// Final output size of a `width`x`height` render.
+    pub fn output_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        match *self {
+            Self::Exact { width, height } => (width, height),
+            _ => self.scaled_dimensions(width, height),
+        }
+    }
+}
+
+fn pixel_count(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64
+}
+
+fn scale_dimensions(width: u32, height: u32, scale: f64) -> (u32, u32) {
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Applies `resize` to a rendered buffer.
+pub(crate) fn resize_for_export(
+    pixels: ExportPixels,
+    width: u32,
+    height: u32,
+    resize: &ExportResize,
+) -> Result<(ExportPixels, u32, u32), ExportPipelineError> {
+    resize.validate()?;
+
+    let (scaled_width, scaled_height) = resize.scaled_dimensions(width, height);
+    // Covering a very different aspect ratio can scale far past the target.
+    if pixel_count(scaled_width, scaled_height) > MAX_EXACT_PIXELS.max(pixel_count(width, height)) {
+        return Err(ExportPipelineError::InvalidFormatOptions(format!(
+            "{resize:?} needs a {scaled_width}x{scaled_height} intermediate from a \
+             {width}x{height} render, above {MAX_EXACT_PIXELS} pixels"
+        )));
+    }
+    let pixels = if (scaled_width, scaled_height) == (width, height) {
+        pixels
+    } else {
+        match pixels {
+            ExportPixels::Rgba8(pixels) => ExportPixels::Rgba8(resize_rgba8(
+                &pixels,
+                width,
+                height,
+                scaled_width,
+                scaled_height,
+                EXPORT_RESAMPLE_FILTER,
+            )?),
+            ExportPixels::Rgba16(pixels) => ExportPixels::Rgba16(resize_rgba16(
+                &pixels,
+                width,
+                height,
+                scaled_width,
+                scaled_height,
+                EXPORT_RESAMPLE_FILTER,
+            )?),
+        }
+    };
+
+    let (output_width, output_height) = resize.output_dimensions(width, height);
+    if (output_width, output_height) == (scaled_width, scaled_height) {
+        return Ok((pixels, output_width, output_height));
+    }
+
+    let x0 = (scaled_width - output_width) / 2;
+    let y0 = (scaled_height - output_height) / 2;
+    let origin = (x0, y0);
+    let size = (output_width, output_height);
+    let cropped = match pixels {
+        ExportPixels::Rgba8(samples) => {
+            ExportPixels::Rgba8(crop_rgba(&samples, scaled_width, origin, size))
+        }
+        ExportPixels::Rgba16(samples) => {
+            ExportPixels::Rgba16(crop_rgba(&samples, scaled_width, origin, size))
+        }
+    };
+    Ok((cropped, output_width, output_height))
+}
+
+fn crop_rgba<T: Copy>(
+    samples: &[T],
+    width: u32,
+    (x0, y0): (u32, u32),
+    (crop_width, crop_height): (u32, u32),
+) -> Vec<T> {
+    let row_len = width as usize * 4;
+    let start = x0 as usize * 4;
+    let end = start + crop_width as usize * 4;
+    samples
+        .chunks_exact(row_len)
+        .skip(y0 as usize)
+        .take(crop_height as usize)
+        .flat_map(|row| row[start..end].iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(width: u32, height: u32) -> ExportPixels {
+        ExportPixels::Rgba8(
+            (0..width * height)
+                .flat_map(|index| [(index % 256) as u8, (index / 256) as u8, 0, 255])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn modes_compute_expected_dimensions() {
+        let cases = [
+            (ExportResize::Original, (6000, 4000)),
+            (ExportResize::LongEdge { pixels: 2048 }, (2048, 1365)),
+            (ExportResize::ShortEdge { pixels: 1080 }, (1620, 1080)),
+            (ExportResize::Megapixels { megapixels: 6.0 }, (3000, 2000)),
+            (
+                ExportResize::Exact {
+                    width: 1000,
+                    height: 1000,
+                },
+                (1000, 1000),
+            ),
+        ];
+
+        for (resize, expected) in cases {
+            assert_eq!(resize.output_dimensions(6000, 4000), expected, "{resize:?}");
+        }
+    }
+
+    #[test]
+    fn only_exact_mode_upscales() {
+        for resize in [
+            ExportResize::LongEdge { pixels: 4000 },
+            ExportResize::ShortEdge { pixels: 4000 },
+            ExportResize::Megapixels { megapixels: 50.0 },
+        ] {
+            assert_eq!(resize.output_dimensions(300, 200), (300, 200), "{resize:?}");
+        }
+
+        let exact = ExportResize::Exact {
+            width: 600,
+            height: 600,
+        };
+        assert_eq!(exact.scaled_dimensions(300, 200), (900, 600));
+        assert_eq!(exact.output_dimensions(300, 200), (600, 600));
+    }
+
+    #[test]
+    fn exact_mode_crops_the_centre_without_resampling_when_sizes_match() {
+        let (pixels, width, height) = resize_for_export(
+            numbered(6, 2),
+            6,
+            2,
+            &ExportResize::Exact {
+                width: 2,
+                height: 2,
+            },
+        )
+        .unwrap();
+
+        assert_eq!((width, height), (2, 2));
+        let ExportPixels::Rgba8(samples) = pixels else {
+            panic!("8-bit input must stay 8-bit");
+        };
+        let columns: Vec<u8> = samples.chunks_exact(4).map(|px| px[0]).collect();
+        assert_eq!(columns, vec![2, 3, 8, 9]);
+    }
+
+    #[test]
+    fn resize_keeps_16_bit_precision() {
+        let pixels = ExportPixels::Rgba16([12_345_u16, 40_001, 777, 65_535].repeat(64));
+
+        let (resized, width, height) =
+            resize_for_export(pixels, 16, 4, &ExportResize::ShortEdge { pixels: 2 }).unwrap();
+
+        assert_eq!((width, height), (8, 2));
+        let ExportPixels::Rgba16(samples) = resized else {
+            panic!("16-bit input must stay 16-bit");
+        };
+        assert!(samples[0].abs_diff(12_345) <= 2);
+    }
+
+    #[test]
+    fn invalid_targets_are_rejected() {
+        for resize in [
+            ExportResize::LongEdge { pixels: 0 },
+            ExportResize::ShortEdge { pixels: 0 },
+            ExportResize::Megapixels { megapixels: 0.0 },
+            ExportResize::Megapixels {
+                megapixels: f64::NAN,
+            },
+            ExportResize::Exact {
+                width: 0,
+                height: 10,
+            },
+            ExportResize::Exact {
+                width: 100_000,
+                height: 100_000,
+            },
+        ] {
+            assert!(resize.validate().is_err(), "{resize:?}");
+        }
+    }
+
+    #[test]
+    fn exact_mode_rejects_oversized_intermediates() {
+        let exact = ExportResize::Exact {
+            width: 1,
+            height: 60_000,
+        };
+        assert!(exact.validate().is_ok());
+
+        // Covering 1x60000 from 6x4 would scale the render to 90000x60000.
+        let result = resize_for_export(numbered(6, 4), 6, 4, &exact);
+
+        assert!(matches!(
+            result,
+            Err(ExportPipelineError::InvalidFormatOptions(_))
+        ));
+    }
+
+    #[test]
+    fn resize_deserializes_from_tagged_json() {
+        let resize: ExportResize =
+            serde_json::from_str(r#"{"mode":"shortEdge","pixels":1080}"#).unwrap();
+        assert_eq!(resize, ExportResize::ShortEdge { pixels: 1080 });
+
+        let resize: ExportResize =
+            serde_json::from_str(r#"{"mode":"exact","width":1200,"height":800}"#).unwrap();
+        assert_eq!(
+            resize,
+            ExportResize::Exact {
+                width: 1200,
+                height: 800
+            }
+        );
+    }
+}
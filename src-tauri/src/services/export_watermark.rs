@@ -0,0 +1,595 @@
+//! Watermark step of the export pipeline, applied after resizing.
+//!
+//! A watermark is either a line of text drawn with a TrueType/OpenType font
+//! file or a logo image composited with its own alpha. Sizes and offsets are
+//! relative to the output, so a preset gives the same look at any export
+//! size. Rendering only depends on the inputs: glyph coverage and logo
+//! resampling have no randomness and blending is done in a fixed order, so
+//! the same export always produces the same pixels.
+
+use crate::services::export_encoding::ExportPixels;
+use crate::services::export_pipeline::ExportPipelineError;
+use ab_glyph::{point, Font, FontVec, Glyph, PxScale, Rect, ScaleFont};
+use luminafast_image_core::{resize_rgba8, ResampleFilter};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    #[serde(flatten)]
+    pub content: WatermarkContent,
+    /// 0.0 (invisible) to 1.0, multiplied with the text coverage or logo alpha.
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub anchor: WatermarkAnchor,
+    /// Inward distance from the anchor, in fractions of the shorter output side.
+    #[serde(default)]
+    pub offset: WatermarkOffset,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WatermarkContent {
+    /// A single line of text.
+    #[serde(rename_all = "camelCase")]
+    Text {
+        text: String,
+        font_path: PathBuf,
+        /// Text height in fractions of the shorter output side.
+        #[serde(default = "default_text_size")]
+        size: f32,
+        /// sRGB colour of the text.
+        #[serde(default = "default_text_color")]
+        color: [u8; 3],
+    },
+    /// A logo, normally a PNG with transparency.
+    #[serde(rename_all = "camelCase")]
+    Image {
+        path: PathBuf,
+        /// Logo width in fractions of the output width, aspect ratio preserved.
+        /// A logo that would be taller than the output is shrunk to fit it.
+        #[serde(default = "default_logo_scale")]
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatermarkAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WatermarkOffset {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Default for WatermarkOffset {
+    fn default() -> Self {
+        Self { x: 0.02, y: 0.02 }
+    }
+}
+
+fn default_opacity() -> f32 {
+    0.5
+}
+
+fn default_text_size() -> f32 {
+    0.05
+}
+
+fn default_text_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_logo_scale() -> f32 {
+    0.2
+}
+
+impl Watermark {
+    /// Checks the numeric settings; font and logo files are read at export.
+    pub fn validate(&self) -> Result<(), ExportPipelineError> {
+        let invalid = |reason: String| Err(ExportPipelineError::InvalidWatermark(reason));
+        let is_fraction = |value: f32| value.is_finite() && value > 0.0 && value <= 1.0;
+
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return invalid(format!("opacity {} must be within 0-1", self.opacity));
+        }
+        if ![self.offset.x, self.offset.y]
+            .iter()
+            .all(|value| value.is_finite() && value.abs() <= 1.0)
+        {
+            return invalid("offsets must be within -1 to 1".to_string());
+        }
+        match &self.content {
+            WatermarkContent::Text { text, .. } if text.trim().is_empty() => {
+                invalid("text is empty".to_string())
+            }
+            WatermarkContent::Text { size, .. } if !is_fraction(*size) => {
+                invalid(format!("text size {size} must be within 0-1"))
+            }
+            WatermarkContent::Image { scale, .. } if !is_fraction(*scale) => {
+                invalid(format!("logo scale {scale} must be within 0-1"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Straight-alpha RGBA8 overlay, before opacity.
+struct Overlay {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// Draws `watermark` onto a `width`x`height` output buffer.
+pub(crate) fn apply_watermark(
+    pixels: &mut ExportPixels,
+    width: u32,
+    height: u32,
+    watermark: &Watermark,
+) -> Result<(), ExportPipelineError> {
+    watermark.validate()?;
+    let short_side = width.min(height) as f32;
+
+    let overlay = match &watermark.content {
+        WatermarkContent::Text {
+            text,
+            font_path,
+            size,
+            color,
+        } => render_text(
+            text,
+            font_path,
+            (size * short_side).max(1.0),
+            *color,
+            (width, height),
+        )?,
+        WatermarkContent::Image { path, scale } => render_logo(path, *scale, (width, height))?,
+    };
+    let Some(overlay) = overlay else {
+        return Ok(());
+    };
+
+    let offset_x = (watermark.offset.x * short_side).round() as i64;
+    let offset_y = (watermark.offset.y * short_side).round() as i64;
+    let (x, y) = anchor_position(
+        watermark.anchor,
+        (width, height),
+        (overlay.width, overlay.height),
+        (offset_x, offset_y),
+    );
+    composite(pixels, width, height, &overlay, x, y, watermark.opacity);
+    Ok(())
+}
+
+/// Top-left corner of the overlay; offsets always point into the image.
+fn anchor_position(
+    anchor: WatermarkAnchor,
+    (width, height): (u32, u32),
+    (overlay_width, overlay_height): (u32, u32),
+    (offset_x, offset_y): (i64, i64),
+) -> (i64, i64) {
+    use WatermarkAnchor::*;
+
+    let free_x = width as i64 - overlay_width as i64;
+    let free_y = height as i64 - overlay_height as i64;
+    let x = match anchor {
+        TopLeft | Left | BottomLeft => offset_x,
+        Top | Center | Bottom => free_x / 2 + offset_x,
+        TopRight | Right | BottomRight => free_x - offset_x,
+    };
+    let y = match anchor {
+        TopLeft | Top | TopRight => offset_y,
+        Left | Center | Right => free_y / 2 + offset_y,
+        BottomLeft | Bottom | BottomRight => free_y - offset_y,
+    };
+    (x, y)
+}
+
+/// Rasterises `text` on one line; `None` when it has no visible glyph.
+///
+/// The overlay never exceeds `max_size`: text running past the output is cut
+/// instead of being rasterised into an unbounded buffer.
+fn render_text(
+    text: &str,
+    font_path: &Path,
+    pixel_height: f32,
+    color: [u8; 3],
+    (max_width, max_height): (u32, u32),
+) -> Result<Option<Overlay>, ExportPipelineError> {
+    let unreadable = |reason: String| {
+        ExportPipelineError::InvalidWatermark(format!("font {}: {reason}", font_path.display()))
+    };
+    let data = fs::read(font_path).map_err(|e| unreadable(e.to_string()))?;
+    let font = FontVec::try_from_vec(data).map_err(|e| unreadable(e.to_string()))?;
+    let scale = PxScale::from(pixel_height);
+    let scaled = font.as_scaled(scale);
+
+    let mut glyphs: Vec<Glyph> = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for character in text.chars().filter(|character| !character.is_control()) {
+        if caret > max_width as f32 {
+            break;
+        }
+        let id = font.glyph_id(character);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(caret, scaled.ascent())));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    let outlined: Vec<_> = glyphs
+        .into_iter()
+        .filter_map(|glyph| font.outline_glyph(glyph))
+        .collect();
+    let Some(bounds) = outlined
+        .iter()
+        .map(|glyph| glyph.px_bounds())
+        .reduce(|a, b| Rect {
+            min: point(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+            max: point(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+        })
+    else {
+        return Ok(None);
+    };
+
+    let width = ((bounds.max.x - bounds.min.x).ceil() as u32).min(max_width);
+    let height = ((bounds.max.y - bounds.min.y).ceil() as u32).min(max_height);
+    let mut coverage = vec![0.0f32; width as usize * height as usize];
+    for glyph in &outlined {
+        let glyph_bounds = glyph.px_bounds();
+        let left = (glyph_bounds.min.x - bounds.min.x) as u32;
+        let top = (glyph_bounds.min.y - bounds.min.y) as u32;
+        glyph.draw(|x, y, value| {
+            let (x, y) = (left + x, top + y);
+            if x < width && y < height {
+                let cell = &mut coverage[(y * width + x) as usize];
+                *cell = cell.max(value);
+            }
+        });
+    }
+
+    let pixels = coverage
+        .into_iter()
+        .flat_map(|value| {
+            let alpha = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            [color[0], color[1], color[2], alpha]
+        })
+        .collect();
+    Ok(Some(Overlay {
+        width,
+        height,
+        pixels,
+    }))
+}
+
+/// Decodes the logo and scales it to `scale` of the output width.
+fn render_logo(
+    path: &Path,
+    scale: f32,
+    (output_width, output_height): (u32, u32),
+) -> Result<Option<Overlay>, ExportPipelineError> {
+    let logo = image::open(path)
+        .map_err(|e| {
+            ExportPipelineError::InvalidWatermark(format!("logo {}: {e}", path.display()))
+        })?
+        .to_rgba8();
+    let (logo_width, logo_height) = logo.dimensions();
+
+    // Scaled from the output width, then fitted inside the output so a tall
+    // logo never resamples into a buffer larger than the image.
+    let target_width = (output_width as f64 * scale as f64).max(1.0);
+    let target_height = target_width * logo_height as f64 / logo_width as f64;
+    let fit = (output_width as f64 / target_width)
+        .min(output_height as f64 / target_height)
+        .min(1.0);
+    let width = ((target_width * fit).round() as u32).clamp(1, output_width.max(1));
+    let height = ((target_height * fit).round() as u32).clamp(1, output_height.max(1));
+    let pixels = if (width, height) == (logo_width, logo_height) {
+        logo.into_raw()
+    } else {
+        resize_rgba8(
+            logo.as_raw(),
+            logo_width,
+            logo_height,
+            width,
+            height,
+            ResampleFilter::Lanczos3,
+        )?
+    };
+    Ok(Some(Overlay {
+        width,
+        height,
+        pixels,
+    }))
+}
+
+/// Blends `overlay` with its top-left corner at (`x`, `y`), clipped to the image.
+fn composite(
+    pixels: &mut ExportPixels,
+    width: u32,
+    height: u32,
+    overlay: &Overlay,
+    x: i64,
+    y: i64,
+    opacity: f32,
+) {
+    for overlay_y in 0..overlay.height {
+        let target_y = y + overlay_y as i64;
+        if target_y < 0 || target_y >= height as i64 {
+            continue;
+        }
+        for overlay_x in 0..overlay.width {
+            let target_x = x + overlay_x as i64;
+            if target_x < 0 || target_x >= width as i64 {
+                continue;
+            }
+
+            let source = ((overlay_y * overlay.width + overlay_x) * 4) as usize;
+            let source = &overlay.pixels[source..source + 4];
+            let alpha = source[3] as f32 / 255.0 * opacity;
+            if alpha <= 0.0 {
+                continue;
+            }
+            let target = ((target_y as u64 * width as u64 + target_x as u64) * 4) as usize;
+
+            match pixels {
+                ExportPixels::Rgba8(pixels) => {
+                    blend(&mut pixels[target..target + 4], source, alpha, 255.0)
+                }
+                ExportPixels::Rgba16(pixels) => {
+                    blend(&mut pixels[target..target + 4], source, alpha, 65535.0)
+                }
+            }
+        }
+    }
+}
+
+/// Source-over blend of an 8-bit overlay sample onto a sample of `max` range.
+fn blend<T>(target: &mut [T], source: &[u8], alpha: f32, max: f32)
+where
+    T: Copy + Into<f32> + FromF32,
+{
+    for channel in 0..3 {
+        let overlay = source[channel] as f32 / 255.0 * max;
+        let base: f32 = target[channel].into();
+        target[channel] = T::from_f32(overlay * alpha + base * (1.0 - alpha));
+    }
+    let base_alpha: f32 = target[3].into();
+    target[3] = T::from_f32(alpha * max + base_alpha * (1.0 - alpha));
+}
+
+trait FromF32 {
+    fn from_f32(value: f32) -> Self;
+}
+
+impl FromF32 for u8 {
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, 255.0) as u8
+    }
+}
+
+impl FromF32 for u16 {
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, 65535.0) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+    use tempfile::TempDir;
+
+    fn fixture_font() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/services/tests/fixtures/block5x7.ttf")
+    }
+
+    fn text_watermark(text: &str) -> Watermark {
+        Watermark {
+            content: WatermarkContent::Text {
+                text: text.to_string(),
+                font_path: fixture_font(),
+                size: 0.2,
+                color: [255, 255, 255],
+            },
+            opacity: 0.8,
+            anchor: WatermarkAnchor::Center,
+            offset: WatermarkOffset::default(),
+        }
+    }
+
+    fn grey(width: u32, height: u32) -> ExportPixels {
+        ExportPixels::Rgba8(vec![128; width as usize * height as usize * 4])
+    }
+
+    fn rgba8(pixels: &ExportPixels) -> &[u8] {
+        match pixels {
+            ExportPixels::Rgba8(pixels) => pixels,
+            ExportPixels::Rgba16(_) => panic!("expected 8-bit pixels"),
+        }
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * width + x) * 4) as usize;
+        [
+            pixels[index],
+            pixels[index + 1],
+            pixels[index + 2],
+            pixels[index + 3],
+        ]
+    }
+
+    fn logo_watermark(dir: &Path) -> Watermark {
+        let path = dir.join("logo.png");
+        let mut logo = RgbaImage::from_pixel(10, 5, Rgba([255, 0, 0, 255]));
+        logo.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        logo.save(&path).unwrap();
+        Watermark {
+            content: WatermarkContent::Image { path, scale: 0.25 },
+            opacity: 1.0,
+            anchor: WatermarkAnchor::TopLeft,
+            offset: WatermarkOffset { x: 0.1, y: 0.1 },
+        }
+    }
+
+    #[test]
+    fn logo_is_scaled_anchored_and_keeps_its_alpha() {
+        let dir = TempDir::new().unwrap();
+        let watermark = logo_watermark(dir.path());
+        let mut pixels = grey(40, 30);
+
+        apply_watermark(&mut pixels, 40, 30, &watermark).unwrap();
+
+        // 10 px wide logo (0.25 x 40), 3 px margin (0.1 x 30).
+        let pixels = rgba8(&pixels);
+        assert_eq!(pixel(pixels, 40, 8, 4), [255, 0, 0, 255]);
+        assert_eq!(pixel(pixels, 40, 2, 4), [128; 4]);
+        assert_eq!(pixel(pixels, 40, 13, 4), [128; 4]);
+        assert_eq!(pixel(pixels, 40, 20, 20), [128; 4]);
+    }
+
+    #[test]
+    fn opacity_and_anchor_place_a_half_transparent_logo() {
+        let dir = TempDir::new().unwrap();
+        let mut watermark = logo_watermark(dir.path());
+        watermark.opacity = 0.5;
+        watermark.anchor = WatermarkAnchor::BottomRight;
+        watermark.offset = WatermarkOffset { x: 0.0, y: 0.0 };
+        let mut pixels = ExportPixels::Rgba16(vec![0; 40 * 30 * 4]);
+
+        apply_watermark(&mut pixels, 40, 30, &watermark).unwrap();
+
+        let ExportPixels::Rgba16(pixels) = pixels else {
+            panic!("expected 16-bit pixels");
+        };
+        let corner = ((29 * 40 + 39) * 4) as usize;
+        assert_eq!(&pixels[corner..corner + 4], &[32768, 0, 0, 32768]);
+        assert_eq!(&pixels[0..4], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn text_rendering_is_deterministic() {
+        let watermark = text_watermark("© LuminaFast");
+
+        let mut first = grey(200, 100);
+        let mut second = grey(200, 100);
+        apply_watermark(&mut first, 200, 100, &watermark).unwrap();
+        apply_watermark(&mut second, 200, 100, &watermark).unwrap();
+
+        assert_eq!(first, second);
+        let changed = rgba8(&first).chunks(4).filter(|p| p[0] > 128).count();
+        assert!(changed > 100, "only {changed} pixels changed");
+        assert_eq!(pixel(rgba8(&first), 200, 0, 0), [128; 4]);
+        // Pixel checksum of the fixture font render: any change in layout,
+        // rasterisation or blending shows up here.
+        assert_eq!(
+            blake3::hash(rgba8(&first)).to_hex().as_str(),
+            "1ede253980e8b2887a0556a094ab9896e4e6124b382bc8bfd44ba6d326d0b652"
+        );
+    }
+
+    #[test]
+    fn long_text_overlay_is_clamped_to_the_output() {
+        let watermark = text_watermark(&"LUMINAFAST ".repeat(10_000));
+
+        let overlay = match &watermark.content {
+            WatermarkContent::Text {
+                text,
+                font_path,
+                color,
+                ..
+            } => render_text(text, font_path, 20.0, *color, (200, 100))
+                .unwrap()
+                .expect("visible glyphs"),
+            WatermarkContent::Image { .. } => unreachable!(),
+        };
+        assert_eq!(overlay.width, 200);
+        assert!(overlay.height <= 100);
+        assert_eq!(overlay.pixels.len(), 200 * overlay.height as usize * 4);
+
+        let mut pixels = grey(200, 100);
+        apply_watermark(&mut pixels, 200, 100, &watermark).unwrap();
+        assert!(rgba8(&pixels).chunks(4).any(|p| p[0] > 128));
+    }
+
+    #[test]
+    fn tall_logo_overlay_is_fitted_inside_the_output() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tall.png");
+        RgbaImage::from_pixel(5, 100, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        // Half the width of 40 px would make the logo 400 px tall.
+        let overlay = render_logo(&path, 0.5, (40, 30))
+            .unwrap()
+            .expect("logo overlay");
+
+        assert_eq!((overlay.width, overlay.height), (2, 30));
+        assert_eq!(overlay.pixels.len(), 2 * 30 * 4);
+    }
+
+    #[test]
+    fn invalid_settings_and_missing_files_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let mut watermark = logo_watermark(dir.path());
+        watermark.opacity = 1.5;
+        assert!(matches!(
+            watermark.validate(),
+            Err(ExportPipelineError::InvalidWatermark(_))
+        ));
+
+        let text = Watermark {
+            content: WatermarkContent::Text {
+                text: "x".to_string(),
+                font_path: dir.path().join("missing.ttf"),
+                size: 0.1,
+                color: [0, 0, 0],
+            },
+            opacity: 1.0,
+            anchor: WatermarkAnchor::default(),
+            offset: WatermarkOffset::default(),
+        };
+        assert!(matches!(
+            apply_watermark(&mut grey(4, 4), 4, 4, &text),
+            Err(ExportPipelineError::InvalidWatermark(_))
+        ));
+    }
+
+    #[test]
+    fn deserializes_with_defaults() {
+        let watermark: Watermark =
+            serde_json::from_str(r#"{"kind":"text","text":"Studio","fontPath":"/fonts/a.ttf"}"#)
+                .unwrap();
+        assert_eq!(watermark.opacity, 0.5);
+        assert_eq!(watermark.anchor, WatermarkAnchor::BottomRight);
+        assert_eq!(
+            watermark.content,
+            WatermarkContent::Text {
+                text: "Studio".to_string(),
+                font_path: PathBuf::from("/fonts/a.ttf"),
+                size: 0.05,
+                color: [255, 255, 255],
+            }
+        );
+    }
+}
@@ -0,0 +1,615 @@
+//! HDR merge of bracketed exposures.
+//!
+//! Each bracket is decoded to linear RGB through `RawDecoder`, tagged with
+//! the relative exposure derived from its `exif_metadata` row, and merged by
+//! `luminafast_image_core::merge_exposures` (translation alignment + weighted
+//! radiance). The radiance map is written as an uncompressed 32-bit float
+//! linear DNG or TIFF; the command layer then ingests it and links it to its
+//! sources through `derived_images`.
+
+use crate::models::discovery::RawFormat;
+use crate::services::export_pipeline::{
+    is_known_raw_extension, resolve_source_image_path, source_extension_or_unknown,
+    ExportPipelineError, RsRawDecoder,
+};
+use luminafast_image_core::{
+    merge_exposures, relative_exposure, HdrFrame, LinearImage, ProcessingError, RawDecoder,
+};
+use rusqlite::{Connection, OptionalExtension};
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// ISO assumed for brackets whose metadata has no ISO (brackets share ISO).
+const DEFAULT_BRACKET_ISO: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrOutputFormat {
+    Dng,
+    Tiff,
+}
+
+impl HdrOutputFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dng => "dng",
+            Self::Tiff => "tiff",
+        }
+    }
+
+    /// Catalog format the merged file is ingested as.
+    pub fn raw_format(self) -> RawFormat {
+        match self {
+            Self::Dng => RawFormat::DNG,
+            Self::Tiff => RawFormat::TIFF,
+        }
+    }
+}
+
+impl TryFrom<&str> for HdrOutputFormat {
+    type Error = HdrMergeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "dng" => Ok(Self::Dng),
+            "tiff" | "tif" => Ok(Self::Tiff),
+            unsupported => Err(HdrMergeError::InvalidOutputFormat(unsupported.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HdrMergeRequest {
+    /// Bracketed frames, in any order.
+    pub image_ids: Vec<i64>,
+    pub output_path: PathBuf,
+    pub format: HdrOutputFormat,
+    /// Alignment search radius in pixels; 0 disables alignment.
+    pub max_shift: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HdrMergeResult {
+    pub output_path: PathBuf,
+    pub format: HdrOutputFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Frame the others were aligned to; the output is at its exposure.
+    pub reference_image_id: i64,
+    /// `(dx, dy)` alignment of each frame, in `image_ids` order.
+    pub offsets: Vec<(i32, i32)>,
+}
+
+#[derive(Debug, Error)]
+pub enum HdrMergeError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Source error: {0}")]
+    Source(#[from] ExportPipelineError),
+
+    #[error("Image processing error: {0}")]
+    Processing(#[from] ProcessingError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unsupported HDR output format: {0}")]
+    InvalidOutputFormat(String),
+
+    #[error("HDR merge requires at least 2 distinct images, got {0}")]
+    NotEnoughFrames(usize),
+
+    #[error("Image {0} is not a RAW file (extension: {1})")]
+    RawSourceRequired(i64, String),
+
+    #[error("Image {0} has no shutter speed or aperture metadata")]
+    ExposureMetadataMissing(i64),
+}
+
+/// Catalog-side inputs of an HDR merge: each bracket's file and exposure.
+///
+/// Built under the database lock by [`prepare_hdr_merge`], then merged by
+/// [`render_hdr_merge`] without touching the catalog.
+#[derive(Debug, Clone)]
+pub struct PreparedHdrMerge {
+    sources: Vec<(PathBuf, f32)>,
+}
+
+/// Validates the request and resolves every bracket's path and exposure.
+pub fn prepare_hdr_merge(
+    conn: &Connection,
+    request: &HdrMergeRequest,
+) -> Result<PreparedHdrMerge, HdrMergeError> {
+    let mut distinct = request.image_ids.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if distinct.len() < 2 || distinct.len() != request.image_ids.len() {
+        return Err(HdrMergeError::NotEnoughFrames(distinct.len()));
+    }
+
+    // Validate every source before decoding anything.
+    let mut sources = Vec::with_capacity(request.image_ids.len());
+    for &image_id in &request.image_ids {
+        let path = resolve_source_image_path(conn, image_id)?;
+        if !is_known_raw_extension(&path) {
+            return Err(HdrMergeError::RawSourceRequired(
+                image_id,
+                source_extension_or_unknown(&path),
+            ));
+        }
+        sources.push((path, load_frame_exposure(conn, image_id)?));
+    }
+
+    Ok(PreparedHdrMerge { sources })
+}
+
+/// Decodes, aligns and merges prepared brackets, then writes the HDR file.
+pub fn render_hdr_merge(
+    prepared: PreparedHdrMerge,
+    request: &HdrMergeRequest,
+) -> Result<HdrMergeResult, HdrMergeError> {
+    render_hdr_merge_internal(prepared, request, &RsRawDecoder)
+}
+
+#[cfg(test)]
+fn merge_hdr_brackets_internal(
+    conn: &Connection,
+    request: &HdrMergeRequest,
+    raw_decoder: &dyn RawDecoder,
+) -> Result<HdrMergeResult, HdrMergeError> {
+    let prepared = prepare_hdr_merge(conn, request)?;
+    render_hdr_merge_internal(prepared, request, raw_decoder)
+}
+
+fn render_hdr_merge_internal(
+    prepared: PreparedHdrMerge,
+    request: &HdrMergeRequest,
+    raw_decoder: &dyn RawDecoder,
+) -> Result<HdrMergeResult, HdrMergeError> {
+    let frames = prepared
+        .sources
+        .into_iter()
+        .map(|(path, exposure)| {
+            let bytes = fs::read(&path)?;
+            let image = raw_decoder.decode_to_linear_rgb(&bytes)?;
+            Ok(HdrFrame::new(image, exposure)?)
+        })
+        .collect::<Result<Vec<_>, HdrMergeError>>()?;
+
+    let merged = merge_exposures(&frames, request.max_shift)?;
+    fs::write(
+        &request.output_path,
+        encode_float_image(&merged.image, request.format),
+    )?;
+
+    Ok(HdrMergeResult {
+        output_path: request.output_path.clone(),
+        format: request.format,
+        width: merged.image.width,
+        height: merged.image.height,
+        reference_image_id: request.image_ids[merged.reference_index],
+        offsets: merged.offsets,
+    })
+}
+
+/// Relative exposure of a catalog image from its EXIF row.
+///
+/// `exif_metadata.shutter_speed` stores log2(seconds).
+fn load_frame_exposure(conn: &Connection, image_id: i64) -> Result<f32, HdrMergeError> {
+    let row = conn
+        .query_row(
+            "SELECT shutter_speed, aperture, iso FROM exif_metadata WHERE image_id = ?1",
+            [image_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<f64>>(0)?,
+                    row.get::<_, Option<f64>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    let Some((Some(shutter_log2), Some(aperture), iso)) = row else {
+        return Err(HdrMergeError::ExposureMetadataMissing(image_id));
+    };
+
+    let iso = iso
+        .filter(|iso| *iso > 0)
+        .map(|iso| iso as f32)
+        .unwrap_or(DEFAULT_BRACKET_ISO);
+    relative_exposure(2f64.powf(shutter_log2) as f32, aperture as f32, iso)
+        .map_err(|_| HdrMergeError::ExposureMetadataMissing(image_id))
+}
+
+// TIFF field types.
+const TYPE_BYTE: u16 = 1;
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+const TYPE_SRATIONAL: u16 = 10;
+
+const PHOTOMETRIC_RGB: u16 = 2;
+const PHOTOMETRIC_LINEAR_RAW: u16 = 34892;
+const SAMPLE_FORMAT_IEEE_FLOAT: u16 = 3;
+const ILLUMINANT_D65: u16 = 21;
+const SOFTWARE: &str = "LuminaFast";
+
+/// XYZ (D65) to linear sRGB, the colour space `RawDecoder` output is in.
+const XYZ_TO_LINEAR_SRGB: [f64; 9] = [
+    3.2406, -1.5372, -0.4986, -0.9689, 1.8758, 0.0415, 0.0557, -0.2040, 1.0570,
+];
+
+struct IfdEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    data: Vec<u8>,
+}
+
+impl IfdEntry {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        Self {
+            tag,
+            field_type: TYPE_SHORT,
+            count: values.len() as u32,
+            data: values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    fn long(tag: u16, value: u32) -> Self {
+        Self {
+            tag,
+            field_type: TYPE_LONG,
+            count: 1,
+            data: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn bytes(tag: u16, values: &[u8]) -> Self {
+        Self {
+            tag,
+            field_type: TYPE_BYTE,
+            count: values.len() as u32,
+            data: values.to_vec(),
+        }
+    }
+
+    fn ascii(tag: u16, value: &str) -> Self {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        Self {
+            tag,
+            field_type: TYPE_ASCII,
+            count: data.len() as u32,
+            data,
+        }
+    }
+
+    fn rationals(tag: u16, values: &[(u32, u32)]) -> Self {
+        Self {
+            tag,
+            field_type: TYPE_RATIONAL,
+            count: values.len() as u32,
+            data: values
+                .iter()
+                .flat_map(|(num, den)| num.to_le_bytes().into_iter().chain(den.to_le_bytes()))
+                .collect(),
+        }
+    }
+
+    fn srationals(tag: u16, values: &[f64]) -> Self {
+        const DENOMINATOR: i32 = 10_000;
+        Self {
+            tag,
+            field_type: TYPE_SRATIONAL,
+            count: values.len() as u32,
+            data: values
+                .iter()
+                .flat_map(|value| {
+                    let numerator = (value * DENOMINATOR as f64).round() as i32;
+                    numerator
+                        .to_le_bytes()
+                        .into_iter()
+                        .chain(DENOMINATOR.to_le_bytes())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Encodes a linear image as a little-endian, single-strip, 32-bit float
+/// RGB TIFF, or a DNG 1.4 LinearRaw when `format` is `Dng`.
+pub fn encode_float_image(image: &LinearImage, format: HdrOutputFormat) -> Vec<u8> {
+    const HEADER_LEN: u32 = 8;
+    let strip: Vec<u8> = image
+        .pixels_rgb_f32
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+    let strip_len = strip.len() as u32;
+
+    let photometric = match format {
+        HdrOutputFormat::Dng => PHOTOMETRIC_LINEAR_RAW,
+        HdrOutputFormat::Tiff => PHOTOMETRIC_RGB,
+    };
+    let mut entries = vec![
+        IfdEntry::long(254, 0),
+        IfdEntry::long(256, image.width),
+        IfdEntry::long(257, image.height),
+        IfdEntry::shorts(258, &[32, 32, 32]),
+        IfdEntry::shorts(259, &[1]),
+        IfdEntry::shorts(262, &[photometric]),
+        IfdEntry::long(273, HEADER_LEN),
+        IfdEntry::shorts(274, &[1]),
+        IfdEntry::shorts(277, &[3]),
+        IfdEntry::long(278, image.height),
+        IfdEntry::long(279, strip_len),
+        IfdEntry::shorts(284, &[1]),
+        IfdEntry::ascii(305, SOFTWARE),
+        IfdEntry::shorts(339, &[SAMPLE_FORMAT_IEEE_FLOAT; 3]),
+    ];
+    if format == HdrOutputFormat::Dng {
+        entries.extend([
+            IfdEntry::bytes(50706, &[1, 4, 0, 0]),
+            IfdEntry::bytes(50707, &[1, 4, 0, 0]),
+            IfdEntry::ascii(50708, SOFTWARE),
+            IfdEntry::srationals(50721, &XYZ_TO_LINEAR_SRGB),
+            IfdEntry::rationals(50728, &[(1, 1), (1, 1), (1, 1)]),
+            IfdEntry::shorts(50778, &[ILLUMINANT_D65]),
+        ]);
+    }
+
+    let ifd_offset = HEADER_LEN + strip_len;
+    let ifd_len = 2 + entries.len() as u32 * 12 + 4;
+    let mut overflow_offset = ifd_offset + ifd_len;
+
+    let mut ifd = Vec::with_capacity(ifd_len as usize);
+    let mut overflow = Vec::new();
+    ifd.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for entry in &entries {
+        ifd.extend_from_slice(&entry.tag.to_le_bytes());
+        ifd.extend_from_slice(&entry.field_type.to_le_bytes());
+        ifd.extend_from_slice(&entry.count.to_le_bytes());
+        if entry.data.len() <= 4 {
+            let mut inline = [0_u8; 4];
+            inline[..entry.data.len()].copy_from_slice(&entry.data);
+            ifd.extend_from_slice(&inline);
+        } else {
+            ifd.extend_from_slice(&overflow_offset.to_le_bytes());
+            overflow.extend_from_slice(&entry.data);
+            if overflow.len() % 2 == 1 {
+                overflow.push(0);
+            }
+            overflow_offset = ifd_offset + ifd_len + overflow.len() as u32;
+        }
+    }
+    ifd.extend_from_slice(&0_u32.to_le_bytes());
+
+    let mut output = Vec::with_capacity((ifd_offset + ifd_len) as usize + overflow.len());
+    output.extend_from_slice(b"II");
+    output.extend_from_slice(&42_u16.to_le_bytes());
+    output.extend_from_slice(&ifd_offset.to_le_bytes());
+    output.extend_from_slice(&strip);
+    output.extend_from_slice(&ifd);
+    output.extend_from_slice(&overflow);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    /// Decodes the fake RAW written by `write_frame`: a 1-byte brightness
+    /// level scaled over a fixed gradient scene.
+    struct MockBracketDecoder;
+
+    impl RawDecoder for MockBracketDecoder {
+        fn decode_to_linear_rgb(&self, input: &[u8]) -> Result<LinearImage, ProcessingError> {
+            let exposure = input[0] as f32 / 16.0;
+            let mut pixels = Vec::new();
+            for y in 0..24 {
+                for x in 0..32 {
+                    let scene = 0.05 + 0.02 * x as f32 + 0.01 * y as f32;
+                    let value = (scene * exposure).min(1.0);
+                    pixels.extend_from_slice(&[value, value, value]);
+                }
+            }
+            LinearImage::new(32, 24, pixels)
+        }
+    }
+
+    fn setup_db(dir: &TempDir, frames: &[(i64, &str, u8, Option<f64>)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE images (id INTEGER PRIMARY KEY, blake3_hash TEXT NOT NULL);
+            CREATE TABLE ingestion_file_status (
+                id INTEGER PRIMARY KEY,
+                blake3_hash TEXT,
+                file_path TEXT NOT NULL
+            );
+            CREATE TABLE exif_metadata (
+                image_id INTEGER PRIMARY KEY,
+                iso INTEGER,
+                aperture REAL,
+                shutter_speed REAL
+            );
+        "#,
+        )
+        .unwrap();
+
+        for &(id, extension, level, shutter_log2) in frames {
+            let path = dir.path().join(format!("frame_{id}.{extension}"));
+            fs::write(&path, [level]).unwrap();
+            conn.execute(
+                "INSERT INTO images (id, blake3_hash) VALUES (?1, ?2)",
+                rusqlite::params![id, format!("hash-{id}")],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO ingestion_file_status (blake3_hash, file_path) VALUES (?1, ?2)",
+                rusqlite::params![format!("hash-{id}"), path.to_string_lossy()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO exif_metadata (image_id, iso, aperture, shutter_speed)
+                 VALUES (?1, 100, 8.0, ?2)",
+                rusqlite::params![id, shutter_log2],
+            )
+            .unwrap();
+        }
+
+        conn
+    }
+
+    fn request(dir: &TempDir, image_ids: Vec<i64>, format: HdrOutputFormat) -> HdrMergeRequest {
+        HdrMergeRequest {
+            image_ids,
+            output_path: dir.path().join(format!("merged.{}", format.as_str())),
+            format,
+            max_shift: 0,
+        }
+    }
+
+    /// Minimal reader for the tags written by `encode_float_image`.
+    fn read_ifd(bytes: &[u8]) -> HashMap<u16, (u16, u32, u32)> {
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let ifd = u32_at(4) as usize;
+        (0..u16_at(ifd) as usize)
+            .map(|index| {
+                let entry = ifd + 2 + index * 12;
+                (
+                    u16_at(entry),
+                    (u16_at(entry + 2), u32_at(entry + 4), u32_at(entry + 8)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_float_tiff_round_trips_through_image_decoder() {
+        let image = LinearImage::new(2, 1, vec![0.25, 0.5, 1.0, 2.5, 4.0, 0.0]).unwrap();
+
+        let bytes = encode_float_image(&image, HdrOutputFormat::Tiff);
+        let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::Tiff)
+            .expect("float TIFF should decode")
+            .to_rgb32f();
+
+        assert_eq!(decoded.dimensions(), (2, 1));
+        assert_eq!(decoded.into_raw(), image.pixels_rgb_f32);
+    }
+
+    #[test]
+    fn test_dng_output_declares_linear_raw_and_dng_version() {
+        let image = LinearImage::new(1, 1, vec![1.5, 1.0, 0.5]).unwrap();
+
+        let bytes = encode_float_image(&image, HdrOutputFormat::Dng);
+        let tags = read_ifd(&bytes);
+
+        assert_eq!(&bytes[..4], b"II*\0");
+        assert_eq!(tags[&262].2, PHOTOMETRIC_LINEAR_RAW as u32);
+        assert_eq!(
+            tags[&50706],
+            (TYPE_BYTE, 4, u32::from_le_bytes([1, 4, 0, 0]))
+        );
+        assert_eq!(tags[&50721].1, 9);
+        assert!(tags.contains_key(&50778));
+        let strip = tags[&273].2 as usize;
+        let first = f32::from_le_bytes(bytes[strip..strip + 4].try_into().unwrap());
+        assert_eq!(first, 1.5);
+    }
+
+    #[test]
+    fn test_merge_writes_hdr_at_reference_exposure() {
+        let dir = TempDir::new().unwrap();
+        // Levels 8/16/32 over 16 = exposures 0.5, 1, 2 (shutter 1/2s, 1s, 2s).
+        let conn = setup_db(
+            &dir,
+            &[
+                (1, "arw", 16, Some(0.0)),
+                (2, "arw", 8, Some(-1.0)),
+                (3, "arw", 32, Some(1.0)),
+            ],
+        );
+        let request = request(&dir, vec![1, 2, 3], HdrOutputFormat::Tiff);
+
+        let result = merge_hdr_brackets_internal(&conn, &request, &MockBracketDecoder).unwrap();
+
+        assert_eq!((result.width, result.height), (32, 24));
+        assert_eq!(result.reference_image_id, 1);
+        assert_eq!(result.offsets, vec![(0, 0); 3]);
+
+        let bytes = fs::read(&result.output_path).unwrap();
+        let merged = image::load_from_memory(&bytes).unwrap().to_rgb32f();
+        // Bottom-right of the scene is 0.90: clipped only in the longest frame.
+        let corner = merged.get_pixel(31, 23)[0];
+        assert!((corner - 0.90).abs() < 1e-4, "corner = {corner}");
+    }
+
+    #[test]
+    fn test_merge_rejects_non_raw_and_missing_exposure() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_db(
+            &dir,
+            &[
+                (1, "arw", 16, Some(0.0)),
+                (2, "jpg", 8, Some(-1.0)),
+                (3, "arw", 32, None),
+            ],
+        );
+
+        let non_raw = merge_hdr_brackets_internal(
+            &conn,
+            &request(&dir, vec![1, 2], HdrOutputFormat::Dng),
+            &MockBracketDecoder,
+        );
+        let missing_exposure = merge_hdr_brackets_internal(
+            &conn,
+            &request(&dir, vec![1, 3], HdrOutputFormat::Dng),
+            &MockBracketDecoder,
+        );
+
+        assert!(matches!(non_raw, Err(HdrMergeError::RawSourceRequired(2, ext)) if ext == "jpg"));
+        assert!(matches!(
+            missing_exposure,
+            Err(HdrMergeError::ExposureMetadataMissing(3))
+        ));
+    }
+
+    #[test]
+    fn test_merge_requires_two_distinct_frames() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_db(&dir, &[(1, "arw", 16, Some(0.0))]);
+
+        let result = merge_hdr_brackets_internal(
+            &conn,
+            &request(&dir, vec![1, 1], HdrOutputFormat::Dng),
+            &MockBracketDecoder,
+        );
+
+        assert!(matches!(result, Err(HdrMergeError::NotEnoughFrames(1))));
+    }
+
+    #[test]
+    fn test_output_format_parsing() {
+        assert_eq!(
+            HdrOutputFormat::try_from("DNG").unwrap(),
+            HdrOutputFormat::Dng
+        );
+        assert_eq!(
+            HdrOutputFormat::try_from("tif").unwrap(),
+            HdrOutputFormat::Tiff
+        );
+        assert!(HdrOutputFormat::try_from("jpeg").is_err());
+    }
+}
@@ -1,12 +1,15 @@
 use crate::models::discovery::{
     BasicExif, BatchIngestionRequest, BatchIngestionResult, DiscoveredFile, DiscoveryError,
-    FormatDetails, IngestionMetadata, IngestionProgress, IngestionResult,
+    FormatDetails, ImportCopyOptions, IngestionMetadata, IngestionProgress, IngestionResult,
 };
 use crate::models::exif::ExifMetadata;
 use crate::services::blake3::Blake3Service;
 use crate::services::db_repository::SqliteDbRepository;
 use crate::services::exif;
 use crate::services::metrics::{DefaultMetricsCollector, MetricsCollector};
+use crate::services::naming::{
+    NameAction, NamePlanner, NamePreview, NamingContext, NamingTemplate, PlannedName,
+};
 use crate::types::db_context::{DBContext, DbPoolMetrics, SessionStatsUpdate};
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -30,6 +33,80 @@ async fn ingest_file_internal(
     svc.ingest_file(&file).await
 }
 
+/// Converts requested paths into `DiscoveredFile`s, leaving out unsupported formats
+async fn discovered_files_from_paths(
+    session_id: Uuid,
+    file_paths: &[std::path::PathBuf],
+) -> Result<Vec<DiscoveredFile>, DiscoveryError> {
+    let mut files = Vec::new();
+    for file_path in file_paths {
+        let path = std::path::Path::new(file_path);
+
+        // Get file metadata
+        let metadata = tokio::fs::metadata(path).await.map_err(|e| {
+            DiscoveryError::IoError(format!(
+                "Failed to read metadata for {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let modified_time = metadata
+            .modified()
+            .map_err(|e| DiscoveryError::IoError(e.to_string()))?;
+
+        // Determine format from extension (only supported formats)
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        let format = match extension.as_deref() {
+            // RAW formats
+            Some("cr3") => crate::models::discovery::RawFormat::CR3,
+            Some("cr2") => crate::models::discovery::RawFormat::CR2,
+            Some("nef") => crate::models::discovery::RawFormat::NEF,
+            Some("arw") => crate::models::discovery::RawFormat::ARW,
+            Some("raf") => crate::models::discovery::RawFormat::RAF,
+            Some("orf") => crate::models::discovery::RawFormat::ORF,
+            Some("pef") => crate::models::discovery::RawFormat::PEF,
+            Some("rw2") => crate::models::discovery::RawFormat::RW2,
+            Some("dng") => crate::models::discovery::RawFormat::DNG,
+            // Standard formats
+            Some("jpg") => crate::models::discovery::RawFormat::JPG,
+            Some("jpeg") => crate::models::discovery::RawFormat::JPEG,
+            Some("png") => crate::models::discovery::RawFormat::PNG,
+            Some("webp") => crate::models::discovery::RawFormat::WEBP,
+            Some("tif") | Some("tiff") => crate::models::discovery::RawFormat::TIFF,
+
+            _ => {
+                // Skip unsupported formats
+                continue;
+            }
+        };
+
+        let discovered_file = crate::models::discovery::DiscoveredFile::new(
+            session_id,
+            path.to_path_buf(),
+            format,
+            metadata.len(),
+            chrono::DateTime::<chrono::Utc>::from(modified_time),
+        );
+
+        files.push(discovered_file);
+    }
+    Ok(files)
+}
+
+/// Copy `source` to `destination`, creating missing folders
+async fn copy_file(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::copy(source, destination).await?;
+    Ok(())
+}
+
 /// Service for ingesting discovered RAW files into the catalog
 pub struct IngestionService {
     /// BLAKE3 service for file hashing
@@ -139,7 +216,7 @@ impl IngestionService {
             BasicExif {
                 make: real_exif.camera_make.clone(),
                 model: real_exif.camera_model.clone(),
-                date_taken: None, // TODO: extract from EXIF DateTimeOriginal tag
+                date_taken: real_exif.captured_at.map(|taken| taken.and_utc()),
                 iso: real_exif.iso.map(|i| i as u16),
                 aperture: real_exif.aperture,
                 shutter_speed: real_exif.shutter_speed.map(|s| format!("{:.2}", s)),
@@ -192,68 +269,21 @@ impl IngestionService {
         self.create_ingestion_session(request.session_id).await?;
 
         // Convert file paths to DiscoveredFile objects
-        let mut files_to_process = Vec::new();
-        for file_path in &request.file_paths {
-            let path = std::path::Path::new(file_path);
-
-            // Get file metadata
-            let metadata = tokio::fs::metadata(path).await.map_err(|e| {
-                DiscoveryError::IoError(format!(
-                    "Failed to read metadata for {}: {}",
-                    path.display(),
-                    e
-                ))
-            })?;
-
-            let modified_time = metadata
-                .modified()
-                .map_err(|e| DiscoveryError::IoError(e.to_string()))?;
-
-            // Determine format from extension (only supported formats)
-            let extension = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext.to_lowercase());
-
-            let format = match extension.as_deref() {
-                // RAW formats
-                Some("cr3") => crate::models::discovery::RawFormat::CR3,
-                Some("cr2") => crate::models::discovery::RawFormat::CR2,
-                Some("nef") => crate::models::discovery::RawFormat::NEF,
-                Some("arw") => crate::models::discovery::RawFormat::ARW,
-                Some("raf") => crate::models::discovery::RawFormat::RAF,
-                Some("orf") => crate::models::discovery::RawFormat::ORF,
-                Some("pef") => crate::models::discovery::RawFormat::PEF,
-                Some("rw2") => crate::models::discovery::RawFormat::RW2,
-                Some("dng") => crate::models::discovery::RawFormat::DNG,
-                // Standard formats
-                Some("jpg") => crate::models::discovery::RawFormat::JPG,
-                Some("jpeg") => crate::models::discovery::RawFormat::JPEG,
-                Some("png") => crate::models::discovery::RawFormat::PNG,
-                Some("webp") => crate::models::discovery::RawFormat::WEBP,
-
-                _ => {
-                    // Skip unsupported formats
-                    continue;
-                }
-            };
-
-            let discovered_file = crate::models::discovery::DiscoveredFile::new(
-                request.session_id,
-                path.to_path_buf(),
-                format,
-                metadata.len(),
-                chrono::DateTime::<chrono::Utc>::from(modified_time),
-            );
-
-            files_to_process.push(discovered_file);
-        }
+        let mut files_to_process =
+            discovered_files_from_paths(request.session_id, &request.file_paths).await?;
 
         // Apply max_files limit
         if let Some(max_files) = request.max_files {
             files_to_process.truncate(max_files);
         }
 
+        // Copy into the library folder first; the copies are what gets catalogued
+        if let Some(copy) = &request.copy_to {
+            files_to_process = self
+                .copy_for_import(files_to_process, copy, &mut result)
+                .await?;
+        }
+
         let total_files = files_to_process.len();
 
         // Update session with total files count
@@ -271,8 +301,8 @@ impl IngestionService {
         // Atomic counters for thread-safe progress tracking
         let processed_count = Arc::new(AtomicUsize::new(0));
         let successful_count = Arc::new(AtomicUsize::new(0));
-        let failed_count = Arc::new(AtomicUsize::new(0));
-        let skipped_count = Arc::new(AtomicUsize::new(0));
+        let failed_count = Arc::new(AtomicUsize::new(result.failed.len()));
+        let skipped_count = Arc::new(AtomicUsize::new(result.skipped.len()));
         let total_size = Arc::new(AtomicUsize::new(0));
         let total_processing_time = Arc::new(AtomicUsize::new(0));
 
@@ -501,6 +531,131 @@ impl IngestionService {
         Ok(result)
     }
 
+    /// Resolve where `copy` would put each of `file_paths` without copying anything
+    pub async fn preview_import_names(
+        &self,
+        file_paths: &[std::path::PathBuf],
+        copy: &ImportCopyOptions,
+    ) -> Result<Vec<NamePreview>, DiscoveryError> {
+        let files = discovered_files_from_paths(Uuid::nil(), file_paths).await?;
+        let planned = self.plan_import_copies(&files, copy).await?;
+
+        Ok(files
+            .into_iter()
+            .zip(planned)
+            .map(|(file, planned)| NamePreview {
+                image_id: None,
+                source: file.path.to_string_lossy().to_string(),
+                target: planned.path.to_string_lossy().to_string(),
+                action: planned.action,
+            })
+            .collect())
+    }
+
+    /// Resolve the destination of every file, in order, with the collision policy applied
+    async fn plan_import_copies(
+        &self,
+        files: &[DiscoveredFile],
+        copy: &ImportCopyOptions,
+    ) -> Result<Vec<PlannedName>, DiscoveryError> {
+        let template = NamingTemplate::parse(&copy.naming.template)
+            .map_err(|e| DiscoveryError::ConfigError(e.to_string()))?;
+        let mut planner = NamePlanner::new(&copy.destination_dir, copy.naming.collision);
+
+        let mut planned = Vec::with_capacity(files.len());
+        for (file, sequence) in files.iter().zip(copy.naming.sequence_start..) {
+            let context = self
+                .import_naming_context(file, template.uses_hash())
+                .await?;
+            let relative = template.render(&context, sequence, &copy.naming.custom_text);
+            // Copies keep the source extension as written
+            let extension = file
+                .path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("");
+            planned.push(planner.claim(&relative, extension));
+        }
+        Ok(planned)
+    }
+
+    /// Naming values read from the file itself; the capture date falls back
+    /// to the modification time and the hash is only computed when needed
+    async fn import_naming_context(
+        &self,
+        file: &DiscoveredFile,
+        with_hash: bool,
+    ) -> Result<NamingContext, DiscoveryError> {
+        let exif = exif::extract_exif_metadata(&file.path.to_string_lossy()).ok();
+        let blake3_hash = if with_hash {
+            let hash = self
+                .blake3_service
+                .hash_file(&file.path)
+                .await
+                .map_err(|e| DiscoveryError::IoError(e.to_string()))?;
+            Some(hash.hash)
+        } else {
+            None
+        };
+        let modified_local = file.modified_at.with_timezone(&chrono::Local).naive_local();
+
+        Ok(NamingContext {
+            filename: file.filename.clone(),
+            captured_at: exif
+                .as_ref()
+                .and_then(|exif| exif.captured_at)
+                .or(Some(modified_local)),
+            camera_model: exif.and_then(|exif| exif.camera_model),
+            rating: None,
+            collection: None,
+            blake3_hash,
+        })
+    }
+
+    /// Copy `files` to their planned destination and return the copies to ingest;
+    /// skipped and failed copies are recorded in `result`
+    async fn copy_for_import(
+        &self,
+        files: Vec<DiscoveredFile>,
+        copy: &ImportCopyOptions,
+        result: &mut BatchIngestionResult,
+    ) -> Result<Vec<DiscoveredFile>, DiscoveryError> {
+        let planned = self.plan_import_copies(&files, copy).await?;
+        let mut copies = Vec::with_capacity(files.len());
+
+        for (file, planned) in files.into_iter().zip(planned) {
+            let not_ingested = |file: DiscoveredFile, error: String| IngestionResult {
+                file,
+                success: false,
+                database_id: None,
+                processing_time_ms: 0,
+                error: Some(error),
+                metadata: None,
+            };
+
+            if planned.action == NameAction::Skip {
+                let error = format!("Destination already exists: {}", planned.path.display());
+                result.add_skipped(not_ingested(file, error));
+                continue;
+            }
+
+            match copy_file(&file.path, &planned.path).await {
+                Ok(()) => copies.push(DiscoveredFile::new(
+                    file.session_id,
+                    planned.path,
+                    file.format,
+                    file.size_bytes,
+                    file.modified_at,
+                )),
+                Err(e) => {
+                    let error = format!("Failed to copy to {}: {}", planned.path.display(), e);
+                    result.add_failed(not_ingested(file, error));
+                }
+            }
+        }
+        Ok(copies)
+    }
+
     /// Check if a file already exists in the database
     pub(crate) async fn check_file_exists(
         &self,
@@ -5,10 +5,11 @@
 
 use crate::models::discovery::{
     BatchIngestionRequest, BatchIngestionResult, DiscoveredFile, FileProcessingStatus,
-    IngestionResult, RawFormat,
+    ImportCopyOptions, IngestionResult, RawFormat,
 };
 use crate::services::blake3::Blake3Service;
 use crate::services::ingestion::{IngestionService, IngestionStats};
+use crate::services::naming::{CollisionPolicy, NameAction, NamingOptions};
 use chrono::Utc;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -55,6 +56,8 @@ fn create_test_database() -> Arc<std::sync::Mutex<rusqlite::Connection>> {
             gps_lat REAL,
             gps_lon REAL,
             color_space TEXT,
+            body_serial TEXT,
+            exposure_bias REAL,
             FOREIGN KEY (image_id) REFERENCES images (id)
         );
         CREATE TABLE image_state (
@@ -109,6 +112,7 @@ fn create_test_raw_file(dir: &TempDir, filename: &str, format: RawFormat) -> Pat
         RawFormat::JPG | RawFormat::JPEG => b"\xFF\xD8\xFF\xE0JFIF",
         RawFormat::PNG => b"\x89PNG\r\n\x1a\n",
         RawFormat::WEBP => b"RIFF\x00\x00\x00\x00WEBP",
+        RawFormat::TIFF => b"II*\x00\x08\x00\x00\x00",
     };
 
     std::fs::write(&file_path, content).expect("Failed to write test file");
@@ -281,6 +285,7 @@ async fn test_batch_ingestion() {
         file_paths,
         skip_existing: false,
         max_files: Some(3),
+        copy_to: None,
     };
 
     // Execute batch ingestion
@@ -299,6 +304,71 @@ async fn test_batch_ingestion() {
     assert!(result.avg_processing_time_ms > 0.0);
 }
 
+#[tokio::test]
+async fn test_batch_ingestion_copies_with_naming_template() {
+    let service = create_ingestion_service();
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let library = temp_dir.path().join("library");
+    let file_paths: Vec<PathBuf> = ["a.CR3", "b.CR3", "c.CR3"]
+        .iter()
+        .map(|filename| create_test_raw_file(&temp_dir, filename, RawFormat::CR3))
+        .collect();
+
+    // An earlier import already produced the second name
+    std::fs::create_dir_all(library.join("shoot")).expect("Failed to create library");
+    std::fs::write(library.join("shoot/b_02.CR3"), b"earlier").expect("Failed to write file");
+
+    let mut naming = NamingOptions::new("{text}/{filename}_{seq:2}");
+    naming.custom_text = "shoot".to_string();
+    naming.collision = CollisionPolicy::Skip;
+    let copy_to = ImportCopyOptions {
+        destination_dir: library.clone(),
+        naming,
+    };
+
+    let previews = service
+        .preview_import_names(&file_paths, &copy_to)
+        .await
+        .expect("Preview failed");
+    let planned: Vec<(PathBuf, NameAction)> = previews
+        .iter()
+        .map(|preview| (PathBuf::from(&preview.target), preview.action))
+        .collect();
+    assert_eq!(
+        planned,
+        vec![
+            (library.join("shoot/a_01.CR3"), NameAction::Create),
+            (library.join("shoot/b_02.CR3"), NameAction::Skip),
+            (library.join("shoot/c_03.CR3"), NameAction::Create),
+        ]
+    );
+    assert!(!library.join("shoot/a_01.CR3").exists());
+
+    let request = BatchIngestionRequest {
+        session_id: Uuid::new_v4(),
+        file_paths,
+        skip_existing: false,
+        max_files: None,
+        copy_to: Some(copy_to),
+    };
+    let result = service
+        .batch_ingest(&request, None)
+        .await
+        .expect("Batch ingestion failed");
+
+    assert_eq!(result.successful.len(), 2);
+    assert_eq!(result.skipped.len(), 1);
+    for ingested in &result.successful {
+        assert!(ingested.file.path.starts_with(library.join("shoot")));
+        assert!(ingested.file.path.exists());
+    }
+    assert_eq!(
+        std::fs::read(library.join("shoot/b_02.CR3")).expect("Failed to read file"),
+        b"earlier"
+    );
+    assert!(temp_dir.path().join("a.CR3").exists());
+}
+
 #[tokio::test]
 async fn test_basic_exif_extraction() {
     let service = create_ingestion_service();
@@ -671,6 +741,7 @@ async fn test_batch_ingestion_request_and_result() {
         file_paths: vec![],
         skip_existing: true,
         max_files: Some(10),
+        copy_to: None,
     };
 
     assert_eq!(request.session_id, session_id);
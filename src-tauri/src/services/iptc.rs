@@ -1,9 +1,10 @@
 //! Service IPTC pour extraction des métadonnées
 //! Phase 2.2 - Extraction IPTC : titre, description, copyright, mots-clés
 #![allow(dead_code)] // Implémentation complète prévue en Phase 2.2
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IptcMetadata {
     pub copyright: Option<String>,
     pub keywords: Vec<String>,
@@ -32,6 +33,61 @@ pub fn extract_iptc(_file_path: &str) -> Result<IptcMetadata, String> {
     })
 }
 
+/// Lit les champs IPTC saisis dans le catalogue (table `image_iptc`).
+///
+/// Les mots-clés ne sont pas stockés dans cette table : ce sont les tags de
+/// l'image, `keywords` est donc toujours vide ici.
+pub fn load_image_iptc(conn: &Connection, image_id: i64) -> rusqlite::Result<IptcMetadata> {
+    let row = conn
+        .query_row(
+            "SELECT author, description, copyright FROM image_iptc WHERE image_id = ?1",
+            [image_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let (author, description, copyright) = row.unwrap_or((None, None, None));
+
+    Ok(IptcMetadata {
+        copyright,
+        keywords: vec![],
+        description,
+        author,
+    })
+}
+
+/// Enregistre les champs IPTC d'une image (upsert). Les champs vides sont
+/// stockés à NULL ; `keywords` est ignoré.
+pub fn save_image_iptc(
+    conn: &Connection,
+    image_id: i64,
+    iptc: &IptcMetadata,
+) -> rusqlite::Result<()> {
+    let non_empty = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    conn.execute(
+        "INSERT INTO image_iptc (image_id, author, description, copyright, updated_at)
+         VALUES (?1, ?2, ?3, ?4, datetime('now'))
+         ON CONFLICT(image_id) DO UPDATE SET
+           author = excluded.author,
+           description = excluded.description,
+           copyright = excluded.copyright,
+           updated_at = excluded.updated_at",
+        params![
+            image_id,
+            non_empty(&iptc.author),
+            non_empty(&iptc.description),
+            non_empty(&iptc.copyright)
+        ],
+    )?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,4 +112,32 @@ mod tests {
         let iptc = result.unwrap();
         assert_eq!(iptc.keywords.len(), 0);
     }
+
+    #[test]
+    fn test_save_then_load_image_iptc() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE images (id INTEGER PRIMARY KEY);")
+            .unwrap();
+        conn.execute_batch(include_str!("../../migrations/015_image_iptc.sql"))
+            .unwrap();
+        conn.execute("INSERT INTO images (id) VALUES (1)", [])
+            .unwrap();
+
+        let empty = load_image_iptc(&conn, 1).unwrap();
+        assert_eq!(empty.copyright, None);
+
+        let iptc = IptcMetadata {
+            copyright: Some("© 2026 Jane Doe".to_string()),
+            keywords: vec!["ignored".to_string()],
+            description: Some("  ".to_string()),
+            author: Some("Jane Doe".to_string()),
+        };
+        save_image_iptc(&conn, 1, &iptc).unwrap();
+
+        let loaded = load_image_iptc(&conn, 1).unwrap();
+        assert_eq!(loaded.copyright.as_deref(), Some("© 2026 Jane Doe"));
+        assert_eq!(loaded.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(loaded.description, None);
+        assert!(loaded.keywords.is_empty());
+    }
 }
@@ -1,21 +1,37 @@
 pub mod blake3;
+pub mod color_palette;
 pub mod db_repository;
+pub mod derived_images;
 pub mod discovery;
+pub mod edit_recipe;
 pub mod event_sourcing;
 pub mod exif;
+pub mod export_batch;
+pub mod export_encoding;
+pub mod export_history;
+pub mod export_metadata;
 pub mod export_pipeline;
+pub mod export_presets;
 pub mod export_rendering;
+pub mod export_sizing;
+pub mod export_watermark;
 pub mod filesystem;
+pub mod hdr_merge;
 pub mod ingestion;
 pub mod iptc;
 pub mod metrics;
+pub mod naming;
+pub mod panorama;
+pub mod perceptual_hash;
 pub mod preview;
 pub mod preview_db;
+pub mod quality_metrics;
 pub mod search;
 pub mod security;
 pub mod settings;
 pub mod smart_query_parser;
 pub mod snapshot_service;
+pub mod stacking;
 pub mod xmp;
 
 #[cfg(test)]
@@ -0,0 +1,583 @@
+//! Token-based file and folder naming shared by exports and copying imports.
+//!
+//! A template is a relative path whose `{token}`s are replaced per image, for
+//! example `{yyyy}/{yyyy}-{mm}-{dd}/{camera}_{seq:4}`. The extension is never
+//! part of the template: exports append the output format's, imports keep the
+//! source's. Token values are sanitised so they can never add folders.
+//!
+//! | Token | Value |
+//! |-------|-------|
+//! | `{filename}` | source file name without extension |
+//! | `{yyyy}` `{yy}` `{mm}` `{dd}` `{hh}` `{min}` `{ss}` | capture date and time |
+//! | `{seq}`, `{seq:N}` | sequence number, zero-padded to `N` digits |
+//! | `{camera}` | camera model |
+//! | `{rating}` | star rating, `0` when unrated |
+//! | `{collection}` | collection name |
+//! | `{text}` | custom text of the naming options |
+//! | `{hash}`, `{hash:N}` | first `N` (default 8) hex digits of the BLAKE3 hash |
+//!
+//! Missing values resolve to `unknown`.
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const MISSING_VALUE: &str = "unknown";
+const DEFAULT_HASH_LENGTH: usize = 8;
+const MAX_HASH_LENGTH: usize = 64;
+const MAX_SEQUENCE_DIGITS: usize = 9;
+/// Characters rejected by Windows or macOS file systems in a path segment.
+const FORBIDDEN_CHARACTERS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NamingError {
+    #[error("Naming template is empty")]
+    EmptyTemplate,
+
+    #[error("Naming template must be a relative path without '.' or '..': {0}")]
+    InvalidPath(String),
+
+    #[error("Unknown naming token '{{{0}}}'")]
+    UnknownToken(String),
+
+    #[error("Invalid argument for naming token '{{{0}}}'")]
+    InvalidArgument(String),
+
+    #[error("Unclosed '{{' in naming template: {0}")]
+    UnclosedToken(String),
+}
+
+/// What to do when a resolved name is already taken on disk.
+///
+/// Two images of the same job resolving to the same name never overwrite
+/// each other: the later one always gets a numeric suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// Append `_1`, `_2`, ... until the name is free.
+    #[default]
+    Suffix,
+    Overwrite,
+    /// Leave the existing file and skip the image.
+    Skip,
+}
+
+/// Template plus the job-wide values it refers to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamingOptions {
+    pub template: String,
+    /// Value of `{text}`.
+    #[serde(default)]
+    pub custom_text: String,
+    /// `{seq}` of the first image.
+    #[serde(default = "default_sequence_start")]
+    pub sequence_start: u32,
+    #[serde(default)]
+    pub collision: CollisionPolicy,
+}
+
+fn default_sequence_start() -> u32 {
+    1
+}
+
+impl NamingOptions {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            custom_text: String::new(),
+            sequence_start: default_sequence_start(),
+            collision: CollisionPolicy::default(),
+        }
+    }
+}
+
+/// Per-image values available to tokens.
+#[derive(Debug, Clone, Default)]
+pub struct NamingContext {
+    /// Source file name, with or without extension.
+    pub filename: String,
+    pub captured_at: Option<NaiveDateTime>,
+    pub camera_model: Option<String>,
+    pub rating: Option<u8>,
+    pub collection: Option<String>,
+    pub blake3_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Filename,
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Sequence { digits: usize },
+    Camera,
+    Rating,
+    Collection,
+    Text,
+    Hash { length: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Token(Token),
+}
+
+/// A parsed, validated naming template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingTemplate {
+    /// Path segments, each a run of literals and tokens.
+    segments: Vec<Vec<Part>>,
+}
+
+impl NamingTemplate {
+    pub fn parse(template: &str) -> Result<Self, NamingError> {
+        if template.trim().is_empty() {
+            return Err(NamingError::EmptyTemplate);
+        }
+        if Path::new(template).has_root() || template.starts_with(['/', '\\']) {
+            return Err(NamingError::InvalidPath(template.to_string()));
+        }
+
+        let segments = split_segments(template)?
+            .into_iter()
+            .map(|segment| {
+                if matches!(segment.trim(), "" | "." | "..") {
+                    return Err(NamingError::InvalidPath(template.to_string()));
+                }
+                parse_segment(segment)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { segments })
+    }
+
+    /// Whether rendering needs the BLAKE3 hash, which imports compute on demand.
+    pub fn uses_hash(&self) -> bool {
+        self.segments
+            .iter()
+            .flatten()
+            .any(|part| matches!(part, Part::Token(Token::Hash { .. })))
+    }
+
+    /// Relative path of one image, without extension.
+    pub fn render(&self, context: &NamingContext, sequence: u32, custom_text: &str) -> PathBuf {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let rendered: String = segment
+                    .iter()
+                    .map(|part| match part {
+                        Part::Literal(text) => text.clone(),
+                        Part::Token(token) => {
+                            sanitize_value(&token_value(*token, context, sequence, custom_text))
+                        }
+                    })
+                    .collect();
+                finish_segment(&rendered)
+            })
+            .collect()
+    }
+}
+
+/// Splits on `/` and `\` outside of tokens.
+fn split_segments(template: &str) -> Result<Vec<&str>, NamingError> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut in_token = false;
+    for (index, character) in template.char_indices() {
+        match character {
+            '{' if !in_token => in_token = true,
+            '}' if in_token => in_token = false,
+            '/' | '\\' if !in_token => {
+                segments.push(&template[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_token {
+        return Err(NamingError::UnclosedToken(template.to_string()));
+    }
+    segments.push(&template[start..]);
+    Ok(segments)
+}
+
+fn parse_segment(segment: &str) -> Result<Vec<Part>, NamingError> {
+    let mut parts = Vec::new();
+    let mut rest = segment;
+    while let Some(open) = rest.find('{') {
+        if open > 0 {
+            parts.push(Part::Literal(sanitize_value(&rest[..open])));
+        }
+        let close = rest[open..]
+            .find('}')
+            .map(|offset| open + offset)
+            .ok_or_else(|| NamingError::UnclosedToken(segment.to_string()))?;
+        parts.push(Part::Token(parse_token(&rest[open + 1..close])?));
+        rest = &rest[close + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(sanitize_value(rest)));
+    }
+    Ok(parts)
+}
+
+fn parse_token(raw: &str) -> Result<Token, NamingError> {
+    let (name, argument) = match raw.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (raw.trim(), None),
+    };
+    let number = |max: usize| -> Result<Option<usize>, NamingError> {
+        argument
+            .map(|argument| {
+                argument
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|value| (1..=max).contains(value))
+                    .ok_or_else(|| NamingError::InvalidArgument(raw.to_string()))
+            })
+            .transpose()
+    };
+
+    let token = match name {
+        "seq" => Token::Sequence {
+            digits: number(MAX_SEQUENCE_DIGITS)?.unwrap_or(1),
+        },
+        "hash" => Token::Hash {
+            length: number(MAX_HASH_LENGTH)?.unwrap_or(DEFAULT_HASH_LENGTH),
+        },
+        _ if argument.is_some() => return Err(NamingError::InvalidArgument(raw.to_string())),
+        "filename" => Token::Filename,
+        "yyyy" => Token::Year,
+        "yy" => Token::ShortYear,
+        "mm" => Token::Month,
+        "dd" => Token::Day,
+        "hh" => Token::Hour,
+        "min" => Token::Minute,
+        "ss" => Token::Second,
+        "camera" => Token::Camera,
+        "rating" => Token::Rating,
+        "collection" => Token::Collection,
+        "text" => Token::Text,
+        _ => return Err(NamingError::UnknownToken(raw.to_string())),
+    };
+    Ok(token)
+}
+
+fn token_value(token: Token, context: &NamingContext, sequence: u32, custom_text: &str) -> String {
+    let date_part = |format: fn(&NaiveDateTime) -> String| {
+        context
+            .captured_at
+            .as_ref()
+            .map_or_else(|| MISSING_VALUE.to_string(), format)
+    };
+    let or_missing = |value: Option<&str>| {
+        value
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .unwrap_or(MISSING_VALUE)
+            .to_string()
+    };
+
+    match token {
+        Token::Filename => Path::new(&context.filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .map_or_else(|| MISSING_VALUE.to_string(), str::to_string),
+        Token::Year => date_part(|date| format!("{:04}", date.year())),
+        Token::ShortYear => date_part(|date| format!("{:02}", date.year().rem_euclid(100))),
+        Token::Month => date_part(|date| format!("{:02}", date.month())),
+        Token::Day => date_part(|date| format!("{:02}", date.day())),
+        Token::Hour => date_part(|date| format!("{:02}", date.hour())),
+        Token::Minute => date_part(|date| format!("{:02}", date.minute())),
+        Token::Second => date_part(|date| format!("{:02}", date.second())),
+        Token::Sequence { digits } => format!("{sequence:0digits$}"),
+        Token::Camera => or_missing(context.camera_model.as_deref()),
+        Token::Rating => context.rating.unwrap_or(0).to_string(),
+        Token::Collection => or_missing(context.collection.as_deref()),
+        Token::Text => custom_text.to_string(),
+        Token::Hash { length } => context.blake3_hash.as_deref().map_or_else(
+            || MISSING_VALUE.to_string(),
+            |hash| hash.chars().take(length).collect(),
+        ),
+    }
+}
+
+/// Replaces characters that are not allowed inside a file name.
+fn sanitize_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|character| {
+            if character.is_control() || FORBIDDEN_CHARACTERS.contains(&character) {
+                '_'
+            } else {
+                character
+            }
+        })
+        .collect()
+}
+
+/// Trims what Windows drops from the end of a segment and keeps the segment
+/// from being empty or a relative reference.
+fn finish_segment(segment: &str) -> String {
+    let trimmed = segment.trim().trim_end_matches('.');
+    if matches!(trimmed, "" | "." | "..") {
+        "_".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Outcome of claiming a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NameAction {
+    Create,
+    Overwrite,
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedName {
+    pub path: PathBuf,
+    pub action: NameAction,
+}
+
+/// Resolved name of one source, as returned by the preview commands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamePreview {
+    /// Catalog ID, for exports.
+    pub image_id: Option<i64>,
+    pub source: String,
+    pub target: String,
+    pub action: NameAction,
+}
+
+/// Assigns final paths under `root`, applying the collision policy against
+/// the disk and against names already claimed by the same job.
+#[derive(Debug)]
+pub struct NamePlanner {
+    root: PathBuf,
+    collision: CollisionPolicy,
+    claimed: HashSet<PathBuf>,
+}
+
+impl NamePlanner {
+    pub fn new(root: impl Into<PathBuf>, collision: CollisionPolicy) -> Self {
+        Self {
+            root: root.into(),
+            collision,
+            claimed: HashSet::new(),
+        }
+    }
+
+    /// Claims `relative` (without extension) plus `extension` under the root.
+    ///
+    /// The extension is appended rather than set, so dots inside the rendered
+    /// name (`portrait.final`, `v1.2`) and the collision suffix are kept.
+    pub fn claim(&mut self, relative: &Path, extension: &str) -> PlannedName {
+        let with_suffix = |suffix: Option<u32>| {
+            let mut name = relative.as_os_str().to_os_string();
+            if let Some(suffix) = suffix {
+                name.push(format!("_{suffix}"));
+            }
+            if !extension.is_empty() {
+                name.push(format!(".{extension}"));
+            }
+            self.root.join(name)
+        };
+
+        let mut suffix = None;
+        loop {
+            let path = with_suffix(suffix);
+            let taken_by_job = self.claimed.contains(&path);
+            let on_disk = path.exists();
+
+            let action = match (taken_by_job, on_disk, self.collision) {
+                (true, ..) | (false, true, CollisionPolicy::Suffix) => None,
+                (false, false, _) => Some(NameAction::Create),
+                (false, true, CollisionPolicy::Overwrite) => Some(NameAction::Overwrite),
+                (false, true, CollisionPolicy::Skip) => Some(NameAction::Skip),
+            };
+
+            if let Some(action) = action {
+                if action != NameAction::Skip {
+                    self.claimed.insert(path.clone());
+                }
+                return PlannedName { path, action };
+            }
+            suffix = Some(suffix.map_or(1, |n| n + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    fn context() -> NamingContext {
+        NamingContext {
+            filename: "DSC_0042.NEF".to_string(),
+            captured_at: NaiveDate::from_ymd_opt(2026, 3, 7)
+                .and_then(|date| date.and_hms_opt(9, 5, 30)),
+            camera_model: Some("Z 8".to_string()),
+            rating: Some(4),
+            collection: Some("Client: Acme/2026".to_string()),
+            blake3_hash: Some("af1349b9f5f9a1a6a0404dea36dcc949".to_string()),
+        }
+    }
+
+    fn render(template: &str) -> PathBuf {
+        NamingTemplate::parse(template)
+            .unwrap()
+            .render(&context(), 7, "web")
+    }
+
+    #[test]
+    fn tokens_resolve_from_context() {
+        assert_eq!(
+            render("{yyyy}/{yyyy}-{mm}-{dd}/{filename}_{hh}{min}{ss}"),
+            PathBuf::from("2026/2026-03-07/DSC_0042_090530")
+        );
+        assert_eq!(
+            render("{yy}_{camera}_{rating}star_{seq:4}_{text}_{hash:6}"),
+            PathBuf::from("26_Z 8_4star_0007_web_af1349")
+        );
+        assert_eq!(render("{seq}-{hash}"), PathBuf::from("7-af1349b9"));
+    }
+
+    #[test]
+    fn token_values_cannot_add_folders() {
+        assert_eq!(
+            render("{collection}/{filename}"),
+            PathBuf::from("Client_ Acme_2026/DSC_0042")
+        );
+    }
+
+    #[test]
+    fn missing_values_resolve_to_unknown() {
+        let template = NamingTemplate::parse("{yyyy}_{camera}_{collection}_{rating}").unwrap();
+        let rendered = template.render(&NamingContext::default(), 1, "");
+        assert_eq!(rendered, PathBuf::from("unknown_unknown_unknown_0"));
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        let cases = [
+            ("", NamingError::EmptyTemplate),
+            (
+                "/abs/{filename}",
+                NamingError::InvalidPath("/abs/{filename}".into()),
+            ),
+            ("a/../b", NamingError::InvalidPath("a/../b".into())),
+            ("a//b", NamingError::InvalidPath("a//b".into())),
+            ("{nope}", NamingError::UnknownToken("nope".into())),
+            ("{seq:0}", NamingError::InvalidArgument("seq:0".into())),
+            (
+                "{camera:3}",
+                NamingError::InvalidArgument("camera:3".into()),
+            ),
+            ("{filename", NamingError::UnclosedToken("{filename".into())),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(
+                NamingTemplate::parse(template),
+                Err(expected),
+                "{template:?}"
+            );
+        }
+        assert!(NamingTemplate::parse("{hash:8}").unwrap().uses_hash());
+        assert!(!NamingTemplate::parse("{filename}").unwrap().uses_hash());
+    }
+
+    #[test]
+    fn planner_applies_collision_policies() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("taken.jpg"), b"existing").unwrap();
+        let relative = Path::new("taken");
+
+        let mut suffix = NamePlanner::new(dir.path(), CollisionPolicy::Suffix);
+        assert_eq!(
+            suffix.claim(relative, "jpg"),
+            PlannedName {
+                path: dir.path().join("taken_1.jpg"),
+                action: NameAction::Create,
+            }
+        );
+        assert_eq!(
+            suffix.claim(relative, "jpg").path,
+            dir.path().join("taken_2.jpg")
+        );
+
+        let mut overwrite = NamePlanner::new(dir.path(), CollisionPolicy::Overwrite);
+        assert_eq!(
+            overwrite.claim(relative, "jpg"),
+            PlannedName {
+                path: dir.path().join("taken.jpg"),
+                action: NameAction::Overwrite,
+            }
+        );
+        // A second image of the same job never replaces the first.
+        assert_eq!(
+            overwrite.claim(relative, "jpg"),
+            PlannedName {
+                path: dir.path().join("taken_1.jpg"),
+                action: NameAction::Create,
+            }
+        );
+
+        let mut skip = NamePlanner::new(dir.path(), CollisionPolicy::Skip);
+        assert_eq!(skip.claim(relative, "jpg").action, NameAction::Skip);
+        assert_eq!(
+            skip.claim(Path::new("sub/free"), "jpg"),
+            PlannedName {
+                path: dir.path().join("sub/free.jpg"),
+                action: NameAction::Create,
+            }
+        );
+    }
+
+    #[test]
+    fn planner_keeps_dots_in_rendered_names() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("portrait.final_edited.jpg"), b"existing").unwrap();
+        let relative = Path::new("portrait.final_edited");
+
+        let mut planner = NamePlanner::new(dir.path(), CollisionPolicy::Suffix);
+        assert_eq!(
+            planner.claim(relative, "jpg"),
+            PlannedName {
+                path: dir.path().join("portrait.final_edited_1.jpg"),
+                action: NameAction::Create,
+            }
+        );
+        assert_eq!(
+            planner.claim(relative, "jpg").path,
+            dir.path().join("portrait.final_edited_2.jpg")
+        );
+        assert_eq!(
+            planner.claim(Path::new("v1.2"), "").path,
+            dir.path().join("v1.2")
+        );
+    }
+
+    #[test]
+    fn options_deserialize_with_defaults() {
+        let options: NamingOptions =
+            serde_json::from_str(r#"{"template":"{filename}","collision":"skip"}"#).unwrap();
+        assert_eq!(options.sequence_start, 1);
+        assert_eq!(options.custom_text, "");
+        assert_eq!(options.collision, CollisionPolicy::Skip);
+    }
+}
@@ -0,0 +1,317 @@
+//! Panorama stitching of overlapping catalog images.
+//!
+//! RAW sources are decoded to linear RGB through `RawDecoder`; other formats
+//! are decoded with `image` and linearised from sRGB. Registration,
+//! projection and blending run in `luminafast_image_core::stitch_panorama`,
+//! and the result is written as a float DNG/TIFF with the HDR merge encoder
+//! so no highlight data is lost. The command layer ingests the file and
+//! links it to its sources through `derived_images`.
+
+use crate::services::export_pipeline::{
+    is_known_raw_extension, resolve_source_image_path, ExportPipelineError, RsRawDecoder,
+};
+use crate::services::hdr_merge::{encode_float_image, HdrOutputFormat};
+use luminafast_image_core::{
+    stitch_panorama, LinearImage, PanoramaOptions, PanoramaProjection, ProcessingError, RawDecoder,
+};
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Clone)]
+pub struct PanoramaRequest {
+    /// Overlapping frames, in any order.
+    pub image_ids: Vec<i64>,
+    pub output_path: PathBuf,
+    pub format: HdrOutputFormat,
+    pub projection: PanoramaProjection,
+}
+
+#[derive(Debug, Clone)]
+pub struct PanoramaResult {
+    pub output_path: PathBuf,
+    pub format: HdrOutputFormat,
+    pub projection: PanoramaProjection,
+    pub width: u32,
+    pub height: u32,
+    /// Frame the projection is centred on.
+    pub reference_image_id: i64,
+    /// Focal length used for the projection, in reference pixels.
+    pub focal_length: f64,
+}
+
+#[derive(Debug, Error)]
+pub enum PanoramaError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Source error: {0}")]
+    Source(#[from] ExportPipelineError),
+
+    #[error("Image processing error: {0}")]
+    Processing(#[from] ProcessingError),
+
+    #[error("Image decode error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Panorama requires at least 2 distinct images, got {0}")]
+    NotEnoughFrames(usize),
+}
+
+/// Catalog-side inputs of a panorama: the resolved file of every frame.
+///
+/// Built under the database lock by [`prepare_panorama`], then stitched by
+/// [`render_panorama`] without touching the catalog.
+#[derive(Debug, Clone)]
+pub struct PreparedPanorama {
+    paths: Vec<PathBuf>,
+}
+
+/// Validates the request and resolves every frame's source file.
+pub fn prepare_panorama(
+    conn: &Connection,
+    request: &PanoramaRequest,
+) -> Result<PreparedPanorama, PanoramaError> {
+    let mut distinct = request.image_ids.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if distinct.len() < 2 || distinct.len() != request.image_ids.len() {
+        return Err(PanoramaError::NotEnoughFrames(distinct.len()));
+    }
+
+    // Resolve every source before decoding anything.
+    let paths = request
+        .image_ids
+        .iter()
+        .map(|&image_id| resolve_source_image_path(conn, image_id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PreparedPanorama { paths })
+}
+
+/// Decodes and stitches prepared frames, then writes the panorama file.
+pub fn render_panorama(
+    prepared: PreparedPanorama,
+    request: &PanoramaRequest,
+) -> Result<PanoramaResult, PanoramaError> {
+    render_panorama_internal(prepared, request, &RsRawDecoder)
+}
+
+#[cfg(test)]
+fn stitch_panorama_images_internal(
+    conn: &Connection,
+    request: &PanoramaRequest,
+    raw_decoder: &dyn RawDecoder,
+) -> Result<PanoramaResult, PanoramaError> {
+    let prepared = prepare_panorama(conn, request)?;
+    render_panorama_internal(prepared, request, raw_decoder)
+}
+
+fn render_panorama_internal(
+    prepared: PreparedPanorama,
+    request: &PanoramaRequest,
+    raw_decoder: &dyn RawDecoder,
+) -> Result<PanoramaResult, PanoramaError> {
+    let frames = prepared
+        .paths
+        .iter()
+        .map(|path| decode_linear(path, raw_decoder))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let options = PanoramaOptions {
+        projection: request.projection,
+        ..PanoramaOptions::default()
+    };
+    let panorama = stitch_panorama(&frames, &options)?;
+    fs::write(
+        &request.output_path,
+        encode_float_image(&panorama.image, request.format),
+    )?;
+
+    Ok(PanoramaResult {
+        output_path: request.output_path.clone(),
+        format: request.format,
+        projection: request.projection,
+        width: panorama.image.width,
+        height: panorama.image.height,
+        reference_image_id: request.image_ids[panorama.reference_index],
+        focal_length: panorama.focal_length,
+    })
+}
+
+/// Decodes a source file to linear RGB, whatever its format.
+fn decode_linear(path: &Path, raw_decoder: &dyn RawDecoder) -> Result<LinearImage, PanoramaError> {
+    if is_known_raw_extension(path) {
+        let bytes = fs::read(path)?;
+        return Ok(raw_decoder.decode_to_linear_rgb(&bytes)?);
+    }
+
+    let decoded = image::open(path)?;
+    let (width, height) = (decoded.width(), decoded.height());
+    // Float sources (HDR merges) are already linear; integer ones are sRGB.
+    let is_float = matches!(
+        decoded.color(),
+        image::ColorType::Rgb32F | image::ColorType::Rgba32F
+    );
+    let mut pixels = decoded.to_rgb32f().into_raw();
+    if !is_float {
+        pixels
+            .iter_mut()
+            .for_each(|value| *value = srgb_to_linear(*value));
+    }
+    Ok(LinearImage::new(width, height, pixels)?)
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const FRAME_WIDTH: u32 = 120;
+    const FRAME_HEIGHT: u32 = 90;
+
+    /// Linear value of a deterministic scene of random-intensity cells.
+    fn scene(x: u32, y: u32) -> f32 {
+        let mut hash = ((x / 7) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ ((y / 7) as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash ^= hash >> 32;
+        0.05 + 0.8 * (hash % 1000) as f32 / 1000.0
+    }
+
+    /// Decodes the fake RAW written by `setup_db`: the crop origin.
+    struct MockCropDecoder;
+
+    impl RawDecoder for MockCropDecoder {
+        fn decode_to_linear_rgb(&self, input: &[u8]) -> Result<LinearImage, ProcessingError> {
+            let origin_x = u32::from(input[0]);
+            let mut pixels = Vec::new();
+            for y in 0..FRAME_HEIGHT {
+                for x in 0..FRAME_WIDTH {
+                    let value = scene(origin_x + x, y);
+                    pixels.extend_from_slice(&[value, value, value]);
+                }
+            }
+            LinearImage::new(FRAME_WIDTH, FRAME_HEIGHT, pixels)
+        }
+    }
+
+    /// Writes 8-bit sRGB PNG crops for `png` frames and fake RAWs otherwise.
+    fn setup_db(dir: &TempDir, frames: &[(i64, &str, u8)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE images (id INTEGER PRIMARY KEY, blake3_hash TEXT NOT NULL);
+            CREATE TABLE ingestion_file_status (
+                id INTEGER PRIMARY KEY,
+                blake3_hash TEXT,
+                file_path TEXT NOT NULL
+            );
+        "#,
+        )
+        .unwrap();
+
+        for &(id, extension, origin_x) in frames {
+            let path = dir.path().join(format!("frame_{id}.{extension}"));
+            if extension == "png" {
+                let crop = image::RgbImage::from_fn(FRAME_WIDTH, FRAME_HEIGHT, |x, y| {
+                    let linear = scene(u32::from(origin_x) + x, y);
+                    let encoded = if linear <= 0.003_130_8 {
+                        linear * 12.92
+                    } else {
+                        1.055 * linear.powf(1.0 / 2.4) - 0.055
+                    };
+                    let level = (encoded * 255.0).round() as u8;
+                    image::Rgb([level, level, level])
+                });
+                crop.save(&path).unwrap();
+            } else {
+                fs::write(&path, [origin_x]).unwrap();
+            }
+            conn.execute(
+                "INSERT INTO images (id, blake3_hash) VALUES (?1, ?2)",
+                rusqlite::params![id, format!("hash-{id}")],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO ingestion_file_status (blake3_hash, file_path) VALUES (?1, ?2)",
+                rusqlite::params![format!("hash-{id}"), path.to_string_lossy()],
+            )
+            .unwrap();
+        }
+
+        conn
+    }
+
+    fn request(dir: &TempDir, image_ids: Vec<i64>) -> PanoramaRequest {
+        PanoramaRequest {
+            image_ids,
+            output_path: dir.path().join("panorama.tiff"),
+            format: HdrOutputFormat::Tiff,
+            projection: PanoramaProjection::Cylindrical,
+        }
+    }
+
+    #[test]
+    fn test_stitches_mixed_raw_and_png_sources() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_db(&dir, &[(1, "arw", 0), (2, "png", 70), (3, "arw", 140)]);
+
+        let result =
+            stitch_panorama_images_internal(&conn, &request(&dir, vec![1, 2, 3]), &MockCropDecoder)
+                .unwrap();
+
+        assert_eq!(result.reference_image_id, 2);
+        assert!(result.width > FRAME_WIDTH + FRAME_WIDTH / 2);
+        let bytes = fs::read(&result.output_path).unwrap();
+        let stitched = image::load_from_memory(&bytes).unwrap().to_rgb32f();
+        assert_eq!(stitched.dimensions(), (result.width, result.height));
+        // The centre of the reference frame is reproduced in linear light.
+        let centre = stitched.get_pixel(result.width / 2, result.height / 2)[0];
+        let expected = scene(70 + FRAME_WIDTH / 2, FRAME_HEIGHT / 2);
+        assert!((centre - expected).abs() < 0.05, "{centre} vs {expected}");
+    }
+
+    #[test]
+    fn test_rejects_duplicate_or_single_frames() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_db(&dir, &[(1, "arw", 0)]);
+
+        let single =
+            stitch_panorama_images_internal(&conn, &request(&dir, vec![1]), &MockCropDecoder);
+        let duplicate =
+            stitch_panorama_images_internal(&conn, &request(&dir, vec![1, 1]), &MockCropDecoder);
+
+        assert!(matches!(single, Err(PanoramaError::NotEnoughFrames(1))));
+        assert!(matches!(duplicate, Err(PanoramaError::NotEnoughFrames(1))));
+    }
+
+    #[test]
+    fn test_reports_frames_that_do_not_overlap() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_db(&dir, &[(1, "arw", 0), (2, "arw", 250)]);
+
+        let result =
+            stitch_panorama_images_internal(&conn, &request(&dir, vec![1, 2]), &MockCropDecoder);
+
+        assert!(matches!(
+            result,
+            Err(PanoramaError::Processing(
+                ProcessingError::InvalidMerge { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,504 @@
+//! Perceptual hashes for near-duplicate and similar image search.
+//!
+//! BLAKE3 only matches byte-identical files. dHash (horizontal gradient
+//! signs on a 9x8 grid) and pHash (low-frequency DCT signs on a 32x32 grid)
+//! are computed from the thumbnail preview, so a RAW and its JPEG, or a
+//! re-saved file, land within a few bits of each other. Hashes are compared
+//! by Hamming distance.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// Hamming distance under which two images are reported as similar.
+pub const DEFAULT_SIMILARITY_THRESHOLD: u32 = 10;
+
+const PHASH_SIZE: usize = 32;
+const PHASH_LOW_FREQUENCIES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerceptualHashAlgorithm {
+    /// Difference hash: robust to exposure/colour changes, fast.
+    DHash,
+    /// DCT hash: more robust to resizing, compression and small crops.
+    PHash,
+}
+
+impl PerceptualHashAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DHash => "dhash",
+            Self::PHash => "phash",
+        }
+    }
+}
+
+impl TryFrom<&str> for PerceptualHashAlgorithm {
+    type Error = PerceptualHashError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "dhash" => Ok(Self::DHash),
+            "phash" => Ok(Self::PHash),
+            other => Err(PerceptualHashError::UnsupportedAlgorithm(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerceptualHashes {
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+impl PerceptualHashes {
+    pub fn get(&self, algorithm: PerceptualHashAlgorithm) -> u64 {
+        match algorithm {
+            PerceptualHashAlgorithm::DHash => self.dhash,
+            PerceptualHashAlgorithm::PHash => self.phash,
+        }
+    }
+}
+
+/// Catalog image within the threshold of the queried image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarImage {
+    pub image_id: i64,
+    pub filename: String,
+    /// Hamming distance to the queried image (0-64).
+    pub distance: u32,
+}
+
+/// Connected set of images, each within the threshold of another member.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarImageGroup {
+    pub image_ids: Vec<i64>,
+}
+
+#[derive(Debug, Error)]
+pub enum PerceptualHashError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Image decode error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Unsupported perceptual hash algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Image {0} has no perceptual hash yet (generate its thumbnail first)")]
+    HashNotFound(i64),
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Computes both hashes of an already decoded image.
+pub fn compute_perceptual_hashes(image: &DynamicImage) -> PerceptualHashes {
+    let grey = image.to_luma8();
+    PerceptualHashes {
+        dhash: dhash(&grey),
+        phash: phash(&grey),
+    }
+}
+
+/// Decodes `path` (typically the thumbnail JPEG) and hashes it.
+pub fn compute_perceptual_hashes_from_file(
+    path: &Path,
+) -> Result<PerceptualHashes, PerceptualHashError> {
+    Ok(compute_perceptual_hashes(&image::open(path)?))
+}
+
+fn dhash(grey: &image::GrayImage) -> u64 {
+    let small = image::imageops::resize(grey, 9, 8, FilterType::Triangle);
+    let mut hash = 0_u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+fn phash(grey: &image::GrayImage) -> u64 {
+    let small = image::imageops::resize(
+        grey,
+        PHASH_SIZE as u32,
+        PHASH_SIZE as u32,
+        FilterType::Triangle,
+    );
+    let pixels: Vec<f64> = small.pixels().map(|pixel| pixel[0] as f64).collect();
+
+    // Separable DCT-II, keeping only the low-frequency corner.
+    let cosines: Vec<f64> = (0..PHASH_LOW_FREQUENCIES * PHASH_SIZE)
+        .map(|index| {
+            let (u, x) = (index / PHASH_SIZE, index % PHASH_SIZE);
+            (std::f64::consts::PI * (2 * x + 1) as f64 * u as f64 / (2 * PHASH_SIZE) as f64).cos()
+        })
+        .collect();
+    let basis = |u: usize, x: usize| cosines[u * PHASH_SIZE + x];
+
+    let mut rows = vec![0.0; PHASH_SIZE * PHASH_LOW_FREQUENCIES];
+    for y in 0..PHASH_SIZE {
+        for u in 0..PHASH_LOW_FREQUENCIES {
+            rows[y * PHASH_LOW_FREQUENCIES + u] = (0..PHASH_SIZE)
+                .map(|x| pixels[y * PHASH_SIZE + x] * basis(u, x))
+                .sum();
+        }
+    }
+    let mut coefficients = [0.0_f64; PHASH_LOW_FREQUENCIES * PHASH_LOW_FREQUENCIES];
+    for v in 0..PHASH_LOW_FREQUENCIES {
+        for u in 0..PHASH_LOW_FREQUENCIES {
+            coefficients[v * PHASH_LOW_FREQUENCIES + u] = (0..PHASH_SIZE)
+                .map(|y| rows[y * PHASH_LOW_FREQUENCIES + u] * basis(v, y))
+                .sum();
+        }
+    }
+
+    // The DC term only carries mean brightness; exclude it from the median.
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    coefficients.iter().fold(0_u64, |hash, &value| {
+        (hash << 1) | u64::from(value > median)
+    })
+}
+
+/// Stores (or replaces) the hashes of an image.
+pub fn upsert_perceptual_hashes(
+    conn: &Connection,
+    image_id: i64,
+    hashes: &PerceptualHashes,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO image_perceptual_hashes (image_id, dhash, phash)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(image_id) DO UPDATE SET
+           dhash = excluded.dhash,
+           phash = excluded.phash,
+           computed_at = datetime('now')",
+        params![image_id, hashes.dhash as i64, hashes.phash as i64],
+    )?;
+    Ok(())
+}
+
+pub fn get_perceptual_hashes(
+    conn: &Connection,
+    image_id: i64,
+) -> rusqlite::Result<Option<PerceptualHashes>> {
+    conn.query_row(
+        "SELECT dhash, phash FROM image_perceptual_hashes WHERE image_id = ?1",
+        [image_id],
+        |row| {
+            Ok(PerceptualHashes {
+                dhash: row.get::<_, i64>(0)? as u64,
+                phash: row.get::<_, i64>(1)? as u64,
+            })
+        },
+    )
+    .optional()
+}
+
+fn load_all_hashes(
+    conn: &Connection,
+    algorithm: PerceptualHashAlgorithm,
+) -> rusqlite::Result<Vec<(i64, u64)>> {
+    // Column names match `as_str`.
+    let mut stmt = conn.prepare(&format!(
+        "SELECT image_id, {} FROM image_perceptual_hashes ORDER BY image_id",
+        algorithm.as_str()
+    ))?;
+    let hashes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(hashes)
+}
+
+/// Images within `max_distance` of `image_id`, closest first.
+pub fn find_similar_images(
+    conn: &Connection,
+    image_id: i64,
+    algorithm: PerceptualHashAlgorithm,
+    max_distance: u32,
+) -> Result<Vec<SimilarImage>, PerceptualHashError> {
+    let query = get_perceptual_hashes(conn, image_id)?
+        .ok_or(PerceptualHashError::HashNotFound(image_id))?
+        .get(algorithm);
+
+    let mut matches: Vec<(i64, u32)> = load_all_hashes(conn, algorithm)?
+        .into_iter()
+        .filter(|(candidate, _)| *candidate != image_id)
+        .map(|(candidate, hash)| (candidate, hamming_distance(query, hash)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .collect();
+    matches.sort_by_key(|&(candidate, distance)| (distance, candidate));
+
+    let mut stmt = conn.prepare("SELECT filename FROM images WHERE id = ?1")?;
+    matches
+        .into_iter()
+        .map(|(candidate, distance)| {
+            let filename = stmt.query_row([candidate], |row| row.get(0))?;
+            Ok(SimilarImage {
+                image_id: candidate,
+                filename,
+                distance,
+            })
+        })
+        .collect()
+}
+
+/// Groups every hashed image with its neighbours within `max_distance`
+/// (single linkage). Only groups of two or more images are returned.
+pub fn group_similar_images(
+    conn: &Connection,
+    algorithm: PerceptualHashAlgorithm,
+    max_distance: u32,
+) -> Result<Vec<SimilarImageGroup>, PerceptualHashError> {
+    let hashes = load_all_hashes(conn, algorithm)?;
+    let mut tree = BkTree::default();
+    for (index, &(_, hash)) in hashes.iter().enumerate() {
+        tree.insert(hash, index);
+    }
+
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for (index, &(_, hash)) in hashes.iter().enumerate() {
+        for neighbour in tree.within(hash, max_distance) {
+            let (a, b) = (root(&mut parents, index), root(&mut parents, neighbour));
+            if a != b {
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<i64>> = BTreeMap::new();
+    for (index, &(image_id, _)) in hashes.iter().enumerate() {
+        let group = root(&mut parents, index);
+        groups.entry(group).or_default().push(image_id);
+    }
+
+    Ok(groups
+        .into_values()
+        .filter(|image_ids| image_ids.len() > 1)
+        .map(|image_ids| SimilarImageGroup { image_ids })
+        .collect())
+}
+
+/// Burkhard-Keller tree over Hamming distance, so grouping a catalog does
+/// not compare every pair of hashes.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    values: Vec<usize>,
+    children: BTreeMap<u32, usize>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, value: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode::new(hash, value));
+            return;
+        }
+        let mut current = 0;
+        loop {
+            let distance = hamming_distance(self.nodes[current].hash, hash);
+            if distance == 0 {
+                self.nodes[current].values.push(value);
+                return;
+            }
+            match self.nodes[current].children.get(&distance) {
+                Some(&child) => current = child,
+                None => {
+                    let index = self.nodes.len();
+                    self.nodes.push(BkNode::new(hash, value));
+                    self.nodes[current].children.insert(distance, index);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn within(&self, hash: u64, max_distance: u32) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(current) = stack.pop() {
+            let node = &self.nodes[current];
+            let distance = hamming_distance(node.hash, hash);
+            if distance <= max_distance {
+                found.extend_from_slice(&node.values);
+            }
+            let low = distance.saturating_sub(max_distance);
+            let high = distance + max_distance;
+            stack.extend(node.children.range(low..=high).map(|(_, &child)| child));
+        }
+        found
+    }
+}
+
+impl BkNode {
+    fn new(hash: u64, value: usize) -> Self {
+        Self {
+            hash,
+            values: vec![value],
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn scene(width: u32, height: u32, brightness: f32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let fx = x as f32 / width as f32;
+            let fy = y as f32 / height as f32;
+            let block = if fx > 0.55 && fy < 0.45 { 0.3 } else { 0.0 };
+            let value = 0.3 + 0.25 * (fx * 7.0).sin() * (fy * 4.0).cos() + block;
+            let level = (value * brightness * 255.0).clamp(0.0, 255.0) as u8;
+            Rgb([level, level / 2, 255 - level])
+        }))
+    }
+
+    fn setup_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE images (id INTEGER PRIMARY KEY, filename TEXT NOT NULL);
+            CREATE TABLE image_perceptual_hashes (
+                image_id INTEGER PRIMARY KEY,
+                dhash INTEGER NOT NULL,
+                phash INTEGER NOT NULL,
+                computed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+        "#,
+        )
+        .unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, image_id: i64, dhash: u64, phash: u64) {
+        conn.execute(
+            "INSERT INTO images (id, filename) VALUES (?1, ?2)",
+            params![image_id, format!("IMG_{image_id}.jpg")],
+        )
+        .unwrap();
+        upsert_perceptual_hashes(conn, image_id, &PerceptualHashes { dhash, phash }).unwrap();
+    }
+
+    #[test]
+    fn test_resized_and_reexposed_copies_stay_close() {
+        let original = compute_perceptual_hashes(&scene(240, 160, 1.0));
+        let resized = compute_perceptual_hashes(&scene(120, 80, 1.0));
+        let darker = compute_perceptual_hashes(&scene(240, 160, 0.85));
+        let mirrored = compute_perceptual_hashes(&scene(240, 160, 1.0).fliph());
+
+        for algorithm in [
+            PerceptualHashAlgorithm::DHash,
+            PerceptualHashAlgorithm::PHash,
+        ] {
+            let hash = original.get(algorithm);
+            assert!(hamming_distance(hash, resized.get(algorithm)) <= 4);
+            assert!(hamming_distance(hash, darker.get(algorithm)) <= 4);
+            assert!(
+                hamming_distance(hash, mirrored.get(algorithm)) > DEFAULT_SIMILARITY_THRESHOLD,
+                "{algorithm:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_hashes_round_trip_through_signed_storage() {
+        let conn = setup_db();
+        insert(&conn, 1, u64::MAX, 1 << 63);
+
+        let stored = get_perceptual_hashes(&conn, 1)
+            .unwrap()
+            .expect("stored hashes");
+
+        assert_eq!(stored.dhash, u64::MAX);
+        assert_eq!(stored.phash, 1 << 63);
+        assert!(get_perceptual_hashes(&conn, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_similar_images_orders_by_distance() {
+        let conn = setup_db();
+        insert(&conn, 1, 0, 0);
+        insert(&conn, 2, 0b111, 0);
+        insert(&conn, 3, 0b1, 0);
+        insert(&conn, 4, u64::MAX, 0);
+
+        let similar = find_similar_images(&conn, 1, PerceptualHashAlgorithm::DHash, 4).unwrap();
+
+        let ids: Vec<_> = similar.iter().map(|image| image.image_id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert_eq!(similar[0].distance, 1);
+        assert_eq!(similar[0].filename, "IMG_3.jpg");
+        assert!(matches!(
+            find_similar_images(&conn, 9, PerceptualHashAlgorithm::DHash, 4),
+            Err(PerceptualHashError::HashNotFound(9))
+        ));
+    }
+
+    #[test]
+    fn test_group_similar_images_links_transitively() {
+        let conn = setup_db();
+        // 1-2 and 2-3 are 3 bits apart; 1-3 is 6 bits apart.
+        insert(&conn, 1, 0, 0);
+        insert(&conn, 2, 0b111, 0);
+        insert(&conn, 3, 0b111_111, 0);
+        insert(&conn, 4, u64::MAX, 0);
+        insert(&conn, 5, u64::MAX, 0);
+        insert(&conn, 6, 0xFFFF_0000, 0);
+
+        let groups = group_similar_images(&conn, PerceptualHashAlgorithm::DHash, 3).unwrap();
+
+        assert_eq!(
+            groups,
+            vec![
+                SimilarImageGroup {
+                    image_ids: vec![1, 2, 3]
+                },
+                SimilarImageGroup {
+                    image_ids: vec![4, 5]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_algorithm_parser() {
+        assert_eq!(
+            PerceptualHashAlgorithm::try_from("pHash").unwrap(),
+            PerceptualHashAlgorithm::PHash
+        );
+        assert!(PerceptualHashAlgorithm::try_from("ahash").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::models::preview::*;
 use chrono::Utc;
+use luminafast_image_core::{resize_rgba8, ResampleFilter};
 use rayon::prelude::*;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -460,8 +461,7 @@ impl PreviewService {
                     let (new_width, new_height) =
                         self.calculate_resize_dimensions(original_width, original_height, size);
 
-                    let resized =
-                        img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
+                    let resized = resize_with_core(&img, new_width, new_height)?;
 
                     resized
                         .save(output_path)
@@ -505,11 +505,8 @@ impl PreviewService {
                 // Redimensionner
                 let (new_width, new_height) = self.calculate_resize_dimensions(width, height, size);
 
-                let resized = image::DynamicImage::ImageRgb8(img).resize(
-                    new_width,
-                    new_height,
-                    image::imageops::FilterType::Lanczos3,
-                );
+                let resized =
+                    resize_with_core(&image::DynamicImage::ImageRgb8(img), new_width, new_height)?;
 
                 resized
                     .save(output_path)
@@ -586,11 +583,8 @@ impl PreviewService {
         // Redimensionner pour la preview standard
         let (new_width, new_height) = self.calculate_resize_dimensions(width, height, size);
 
-        let resized = image::DynamicImage::ImageRgb8(img).resize(
-            new_width,
-            new_height,
-            image::imageops::FilterType::Lanczos3,
-        );
+        let resized =
+            resize_with_core(&image::DynamicImage::ImageRgb8(img), new_width, new_height)?;
 
         resized
             .save(output_path)
@@ -626,7 +620,7 @@ impl PreviewService {
         let (new_width, new_height) =
             self.calculate_resize_dimensions(original_width, original_height, size);
 
-        let resized = img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
+        let resized = resize_with_core(&img, new_width, new_height)?;
 
         // Sauvegarder avec la qualité appropriée pour thumbnail
         // Pour l'instant, utiliser save() simple - la qualité JPEG sera gérée plus tard
@@ -664,7 +658,7 @@ impl PreviewService {
         let (new_width, new_height) =
             self.calculate_resize_dimensions(original_width, original_height, size);
 
-        let resized = img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
+        let resized = resize_with_core(&img, new_width, new_height)?;
 
         // Sauvegarder avec la qualité appropriée pour preview standard
         // Pour l'instant, utiliser save() simple - la qualité JPEG sera gérée plus tard
@@ -861,12 +855,45 @@ impl PreviewService {
         target_size: (u32, u32),
     ) -> (u32, u32) {
         let scale_factor = target_size.0 as f32 / original_width.max(original_height) as f32;
-        let new_width = (original_width as f32 * scale_factor) as u32;
-        let new_height = (original_height as f32 * scale_factor) as u32;
+        let new_width = ((original_width as f32 * scale_factor) as u32).max(1);
+        let new_height = ((original_height as f32 * scale_factor) as u32).max(1);
         (new_width, new_height)
     }
 }
 
+/// Redimensionne une image avec le rééchantillonnage linéaire partagé du core
+/// (Lanczos3), pour que la pyramide de previews et l'export restent cohérents.
+///
+/// Les previews étant enregistrées en JPEG, le résultat est converti en RGB.
+fn resize_with_core(
+    img: &image::DynamicImage,
+    new_width: u32,
+    new_height: u32,
+) -> Result<image::DynamicImage, PreviewError> {
+    let rgba = img.to_rgba8();
+    let resized = resize_rgba8(
+        rgba.as_raw(),
+        rgba.width(),
+        rgba.height(),
+        new_width,
+        new_height,
+        ResampleFilter::Lanczos3,
+    )
+    .map_err(|e| PreviewError::ProcessingError {
+        message: format!("Erreur redimensionnement: {}", e),
+    })?;
+
+    let resized = image::RgbaImage::from_raw(new_width, new_height, resized).ok_or_else(|| {
+        PreviewError::ProcessingError {
+            message: "Buffer redimensionné invalide".to_string(),
+        }
+    })?;
+
+    Ok(image::DynamicImage::ImageRgb8(
+        image::DynamicImage::ImageRgba8(resized).to_rgb8(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     #[tokio::test]
@@ -1036,6 +1063,23 @@ mod tests {
         assert!(error.to_string().contains("30s"));
     }
 
+    #[test]
+    fn test_resize_with_core_outputs_rgb_at_requested_size() {
+        let source = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            60,
+            40,
+            image::Rgba([90, 120, 200, 255]),
+        ));
+
+        let resized = super::resize_with_core(&source, 24, 16).unwrap();
+
+        assert_eq!((resized.width(), resized.height()), (24, 16));
+        let rgb = resized
+            .as_rgb8()
+            .expect("preview resize should produce RGB");
+        assert!(rgb.pixels().all(|px| px.0 == [90, 120, 200]));
+    }
+
     #[test]
     fn test_cache_cleanup_config_default() {
         let config = CacheCleanupConfig::default();
@@ -0,0 +1,205 @@
+//! Sharpness and exposure metrics for culling.
+//!
+//! Metrics are computed from the standard preview (fixed 1440px long edge),
+//! so sharpness scores are comparable across a burst regardless of the
+//! source resolution. Sharpness is the variance of the 4-neighbour Laplacian
+//! of 8-bit luma: in-focus frames score in the hundreds, visibly blurred
+//! frames in the tens.
+
+use image::DynamicImage;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+
+/// Channel level (8-bit) at or above which a pixel counts as clipped.
+const HIGHLIGHT_CLIP_LEVEL: u8 = 250;
+/// Luma level (8-bit) at or below which a pixel counts as crushed.
+const SHADOW_CLIP_LEVEL: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityMetrics {
+    pub sharpness: f64,
+    /// Mean gamma-encoded luma, 0.0-1.0.
+    pub mean_luminance: f64,
+    /// Fraction of pixels with at least one clipped channel.
+    pub highlights_clipped: f64,
+    /// Fraction of pixels crushed to black.
+    pub shadows_clipped: f64,
+}
+
+pub fn compute_quality_metrics(image: &DynamicImage) -> QualityMetrics {
+    let rgb = image.to_rgb8();
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let pixel_count = (width * height).max(1) as f64;
+
+    let mut luma = Vec::with_capacity(width * height);
+    let mut highlights = 0_usize;
+    let mut shadows = 0_usize;
+    for pixel in rgb.pixels() {
+        let [r, g, b] = pixel.0;
+        let value = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+        if r.max(g).max(b) >= HIGHLIGHT_CLIP_LEVEL {
+            highlights += 1;
+        }
+        if value <= SHADOW_CLIP_LEVEL as f64 {
+            shadows += 1;
+        }
+        luma.push(value);
+    }
+
+    QualityMetrics {
+        sharpness: laplacian_variance(&luma, width, height),
+        mean_luminance: luma.iter().sum::<f64>() / pixel_count / 255.0,
+        highlights_clipped: highlights as f64 / pixel_count,
+        shadows_clipped: shadows as f64 / pixel_count,
+    }
+}
+
+/// Decodes `path` (typically the standard preview JPEG) and scores it.
+pub fn compute_quality_metrics_from_file(path: &Path) -> Result<QualityMetrics, image::ImageError> {
+    Ok(compute_quality_metrics(&image::open(path)?))
+}
+
+fn laplacian_variance(luma: &[f64], width: usize, height: usize) -> f64 {
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let mut sum = 0.0;
+    let mut sum_squares = 0.0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let index = y * width + x;
+            let response = luma[index - width] + luma[index + width] + luma[index - 1]
+                - 4.0 * luma[index]
+                + luma[index + 1];
+            sum += response;
+            sum_squares += response * response;
+        }
+    }
+
+    let count = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / count;
+    sum_squares / count - mean * mean
+}
+
+/// Stores (or replaces) the metrics of an image.
+pub fn upsert_quality_metrics(
+    conn: &Connection,
+    image_id: i64,
+    metrics: &QualityMetrics,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO image_quality_metrics
+            (image_id, sharpness, mean_luminance, highlights_clipped, shadows_clipped)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(image_id) DO UPDATE SET
+           sharpness = excluded.sharpness,
+           mean_luminance = excluded.mean_luminance,
+           highlights_clipped = excluded.highlights_clipped,
+           shadows_clipped = excluded.shadows_clipped,
+           computed_at = datetime('now')",
+        params![
+            image_id,
+            metrics.sharpness,
+            metrics.mean_luminance,
+            metrics.highlights_clipped,
+            metrics.shadows_clipped
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_quality_metrics(
+    conn: &Connection,
+    image_id: i64,
+) -> rusqlite::Result<Option<QualityMetrics>> {
+    conn.query_row(
+        "SELECT sharpness, mean_luminance, highlights_clipped, shadows_clipped
+         FROM image_quality_metrics WHERE image_id = ?1",
+        [image_id],
+        |row| {
+            Ok(QualityMetrics {
+                sharpness: row.get(0)?,
+                mean_luminance: row.get(1)?,
+                highlights_clipped: row.get(2)?,
+                shadows_clipped: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn checkerboard(cell: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            let level = if (x / cell + y / cell) % 2 == 0 {
+                40
+            } else {
+                200
+            };
+            Rgb([level, level, level])
+        }))
+    }
+
+    #[test]
+    fn test_blur_lowers_sharpness() {
+        let sharp = compute_quality_metrics(&checkerboard(4));
+        let blurred = compute_quality_metrics(&checkerboard(4).blur(2.0));
+        let flat = compute_quality_metrics(&DynamicImage::ImageRgb8(RgbImage::from_pixel(
+            16,
+            16,
+            Rgb([128, 128, 128]),
+        )));
+
+        assert!(sharp.sharpness > 10.0 * blurred.sharpness);
+        assert!(blurred.sharpness > 0.0);
+        assert_eq!(flat.sharpness, 0.0);
+    }
+
+    #[test]
+    fn test_exposure_metrics_count_clipped_pixels() {
+        // Left half blown out, right quarter crushed, the rest mid-grey.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 4, |x, _| match x {
+            0..=3 => Rgb([255, 240, 230]),
+            4..=5 => Rgb([128, 128, 128]),
+            _ => Rgb([0, 0, 0]),
+        }));
+
+        let metrics = compute_quality_metrics(&image);
+
+        assert_eq!(metrics.highlights_clipped, 0.5);
+        assert_eq!(metrics.shadows_clipped, 0.25);
+        assert!(metrics.mean_luminance > 0.5 && metrics.mean_luminance < 0.65);
+    }
+
+    #[test]
+    fn test_metrics_round_trip_through_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE images (id INTEGER PRIMARY KEY); INSERT INTO images (id) VALUES (7);",
+        )
+        .unwrap();
+        conn.execute_batch(include_str!(
+            "../../migrations/012_image_quality_metrics.sql"
+        ))
+        .unwrap();
+        let metrics = QualityMetrics {
+            sharpness: 312.5,
+            mean_luminance: 0.42,
+            highlights_clipped: 0.01,
+            shadows_clipped: 0.0,
+        };
+
+        upsert_quality_metrics(&conn, 7, &metrics).unwrap();
+        upsert_quality_metrics(&conn, 7, &metrics).unwrap();
+
+        assert_eq!(get_quality_metrics(&conn, 7).unwrap(), Some(metrics));
+        assert_eq!(get_quality_metrics(&conn, 8).unwrap(), None);
+    }
+}
@@ -1,10 +1,37 @@
 use crate::database::Database;
+use crate::services::color_palette::ColorQuery;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 /// Service de recherche avancée : requêtes SQL générées depuis les filtres JSON frontend
-/// Supporte les champs : iso, star, camera, lens, date, etc.
+/// Supporte les champs : iso, star, camera, lens, sharpness, brightness, color, etc.
 pub struct SearchService;
 
+/// Sens du tri des résultats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Tri des résultats de recherche, ex: {"field":"sharpness","direction":"desc"}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchSort {
+    pub field: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+/// Tables jointes par `search` : `i` (image + état), `e` (EXIF), `q` (métriques qualité)
+const SEARCH_FROM_CLAUSE: &str = "FROM (
+        SELECT images.*, image_state.rating AS rating, image_state.flag AS flag
+        FROM images LEFT JOIN image_state ON image_state.image_id = images.id
+    ) i
+    LEFT JOIN exif_metadata e ON e.image_id = i.id
+    LEFT JOIN image_quality_metrics q ON q.image_id = i.id";
+
 /// Structure pour les résultats bruts de la BD
 #[derive(Debug, Clone)]
 pub struct SearchResult {
@@ -82,6 +109,33 @@ impl SearchService {
                         return Err("Valeur lens invalide".to_string());
                     }
                 }
+                "sharpness" | "brightness" | "highlights_clipped" | "shadows_clipped" => {
+                    let column = Self::quality_column(field).unwrap_or("q.sharpness");
+                    let number = value
+                        .as_f64()
+                        .ok_or_else(|| format!("Valeur {} invalide", field))?;
+                    match operator {
+                        ">" => format!("{} > {}", column, number),
+                        ">=" => format!("{} >= {}", column, number),
+                        "<" => format!("{} < {}", column, number),
+                        "<=" => format!("{} <= {}", column, number),
+                        "=" | ":" => format!("{} = {}", column, number),
+                        _ => {
+                            return Err(format!("Opérateur invalide pour {}: {}", field, operator))
+                        }
+                    }
+                }
+                "color" => {
+                    // Valeur : nom de couleur, "#rrggbb" ou {"color", "distance", "weight"}
+                    let condition = ColorQuery::from_value(value)
+                        .map_err(|e| e.to_string())?
+                        .sql_condition("i.id");
+                    match operator {
+                        ":" | "=" => condition,
+                        "!=" => format!("NOT {}", condition),
+                        _ => return Err(format!("Opérateur invalide pour color: {}", operator)),
+                    }
+                }
                 _ => return Err(format!("Champ de recherche non supporté: {}", field)),
             };
 
@@ -91,22 +145,58 @@ impl SearchService {
         Ok(clauses.join(" AND "))
     }
 
+    /// Colonne SQL d'une métrique qualité (netteté, exposition)
+    fn quality_column(field: &str) -> Option<&'static str> {
+        match field {
+            "sharpness" => Some("q.sharpness"),
+            "brightness" => Some("q.mean_luminance"),
+            "highlights_clipped" => Some("q.highlights_clipped"),
+            "shadows_clipped" => Some("q.shadows_clipped"),
+            _ => None,
+        }
+    }
+
+    /// Convertit un tri en clause ORDER BY (sans le mot-clé)
+    /// Les images sans valeur (ex: métriques pas encore calculées) sont placées en dernier
+    pub fn build_order_clause(sort: &SearchSort) -> Result<String, String> {
+        let column = match sort.field.as_str() {
+            "iso" => "e.iso",
+            "star" => "i.rating",
+            "filename" => "i.filename",
+            "captured_at" => "i.captured_at",
+            "imported_at" => "i.imported_at",
+            field => Self::quality_column(field)
+                .ok_or_else(|| format!("Champ de tri non supporté: {}", field))?,
+        };
+        let direction = match sort.direction {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+
+        Ok(format!(
+            "{} IS NULL, {} {}, i.id ASC",
+            column, column, direction
+        ))
+    }
+
     /// Effectue une recherche sur le catalogue
     /// Combine la recherche texte libre sur filename avec les filtres structurés
     pub fn search(
         db: &mut Database,
         text: &str,
         filters: &[Value],
+        sort: Option<&SearchSort>,
     ) -> Result<Vec<SearchResult>, String> {
         let conn = db.connection();
 
-        let mut query = "SELECT id, filename, blake3_hash, rating, flag ".to_string();
-        query.push_str("FROM images WHERE 1=1");
+        let mut query = "SELECT i.id, i.filename, i.blake3_hash, i.rating, i.flag ".to_string();
+        query.push_str(SEARCH_FROM_CLAUSE);
+        query.push_str(" WHERE 1=1");
         let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
         // Ajouter filtre texte libre si présent (sécurisé avec paramètre lié)
         if !text.is_empty() {
-            query.push_str(" AND (filename LIKE ?)");
+            query.push_str(" AND (i.filename LIKE ?)");
             params.push(Box::new(format!("%{}%", text)));
         }
 
@@ -116,6 +206,10 @@ impl SearchService {
             query.push_str(&format!(" AND ({})", where_clause));
         }
 
+        if let Some(sort) = sort {
+            query.push_str(&format!(" ORDER BY {}", Self::build_order_clause(sort)?));
+        }
+
         query.push_str(" LIMIT 1000");
 
         let mut stmt = conn
@@ -187,6 +281,97 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_build_where_clause_quality_metrics() {
+        let filters = vec![
+            json!({"field": "sharpness", "operator": ">=", "value": 120.5}),
+            json!({"field": "highlights_clipped", "operator": "<", "value": 0.02}),
+        ];
+        let clause = SearchService::build_where_clause(&filters).unwrap();
+        assert!(clause.contains("q.sharpness >= 120.5"));
+        assert!(clause.contains("q.highlights_clipped < 0.02"));
+
+        let invalid = vec![json!({"field": "sharpness", "operator": ">", "value": "sharp"})];
+        assert!(SearchService::build_where_clause(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_build_where_clause_color() {
+        let filters = vec![json!({"field": "color", "operator": ":", "value": "teal"})];
+        let clause = SearchService::build_where_clause(&filters).unwrap();
+        assert!(clause.starts_with("EXISTS (SELECT 1 FROM image_color_palettes cp"));
+        assert!(clause.contains("cp.image_id = i.id"));
+
+        let excluded = vec![json!({"field": "color", "operator": "!=", "value": "#ff0000"})];
+        assert!(SearchService::build_where_clause(&excluded)
+            .unwrap()
+            .starts_with("NOT EXISTS"));
+
+        let unknown = vec![json!({"field": "color", "operator": ":", "value": "tealish"})];
+        assert!(SearchService::build_where_clause(&unknown).is_err());
+        let invalid = vec![json!({"field": "color", "operator": ">", "value": "teal"})];
+        assert!(SearchService::build_where_clause(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_build_order_clause_puts_missing_values_last() {
+        let sort = SearchSort {
+            field: "sharpness".to_string(),
+            direction: SortDirection::Desc,
+        };
+        assert_eq!(
+            SearchService::build_order_clause(&sort).unwrap(),
+            "q.sharpness IS NULL, q.sharpness DESC, i.id ASC"
+        );
+
+        let invalid = SearchSort {
+            field: "blake3_hash; DROP TABLE images".to_string(),
+            direction: SortDirection::Asc,
+        };
+        assert!(SearchService::build_order_clause(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_search_filters_and_sorts_by_sharpness() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("catalog.db")).unwrap();
+        db.initialize().unwrap();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO images (id, blake3_hash, filename, extension) VALUES
+                    (1, 'h1', 'burst_01.arw', 'arw'),
+                    (2, 'h2', 'burst_02.arw', 'arw'),
+                    (3, 'h3', 'burst_03.arw', 'arw'),
+                    (4, 'h4', 'burst_04.arw', 'arw');
+                 INSERT INTO image_state (image_id, rating) VALUES (2, 4);
+                 INSERT INTO image_quality_metrics
+                    (image_id, sharpness, mean_luminance, highlights_clipped, shadows_clipped)
+                 VALUES (1, 35.0, 0.4, 0.0, 0.0),
+                        (2, 410.0, 0.5, 0.0, 0.0),
+                        (3, 220.0, 0.5, 0.1, 0.0);",
+            )
+            .unwrap();
+        let sort = SearchSort {
+            field: "sharpness".to_string(),
+            direction: SortDirection::Desc,
+        };
+
+        let sharp = SearchService::search(
+            &mut db,
+            "burst",
+            &[json!({"field": "sharpness", "operator": ">", "value": 100})],
+            Some(&sort),
+        )
+        .unwrap();
+        let all = SearchService::search(&mut db, "", &[], Some(&sort)).unwrap();
+
+        let ids: Vec<u32> = sharp.iter().map(|result| result.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(sharp[0].rating, Some(4));
+        let ids: Vec<u32> = all.iter().map(|result| result.id).collect();
+        assert_eq!(ids, vec![2, 3, 1, 4]);
+    }
+
     #[test]
     fn test_build_where_clause_empty_filters() {
         let filters = vec![];
@@ -1,3 +1,4 @@
+use crate::services::color_palette::ColorQuery;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 
@@ -9,13 +10,25 @@ pub struct SmartQueryRule {
     pub value: serde_json::Value,
 }
 
+/// Optional ordering of smart collection results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartQuerySort {
+    pub field: String,
+    pub direction: String, // "asc" or "desc"
+}
+
 /// Smart query with rules and combinator
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SmartQuery {
     pub rules: Vec<SmartQueryRule>,
     pub combinator: String, // "AND" or "OR"
+    #[serde(default)]
+    pub sort: Option<SmartQuerySort>,
 }
 
+/// Default ordering when a smart query has no `sort`
+pub const DEFAULT_SMART_QUERY_ORDER: &str = "images.imported_at DESC";
+
 /// Parse smart query JSON and return SQL WHERE clause
 ///
 /// # Arguments
@@ -52,6 +65,54 @@ pub fn parse_smart_query(json: &str) -> Result<String, Box<dyn Error>> {
     Ok(format!("({})", clauses.join(joiner)))
 }
 
+/// Parse the optional `sort` of a smart query and return an SQL ORDER BY clause
+/// (without ORDER BY keyword). Images missing the sort value come last.
+pub fn parse_smart_query_order(json: &str) -> Result<String, Box<dyn Error>> {
+    let query: SmartQuery = serde_json::from_str(json)?;
+
+    let Some(sort) = query.sort else {
+        return Ok(DEFAULT_SMART_QUERY_ORDER.to_string());
+    };
+
+    let column = sort_column(&sort.field.to_lowercase())
+        .ok_or_else(|| format!("Unsupported sort field: {}", sort.field))?;
+    let direction = match sort.direction.to_lowercase().as_str() {
+        "asc" => "ASC",
+        "desc" => "DESC",
+        other => return Err(format!("Unsupported sort direction: {}", other).into()),
+    };
+
+    Ok(format!(
+        "{} IS NULL, {} {}, images.id ASC",
+        column, column, direction
+    ))
+}
+
+/// Sortable columns (numeric and quality fields, plus filename and dates)
+fn sort_column(field: &str) -> Option<&'static str> {
+    match field {
+        "rating" => Some("image_state.rating"),
+        "iso" => Some("exif_metadata.iso"),
+        "aperture" => Some("exif_metadata.aperture"),
+        "focal_length" => Some("exif_metadata.focal_length"),
+        "filename" => Some("images.filename"),
+        "captured_at" => Some("images.captured_at"),
+        "imported_at" => Some("images.imported_at"),
+        other => quality_column(other),
+    }
+}
+
+/// Quality metrics columns (see migration 012)
+fn quality_column(field: &str) -> Option<&'static str> {
+    match field {
+        "sharpness" => Some("image_quality_metrics.sharpness"),
+        "brightness" => Some("image_quality_metrics.mean_luminance"),
+        "highlights_clipped" => Some("image_quality_metrics.highlights_clipped"),
+        "shadows_clipped" => Some("image_quality_metrics.shadows_clipped"),
+        _ => None,
+    }
+}
+
 /// Build SQL clause for a single rule
 fn build_sql_clause(rule: &SmartQueryRule) -> Result<String, Box<dyn Error>> {
     let operator = rule.operator.to_lowercase();
@@ -65,6 +126,20 @@ fn build_sql_clause(rule: &SmartQueryRule) -> Result<String, Box<dyn Error>> {
         "focal_length" => {
             build_numeric_clause("exif_metadata.focal_length", &operator, &rule.value)
         }
+        "sharpness" | "brightness" | "highlights_clipped" | "shadows_clipped" => {
+            let column = quality_column(&field).ok_or("Unsupported quality field")?;
+            build_numeric_clause(column, &operator, &rule.value)
+        }
+
+        // Dominant colour palette (see migration 014)
+        "color" => {
+            let condition = ColorQuery::from_value(&rule.value)?.sql_condition("images.id");
+            match operator.as_str() {
+                "=" | "==" | "eq" | "contains" => Ok(condition),
+                "!=" | "<>" | "ne" | "not_contains" => Ok(format!("NOT {}", condition)),
+                _ => Err(format!("Unsupported operator for color field: {}", operator).into()),
+            }
+        }
 
         // String fields
         "camera_make" => build_string_clause("exif_metadata.camera_make", &operator, &rule.value),
@@ -252,6 +327,44 @@ mod tests {
         assert!(sql.contains("Canon''s Camera"));
     }
 
+    #[test]
+    fn test_parse_smart_query_sharpness_filter() {
+        let json = r#"{"rules":[{"field":"sharpness","operator":">=","value":150},{"field":"shadows_clipped","operator":"<","value":0.05}],"combinator":"AND"}"#;
+        let sql = parse_smart_query(json).unwrap();
+        assert!(sql.contains("image_quality_metrics.sharpness >= 150"));
+        assert!(sql.contains("image_quality_metrics.shadows_clipped < 0.05"));
+    }
+
+    #[test]
+    fn test_parse_smart_query_color_filter() {
+        let json = r#"{"rules":[{"field":"color","operator":"=","value":{"color":"teal","weight":0.3}},{"field":"color","operator":"!=","value":"red"}],"combinator":"AND"}"#;
+        let sql = parse_smart_query(json).unwrap();
+        assert!(sql.contains("cp.image_id = images.id AND cp.weight >= (0.3)"));
+        assert!(sql.contains("AND NOT EXISTS (SELECT 1 FROM image_color_palettes"));
+
+        let unknown =
+            r#"{"rules":[{"field":"color","operator":"=","value":"tealish"}],"combinator":"AND"}"#;
+        assert!(parse_smart_query(unknown).is_err());
+    }
+
+    #[test]
+    fn test_parse_smart_query_order() {
+        let sorted = r#"{"rules":[{"field":"rating","operator":">=","value":0}],"combinator":"AND","sort":{"field":"sharpness","direction":"desc"}}"#;
+        let unsorted =
+            r#"{"rules":[{"field":"rating","operator":">=","value":0}],"combinator":"AND"}"#;
+        let invalid = r#"{"rules":[{"field":"rating","operator":">=","value":0}],"combinator":"AND","sort":{"field":"random()","direction":"desc"}}"#;
+
+        assert_eq!(
+            parse_smart_query_order(sorted).unwrap(),
+            "image_quality_metrics.sharpness IS NULL, image_quality_metrics.sharpness DESC, images.id ASC"
+        );
+        assert_eq!(
+            parse_smart_query_order(unsorted).unwrap(),
+            DEFAULT_SMART_QUERY_ORDER
+        );
+        assert!(parse_smart_query_order(invalid).is_err());
+    }
+
     #[test]
     fn test_build_numeric_clause_aperture() {
         let rule = SmartQueryRule {
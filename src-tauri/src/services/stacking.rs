@@ -0,0 +1,814 @@
+//! Image stacks: bursts and exposure brackets shown as a single cover image.
+//!
+//! Detection works on the EXIF stored at ingestion. Frames from the same
+//! camera body (serial number, or make + model when the body records none)
+//! captured in quick succession form a run. A run at constant exposure is a
+//! burst; a run whose exposure varies is split into brackets, each a sequence
+//! of distinct exposures. Exposure is read from the exposure bias when every
+//! frame records one, otherwise from the shutter speed.
+
+use chrono::{DateTime, NaiveDateTime};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use thiserror::Error;
+
+/// Longest pause between the end of one exposure and the next frame.
+pub const DEFAULT_MAX_GAP_SECONDS: f64 = 2.0;
+
+/// Exposures closer than this (in EV) count as the same exposure.
+const EXPOSURE_TOLERANCE_EV: f64 = 0.25;
+
+/// Condition on `images i` keeping what the grid shows when stacks are
+/// collapsed: unstacked images, stack covers and members of expanded stacks.
+pub const STACK_TOPS_CONDITION: &str = "NOT EXISTS (
+    SELECT 1 FROM image_stack_members sm
+    JOIN image_stacks s ON s.id = sm.stack_id
+    WHERE sm.image_id = i.id AND s.collapsed = 1 AND s.cover_image_id != i.id)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StackKind {
+    Burst,
+    Bracket,
+    Manual,
+}
+
+impl StackKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Burst => "burst",
+            Self::Bracket => "bracket",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+impl TryFrom<&str> for StackKind {
+    type Error = StackingError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "burst" => Ok(Self::Burst),
+            "bracket" => Ok(Self::Bracket),
+            "manual" => Ok(Self::Manual),
+            other => Err(StackingError::UnknownKind(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageStack {
+    pub id: i64,
+    pub cover_image_id: i64,
+    pub kind: StackKind,
+    pub collapsed: bool,
+    /// Members in display order.
+    pub image_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackingOptions {
+    pub max_gap_seconds: f64,
+}
+
+impl Default for StackingOptions {
+    fn default() -> Self {
+        Self {
+            max_gap_seconds: DEFAULT_MAX_GAP_SECONDS,
+        }
+    }
+}
+
+/// Frame considered for automatic stacking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackCandidate {
+    pub image_id: i64,
+    pub captured_at: NaiveDateTime,
+    /// Body serial number, or make + model.
+    pub camera_key: String,
+    /// EV.
+    pub exposure_bias: Option<f64>,
+    /// log2(seconds), as stored in `exif_metadata`.
+    pub shutter_speed: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedStack {
+    pub kind: StackKind,
+    /// Capture order.
+    pub image_ids: Vec<i64>,
+    /// First burst frame, or the middle exposure of a bracket.
+    pub cover_image_id: i64,
+}
+
+#[derive(Debug, Error)]
+pub enum StackingError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("A stack needs at least two images")]
+    TooFewImages,
+
+    #[error("Image {0} is already in a stack")]
+    AlreadyStacked(i64),
+
+    #[error("Stack {0} not found")]
+    StackNotFound(i64),
+
+    #[error("Image {0} is not a member of the stack")]
+    NotAMember(i64),
+
+    #[error("New order for stack {0} must list each member exactly once")]
+    InvalidOrder(i64),
+
+    #[error("Unknown stack kind: {0}")]
+    UnknownKind(String),
+}
+
+/// Groups candidates into burst and bracket stacks. Runs that do not form a
+/// stack of at least two frames are left out.
+pub fn detect_stacks(
+    candidates: &[StackCandidate],
+    options: &StackingOptions,
+) -> Vec<DetectedStack> {
+    let mut by_camera: BTreeMap<&str, Vec<&StackCandidate>> = BTreeMap::new();
+    for candidate in candidates {
+        by_camera
+            .entry(candidate.camera_key.as_str())
+            .or_default()
+            .push(candidate);
+    }
+
+    let mut stacks = Vec::new();
+    for frames in by_camera.values_mut() {
+        frames.sort_by(|a, b| {
+            a.captured_at
+                .cmp(&b.captured_at)
+                .then(a.image_id.cmp(&b.image_id))
+        });
+
+        let mut run_start = 0;
+        for index in 1..=frames.len() {
+            if index == frames.len() || !follows(frames[index - 1], frames[index], options) {
+                stacks.extend(classify_run(&frames[run_start..index]));
+                run_start = index;
+            }
+        }
+    }
+    stacks
+}
+
+/// Whether `next` was shot within the gap after `previous` finished exposing.
+fn follows(previous: &StackCandidate, next: &StackCandidate, options: &StackingOptions) -> bool {
+    let elapsed = (next.captured_at - previous.captured_at).num_milliseconds() as f64 / 1000.0;
+    let exposure = previous.shutter_speed.map_or(0.0, f64::exp2);
+    elapsed - exposure <= options.max_gap_seconds
+}
+
+fn classify_run(run: &[&StackCandidate]) -> Vec<DetectedStack> {
+    if run.len() < 2 {
+        return Vec::new();
+    }
+
+    let offsets = match exposure_offsets(run) {
+        Some(offsets) if offsets.iter().any(|&ev| !same_exposure(ev, offsets[0])) => offsets,
+        _ => {
+            return vec![DetectedStack {
+                kind: StackKind::Burst,
+                image_ids: run.iter().map(|frame| frame.image_id).collect(),
+                cover_image_id: run[0].image_id,
+            }]
+        }
+    };
+
+    // A bracket ends when an exposure repeats.
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut current = vec![0];
+    for index in 1..run.len() {
+        if current
+            .iter()
+            .any(|&member| same_exposure(offsets[member], offsets[index]))
+        {
+            groups.push(std::mem::replace(&mut current, vec![index]));
+        } else {
+            current.push(index);
+        }
+    }
+    groups.push(current);
+
+    // Consecutive leftover frames at one exposure still make a burst.
+    let mut stacks = Vec::new();
+    let mut singles: Vec<usize> = Vec::new();
+    for group in groups {
+        if group.len() >= 2 {
+            push_burst(&mut stacks, run, &mut singles);
+            let mut by_exposure = group.clone();
+            by_exposure.sort_by(|&a, &b| offsets[a].total_cmp(&offsets[b]));
+            stacks.push(DetectedStack {
+                kind: StackKind::Bracket,
+                image_ids: group.iter().map(|&index| run[index].image_id).collect(),
+                cover_image_id: run[by_exposure[(by_exposure.len() - 1) / 2]].image_id,
+            });
+        } else {
+            let index = group[0];
+            if singles
+                .last()
+                .is_some_and(|&last| !same_exposure(offsets[last], offsets[index]))
+            {
+                push_burst(&mut stacks, run, &mut singles);
+            }
+            singles.push(index);
+        }
+    }
+    push_burst(&mut stacks, run, &mut singles);
+    stacks
+}
+
+fn push_burst(stacks: &mut Vec<DetectedStack>, run: &[&StackCandidate], frames: &mut Vec<usize>) {
+    if frames.len() >= 2 {
+        stacks.push(DetectedStack {
+            kind: StackKind::Burst,
+            image_ids: frames.iter().map(|&index| run[index].image_id).collect(),
+            cover_image_id: run[frames[0]].image_id,
+        });
+    }
+    frames.clear();
+}
+
+fn exposure_offsets(run: &[&StackCandidate]) -> Option<Vec<f64>> {
+    run.iter()
+        .map(|frame| frame.exposure_bias)
+        .collect::<Option<Vec<_>>>()
+        .or_else(|| run.iter().map(|frame| frame.shutter_speed).collect())
+}
+
+fn same_exposure(a: f64, b: f64) -> bool {
+    (a - b).abs() < EXPOSURE_TOLERANCE_EV
+}
+
+/// Parses `images.captured_at`, written either by rusqlite from a
+/// `DateTime<Utc>` or in EXIF / ISO 8601 form.
+pub fn parse_captured_at(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.naive_utc());
+    }
+    if let Ok(datetime) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z") {
+        return Some(datetime.naive_utc());
+    }
+    [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y:%m:%d %H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+/// Unstacked camera images with a capture time and a known camera. Derived
+/// images (HDR merges, panoramas) are left out.
+pub fn load_stack_candidates(conn: &Connection) -> Result<Vec<StackCandidate>, StackingError> {
+    let mut stmt = conn.prepare(
+        "SELECT i.id, i.captured_at, e.body_serial, e.camera_make, e.camera_model,
+                e.exposure_bias, e.shutter_speed
+         FROM images i
+         JOIN exif_metadata e ON e.image_id = i.id
+         WHERE i.captured_at IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM image_stack_members sm WHERE sm.image_id = i.id)
+           AND NOT EXISTS (SELECT 1 FROM image_derivations d WHERE d.derived_image_id = i.id)",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<f64>>(5)?,
+            row.get::<_, Option<f64>>(6)?,
+        ))
+    })?;
+
+    let mut candidates = Vec::new();
+    for row in rows {
+        let (image_id, captured_at, serial, make, model, exposure_bias, shutter_speed) = row?;
+        let Some(captured_at) = parse_captured_at(&captured_at) else {
+            continue;
+        };
+        let camera_key = match (serial.filter(|serial| !serial.is_empty()), make, model) {
+            (Some(serial), _, _) => serial,
+            (None, None, None) => continue,
+            (None, make, model) => {
+                format!("{}|{}", make.unwrap_or_default(), model.unwrap_or_default())
+            }
+        };
+        candidates.push(StackCandidate {
+            image_id,
+            captured_at,
+            camera_key,
+            exposure_bias,
+            shutter_speed,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Detects and persists stacks among unstacked images. Burst covers are the
+/// sharpest frame when quality metrics are available.
+pub fn auto_stack_images(
+    conn: &mut Connection,
+    options: &StackingOptions,
+) -> Result<Vec<ImageStack>, StackingError> {
+    let detected = detect_stacks(&load_stack_candidates(conn)?, options);
+
+    let tx = conn.transaction()?;
+    let mut stack_ids = Vec::with_capacity(detected.len());
+    for stack in &detected {
+        let cover = match stack.kind {
+            StackKind::Burst => sharpest_image(&tx, &stack.image_ids)?,
+            _ => None,
+        };
+        stack_ids.push(insert_stack(
+            &tx,
+            stack.kind,
+            cover.unwrap_or(stack.cover_image_id),
+            &stack.image_ids,
+        )?);
+    }
+    tx.commit()?;
+
+    stack_ids
+        .into_iter()
+        .map(|stack_id| get_stack(conn, stack_id))
+        .collect()
+}
+
+fn sharpest_image(conn: &Connection, image_ids: &[i64]) -> Result<Option<i64>, StackingError> {
+    let mut stmt =
+        conn.prepare("SELECT sharpness FROM image_quality_metrics WHERE image_id = ?1")?;
+    let mut best: Option<(i64, f64)> = None;
+    for &image_id in image_ids {
+        let sharpness: Option<f64> = stmt.query_row([image_id], |row| row.get(0)).optional()?;
+        match (sharpness, best) {
+            (Some(sharpness), Some((_, best_sharpness))) if sharpness <= best_sharpness => {}
+            (Some(sharpness), _) => best = Some((image_id, sharpness)),
+            (None, _) => {}
+        }
+    }
+    Ok(best.map(|(image_id, _)| image_id))
+}
+
+fn insert_stack(
+    conn: &Connection,
+    kind: StackKind,
+    cover_image_id: i64,
+    image_ids: &[i64],
+) -> Result<i64, StackingError> {
+    conn.execute(
+        "INSERT INTO image_stacks (cover_image_id, kind) VALUES (?1, ?2)",
+        params![cover_image_id, kind.as_str()],
+    )?;
+    let stack_id = conn.last_insert_rowid();
+    let mut stmt = conn.prepare(
+        "INSERT INTO image_stack_members (stack_id, image_id, position) VALUES (?1, ?2, ?3)",
+    )?;
+    for (position, image_id) in image_ids.iter().enumerate() {
+        stmt.execute(params![stack_id, image_id, position as i64])?;
+    }
+    Ok(stack_id)
+}
+
+/// Stacks the images by hand, in the given order. The cover defaults to the
+/// first image.
+pub fn create_stack(
+    conn: &mut Connection,
+    image_ids: &[i64],
+    cover_image_id: Option<i64>,
+) -> Result<ImageStack, StackingError> {
+    let mut seen = HashSet::new();
+    let image_ids: Vec<i64> = image_ids
+        .iter()
+        .copied()
+        .filter(|image_id| seen.insert(*image_id))
+        .collect();
+    if image_ids.len() < 2 {
+        return Err(StackingError::TooFewImages);
+    }
+    let cover_image_id = cover_image_id.unwrap_or(image_ids[0]);
+    if !image_ids.contains(&cover_image_id) {
+        return Err(StackingError::NotAMember(cover_image_id));
+    }
+
+    let tx = conn.transaction()?;
+    for &image_id in &image_ids {
+        if stack_of(&tx, image_id)?.is_some() {
+            return Err(StackingError::AlreadyStacked(image_id));
+        }
+    }
+    let stack_id = insert_stack(&tx, StackKind::Manual, cover_image_id, &image_ids)?;
+    tx.commit()?;
+
+    get_stack(conn, stack_id)
+}
+
+fn stack_of(conn: &Connection, image_id: i64) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT stack_id FROM image_stack_members WHERE image_id = ?1",
+        [image_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Dissolves the stack, leaving its images in the catalog.
+pub fn dissolve_stack(conn: &Connection, stack_id: i64) -> Result<(), StackingError> {
+    // Members go through ON DELETE CASCADE.
+    let deleted = conn.execute("DELETE FROM image_stacks WHERE id = ?1", [stack_id])?;
+    if deleted == 0 {
+        return Err(StackingError::StackNotFound(stack_id));
+    }
+    Ok(())
+}
+
+/// Takes images out of a stack. The stack is dissolved when fewer than two
+/// images remain (`None`); a removed cover passes to the first remaining image.
+pub fn remove_from_stack(
+    conn: &mut Connection,
+    stack_id: i64,
+    image_ids: &[i64],
+) -> Result<Option<ImageStack>, StackingError> {
+    let stack = get_stack(conn, stack_id)?;
+    if let Some(&outsider) = image_ids.iter().find(|id| !stack.image_ids.contains(id)) {
+        return Err(StackingError::NotAMember(outsider));
+    }
+    let remaining: Vec<i64> = stack
+        .image_ids
+        .iter()
+        .copied()
+        .filter(|id| !image_ids.contains(id))
+        .collect();
+    if remaining.len() < 2 {
+        dissolve_stack(conn, stack_id)?;
+        return Ok(None);
+    }
+
+    let tx = conn.transaction()?;
+    for image_id in image_ids {
+        tx.execute(
+            "DELETE FROM image_stack_members WHERE stack_id = ?1 AND image_id = ?2",
+            params![stack_id, image_id],
+        )?;
+    }
+    if !remaining.contains(&stack.cover_image_id) {
+        tx.execute(
+            "UPDATE image_stacks SET cover_image_id = ?1 WHERE id = ?2",
+            params![remaining[0], stack_id],
+        )?;
+    }
+    write_positions(&tx, stack_id, &remaining)?;
+    tx.commit()?;
+
+    get_stack(conn, stack_id).map(Some)
+}
+
+/// Sets the display order; `image_ids` must list every member exactly once.
+pub fn reorder_stack(
+    conn: &mut Connection,
+    stack_id: i64,
+    image_ids: &[i64],
+) -> Result<ImageStack, StackingError> {
+    let stack = get_stack(conn, stack_id)?;
+    let current: HashSet<i64> = stack.image_ids.iter().copied().collect();
+    let requested: HashSet<i64> = image_ids.iter().copied().collect();
+    if image_ids.len() != stack.image_ids.len() || current != requested {
+        return Err(StackingError::InvalidOrder(stack_id));
+    }
+
+    let tx = conn.transaction()?;
+    write_positions(&tx, stack_id, image_ids)?;
+    tx.commit()?;
+
+    get_stack(conn, stack_id)
+}
+
+fn write_positions(conn: &Connection, stack_id: i64, image_ids: &[i64]) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "UPDATE image_stack_members SET position = ?1 WHERE stack_id = ?2 AND image_id = ?3",
+    )?;
+    for (position, image_id) in image_ids.iter().enumerate() {
+        stmt.execute(params![position as i64, stack_id, image_id])?;
+    }
+    Ok(())
+}
+
+/// Collapsed stacks show only their cover in `get_all_images` stack-tops mode.
+pub fn set_stack_collapsed(
+    conn: &Connection,
+    stack_id: i64,
+    collapsed: bool,
+) -> Result<ImageStack, StackingError> {
+    let updated = conn.execute(
+        "UPDATE image_stacks SET collapsed = ?1 WHERE id = ?2",
+        params![collapsed, stack_id],
+    )?;
+    if updated == 0 {
+        return Err(StackingError::StackNotFound(stack_id));
+    }
+    get_stack(conn, stack_id)
+}
+
+pub fn set_stack_cover(
+    conn: &Connection,
+    stack_id: i64,
+    image_id: i64,
+) -> Result<ImageStack, StackingError> {
+    let stack = get_stack(conn, stack_id)?;
+    if !stack.image_ids.contains(&image_id) {
+        return Err(StackingError::NotAMember(image_id));
+    }
+    conn.execute(
+        "UPDATE image_stacks SET cover_image_id = ?1 WHERE id = ?2",
+        params![image_id, stack_id],
+    )?;
+    get_stack(conn, stack_id)
+}
+
+pub fn get_stack(conn: &Connection, stack_id: i64) -> Result<ImageStack, StackingError> {
+    let (cover_image_id, kind, collapsed) = conn
+        .query_row(
+            "SELECT cover_image_id, kind, collapsed FROM image_stacks WHERE id = ?1",
+            [stack_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            },
+        )
+        .optional()?
+        .ok_or(StackingError::StackNotFound(stack_id))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT image_id FROM image_stack_members WHERE stack_id = ?1 ORDER BY position, image_id",
+    )?;
+    let image_ids = stmt
+        .query_map([stack_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+
+    Ok(ImageStack {
+        id: stack_id,
+        cover_image_id,
+        kind: StackKind::try_from(kind.as_str())?,
+        collapsed,
+        image_ids,
+    })
+}
+
+pub fn list_stacks(conn: &Connection) -> Result<Vec<ImageStack>, StackingError> {
+    let mut stmt = conn.prepare("SELECT id FROM image_stacks ORDER BY id")?;
+    let stack_ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    stack_ids
+        .into_iter()
+        .map(|stack_id| get_stack(conn, stack_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    fn frame(
+        image_id: i64,
+        captured_at: &str,
+        camera_key: &str,
+        exposure_bias: Option<f64>,
+    ) -> StackCandidate {
+        StackCandidate {
+            image_id,
+            captured_at: parse_captured_at(captured_at).expect("valid timestamp"),
+            camera_key: camera_key.to_string(),
+            exposure_bias,
+            shutter_speed: Some(-7.0),
+        }
+    }
+
+    #[test]
+    fn test_detect_stacks_splits_bursts_by_time_and_camera() {
+        let candidates = vec![
+            frame(1, "2024-05-01 10:00:00.000", "A1", Some(0.0)),
+            frame(2, "2024-05-01 10:00:00.100", "A1", Some(0.0)),
+            frame(3, "2024-05-01 10:00:00.200", "A1", Some(0.0)),
+            // Second body shooting at the same moment.
+            frame(4, "2024-05-01 10:00:00.150", "B2", Some(0.0)),
+            frame(5, "2024-05-01 10:00:00.250", "B2", Some(0.0)),
+            // Same body, a minute later: new burst.
+            frame(6, "2024-05-01 10:01:00.000", "A1", Some(0.0)),
+            frame(7, "2024-05-01 10:01:01.000", "A1", Some(0.0)),
+            // Lone frame.
+            frame(8, "2024-05-01 11:00:00.000", "A1", Some(0.0)),
+        ];
+
+        let stacks = detect_stacks(&candidates, &StackingOptions::default());
+
+        let groups: Vec<(StackKind, Vec<i64>)> = stacks
+            .iter()
+            .map(|stack| (stack.kind, stack.image_ids.clone()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (StackKind::Burst, vec![1, 2, 3]),
+                (StackKind::Burst, vec![6, 7]),
+                (StackKind::Burst, vec![4, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_stacks_splits_consecutive_brackets() {
+        // Two -2/0/+2 brackets back to back, then a burst at 0 EV.
+        let evs = [0.0, -2.0, 2.0, 0.0, -2.0, 2.0, 0.0, 0.0];
+        let candidates: Vec<StackCandidate> = evs
+            .iter()
+            .enumerate()
+            .map(|(index, &ev)| {
+                frame(
+                    index as i64 + 1,
+                    &format!("2024-05-01 10:00:0{}", index),
+                    "A1",
+                    Some(ev),
+                )
+            })
+            .collect();
+
+        let stacks = detect_stacks(&candidates, &StackingOptions::default());
+
+        assert_eq!(
+            stacks,
+            vec![
+                DetectedStack {
+                    kind: StackKind::Bracket,
+                    image_ids: vec![1, 2, 3],
+                    cover_image_id: 1,
+                },
+                DetectedStack {
+                    kind: StackKind::Bracket,
+                    image_ids: vec![4, 5, 6],
+                    cover_image_id: 4,
+                },
+                DetectedStack {
+                    kind: StackKind::Burst,
+                    image_ids: vec![7, 8],
+                    cover_image_id: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_exposure_does_not_break_bracket() {
+        // +2 EV frame exposes for 4 s; the next frame starts right after.
+        let mut candidates = vec![
+            frame(1, "2024-05-01 20:00:00", "A1", Some(0.0)),
+            frame(2, "2024-05-01 20:00:02", "A1", Some(2.0)),
+            frame(3, "2024-05-01 20:00:07", "A1", Some(-2.0)),
+        ];
+        candidates[1].shutter_speed = Some(2.0);
+
+        let stacks = detect_stacks(&candidates, &StackingOptions::default());
+
+        assert_eq!(stacks.len(), 1);
+        assert_eq!(stacks[0].image_ids, vec![1, 2, 3]);
+        assert_eq!(stacks[0].cover_image_id, 1);
+    }
+
+    #[test]
+    fn test_parse_captured_at_formats() {
+        let expected = parse_captured_at("2024-05-01T10:00:03.250").expect("iso");
+        assert_eq!(
+            parse_captured_at("2024-05-01 10:00:03.250+00:00"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_captured_at("2024-05-01T10:00:03.250Z"),
+            Some(expected)
+        );
+        assert!(parse_captured_at("2024:05:01 10:00:03").is_some());
+        assert!(parse_captured_at("yesterday").is_none());
+    }
+
+    fn setup_db() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("catalog.db")).unwrap();
+        db.initialize().unwrap();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO images (id, blake3_hash, filename, extension, captured_at) VALUES
+                    (1, 'h1', 'a.arw', 'arw', '2024-05-01 10:00:00.000+00:00'),
+                    (2, 'h2', 'b.arw', 'arw', '2024-05-01 10:00:00.100+00:00'),
+                    (3, 'h3', 'c.arw', 'arw', '2024-05-01 10:00:00.200+00:00'),
+                    (4, 'h4', 'd.arw', 'arw', '2024-05-01 12:00:00.000+00:00'),
+                    (5, 'h5', 'e.arw', 'arw', '2024-05-01 12:00:00.100+00:00');
+                 INSERT INTO exif_metadata (image_id, camera_make, camera_model, body_serial,
+                                            exposure_bias, shutter_speed) VALUES
+                    (1, 'Sony', 'A7', '42', 0.0, -8.0),
+                    (2, 'Sony', 'A7', '42', 0.0, -8.0),
+                    (3, 'Sony', 'A7', '42', 0.0, -8.0),
+                    (4, 'Sony', 'A7', '42', 0.0, -8.0);
+                 INSERT INTO image_quality_metrics
+                    (image_id, sharpness, mean_luminance, highlights_clipped, shadows_clipped)
+                 VALUES (1, 50.0, 0.5, 0.0, 0.0), (2, 400.0, 0.5, 0.0, 0.0);",
+            )
+            .unwrap();
+        (dir, db)
+    }
+
+    #[test]
+    fn test_auto_stack_persists_with_sharpest_cover() {
+        let (_dir, mut db) = setup_db();
+
+        let stacks = auto_stack_images(db.connection(), &StackingOptions::default()).unwrap();
+        // Already stacked images are not stacked again.
+        let again = auto_stack_images(db.connection(), &StackingOptions::default()).unwrap();
+
+        assert_eq!(stacks.len(), 1);
+        assert_eq!(stacks[0].kind, StackKind::Burst);
+        assert_eq!(stacks[0].image_ids, vec![1, 2, 3]);
+        assert_eq!(stacks[0].cover_image_id, 2);
+        assert!(stacks[0].collapsed);
+        assert!(again.is_empty());
+    }
+
+    #[test]
+    fn test_stack_tops_condition_hides_collapsed_members() {
+        let (_dir, mut db) = setup_db();
+        let stack = create_stack(db.connection(), &[1, 2, 3], Some(2)).unwrap();
+        let visible = |db: &mut Database| -> Vec<i64> {
+            let mut stmt = db
+                .connection()
+                .prepare(&format!(
+                    "SELECT i.id FROM images i WHERE {} ORDER BY i.id",
+                    STACK_TOPS_CONDITION
+                ))
+                .unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<i64>>>()
+                .unwrap()
+        };
+
+        assert_eq!(visible(&mut db), vec![2, 4, 5]);
+        set_stack_collapsed(db.connection(), stack.id, false).unwrap();
+        assert_eq!(visible(&mut db), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_manual_stack_lifecycle() {
+        let (_dir, mut db) = setup_db();
+        let conn = db.connection();
+
+        let stack = create_stack(conn, &[4, 5, 4], None).unwrap();
+        assert_eq!(stack.image_ids, vec![4, 5]);
+        assert_eq!(stack.kind, StackKind::Manual);
+        assert!(matches!(
+            create_stack(conn, &[5, 1], None),
+            Err(StackingError::AlreadyStacked(5))
+        ));
+        assert!(matches!(
+            create_stack(conn, &[1], None),
+            Err(StackingError::TooFewImages)
+        ));
+
+        let stack = create_stack(conn, &[1, 2, 3], Some(1)).unwrap();
+        let reordered = reorder_stack(conn, stack.id, &[3, 1, 2]).unwrap();
+        assert_eq!(reordered.image_ids, vec![3, 1, 2]);
+        assert!(matches!(
+            reorder_stack(conn, stack.id, &[3, 1]),
+            Err(StackingError::InvalidOrder(_))
+        ));
+
+        let expanded = set_stack_collapsed(conn, stack.id, false).unwrap();
+        assert!(!expanded.collapsed);
+        let covered = set_stack_cover(conn, stack.id, 2).unwrap();
+        assert_eq!(covered.cover_image_id, 2);
+
+        let remaining = remove_from_stack(conn, stack.id, &[2])
+            .unwrap()
+            .expect("two images remain");
+        assert_eq!(remaining.image_ids, vec![3, 1]);
+        assert_eq!(remaining.cover_image_id, 3);
+        assert_eq!(remove_from_stack(conn, stack.id, &[1]).unwrap(), None);
+        assert!(matches!(
+            get_stack(conn, stack.id),
+            Err(StackingError::StackNotFound(_))
+        ));
+
+        assert_eq!(list_stacks(conn).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+#!/usr/bin/env python3
+"""Generates block5x7.ttf, the font used by the text watermark tests.
+
+Each glyph is a 5x7 grid of square cells, one TrueType contour per filled
+cell. Letters A-Z (lowercase maps to the same glyphs), digits, a few
+punctuation marks and the copyright sign are covered; anything else renders
+as the empty .notdef glyph.
+
+Usage: python3 generate_block_font.py [output.ttf]
+"""
+
+import struct
+import sys
+from pathlib import Path
+
+UNITS_PER_EM = 1000
+CELL = 100
+ADVANCE = 600
+ASCENT = 800
+DESCENT = -200
+
+GLYPHS = {
+    " ": [],
+    "A": [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"],
+    "B": ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."],
+    "C": [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."],
+    "D": ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."],
+    "E": ["#####", "#....", "#....", "####.", "#....", "#....", "#####"],
+    "F": ["#####", "#....", "#....", "####.", "#....", "#....", "#...."],
+    "G": [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"],
+    "H": ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"],
+    "I": [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."],
+    "J": ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."],
+    "K": ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"],
+    "L": ["#....", "#....", "#....", "#....", "#....", "#....", "#####"],
+    "M": ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"],
+    "N": ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"],
+    "O": [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."],
+    "P": ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."],
+    "Q": [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"],
+    "R": ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"],
+    "S": [".####", "#....", "#....", ".###.", "....#", "....#", "####."],
+    "T": ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."],
+    "U": ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."],
+    "V": ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."],
+    "W": ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."],
+    "X": ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"],
+    "Y": ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."],
+    "Z": ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"],
+    "0": [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."],
+    "1": ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."],
+    "2": [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"],
+    "3": ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."],
+    "4": ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."],
+    "5": ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."],
+    "6": ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."],
+    "7": ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."],
+    "8": [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."],
+    "9": [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."],
+    ".": [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."],
+    ",": [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."],
+    "-": [".....", ".....", ".....", "#####", ".....", ".....", "....."],
+    "©": [".###.", "#...#", "#.###", "#.#.#", "#.###", "#...#", ".###."],
+}
+
+
+def glyph_outline(rows):
+    """Simple glyph with one clockwise square contour per filled cell."""
+    cells = [
+        (column, len(rows) - 1 - row)
+        for row, line in enumerate(rows)
+        for column, mark in enumerate(line)
+        if mark == "#"
+    ]
+    if not cells:
+        return b"", (0, 0, 0, 0)
+
+    points = []
+    for column, row in cells:
+        x0, y0 = column * CELL, row * CELL
+        x1, y1 = x0 + CELL, y0 + CELL
+        points += [(x0, y0), (x0, y1), (x1, y1), (x1, y0)]
+
+    xs = [x for x, _ in points]
+    ys = [y for _, y in points]
+    bbox = (min(xs), min(ys), max(xs), max(ys))
+
+    data = struct.pack(">hhhhh", len(cells), *bbox)
+    data += b"".join(struct.pack(">H", index * 4 + 3) for index in range(len(cells)))
+    data += struct.pack(">H", 0)  # no instructions
+    data += bytes([0x01]) * len(points)  # on-curve, 16-bit deltas
+    previous = 0
+    for x, _ in points:
+        data += struct.pack(">h", x - previous)
+        previous = x
+    previous = 0
+    for _, y in points:
+        data += struct.pack(">h", y - previous)
+        previous = y
+    if len(data) % 2:
+        data += b"\0"
+    return data, bbox
+
+
+def cmap_table(mapping):
+    """Format 4 subtable, one segment per code point."""
+    codes = sorted(mapping)
+    segments = [(code, code, mapping[code]) for code in codes] + [(0xFFFF, 0xFFFF, 0)]
+    count = len(segments)
+    search_range = 2 ** (count.bit_length() - 1) * 2
+    entry_selector = count.bit_length() - 1
+    range_shift = count * 2 - search_range
+
+    body = struct.pack(">HHHH", count * 2, search_range, entry_selector, range_shift)
+    body += b"".join(struct.pack(">H", end) for _, end, _ in segments)
+    body += struct.pack(">H", 0)
+    body += b"".join(struct.pack(">H", start) for start, _, _ in segments)
+    # idDelta is added modulo 65536 to the code point to get the glyph id.
+    body += b"".join(struct.pack(">H", (glyph - start) % 0x10000) for start, _, glyph in segments)
+    body += b"".join(struct.pack(">H", 0) for _ in segments)
+    subtable = struct.pack(">HHH", 4, 6 + len(body), 0) + body
+    return struct.pack(">HHHHI", 0, 1, 3, 1, 12) + subtable
+
+
+def font_bytes():
+    names = list(GLYPHS)
+    outlines = [(b"", (0, 0, 0, 0))] + [glyph_outline(GLYPHS[name]) for name in names]
+
+    glyf = b""
+    loca = []
+    for data, _ in outlines:
+        loca.append(len(glyf))
+        glyf += data
+    loca.append(len(glyf))
+
+    mapping = {}
+    for index, name in enumerate(names, start=1):
+        mapping[ord(name)] = index
+        if name.isalpha() and name.isascii():
+            mapping[ord(name.lower())] = index
+
+    num_glyphs = len(outlines)
+    boxes = [bbox for data, bbox in outlines if data]
+    x_min = min(box[0] for box in boxes)
+    y_min = min(box[1] for box in boxes)
+    x_max = max(box[2] for box in boxes)
+    y_max = max(box[3] for box in boxes)
+    max_contours = max("".join(rows).count("#") for rows in GLYPHS.values())
+    max_points = max_contours * 4
+
+    tables = {
+        b"head": struct.pack(
+            ">IIIIHHqqhhhhHHhhh",
+            0x00010000, 0x00010000, 0, 0x5F0F3CF5, 0b1011, UNITS_PER_EM,
+            0, 0, x_min, y_min, x_max, y_max, 0, 8, 2, 1, 0,
+        ),
+        b"hhea": struct.pack(
+            ">IhhhHhhhhhhhhhhhH",
+            0x00010000, ASCENT, DESCENT, 0, ADVANCE, 0, 0, x_max, 1, 0, 0,
+            0, 0, 0, 0, 0, num_glyphs,
+        ),
+        b"maxp": struct.pack(
+            ">IHHHHHHHHHHHHHH",
+            0x00010000, num_glyphs, max_points, max_contours, 0, 0, 2,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ),
+        b"hmtx": b"".join(struct.pack(">Hh", ADVANCE, bbox[0]) for _, bbox in outlines),
+        b"cmap": cmap_table(mapping),
+        b"loca": b"".join(struct.pack(">I", offset) for offset in loca),
+        b"glyf": glyf,
+        b"post": struct.pack(">IIhhIIIII", 0x00030000, 0, -100, 50, 1, 0, 0, 0, 0),
+    }
+
+    tags = sorted(tables)
+    count = len(tags)
+    search_range = 2 ** (count.bit_length() - 1) * 16
+    header = struct.pack(
+        ">IHHHH", 0x00010000, count, search_range, count.bit_length() - 1,
+        count * 16 - search_range,
+    )
+    offset = len(header) + 16 * count
+    directory = b""
+    data = b""
+    for tag in tags:
+        table = tables[tag]
+        padded = table + b"\0" * (-len(table) % 4)
+        checksum = sum(struct.unpack(">%dI" % (len(padded) // 4), padded)) & 0xFFFFFFFF
+        directory += struct.pack(">4sIII", tag, checksum, offset + len(data), len(table))
+        data += padded
+    return header + directory + data
+
+
+if __name__ == "__main__":
+    output = Path(sys.argv[1]) if len(sys.argv) > 1 else Path(__file__).with_name("block5x7.ttf")
+    output.write_bytes(font_bytes())
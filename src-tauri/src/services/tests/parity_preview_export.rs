@@ -1,15 +1,25 @@
 use crate::models::event::{Event, EventPayload, EventType, TargetType};
 use crate::services::event_sourcing::EventStore;
-use crate::services::export_pipeline::{export_image_with_edits, ExportFormat, ExportRequest};
+use crate::services::export_encoding::ExportFormatOptions;
+use crate::services::export_metadata::ExportMetadataOptions;
+use crate::services::export_pipeline::{
+    export_image_with_edits, ExportFormat, ExportRequest, ExportTarget,
+};
+use crate::services::export_sizing::ExportResize;
 use chrono::Utc;
 use image::RgbaImage;
-use luminafast_image_core::{apply_filters, PixelFilters};
+use luminafast_image_core::{apply_filters, compare_rgba8, ComparisonOptions, PixelFilters};
 use rusqlite::{params, Connection};
 use serde_json::json;
 use std::path::Path;
 use tempfile::tempdir;
 
-const PREVIEW_EXPORT_PARITY_DELTA_THRESHOLD: f64 = 2.0;
+/// Export must match the shared-core preview up to 8-bit rounding: no pixel
+/// may show a just-noticeable colour difference (ΔE00 ≥ 1).
+const PARITY_MIN_PSNR_DB: f64 = 50.0;
+const PARITY_MIN_SSIM: f64 = 0.999;
+const PARITY_MAX_MEAN_DELTA_E: f64 = 0.25;
+const PARITY_MAX_DELTA_E: f64 = 1.0;
 
 #[derive(Clone)]
 struct ParityPreset {
@@ -202,6 +212,12 @@ fn setup_test_db() -> Connection {
             event_ids TEXT NOT NULL,
             created_at TEXT NOT NULL
         );
+
+        CREATE TABLE image_process_versions (
+            image_id INTEGER PRIMARY KEY,
+            process_version INTEGER NOT NULL,
+            pinned_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
     "#,
     )
     .expect("create export parity schema");
@@ -297,20 +313,49 @@ fn render_preview_reference(preset: &ParityPreset) -> Vec<u8> {
         .expect("render preview reference from shared core")
 }
 
-fn compute_mean_absolute_rgb_delta(actual: &[u8], expected: &[u8]) -> f64 {
-    assert_eq!(actual.len(), expected.len(), "buffer length mismatch");
-
-    let mut sum = 0.0_f64;
-    let mut channels = 0_usize;
-
-    for index in (0..actual.len()).step_by(4) {
-        sum += (actual[index] as f64 - expected[index] as f64).abs();
-        sum += (actual[index + 1] as f64 - expected[index + 1] as f64).abs();
-        sum += (actual[index + 2] as f64 - expected[index + 2] as f64).abs();
-        channels += 3;
-    }
-
-    sum / channels as f64
+fn assert_preview_export_parity(label: &str, preset: &ParityPreset, exported: &[u8]) {
+    let expected = render_preview_reference(preset);
+    let comparison = compare_rgba8(
+        &expected,
+        exported,
+        preset.width,
+        preset.height,
+        &ComparisonOptions::default(),
+    )
+    .expect("compare export against preview");
+
+    assert!(
+        comparison.psnr >= PARITY_MIN_PSNR_DB,
+        "Preset '{}' ({}) PSNR too low: {:.2} dB < {} dB",
+        preset.name,
+        label,
+        comparison.psnr,
+        PARITY_MIN_PSNR_DB
+    );
+    assert!(
+        comparison.ssim >= PARITY_MIN_SSIM,
+        "Preset '{}' ({}) SSIM too low: {:.5} < {}",
+        preset.name,
+        label,
+        comparison.ssim,
+        PARITY_MIN_SSIM
+    );
+    assert!(
+        comparison.mean_delta_e <= PARITY_MAX_MEAN_DELTA_E,
+        "Preset '{}' ({}) mean ΔE00 too high: {:.3} > {}",
+        preset.name,
+        label,
+        comparison.mean_delta_e,
+        PARITY_MAX_MEAN_DELTA_E
+    );
+    assert!(
+        comparison.max_delta_e <= PARITY_MAX_DELTA_E,
+        "Preset '{}' ({}) max ΔE00 too high: {:.3} > {}",
+        preset.name,
+        label,
+        comparison.max_delta_e,
+        PARITY_MAX_DELTA_E
+    );
 }
 
 fn run_export_for_preset(
@@ -363,6 +408,15 @@ fn run_export_for_preset(
         image_id,
         output_path: output_path.clone(),
         format: ExportFormat::Tiff,
+        resize: ExportResize::Original,
+        options: ExportFormatOptions::default(),
+        // Pixel parity only: the schema has no catalog metadata tables.
+        metadata: ExportMetadataOptions {
+            embed: false,
+            ..ExportMetadataOptions::default()
+        },
+        watermark: None,
+        target: ExportTarget::Current,
     };
 
     let result = export_image_with_edits(conn, &request).expect("export image for parity test");
@@ -382,18 +436,9 @@ fn test_preview_export_parity_with_events_only() {
 
     for (index, preset) in parity_presets().iter().enumerate() {
         let image_id = (index as i64) + 1;
-        let expected_preview = render_preview_reference(preset);
         let exported_buffer = run_export_for_preset(&conn, image_id, preset, false);
 
-        let mean_delta = compute_mean_absolute_rgb_delta(&exported_buffer, &expected_preview);
-
-        assert!(
-            mean_delta <= PREVIEW_EXPORT_PARITY_DELTA_THRESHOLD,
-            "Preset '{}' exceeded parity threshold: delta={} > {}",
-            preset.name,
-            mean_delta,
-            PREVIEW_EXPORT_PARITY_DELTA_THRESHOLD
-        );
+        assert_preview_export_parity("events", preset, &exported_buffer);
     }
 }
 
@@ -403,17 +448,8 @@ fn test_preview_export_parity_with_snapshot_seed() {
 
     for (index, preset) in parity_presets().iter().enumerate() {
         let image_id = (index as i64) + 101;
-        let expected_preview = render_preview_reference(preset);
         let exported_buffer = run_export_for_preset(&conn, image_id, preset, true);
 
-        let mean_delta = compute_mean_absolute_rgb_delta(&exported_buffer, &expected_preview);
-
-        assert!(
-            mean_delta <= PREVIEW_EXPORT_PARITY_DELTA_THRESHOLD,
-            "Preset '{}' (snapshot path) exceeded parity threshold: delta={} > {}",
-            preset.name,
-            mean_delta,
-            PREVIEW_EXPORT_PARITY_DELTA_THRESHOLD
-        );
+        assert_preview_export_parity("snapshot path", preset, &exported_buffer);
     }
 }
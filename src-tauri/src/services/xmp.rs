@@ -10,6 +10,8 @@
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
+use rusqlite::Connection;
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
@@ -34,6 +36,68 @@ pub fn build_xmp_path(image_path: &str) -> PathBuf {
     Path::new(image_path).with_extension("xmp")
 }
 
+/// Charge les tags d'une image depuis le catalogue.
+///
+/// Retourne (flat_tags, hierarchical_subjects).
+/// `hierarchical_subjects` suit la convention Lightroom : "Parent/Enfant/SousEnfant".
+pub fn load_image_tags(
+    conn: &Connection,
+    image_id: i64,
+) -> rusqlite::Result<(Vec<String>, Vec<String>)> {
+    // Charger tous les tags avec leur parent_id
+    let mut stmt = conn.prepare("SELECT id, name, parent_id FROM tags")?;
+    let lookup: HashMap<u32, (String, Option<u32>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+        .collect::<Result<_, _>>()?;
+
+    // Charger les tag ids de l'image
+    let mut stmt =
+        conn.prepare("SELECT tag_id FROM image_tags WHERE image_id = ? ORDER BY tag_id")?;
+    let image_tag_ids: Vec<u32> = stmt
+        .query_map([image_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    let mut flat_tags = Vec::new();
+    let mut hierarchical_subjects = Vec::new();
+
+    for tag_id in &image_tag_ids {
+        if let Some((name, _)) = lookup.get(tag_id) {
+            flat_tags.push(name.clone());
+
+            // Construire le chemin hiérarchique complet : "Racine/Parent/Enfant"
+            let path = build_tag_path(*tag_id, &lookup);
+            if path.contains('/') {
+                // Uniquement si hiérarchique (au moins 2 niveaux)
+                hierarchical_subjects.push(path);
+            }
+        }
+    }
+
+    Ok((flat_tags, hierarchical_subjects))
+}
+
+/// Remonte la chaîne parent jusqu'à la racine et construit le chemin slash-séparé.
+fn build_tag_path(tag_id: u32, lookup: &HashMap<u32, (String, Option<u32>)>) -> String {
+    let mut parts = Vec::new();
+    let mut current_id = tag_id;
+
+    // Protection contre les cycles (max 20 niveaux)
+    for _ in 0..20 {
+        if let Some((name, parent_id)) = lookup.get(&current_id) {
+            parts.push(name.clone());
+            match parent_id {
+                Some(pid) => current_id = *pid,
+                None => break,
+            }
+        } else {
+            break;
+        }
+    }
+
+    parts.reverse();
+    parts.join("/")
+}
+
 /// Lit et parse un fichier `.xmp` de manière asynchrone.
 pub async fn read_xmp_async(xmp_path: &Path) -> Result<XmpData, String> {
     let content = match tokio::fs::read_to_string(xmp_path).await {
@@ -145,6 +145,15 @@ describe('App export shortcut integration', () => {
       height: 3000,
       appliedEditEvents: 4,
       usedSnapshot: false,
+      editState: {
+        target: { kind: 'current' },
+        snapshotId: null,
+        snapshotName: null,
+        lastEventAt: null,
+      },
+      recipeHash: 'a1b2c3',
+      quality: null,
+      fileSizeBytes: 72_000_000,
     });
 
     render(<App />);
@@ -8,6 +8,9 @@ import { useEditStore } from '@/stores/editStore';
 vi.mock('@/services/catalogService', () => ({
   CatalogService: {
     getEditEvents: vi.fn(),
+    getEditRecipe: vi
+      .fn()
+      .mockResolvedValue({ schemaVersion: 1, processVersion: 1, adjustments: {} }),
   },
 }));
 
@@ -68,6 +68,7 @@ export const BeforeAfterComparison = ({
             position={splitPosition}
             onPositionChange={onSplitPositionChange}
             editState={editState}
+            imageId={imageId}
           />
         )}
 
@@ -79,6 +80,7 @@ export const BeforeAfterComparison = ({
             opacity={opacity}
             onOpacityChange={onOpacityChange}
             editState={editState}
+            imageId={imageId}
           />
         )}
 
@@ -88,6 +90,7 @@ export const BeforeAfterComparison = ({
             beforeUrl={beforeUrl}
             afterUrl={afterUrl}
             editState={editState}
+            imageId={imageId}
             containerRef={containerRef as React.RefObject<HTMLDivElement>}
           />
         )}
@@ -15,6 +15,7 @@ export const OverlayComparison = ({
   opacity,
   onOpacityChange,
   editState,
+  imageId,
 }: OverlayComparisonProps) => {
   const canvasRef = useRef<HTMLCanvasElement>(null);
 
@@ -26,7 +27,7 @@ export const OverlayComparison = ({
   );
 
   // Rendu WASM du canvas overlay avec filtres appliqués
-  useWasmCanvasRender(canvasRef, afterUrl, editState);
+  useWasmCanvasRender(canvasRef, afterUrl, editState, undefined, undefined, imageId);
 
   return (
     <div className="flex flex-col w-full h-full bg-zinc-950">
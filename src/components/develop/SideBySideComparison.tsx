@@ -13,6 +13,7 @@ export const SideBySideComparison = ({
   beforeUrl,
   afterUrl,
   editState,
+  imageId,
   containerRef,
 }: SideBySideComparisonProps) => {
   const canvasRef = useRef<HTMLCanvasElement>(null);
@@ -54,7 +55,7 @@ export const SideBySideComparison = ({
   }, []);
 
   // Rendu WASM du canvas bas avec filtres appliqués
-  useWasmCanvasRender(canvasRef, afterUrl, editState);
+  useWasmCanvasRender(canvasRef, afterUrl, editState, undefined, undefined, imageId);
 
   // Transformation CSS appliquée aux deux images
   const transform = `scale(${zoom}) translate(${panX}px, ${panY}px)`;
@@ -15,6 +15,7 @@ export const SplitViewComparison = ({
   position,
   onPositionChange,
   editState,
+  imageId,
 }: SplitViewComparisonProps) => {
   const containerRef = useRef<HTMLDivElement>(null);
   const canvasRef = useRef<HTMLCanvasElement>(null);
@@ -58,7 +59,7 @@ export const SplitViewComparison = ({
   }, [isDragging, handleMouseMove, handleMouseUp]);
 
   // Rendu WASM de l'image après avec filtres appliqués
-  useWasmCanvasRender(canvasRef, afterUrl, editState);
+  useWasmCanvasRender(canvasRef, afterUrl, editState, undefined, undefined, imageId);
 
   return (
     <div ref={containerRef} className="flex w-full h-full overflow-hidden bg-zinc-950">
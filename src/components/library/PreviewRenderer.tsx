@@ -135,6 +135,7 @@ export const PreviewRenderer: React.FC<PreviewRendererProps> = ({
     pixelFilters,
     2048, // Standard max width for clean rendering
     1536, // Standard max height (aspect ratio 4:3)
+    imageId, // Retouches heal/clone de la recette
   );
 
   // Monitor EditStore changes and update filters reactively (all 9 filters for WASM)
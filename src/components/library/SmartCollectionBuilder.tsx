@@ -27,6 +27,11 @@ const FIELD_TYPES: Record<SmartQueryField, 'numeric' | 'string' | 'enum'> = {
   flag: 'enum',
   color_label: 'enum',
   filename: 'string',
+  sharpness: 'numeric',
+  brightness: 'numeric',
+  highlights_clipped: 'numeric',
+  shadows_clipped: 'numeric',
+  color: 'enum',
 };
 
 const FIELD_LABELS: Record<SmartQueryField, string> = {
@@ -40,6 +45,11 @@ const FIELD_LABELS: Record<SmartQueryField, string> = {
   flag: 'Flag',
   color_label: 'Color Label',
   filename: 'Filename',
+  sharpness: 'Sharpness',
+  brightness: 'Brightness (0-1)',
+  highlights_clipped: 'Clipped Highlights (0-1)',
+  shadows_clipped: 'Crushed Shadows (0-1)',
+  color: 'Dominant Colour',
 };
 
 // Operator definitions by field type
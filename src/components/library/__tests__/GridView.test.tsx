@@ -8,6 +8,9 @@ import { DEFAULT_EDIT_STATE } from '../../../types';
 vi.mock('@/services/catalogService', () => ({
   CatalogService: {
     getEditEvents: vi.fn().mockResolvedValue([]),
+    getEditRecipe: vi
+      .fn()
+      .mockResolvedValue({ schemaVersion: 1, processVersion: 1, adjustments: {} }),
   },
 }));
 
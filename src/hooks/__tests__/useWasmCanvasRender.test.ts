@@ -0,0 +1,147 @@
+import { describe, it, expect, beforeEach, afterEach, vi } from 'vitest';
+import { renderHook, waitFor } from '@testing-library/react';
+import { useWasmCanvasRender } from '../useWasmCanvasRender';
+import { CatalogService } from '@/services/catalogService';
+import { renderWithWasm } from '@/services/wasmRenderingService';
+import type { PixelFilterState, RetouchSpot } from '@/types/rendering';
+
+vi.mock('@/services/catalogService', () => ({
+  CatalogService: {
+    getEditRecipe: vi.fn(),
+  },
+}));
+
+vi.mock('@/services/wasmRenderingService', () => ({
+  renderWithWasm: vi.fn().mockResolvedValue(undefined),
+}));
+
+const mockGetEditRecipe = vi.mocked(CatalogService.getEditRecipe);
+const mockRenderWithWasm = vi.mocked(renderWithWasm);
+
+const NEUTRAL_FILTERS: PixelFilterState = {
+  exposure: 0,
+  contrast: 0,
+  saturation: 0,
+  highlights: 0,
+  shadows: 0,
+  clarity: 0,
+  vibrance: 0,
+  colorTemp: 0,
+  tint: 0,
+};
+
+const DUST_SPOT: RetouchSpot = {
+  mode: 'heal',
+  target: [{ x: 0.2, y: 0.3 }],
+  source: { x: 0.6, y: 0.3 },
+  radius: 0.02,
+};
+
+// Image qui « charge » dès que src est affecté (jsdom ne charge pas les images)
+class LoadedImage {
+  width = 400;
+  height = 300;
+  crossOrigin = '';
+  onload: (() => void) | null = null;
+  onerror: (() => void) | null = null;
+
+  set src(_value: string) {
+    queueMicrotask(() => this.onload?.());
+  }
+}
+
+describe('useWasmCanvasRender', () => {
+  const OriginalImage = globalThis.Image;
+
+  beforeEach(() => {
+    vi.clearAllMocks();
+    globalThis.Image = LoadedImage as unknown as typeof Image;
+    vi.spyOn(HTMLCanvasElement.prototype, 'getContext').mockReturnValue(null);
+  });
+
+  afterEach(() => {
+    globalThis.Image = OriginalImage;
+    vi.restoreAllMocks();
+  });
+
+  it('renders retouch-only edits through WASM with the recipe spots', async () => {
+    mockGetEditRecipe.mockResolvedValue({
+      schemaVersion: 1,
+      processVersion: 1,
+      adjustments: {},
+      retouch: [DUST_SPOT],
+    });
+    const canvasRef = { current: document.createElement('canvas') };
+
+    renderHook(() =>
+      useWasmCanvasRender(canvasRef, '/preview.jpg', NEUTRAL_FILTERS, undefined, undefined, 7),
+    );
+
+    await waitFor(() => {
+      expect(mockRenderWithWasm).toHaveBeenCalledWith(
+        canvasRef.current,
+        '/preview.jpg',
+        NEUTRAL_FILTERS,
+        400,
+        300,
+        [DUST_SPOT],
+      );
+    });
+    expect(mockGetEditRecipe).toHaveBeenCalledWith(7);
+  });
+
+  it('loads the recipe once per image, not on every filter change', async () => {
+    mockGetEditRecipe.mockResolvedValue({
+      schemaVersion: 1,
+      processVersion: 1,
+      adjustments: {},
+      retouch: [DUST_SPOT],
+    });
+    const canvasRef = { current: document.createElement('canvas') };
+    const brighter: PixelFilterState = { ...NEUTRAL_FILTERS, exposure: 0.5 };
+
+    const { rerender } = renderHook(
+      ({ filters }) =>
+        useWasmCanvasRender(canvasRef, '/preview.jpg', filters, undefined, undefined, 7),
+      { initialProps: { filters: NEUTRAL_FILTERS } },
+    );
+    await waitFor(() => {
+      expect(mockRenderWithWasm).toHaveBeenCalledWith(
+        canvasRef.current,
+        '/preview.jpg',
+        NEUTRAL_FILTERS,
+        400,
+        300,
+        [DUST_SPOT],
+      );
+    });
+
+    rerender({ filters: brighter });
+
+    await waitFor(() => {
+      expect(mockRenderWithWasm).toHaveBeenCalledWith(
+        canvasRef.current,
+        '/preview.jpg',
+        brighter,
+        400,
+        300,
+        [DUST_SPOT],
+      );
+    });
+    expect(mockGetEditRecipe).toHaveBeenCalledTimes(1);
+  });
+
+  it('draws the source directly when there are neither filters nor spots', async () => {
+    mockGetEditRecipe.mockResolvedValue({ schemaVersion: 1, processVersion: 1, adjustments: {} });
+    const canvasRef = { current: document.createElement('canvas') };
+
+    renderHook(() =>
+      useWasmCanvasRender(canvasRef, '/preview.jpg', NEUTRAL_FILTERS, undefined, undefined, 7),
+    );
+
+    await waitFor(() => {
+      expect(mockGetEditRecipe).toHaveBeenCalledWith(7);
+    });
+    expect(mockRenderWithWasm).not.toHaveBeenCalled();
+  });
+});
@@ -8,16 +8,32 @@
  * - Chargement de l'image source
  * - Détermination des dimensions (adaptées aux contraintes max)
  * - Conversion optionnelle des filtres (si EditState reçu)
+ * - Chargement des retouches heal/clone de la recette (si imageId fourni)
  * - Appel à renderWithWasm() si modifications présentes
  * - Fallback: dessin direct si pas de filtres
  */
 
 import type { RefObject } from 'react';
-import { useEffect } from 'react';
+import { useEffect, useState } from 'react';
 import { renderWithWasm } from '@/services/wasmRenderingService';
+import { CatalogService } from '@/services/catalogService';
 import { editStateToPixelFilters, hasNonNeutralFilters } from '@/lib/filterUtils';
 import type { EditState } from '@/types';
-import type { PixelFilterState } from '@/types/rendering';
+import type { PixelFilterState, RetouchSpot } from '@/types/rendering';
+
+/**
+ * Retouches heal/clone de la recette d'une image (vide si indisponible)
+ */
+async function loadRetouchSpots(imageId: number | undefined): Promise<RetouchSpot[]> {
+  if (imageId === undefined) return [];
+  try {
+    const recipe = await CatalogService.getEditRecipe(imageId);
+    return recipe?.retouch ?? [];
+  } catch (error) {
+    console.warn(`[useWasmCanvasRender] Recette indisponible pour l'image ${imageId}:`, error);
+    return [];
+  }
+}
 
 /**
  * Hook personnalisé pour rendu WASM d'une image sur un canvas
@@ -28,6 +44,7 @@ import type { PixelFilterState } from '@/types/rendering';
  * @param filters - État des modifications : EditState (EditEvent[]) ou PixelFilterState
  * @param maxWidth - Largeur maximale disponible (optionnel, pour responsive)
  * @param maxHeight - Hauteur maximale disponible (optionnel, pour responsive)
+ * @param imageId - Image dont la recette fournit les retouches (optionnel)
  *
  * @example
  * const canvasRef = useRef<HTMLCanvasElement>(null);
@@ -39,7 +56,23 @@ export function useWasmCanvasRender(
   filters: EditState | PixelFilterState | undefined,
   maxWidth?: number,
   maxHeight?: number,
+  imageId?: number,
 ): void {
+  // Retouches chargées une fois par image : la recette n'est pas relue
+  // à chaque changement de filtres (rejoue tout l'historique côté Rust)
+  const [retouch, setRetouch] = useState<RetouchSpot[]>([]);
+
+  useEffect(() => {
+    let cancelled = false;
+    setRetouch([]);
+    loadRetouchSpots(imageId).then((spots) => {
+      if (!cancelled) setRetouch(spots);
+    });
+    return () => {
+      cancelled = true;
+    };
+  }, [imageId]);
+
   useEffect(() => {
     const canvas = canvasRef.current;
     if (!canvas || !imageUrl) return;
@@ -87,10 +120,18 @@ export function useWasmCanvasRender(
             pixelFilters = editStateToPixelFilters(undefined);
           }
 
-          // Déterminer si des filtres non-neutres sont appliqués
-          if (hasNonNeutralFilters(pixelFilters)) {
+          // Déterminer si des filtres non-neutres ou des retouches (heal/clone,
+          // rejouées par WASM comme à l'export) sont appliqués
+          if (hasNonNeutralFilters(pixelFilters) || retouch.length > 0) {
             // Utiliser WASM pour le rendu avec filtres appliqués
-            await renderWithWasm(canvas, imageUrl, pixelFilters, canvasWidth, canvasHeight);
+            await renderWithWasm(
+              canvas,
+              imageUrl,
+              pixelFilters,
+              canvasWidth,
+              canvasHeight,
+              retouch,
+            );
           } else {
             // Fallback : dessiner l'image sans filtres
             const ctx = canvas.getContext('2d');
@@ -111,5 +152,5 @@ export function useWasmCanvasRender(
     };
 
     renderImage();
-  }, [canvasRef, imageUrl, filters, maxWidth, maxHeight]);
+  }, [canvasRef, imageUrl, filters, maxWidth, maxHeight, retouch]);
 }
@@ -164,3 +164,55 @@ describe('CatalogService — Collection Methods (Phase 3.2)', () => {
     });
   });
 });
+
+describe('CatalogService — getImageQualityMetrics', () => {
+  beforeEach(() => {
+    vi.clearAllMocks();
+  });
+
+  it('should invoke get_image_quality_metrics and return the metrics', async () => {
+    const metrics = {
+      sharpness: 312.5,
+      meanLuminance: 0.42,
+      highlightsClipped: 0.01,
+      shadowsClipped: 0,
+    };
+    mockTauriInvoke.mockResolvedValue(metrics);
+
+    const result = await CatalogService.getImageQualityMetrics(7);
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('get_image_quality_metrics', { id: 7 });
+    expect(result).toEqual(metrics);
+  });
+
+  it('should return null when metrics have not been computed yet', async () => {
+    mockTauriInvoke.mockResolvedValue(null);
+
+    await expect(CatalogService.getImageQualityMetrics(8)).resolves.toBeNull();
+  });
+});
+
+describe('CatalogService — getImageColorPalette', () => {
+  beforeEach(() => {
+    vi.clearAllMocks();
+  });
+
+  it('should invoke get_image_color_palette and return the palette', async () => {
+    const palette = [
+      { l: 48.25, a: -28.84, b: -8.48, weight: 0.75, hex: '#008080' },
+      { l: 74.94, a: 23.93, b: 78.96, weight: 0.25, hex: '#ffa500' },
+    ];
+    mockTauriInvoke.mockResolvedValue(palette);
+
+    const result = await CatalogService.getImageColorPalette(7);
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('get_image_color_palette', { id: 7 });
+    expect(result).toEqual(palette);
+  });
+
+  it('should throw when the backend returns an error string', async () => {
+    mockTauriInvoke.mockResolvedValue('Failed to load colour palette for image 7');
+
+    await expect(CatalogService.getImageColorPalette(7)).rejects.toThrow('colour palette');
+  });
+});
@@ -25,7 +25,9 @@ import {
   RawFormat,
   DiscoveryStatus,
   FileProcessingStatus,
+  ImportCopyOptions,
 } from '../../types/discovery';
+import type { NamePreview } from '../../types/naming';
 
 import { DiscoveryService, ServiceError, ServiceErrorType } from '../discoveryService';
 
@@ -375,6 +377,35 @@ describe('DiscoveryService', () => {
       expect(result).toEqual(mockResult);
       expect(mockInvoke).toHaveBeenCalledWith('batch_ingest', { request: mockRequest });
     });
+
+    it('should preview copy destinations of an import', async () => {
+      const copyTo: ImportCopyOptions = {
+        destinationDir: '/library',
+        naming: { template: '{yyyy}/{yyyy}-{mm}-{dd}/{filename}', collision: 'skip' },
+      };
+      const previews: NamePreview[] = [
+        {
+          imageId: null,
+          source: '/card/IMG_001.CR3',
+          target: '/library/2026/2026-03-07/IMG_001.CR3',
+          action: 'create',
+        },
+      ];
+      mockInvoke.mockResolvedValue(previews);
+
+      const result = await service.previewImportNames(
+        'test-session-id',
+        ['/card/IMG_001.CR3'],
+        copyTo,
+      );
+
+      expect(result).toEqual(previews);
+      expect(mockInvoke).toHaveBeenCalledWith('preview_import_names', {
+        sessionId: 'test-session-id',
+        filePaths: ['/card/IMG_001.CR3'],
+        copyTo,
+      });
+    });
   });
 
   describe('Utility Operations', () => {
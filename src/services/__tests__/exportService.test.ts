@@ -1,8 +1,10 @@
+import { listen } from '@tauri-apps/api/event';
 import { save } from '@tauri-apps/plugin-dialog';
 import { beforeEach, describe, expect, it, vi } from 'vitest';
 import {
   buildDefaultExportFilename,
   ExportService,
+  type ExportHistoryEntry,
   type ExportResultDTO,
 } from '@/services/exportService';
 
@@ -84,6 +86,15 @@ describe('ExportService', () => {
         height: 3000,
         appliedEditEvents: 6,
         usedSnapshot: false,
+        editState: {
+          target: { kind: 'current' },
+          snapshotId: null,
+          snapshotName: null,
+          lastEventAt: '2026-03-01T10:30:00Z',
+        },
+        recipeHash: 'a1b2c3',
+        quality: 90,
+        fileSizeBytes: 2_400_000,
       };
       mockTauriInvoke.mockResolvedValue(dto);
 
@@ -97,6 +108,10 @@ describe('ExportService', () => {
         imageId: '42',
         outputPath: '/tmp/portrait_edited.jpg',
         format: 'jpeg',
+        resize: null,
+        options: null,
+        metadata: null,
+        target: null,
       });
       expect(result).toEqual(dto);
     });
@@ -110,6 +125,15 @@ describe('ExportService', () => {
         height: 3400,
         appliedEditEvents: 3,
         usedSnapshot: true,
+        editState: {
+          target: { kind: 'current' },
+          snapshotId: 2,
+          snapshotName: 'Before retouch',
+          lastEventAt: null,
+        },
+        recipeHash: 'd4e5f6',
+        quality: null,
+        fileSizeBytes: 102_000_000,
       } satisfies ExportResultDTO);
 
       await ExportService.exportEditedImage({
@@ -123,6 +147,96 @@ describe('ExportService', () => {
         imageId: '7',
         outputPath: '/tmp/raw_edited.tiff',
         format: 'tiff',
+        resize: null,
+        options: null,
+        metadata: null,
+        target: null,
+      });
+    });
+
+    it('forwards resize mode and file size cap', async () => {
+      mockTauriInvoke.mockResolvedValue({});
+
+      await ExportService.exportEditedImage({
+        imageId: 11,
+        outputPath: '/tmp/delivery.jpg',
+        format: 'jpeg',
+        resize: { mode: 'shortEdge', pixels: 1080 },
+        options: { maxFileSizeKb: 500, chromaSubsampling: '4:2:0', dpi: 300 },
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('export_image_edited', {
+        imageId: '11',
+        outputPath: '/tmp/delivery.jpg',
+        format: 'jpeg',
+        resize: { mode: 'shortEdge', pixels: 1080 },
+        options: { maxFileSizeKb: 500, chromaSubsampling: '4:2:0', dpi: 300 },
+        metadata: null,
+        target: null,
+      });
+    });
+
+    it('forwards format options such as 16-bit PNG', async () => {
+      mockTauriInvoke.mockResolvedValue({});
+
+      await ExportService.exportEditedImage({
+        imageId: 9,
+        outputPath: '/tmp/raw_edited.png',
+        format: 'png',
+        options: { bitDepth: 16, pngCompression: 'best' },
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('export_image_edited', {
+        imageId: '9',
+        outputPath: '/tmp/raw_edited.png',
+        format: 'png',
+        resize: null,
+        options: { bitDepth: 16, pngCompression: 'best' },
+        metadata: null,
+        target: null,
+      });
+    });
+
+    it('forwards metadata stripping options', async () => {
+      mockTauriInvoke.mockResolvedValue({});
+
+      await ExportService.exportEditedImage({
+        imageId: 4,
+        outputPath: '/tmp/client.tiff',
+        format: 'tiff',
+        metadata: { stripGps: true, stripCameraSerials: true, includeHistory: true },
+        target: null,
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('export_image_edited', {
+        imageId: '4',
+        outputPath: '/tmp/client.tiff',
+        format: 'tiff',
+        resize: null,
+        options: null,
+        metadata: { stripGps: true, stripCameraSerials: true, includeHistory: true },
+        target: null,
+      });
+    });
+
+    it('forwards a snapshot target', async () => {
+      mockTauriInvoke.mockResolvedValue({});
+
+      await ExportService.exportEditedImage({
+        imageId: 12,
+        outputPath: '/tmp/client_v2.jpg',
+        format: 'jpeg',
+        target: { kind: 'snapshot', snapshotId: 5 },
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('export_image_edited', {
+        imageId: '12',
+        outputPath: '/tmp/client_v2.jpg',
+        format: 'jpeg',
+        resize: null,
+        options: null,
+        metadata: null,
+        target: { kind: 'snapshot', snapshotId: 5 },
       });
     });
   });
@@ -140,4 +254,183 @@ describe('ExportService', () => {
       expect(mockTauriInvoke).not.toHaveBeenCalled();
     });
   });
+
+  describe('batch export', () => {
+    it('invokes batch_export with a nested camelCase request', async () => {
+      mockTauriInvoke.mockResolvedValue({
+        jobId: 'job-1',
+        total: 2,
+        exported: [],
+        failed: [],
+        cancelledImageIds: [],
+        skippedImageIds: [],
+      });
+
+      const result = await ExportService.batchExport({
+        source: { kind: 'collection', collectionId: 7 },
+        outputDir: '/tmp/delivery',
+        format: 'jpeg',
+        resize: { mode: 'longEdge', pixels: 2048 },
+        maxWorkers: 2,
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('batch_export', {
+        request: {
+          source: { kind: 'collection', collectionId: 7 },
+          outputDir: '/tmp/delivery',
+          fileNameTemplate: null,
+          customText: '',
+          sequenceStart: null,
+          collision: 'suffix',
+          format: 'jpeg',
+          resize: { mode: 'longEdge', pixels: 2048 },
+          options: undefined,
+          metadata: undefined,
+          watermark: null,
+          rawOnly: false,
+          maxWorkers: 2,
+        },
+      });
+      expect(result.jobId).toBe('job-1');
+    });
+
+    it('previews export names with the same request shape', async () => {
+      mockTauriInvoke.mockResolvedValue([
+        { imageId: 3, source: 'a.nef', target: '/out/web/a_001.jpg', action: 'create' },
+      ]);
+
+      const result = await ExportService.previewExportNames({
+        source: { kind: 'images', imageIds: [3] },
+        outputDir: '/out',
+        fileNameTemplate: '{text}/{filename}_{seq:3}',
+        customText: 'web',
+        collision: 'skip',
+        format: 'jpeg',
+      });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith(
+        'preview_export_names',
+        expect.objectContaining({
+          request: expect.objectContaining({
+            fileNameTemplate: '{text}/{filename}_{seq:3}',
+            customText: 'web',
+            sequenceStart: null,
+            collision: 'skip',
+          }),
+        }),
+      );
+      expect(result[0].action).toBe('create');
+    });
+
+    it('invokes cancel_batch_export with the job id', async () => {
+      mockTauriInvoke.mockResolvedValue(true);
+
+      await expect(ExportService.cancelBatchExport('job-1')).resolves.toBe(true);
+      expect(mockTauriInvoke).toHaveBeenCalledWith('cancel_batch_export', { jobId: 'job-1' });
+    });
+
+    it('listens to export-progress events', async () => {
+      const handler = vi.fn();
+
+      await ExportService.onExportProgress(handler);
+
+      expect(listen).toHaveBeenCalledWith('export-progress', expect.any(Function));
+      const forward = vi.mocked(listen).mock.calls[0]?.[1];
+      const progress = { jobId: 'job-1', processed: 1, total: 2 };
+      forward?.({ payload: progress } as never);
+      expect(handler).toHaveBeenCalledWith(progress);
+    });
+  });
+
+  describe('export presets', () => {
+    it('creates a preset with nested settings', async () => {
+      const settings = {
+        format: 'jpeg' as const,
+        resize: { mode: 'longEdge' as const, pixels: 2048 },
+        options: { quality: 82 },
+        colorSpace: 'srgb' as const,
+        fileNameTemplate: '{filename}-web',
+        watermark: {
+          kind: 'image' as const,
+          path: '/branding/logo.png',
+          scale: 0.15,
+          opacity: 0.7,
+          anchor: 'bottomRight' as const,
+        },
+      };
+      mockTauriInvoke.mockResolvedValue({ id: 1, name: 'Web', settings });
+
+      const preset = await ExportService.createExportPreset('Web', settings);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('create_export_preset', {
+        name: 'Web',
+        settings,
+      });
+      expect(preset.id).toBe(1);
+    });
+
+    it('round-trips presets through JSON commands', async () => {
+      mockTauriInvoke.mockResolvedValueOnce('{"version":1,"presets":[]}');
+      mockTauriInvoke.mockResolvedValueOnce([]);
+
+      const json = await ExportService.exportPresetsToJson();
+      await ExportService.importPresetsFromJson(json);
+
+      expect(mockTauriInvoke).toHaveBeenNthCalledWith(1, 'export_presets_to_json', {
+        presetIds: null,
+      });
+      expect(mockTauriInvoke).toHaveBeenNthCalledWith(2, 'import_presets_from_json', { json });
+    });
+
+    it('exports a collection with a preset', async () => {
+      mockTauriInvoke.mockResolvedValue({ jobId: 'job-2' });
+
+      await ExportService.exportWithPreset(3, { kind: 'images', imageIds: [1, 2] });
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('export_with_preset', {
+        presetId: 3,
+        source: { kind: 'images', imageIds: [1, 2] },
+        outputDir: null,
+      });
+    });
+  });
+
+  describe('export history', () => {
+    it('lists the history of one image', async () => {
+      mockTauriInvoke.mockResolvedValue([]);
+
+      await ExportService.listExportHistory(12);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('list_export_history', { imageId: 12 });
+    });
+
+    it('re-exports the selected stale entries', async () => {
+      const stale: ExportHistoryEntry = {
+        id: 4,
+        imageId: 12,
+        outputPath: '/galleries/client/portrait.jpg',
+        presetId: 3,
+        presetName: 'Client web',
+        recipeHash: 'a1b2c3',
+        settings: {
+          format: 'jpeg',
+          resize: { mode: 'longEdge', pixels: 2048 },
+          options: {},
+          metadata: {},
+          watermark: null,
+          target: { kind: 'current' },
+          rawOnly: false,
+        },
+        exportedAt: '2026-10-01T09:00:00Z',
+      };
+      mockTauriInvoke.mockResolvedValueOnce([stale]);
+      mockTauriInvoke.mockResolvedValueOnce({ jobId: 'job-3' });
+
+      const entries = await ExportService.listStaleExports();
+      await ExportService.reexportStale(entries.map((entry) => entry.id));
+
+      expect(mockTauriInvoke).toHaveBeenNthCalledWith(1, 'list_stale_exports');
+      expect(mockTauriInvoke).toHaveBeenNthCalledWith(2, 'reexport_stale', { entryIds: [4] });
+    });
+  });
 });
@@ -146,6 +146,48 @@ describe('HashingService', () => {
     });
   });
 
+  describe('findSimilarImages', () => {
+    it('should forward threshold and algorithm', async () => {
+      const mockResponse = [{ imageId: 7, filename: 'IMG_0007.jpg', distance: 3 }];
+      mockTauriInvoke.mockResolvedValue(mockResponse);
+
+      const result = await HashingService.findSimilarImages(42, 6, 'dhash');
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('find_similar_images', {
+        imageId: 42,
+        maxDistance: 6,
+        algorithm: 'dhash',
+      });
+      expect(result).toEqual(mockResponse);
+    });
+
+    it('should let the backend pick defaults', async () => {
+      mockTauriInvoke.mockResolvedValue([]);
+
+      await HashingService.findSimilarImages(42);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('find_similar_images', {
+        imageId: 42,
+        maxDistance: null,
+        algorithm: null,
+      });
+    });
+  });
+
+  describe('groupSimilarImages', () => {
+    it('should return similarity groups', async () => {
+      mockTauriInvoke.mockResolvedValue([{ imageIds: [1, 2, 3] }]);
+
+      const groups = await HashingService.groupSimilarImages(8);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('group_similar_images', {
+        maxDistance: 8,
+        algorithm: null,
+      });
+      expect(groups).toEqual([{ imageIds: [1, 2, 3] }]);
+    });
+  });
+
   describe('detectDuplicates', () => {
     it('should detect duplicates successfully', async () => {
       const mockResponse = {
@@ -0,0 +1,109 @@
+import { beforeEach, describe, expect, it, vi } from 'vitest';
+import { MergeService, type HdrMergeResultDTO } from '@/services/mergeService';
+
+const mockTauriInvoke = vi.fn();
+
+Object.defineProperty(window, '__TAURI_INTERNALS__', {
+  value: { invoke: mockTauriInvoke },
+  writable: true,
+});
+
+describe('MergeService', () => {
+  beforeEach(() => {
+    vi.clearAllMocks();
+  });
+
+  it('invokes merge_hdr with DNG output and default alignment', async () => {
+    const mockResult: HdrMergeResultDTO = {
+      imageId: 42,
+      outputPath: '/tmp/interior_hdr.dng',
+      format: 'dng',
+      width: 6000,
+      height: 4000,
+      sourceImageIds: [1, 2, 3],
+      referenceImageId: 2,
+      offsets: [
+        [1, 0],
+        [0, 0],
+        [-2, 1],
+      ],
+    };
+    mockTauriInvoke.mockResolvedValue(mockResult);
+
+    const result = await MergeService.mergeHdr({
+      imageIds: [1, 2, 3],
+      outputPath: '/tmp/interior_hdr.dng',
+    });
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('merge_hdr', {
+      imageIds: [1, 2, 3],
+      outputPath: '/tmp/interior_hdr.dng',
+      format: 'dng',
+      maxShift: null,
+    });
+    expect(result).toEqual(mockResult);
+  });
+
+  it('forwards TIFF format and alignment radius', async () => {
+    mockTauriInvoke.mockResolvedValue({});
+
+    await MergeService.mergeHdr({
+      imageIds: [4, 5],
+      outputPath: '/tmp/bracket.tiff',
+      format: 'tiff',
+      maxShift: 0,
+    });
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('merge_hdr', {
+      imageIds: [4, 5],
+      outputPath: '/tmp/bracket.tiff',
+      format: 'tiff',
+      maxShift: 0,
+    });
+  });
+
+  it('invokes merge_panorama with default projection', async () => {
+    mockTauriInvoke.mockResolvedValue({});
+
+    await MergeService.mergePanorama({
+      imageIds: [7, 8, 9],
+      outputPath: '/tmp/skyline.dng',
+    });
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('merge_panorama', {
+      imageIds: [7, 8, 9],
+      outputPath: '/tmp/skyline.dng',
+      format: 'dng',
+      projection: null,
+    });
+  });
+
+  it('forwards spherical projection and TIFF format', async () => {
+    mockTauriInvoke.mockResolvedValue({});
+
+    await MergeService.mergePanorama({
+      imageIds: [7, 8],
+      outputPath: '/tmp/sphere.tiff',
+      format: 'tiff',
+      projection: 'spherical',
+    });
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('merge_panorama', {
+      imageIds: [7, 8],
+      outputPath: '/tmp/sphere.tiff',
+      format: 'tiff',
+      projection: 'spherical',
+    });
+  });
+
+  it('loads derivation sources', async () => {
+    mockTauriInvoke.mockResolvedValue([
+      { sourceImageId: 1, derivation: 'hdr_merge', sourceOrder: 0 },
+    ]);
+
+    const sources = await MergeService.getDerivationSources(42);
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('get_derivation_sources', { imageId: 42 });
+    expect(sources).toHaveLength(1);
+  });
+});
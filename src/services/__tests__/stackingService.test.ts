@@ -0,0 +1,74 @@
+import { beforeEach, describe, expect, it, vi } from 'vitest';
+import { StackingService, type ImageStackDTO } from '@/services/stackingService';
+
+const mockTauriInvoke = vi.fn();
+
+Object.defineProperty(window, '__TAURI_INTERNALS__', {
+  value: { invoke: mockTauriInvoke },
+  writable: true,
+});
+
+const burst: ImageStackDTO = {
+  id: 1,
+  coverImageId: 12,
+  kind: 'burst',
+  collapsed: true,
+  imageIds: [11, 12, 13],
+};
+
+describe('StackingService', () => {
+  beforeEach(() => {
+    vi.clearAllMocks();
+  });
+
+  it('invokes auto_stack_images with the default gap', async () => {
+    mockTauriInvoke.mockResolvedValue([burst]);
+
+    const result = await StackingService.autoStackImages();
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('auto_stack_images', { maxGapSeconds: null });
+    expect(result).toEqual([burst]);
+  });
+
+  it('stacks images with an explicit cover', async () => {
+    mockTauriInvoke.mockResolvedValue({ ...burst, kind: 'manual' });
+
+    await StackingService.stackImages([11, 12, 13], 12);
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('stack_images', {
+      imageIds: [11, 12, 13],
+      coverImageId: 12,
+    });
+  });
+
+  it('resolves to null when unstacking dissolves the stack', async () => {
+    mockTauriInvoke.mockResolvedValue(null);
+
+    const result = await StackingService.unstackImages(1);
+
+    expect(mockTauriInvoke).toHaveBeenCalledWith('unstack_images', { stackId: 1, imageIds: null });
+    expect(result).toBeNull();
+  });
+
+  it('forwards reorder and collapse requests', async () => {
+    mockTauriInvoke.mockResolvedValue(burst);
+
+    await StackingService.reorderStack(1, [13, 11, 12]);
+    await StackingService.setStackCollapsed(1, false);
+
+    expect(mockTauriInvoke).toHaveBeenNthCalledWith(1, 'reorder_stack', {
+      stackId: 1,
+      imageIds: [13, 11, 12],
+    });
+    expect(mockTauriInvoke).toHaveBeenNthCalledWith(2, 'set_stack_collapsed', {
+      stackId: 1,
+      collapsed: false,
+    });
+  });
+
+  it('propagates backend errors', async () => {
+    mockTauriInvoke.mockRejectedValue(new Error('Image 12 is already in a stack'));
+
+    await expect(StackingService.stackImages([12, 14])).rejects.toThrow('already in a stack');
+  });
+});
@@ -13,6 +13,8 @@ import {
   resetWasmModule,
   measureWasmLatency,
   normalizeFiltersForWasm,
+  canUseWasmThreads,
+  isWasmThreaded,
 } from '@/services/wasmRenderingService';
 import type { PixelFilterState } from '@/types/rendering';
 
@@ -475,6 +477,31 @@ describe('wasmRenderingService', () => {
     });
   });
 
+  describe('WASM threads', () => {
+    afterEach(() => {
+      vi.unstubAllGlobals();
+    });
+
+    it('should not use threads without cross-origin isolation', () => {
+      vi.stubGlobal('crossOriginIsolated', false);
+
+      expect(canUseWasmThreads()).toBe(false);
+    });
+
+    it('should use threads when isolated and SharedArrayBuffer exists', () => {
+      vi.stubGlobal('crossOriginIsolated', true);
+      vi.stubGlobal('SharedArrayBuffer', ArrayBuffer);
+
+      expect(canUseWasmThreads()).toBe(true);
+    });
+
+    it('should fall back to single-thread when the module is not threaded', async () => {
+      await loadWasmModule();
+
+      expect(isWasmThreaded()).toBe(false);
+    });
+  });
+
   describe('resetWasmModule', () => {
     it('should clear WASM state', async () => {
       await loadWasmModule();
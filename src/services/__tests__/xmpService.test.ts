@@ -96,4 +96,27 @@ describe('XmpService', () => {
       expect(result.exists).toBe(false);
     });
   });
+
+  // ── IPTC ─────────────────────────────────────────────────────────────────────
+
+  describe('getImageIptc / setImageIptc', () => {
+    it('invokes get_image_iptc with imageId', async () => {
+      const dto = { author: 'Jane Doe', description: null, copyright: '© 2026 Jane Doe' };
+      mockTauriInvoke.mockResolvedValue(dto);
+
+      const result = await XmpService.getImageIptc(3);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('get_image_iptc', { imageId: 3 });
+      expect(result.copyright).toBe('© 2026 Jane Doe');
+    });
+
+    it('invokes set_image_iptc with the fields', async () => {
+      mockTauriInvoke.mockResolvedValue(undefined);
+      const iptc = { author: null, description: 'Harbour at dusk', copyright: null };
+
+      await XmpService.setImageIptc(3, iptc);
+
+      expect(mockTauriInvoke).toHaveBeenCalledWith('set_image_iptc', { imageId: 3, iptc });
+    });
+  });
 });
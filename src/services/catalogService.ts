@@ -1,5 +1,6 @@
 import type { CollectionDTO, ImageDTO, ImageDetailDTO, ImageFilter } from '../types/dto';
 import type { FolderTreeNode } from '../types/folder';
+import type { EditRecipe } from '../types/rendering';
 import type { EventDTO } from './eventService';
 import { appendEvent as appendEventToStore } from './eventService';
 
@@ -91,6 +92,50 @@ export class CatalogService {
       throw error;
     }
   }
+  /**
+   * Get sharpness and exposure metrics for a single image (culling)
+   * Returns null until the standard preview has been generated.
+   */
+  static async getImageQualityMetrics(
+    id: number,
+  ): Promise<import('../types/dto').QualityMetricsDTO | null> {
+    try {
+      const invoke = this.getInvoke();
+      const result = await invoke('get_image_quality_metrics', { id });
+
+      if (typeof result === 'string') {
+        throw new Error(result);
+      }
+
+      return (result ?? null) as import('../types/dto').QualityMetricsDTO | null;
+    } catch (error) {
+      console.error(`Failed to get quality metrics for image ID ${id}:`, error);
+      throw error;
+    }
+  }
+  /**
+   * Get the dominant colour palette of a single image, most dominant first
+   * Empty until the thumbnail has been generated.
+   */
+  static async getImageColorPalette(
+    id: number,
+  ): Promise<import('../types/dto').PaletteColorDTO[]> {
+    try {
+      const invoke = this.getInvoke();
+      const result = await invoke('get_image_color_palette', { id });
+
+      if (typeof result === 'string') {
+        throw new Error(result);
+      }
+
+      return result as import('../types/dto').PaletteColorDTO[];
+    } catch (error) {
+      console.error(`Failed to get colour palette for image ID ${id}:`, error);
+      throw error;
+    }
+  }
+
+
 
   /**
    * Update image state (rating, flag, color_label) - Checkpoint 2
@@ -361,6 +406,19 @@ export class CatalogService {
     }
   }
 
+  /**
+   * Recette d'édition résolue (snapshot + événements), retouches comprises
+   */
+  static async getEditRecipe(imageId: number): Promise<EditRecipe> {
+    try {
+      const invoke = this.getInvoke();
+      const result = await invoke('get_edit_recipe', { imageId });
+      return result as EditRecipe;
+    } catch (error) {
+      throw this.parseError(error);
+    }
+  }
+
   /**
    * Utility method to check if a result is an error
    */
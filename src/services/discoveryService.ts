@@ -13,11 +13,13 @@ import type {
   IngestionResult,
   BatchIngestionRequest,
   BatchIngestionResult,
+  ImportCopyOptions,
   DiscoveryStats,
   DiscoveryProgress,
   PathValidationResult,
   ConfigValidationResult,
 } from '../types/discovery';
+import type { NamePreview } from '../types/naming';
 
 // ============================================================================
 // Service Configuration
@@ -449,6 +451,26 @@ export class DiscoveryService {
     }
   }
 
+  /**
+   * Resolve where a copying import would put each file of a discovery session, without copying
+   */
+  async previewImportNames(
+    sessionId: string,
+    filePaths: string[],
+    copyTo: ImportCopyOptions,
+  ): Promise<NamePreview[]> {
+    try {
+      return await this.executeCommand<NamePreview[]>('preview_import_names', {
+        sessionId,
+        filePaths,
+        copyTo,
+      });
+    } catch (error) {
+      this.logError('Failed to preview import names', error);
+      throw error;
+    }
+  }
+
   // ============================================================================
   // Utility Commands
   // ============================================================================
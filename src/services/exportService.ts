@@ -1,6 +1,58 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { save } from '@tauri-apps/plugin-dialog';
+import type { CollisionPolicy, NamePreview } from '../types/naming';
 
-export type ExportFormat = 'jpeg' | 'tiff';
+export type ExportFormat = 'jpeg' | 'tiff' | 'png' | 'webp' | 'avif';
+
+/** Per-format encoder settings, mirroring the Rust `ExportFormatOptions`. */
+export interface ExportFormatOptions {
+  /** 8, or 16 for PNG and TIFF (keeps linear RAW precision). */
+  bitDepth?: 8 | 16;
+  /** `auto` keeps alpha only when some pixel is not fully opaque. */
+  alpha?: 'auto' | 'keep' | 'discard';
+  /** Quality 1..100 for JPEG, lossy WebP and AVIF; upper bound under `maxFileSizeKb`. */
+  quality?: number;
+  /** WebP only. */
+  lossless?: boolean;
+  /** AVIF encoder speed, 1 (slowest) to 10 (fastest). */
+  avifSpeed?: number;
+  pngCompression?: 'none' | 'fast' | 'default' | 'best';
+  tiffCompression?: 'none' | 'lzw' | 'deflate' | 'packbits';
+  chromaSubsampling?: '4:4:4' | '4:2:2' | '4:2:0';
+  /** Written to JPEG, PNG and TIFF headers. */
+  dpi?: number;
+  /** Lossy formats only: the highest quality whose file fits this many KiB. */
+  maxFileSizeKb?: number;
+}
+
+/** Catalog metadata embedded into JPEG, PNG and TIFF exports. */
+export interface ExportMetadataOptions {
+  /** Defaults to true; false writes pixels only. */
+  embed?: boolean;
+  stripGps?: boolean;
+  /** Drops body serial number and lens model. */
+  stripCameraSerials?: boolean;
+  /** Keeps the copyright notice and nothing else. */
+  copyrightOnly?: boolean;
+  /** Adds the develop history as `xmpMM:History`. */
+  includeHistory?: boolean;
+}
+
+/** Edit state to export; the latest one (`current`) by default. */
+export type ExportTarget =
+  | { kind: 'current' }
+  | { kind: 'snapshot'; snapshotId: number }
+  /** Newest snapshot taken by `at` (RFC 3339) plus the edits recorded up to it. */
+  | { kind: 'at'; at: string };
+
+/** Edit state an export rendered. */
+export interface ExportEditState {
+  target: ExportTarget;
+  snapshotId: number | null;
+  snapshotName: string | null;
+  /** Newest edit replayed on top of the snapshot. */
+  lastEventAt: string | null;
+}
 
 export interface ExportResultDTO {
   imageId: number;
@@ -10,18 +62,207 @@ export interface ExportResultDTO {
   height: number;
   appliedEditEvents: number;
   usedSnapshot: boolean;
+  editState: ExportEditState;
+  /** BLAKE3 of the rendered edit recipe; it changes when the image is edited. */
+  recipeHash: string;
+  /** Encoder quality actually used (searched under `maxFileSizeKb`); null when lossless. */
+  quality: number | null;
+  fileSizeBytes: number;
 }
 
+/**
+ * Output size. Only `exact` upscales; it covers the box then centre-crops.
+ * The box and the scaled render it crops from are limited to 200 MP.
+ */
+export type ExportResize =
+  | { mode: 'original' }
+  | { mode: 'longEdge'; pixels: number }
+  | { mode: 'shortEdge'; pixels: number }
+  | { mode: 'megapixels'; megapixels: number }
+  | { mode: 'exact'; width: number; height: number };
+
 export interface ExportEditedImageRequest {
   imageId: number;
   outputPath: string;
   format: ExportFormat;
   rawOnly?: boolean;
+  /** The render resolution is kept when omitted. */
+  resize?: ExportResize;
+  options?: ExportFormatOptions;
+  /** EXIF, XMP and IPTC are embedded with default settings when omitted. */
+  metadata?: ExportMetadataOptions;
+  target?: ExportTarget;
+}
+
+export type WatermarkAnchor =
+  | 'topLeft'
+  | 'top'
+  | 'topRight'
+  | 'left'
+  | 'center'
+  | 'right'
+  | 'bottomLeft'
+  | 'bottom'
+  | 'bottomRight';
+
+/**
+ * Text or logo drawn after resizing. Sizes and offsets are fractions of the
+ * output so a preset looks the same at any export size.
+ */
+export type Watermark = (
+  | {
+      kind: 'text';
+      text: string;
+      /** TrueType/OpenType font file. */
+      fontPath: string;
+      /** Text height as a fraction of the shorter side, 0.05 by default. */
+      size?: number;
+      /** sRGB, white by default. */
+      color?: [number, number, number];
+    }
+  | {
+      kind: 'image';
+      /** Logo file, normally a PNG with transparency. */
+      path: string;
+      /** Logo width as a fraction of the output width, 0.2 by default. */
+      scale?: number;
+    }
+) & {
+  /** 0-1, 0.5 by default. */
+  opacity?: number;
+  /** `bottomRight` by default. */
+  anchor?: WatermarkAnchor;
+  /** Inward distance from the anchor as a fraction of the shorter side, 0.02 by default. */
+  offset?: { x: number; y: number };
+};
+
+export type BatchExportSource =
+  | { kind: 'images'; imageIds: number[] }
+  | { kind: 'collection'; collectionId: number };
+
+export interface BatchExportRequest {
+  source: BatchExportSource;
+  outputDir: string;
+  /**
+   * Output path under `outputDir` without extension, see `types/naming`;
+   * `{filename}_edited` by default.
+   */
+  fileNameTemplate?: string;
+  /** Value of the `{text}` token. */
+  customText?: string;
+  /** `{seq}` of the first image, 1 by default. */
+  sequenceStart?: number;
+  /** `suffix` by default. */
+  collision?: CollisionPolicy;
+  format: ExportFormat;
+  resize?: ExportResize;
+  options?: ExportFormatOptions;
+  metadata?: ExportMetadataOptions;
+  watermark?: Watermark | null;
+  rawOnly?: boolean;
+  /** Parallel renders, 4 by default and at most 8. */
+  maxWorkers?: number;
+}
+
+/** Payload of the `export-progress` event. */
+export interface ExportProgress {
+  jobId: string;
+  total: number;
+  processed: number;
+  successful: number;
+  failed: number;
+  cancelled: number;
+  /** Images whose output already existed under the `skip` policy. */
+  skipped: number;
+  currentFile: string | null;
+  /** 0.0 - 1.0 */
+  percentage: number;
+}
+
+export interface BatchExportResultDTO {
+  jobId: string;
+  total: number;
+  exported: ExportResultDTO[];
+  failed: { imageId: number; error: string }[];
+  cancelledImageIds: number[];
+  skippedImageIds: number[];
+}
+
+/** Exports are rendered in sRGB; no other colour space is available yet. */
+export type ExportColorSpace = 'srgb';
+
+/** Stored export settings, mirroring the Rust `ExportPresetSettings`. */
+export interface ExportPresetSettings {
+  format: ExportFormat;
+  resize?: ExportResize;
+  options?: ExportFormatOptions;
+  metadata?: ExportMetadataOptions;
+  /** Only `'srgb'` is accepted: the setting has no other effect yet. */
+  colorSpace?: ExportColorSpace;
+  watermark?: Watermark | null;
+  /** Used when `exportWithPreset` is called without a directory. */
+  outputDir?: string | null;
+  fileNameTemplate?: string;
+  customText?: string;
+  sequenceStart?: number;
+  collision?: CollisionPolicy;
+}
+
+export interface ExportPreset {
+  id: number;
+  name: string;
+  settings: ExportPresetSettings;
+  createdAt: string;
+  updatedAt: string;
+}
+
+/** Render settings recorded with each export, enough to export again. */
+export interface ExportHistorySettings {
+  format: ExportFormat;
+  resize: ExportResize;
+  options: ExportFormatOptions;
+  metadata: ExportMetadataOptions;
+  watermark: Watermark | null;
+  target: ExportTarget;
+  rawOnly: boolean;
+}
+
+export interface ExportHistoryEntry {
+  id: number;
+  imageId: number;
+  outputPath: string;
+  /** Null for one-off exports and once the preset is deleted. */
+  presetId: number | null;
+  presetName: string | null;
+  recipeHash: string;
+  settings: ExportHistorySettings;
+  exportedAt: string;
+}
+
+function toBatchRequestArg(request: BatchExportRequest): Record<string, unknown> {
+  return {
+    source: request.source,
+    outputDir: request.outputDir,
+    fileNameTemplate: request.fileNameTemplate ?? null,
+    customText: request.customText ?? '',
+    sequenceStart: request.sequenceStart ?? null,
+    collision: request.collision ?? 'suffix',
+    format: request.format,
+    resize: request.resize,
+    options: request.options,
+    metadata: request.metadata,
+    watermark: request.watermark ?? null,
+    rawOnly: request.rawOnly ?? false,
+    maxWorkers: request.maxWorkers ?? null,
+  };
 }
 
 const EXPORT_EXTENSION_BY_FORMAT: Record<ExportFormat, string> = {
   jpeg: 'jpg',
   tiff: 'tiff',
+  png: 'png',
+  webp: 'webp',
+  avif: 'avif',
 };
 
 const EXPORT_FILTERS: Record<ExportFormat, { name: string; extensions: string[] }> = {
@@ -33,6 +274,18 @@ const EXPORT_FILTERS: Record<ExportFormat, { name: string; extensions: string[]
     name: 'TIFF image',
     extensions: ['tif', 'tiff'],
   },
+  png: {
+    name: 'PNG image',
+    extensions: ['png'],
+  },
+  webp: {
+    name: 'WebP image',
+    extensions: ['webp'],
+  },
+  avif: {
+    name: 'AVIF image',
+    extensions: ['avif'],
+  },
 };
 
 function getFilenameWithoutExtension(filename: string): string {
@@ -100,16 +353,135 @@ export class ExportService {
       imageId: String(request.imageId),
       outputPath: request.outputPath,
       format: request.format,
+      resize: request.resize ?? null,
+      options: request.options ?? null,
+      metadata: request.metadata ?? null,
+      target: request.target ?? null,
     });
 
     return result as ExportResultDTO;
   }
 
+  /**
+   * Exports many images in parallel. Progress arrives through `onExportProgress`;
+   * its first event carries the job ID to pass to `cancelBatchExport`.
+   */
+  static async batchExport(request: BatchExportRequest): Promise<BatchExportResultDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('batch_export', { request: toBatchRequestArg(request) });
+
+    return result as BatchExportResultDTO;
+  }
+
+  /** Resolves every output path and collision action without exporting. */
+  static async previewExportNames(request: BatchExportRequest): Promise<NamePreview[]> {
+    const invoke = this.getInvoke();
+    const result = await invoke('preview_export_names', { request: toBatchRequestArg(request) });
+    return result as NamePreview[];
+  }
+
+  /** Resolves to false when the job already finished. */
+  static async cancelBatchExport(jobId: string): Promise<boolean> {
+    const invoke = this.getInvoke();
+    const result = await invoke('cancel_batch_export', { jobId });
+    return result as boolean;
+  }
+
+  static async onExportProgress(handler: (progress: ExportProgress) => void): Promise<UnlistenFn> {
+    return listen<ExportProgress>('export-progress', (event) => handler(event.payload));
+  }
+
+  static async listExportPresets(): Promise<ExportPreset[]> {
+    const invoke = this.getInvoke();
+    const result = await invoke('list_export_presets');
+    return result as ExportPreset[];
+  }
+
+  static async createExportPreset(
+    name: string,
+    settings: ExportPresetSettings,
+  ): Promise<ExportPreset> {
+    const invoke = this.getInvoke();
+    const result = await invoke('create_export_preset', { name, settings });
+    return result as ExportPreset;
+  }
+
+  static async updateExportPreset(
+    presetId: number,
+    name: string,
+    settings: ExportPresetSettings,
+  ): Promise<ExportPreset> {
+    const invoke = this.getInvoke();
+    const result = await invoke('update_export_preset', { presetId, name, settings });
+    return result as ExportPreset;
+  }
+
+  static async deleteExportPreset(presetId: number): Promise<void> {
+    const invoke = this.getInvoke();
+    await invoke('delete_export_preset', { presetId });
+  }
+
+  /** Shareable JSON document of the given presets, or of all of them. */
+  static async exportPresetsToJson(presetIds?: number[]): Promise<string> {
+    const invoke = this.getInvoke();
+    const result = await invoke('export_presets_to_json', { presetIds: presetIds ?? null });
+    return result as string;
+  }
+
+  /** Presets whose name already exists are replaced. */
+  static async importPresetsFromJson(json: string): Promise<ExportPreset[]> {
+    const invoke = this.getInvoke();
+    const result = await invoke('import_presets_from_json', { json });
+    return result as ExportPreset[];
+  }
+
+  /** `outputDir` overrides the preset directory and is required when it has none. */
+  static async exportWithPreset(
+    presetId: number,
+    source: BatchExportSource,
+    outputDir?: string,
+  ): Promise<BatchExportResultDTO> {
+    const invoke = this.getInvoke();
+    const result = await invoke('export_with_preset', {
+      presetId,
+      source,
+      outputDir: outputDir ?? null,
+    });
+    return result as BatchExportResultDTO;
+  }
+
+  /** Every recorded export, newest first; only those of `imageId` when given. */
+  static async listExportHistory(imageId?: number): Promise<ExportHistoryEntry[]> {
+    const invoke = this.getInvoke();
+    const result = await invoke('list_export_history', { imageId: imageId ?? null });
+    return result as ExportHistoryEntry[];
+  }
+
+  /** Latest export of each file whose image was edited since. */
+  static async listStaleExports(): Promise<ExportHistoryEntry[]> {
+    const invoke = this.getInvoke();
+    const result = await invoke('list_stale_exports');
+    return result as ExportHistoryEntry[];
+  }
+
+  /**
+   * Exports stale files again over the delivered ones, all of them or the
+   * history entries `entryIds`. Reports progress like `batchExport`.
+   */
+  static async reexportStale(entryIds?: number[]): Promise<BatchExportResultDTO> {
+    const invoke = this.getInvoke();
+    const result = await invoke('reexport_stale', { entryIds: entryIds ?? null });
+    return result as BatchExportResultDTO;
+  }
+
   static async exportWithDialog(params: {
     imageId: number;
     sourceFilename: string;
     format?: ExportFormat;
     rawOnly?: boolean;
+    options?: ExportFormatOptions;
+    metadata?: ExportMetadataOptions;
   }): Promise<ExportResultDTO | null> {
     const format = params.format ?? 'jpeg';
     const outputPath = await this.promptOutputPath(params.sourceFilename, format);
@@ -123,6 +495,8 @@ export class ExportService {
       outputPath,
       format,
       rawOnly: params.rawOnly,
+      options: params.options,
+      metadata: params.metadata,
     });
   }
 }
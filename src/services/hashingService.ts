@@ -4,6 +4,9 @@ import type {
   HashBenchmarkResult,
   HashError,
   HashProgressCallback,
+  PerceptualHashAlgorithm,
+  SimilarImage,
+  SimilarImageGroup,
 } from '@/types';
 import { HashType, HashErrorType } from '../types/hashing';
 
@@ -189,6 +192,45 @@ export class HashingService {
     }
   }
 
+  /**
+   * Recherche les images visuellement proches (hashes perceptuels du thumbnail)
+   * Complète detectDuplicates, limité aux fichiers identiques octet par octet
+   */
+  static async findSimilarImages(
+    imageId: number,
+    maxDistance?: number,
+    algorithm?: PerceptualHashAlgorithm,
+  ): Promise<SimilarImage[]> {
+    try {
+      const result = await this.invokeTauri('find_similar_images', {
+        imageId,
+        maxDistance: maxDistance ?? null,
+        algorithm: algorithm ?? null,
+      });
+      return result as SimilarImage[];
+    } catch (error) {
+      throw this.parseError(error);
+    }
+  }
+
+  /**
+   * Regroupe les images quasi identiques de tout le catalogue
+   */
+  static async groupSimilarImages(
+    maxDistance?: number,
+    algorithm?: PerceptualHashAlgorithm,
+  ): Promise<SimilarImageGroup[]> {
+    try {
+      const result = await this.invokeTauri('group_similar_images', {
+        maxDistance: maxDistance ?? null,
+        algorithm: algorithm ?? null,
+      });
+      return result as SimilarImageGroup[];
+    } catch (error) {
+      throw this.parseError(error);
+    }
+  }
+
   // --- Méthodes privées ---
 
   /**
@@ -253,6 +295,10 @@ export class HashingService {
           duplicates: [],
         };
 
+      case 'find_similar_images':
+      case 'group_similar_images':
+        return [];
+
       case 'verify_file_integrity':
         return true;
 
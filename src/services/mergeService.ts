@@ -0,0 +1,115 @@
+export type HdrOutputFormat = 'dng' | 'tiff';
+
+export interface HdrMergeResultDTO {
+  /** Catalog ID of the ingested HDR image */
+  imageId: number;
+  outputPath: string;
+  format: HdrOutputFormat;
+  width: number;
+  height: number;
+  sourceImageIds: number[];
+  /** Source the other frames were aligned to; the HDR is at its exposure */
+  referenceImageId: number;
+  /** `[dx, dy]` alignment of each source, in `sourceImageIds` order */
+  offsets: [number, number][];
+}
+
+export type PanoramaProjection = 'cylindrical' | 'spherical';
+
+export interface PanoramaResultDTO {
+  /** Catalog ID of the ingested panorama */
+  imageId: number;
+  outputPath: string;
+  format: HdrOutputFormat;
+  projection: PanoramaProjection;
+  width: number;
+  height: number;
+  sourceImageIds: number[];
+  /** Source the projection is centred on */
+  referenceImageId: number;
+  /** Focal length used for the projection, in reference pixels */
+  focalLength: number;
+}
+
+export interface DerivationSourceDTO {
+  sourceImageId: number;
+  derivation: 'hdr_merge' | 'panorama';
+  sourceOrder: number;
+}
+
+export interface MergeHdrRequest {
+  imageIds: number[];
+  outputPath: string;
+  format?: HdrOutputFormat;
+  /** Alignment search radius in pixels; 0 disables alignment. */
+  maxShift?: number;
+}
+
+export interface MergePanoramaRequest {
+  imageIds: number[];
+  outputPath: string;
+  format?: HdrOutputFormat;
+  projection?: PanoramaProjection;
+}
+
+export class MergeService {
+  private static getInvoke() {
+    if (typeof window !== 'undefined') {
+      const tauriWindow = window as unknown as {
+        __TAURI__?: {
+          invoke: (command: string, args?: Record<string, unknown>) => Promise<unknown>;
+        };
+        __TAURI_INTERNALS__?: {
+          invoke: (command: string, args?: Record<string, unknown>) => Promise<unknown>;
+        };
+      };
+
+      if (tauriWindow.__TAURI__?.invoke) {
+        return tauriWindow.__TAURI__.invoke;
+      }
+
+      if (tauriWindow.__TAURI_INTERNALS__?.invoke) {
+        return tauriWindow.__TAURI_INTERNALS__.invoke;
+      }
+    }
+
+    throw new Error('Tauri API not available');
+  }
+
+  /** Merges bracketed RAW exposures and ingests the result as a new image. */
+  static async mergeHdr(request: MergeHdrRequest): Promise<HdrMergeResultDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('merge_hdr', {
+      imageIds: request.imageIds,
+      outputPath: request.outputPath,
+      format: request.format ?? 'dng',
+      maxShift: request.maxShift ?? null,
+    });
+
+    return result as HdrMergeResultDTO;
+  }
+
+  /** Stitches overlapping images and ingests the panorama as a new image. */
+  static async mergePanorama(request: MergePanoramaRequest): Promise<PanoramaResultDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('merge_panorama', {
+      imageIds: request.imageIds,
+      outputPath: request.outputPath,
+      format: request.format ?? 'dng',
+      projection: request.projection ?? null,
+    });
+
+    return result as PanoramaResultDTO;
+  }
+
+  /** Images a derived image was built from; empty for regular imports. */
+  static async getDerivationSources(imageId: number): Promise<DerivationSourceDTO[]> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('get_derivation_sources', { imageId });
+
+    return result as DerivationSourceDTO[];
+  }
+}
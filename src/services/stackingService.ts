@@ -0,0 +1,105 @@
+export type StackKind = 'burst' | 'bracket' | 'manual';
+
+export interface ImageStackDTO {
+  id: number;
+  coverImageId: number;
+  kind: StackKind;
+  /** Collapsed stacks show only their cover when listing stack tops */
+  collapsed: boolean;
+  /** Members in display order */
+  imageIds: number[];
+}
+
+export class StackingService {
+  private static getInvoke() {
+    if (typeof window !== 'undefined') {
+      const tauriWindow = window as unknown as {
+        __TAURI__?: {
+          invoke: (command: string, args?: Record<string, unknown>) => Promise<unknown>;
+        };
+        __TAURI_INTERNALS__?: {
+          invoke: (command: string, args?: Record<string, unknown>) => Promise<unknown>;
+        };
+      };
+
+      if (tauriWindow.__TAURI__?.invoke) {
+        return tauriWindow.__TAURI__.invoke;
+      }
+
+      if (tauriWindow.__TAURI_INTERNALS__?.invoke) {
+        return tauriWindow.__TAURI_INTERNALS__.invoke;
+      }
+    }
+
+    throw new Error('Tauri API not available');
+  }
+
+  /**
+   * Groups unstacked images into burst and bracket stacks.
+   * @param maxGapSeconds Longest pause between frames of one stack (default 2 s)
+   */
+  static async autoStackImages(maxGapSeconds?: number): Promise<ImageStackDTO[]> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('auto_stack_images', { maxGapSeconds: maxGapSeconds ?? null });
+
+    return result as ImageStackDTO[];
+  }
+
+  /** Stacks images in the given order; the cover defaults to the first. */
+  static async stackImages(imageIds: number[], coverImageId?: number): Promise<ImageStackDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('stack_images', {
+      imageIds,
+      coverImageId: coverImageId ?? null,
+    });
+
+    return result as ImageStackDTO;
+  }
+
+  /**
+   * Removes images from a stack, or dissolves it when `imageIds` is omitted.
+   * Resolves to null once the stack has been dissolved.
+   */
+  static async unstackImages(stackId: number, imageIds?: number[]): Promise<ImageStackDTO | null> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('unstack_images', { stackId, imageIds: imageIds ?? null });
+
+    return (result ?? null) as ImageStackDTO | null;
+  }
+
+  /** Sets the display order; every member must be listed once. */
+  static async reorderStack(stackId: number, imageIds: number[]): Promise<ImageStackDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('reorder_stack', { stackId, imageIds });
+
+    return result as ImageStackDTO;
+  }
+
+  static async setStackCollapsed(stackId: number, collapsed: boolean): Promise<ImageStackDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('set_stack_collapsed', { stackId, collapsed });
+
+    return result as ImageStackDTO;
+  }
+
+  static async setStackCover(stackId: number, imageId: number): Promise<ImageStackDTO> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('set_stack_cover', { stackId, imageId });
+
+    return result as ImageStackDTO;
+  }
+
+  static async getImageStacks(): Promise<ImageStackDTO[]> {
+    const invoke = this.getInvoke();
+
+    const result = await invoke('get_image_stacks');
+
+    return result as ImageStackDTO[];
+  }
+}
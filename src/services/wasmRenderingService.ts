@@ -4,7 +4,28 @@
  * Avec fallback gracieux sur CSS filters si WASM non-disponible
  */
 
-import type { PixelFilterState } from '@/types/rendering';
+import type { PixelFilterState, RetouchSpot } from '@/types/rendering';
+
+/** Instance de `PipelineBuilder` (luminafast-wasm/src/pipeline.rs) */
+interface WasmPipelineBuilder {
+  readonly width: number;
+  readonly height: number;
+  set_recipe(recipeJson: string): void;
+  set_input(pixels: Uint8ClampedArray | Uint8Array, width: number, height: number): void;
+  set_linear_input(samples: Float32Array, width: number, height: number): void;
+  render(): void;
+  output(): Uint8Array;
+  /** Adresse du buffer d'entrée dans la mémoire WASM, à remplir par la page */
+  prepare_input(width: number, height: number): number;
+  output_ptr(): number;
+  output_len(): number;
+  /** Rend puis copie le résultat dans `target` (ex. `ImageData.data`) */
+  render_into(target: Uint8ClampedArray): void;
+  /** Étapes reprises du cache lors du dernier rendu */
+  readonly cached_steps: number;
+  set_cache_budget(budgetBytes: number): void;
+  free(): void;
+}
 
 // Type défini par wasm-bindgen (sera disponible après import dynamique)
 interface WasmExports {
@@ -34,6 +55,24 @@ interface WasmExports {
   };
   /** Phase 5.1 — Histogramme WASM : retourne 768 uint32 (r[256] g[256] b[256]) */
   compute_histogram(pixels: Uint8Array, width: number, height: number): Uint32Array;
+  /** Rejoue les retouches heal/clone (tableau `retouch` de la recette, sérialisé en JSON) */
+  apply_retouch_spots(
+    pixels: Uint8ClampedArray,
+    width: number,
+    height: number,
+    spotsJson: string,
+  ): Uint8Array;
+  /** Pipeline complet construit depuis une recette d'édition JSON (buffers réutilisés) */
+  PipelineBuilder: {
+    new (): WasmPipelineBuilder;
+    from_recipe(recipeJson: string): WasmPipelineBuilder;
+  };
+  /** Mémoire linéaire du module (vues sans copie sur les buffers du PipelineBuilder) */
+  wasm_memory(): WebAssembly.Memory;
+  /** `true` pour le build multi-thread (feature `threads`) */
+  threads_enabled?(): boolean;
+  /** Build multi-thread uniquement : démarre le pool de Web Workers rayon */
+  initThreadPool?(threads: number): Promise<void>;
   default: () => Promise<void>;
 }
 
@@ -46,6 +85,8 @@ declare global {
 interface WasmModuleStatus {
   available: boolean;
   loaded: boolean;
+  /** Build multi-thread chargé et pool de workers démarré */
+  threaded?: boolean;
   lastError?: string;
 }
 
@@ -62,6 +103,56 @@ let wasmStatus: WasmModuleStatus = {
  */
 let cachedFiltersInstance: InstanceType<WasmExports['PixelFiltersWasm']> | null = null;
 
+/**
+ * PipelineBuilder réutilisé entre les frames (buffers d'entrée/sortie conservés côté WASM)
+ */
+let cachedPipelineBuilder: WasmPipelineBuilder | null = null;
+
+/**
+ * Source déjà chargée dans le PipelineBuilder : tant qu'elle ne change pas, on ne
+ * rappelle pas prepare_input, qui viderait le cache d'étapes côté WASM
+ */
+let uploadedPipelineSource: string | null = null;
+
+/**
+ * Build multi-thread optionnel (`npm run wasm:build:threads` → luminafast-wasm/pkg-threads).
+ * import.meta.glob retourne un objet vide si le build n'existe pas.
+ */
+const threadedWasmLoaders = import.meta.glob('../../luminafast-wasm/pkg-threads/luminafast_wasm.js');
+
+/**
+ * Le build multi-thread exige SharedArrayBuffer, donc une page cross-origin isolée
+ * (en-têtes COOP/COEP). Sinon on reste sur le build mono-thread.
+ */
+export function canUseWasmThreads(): boolean {
+  return typeof SharedArrayBuffer !== 'undefined' && globalThis.crossOriginIsolated === true;
+}
+
+/**
+ * Charge le build multi-thread et démarre son pool de workers.
+ * @returns null si indisponible (pas d'isolation, build absent ou échec d'init)
+ * @internal
+ */
+async function loadThreadedWasmModule(): Promise<WasmExports | null> {
+  const loader = Object.values(threadedWasmLoaders)[0];
+  if (!loader || !canUseWasmThreads()) {
+    return null;
+  }
+
+  try {
+    const wasmModule = (await loader()) as WasmExports;
+    await wasmModule.default();
+    if (typeof wasmModule.initThreadPool !== 'function') {
+      return null;
+    }
+    await wasmModule.initThreadPool(navigator.hardwareConcurrency || 4);
+    return wasmModule;
+  } catch (error) {
+    console.warn('[WASM] Build multi-thread indisponible, repli mono-thread', error);
+    return null;
+  }
+}
+
 /**
  * Charge le module WASM de manière asynchrone
  * @returns Promesse résolue si WASM chargé avec succès
@@ -77,11 +168,18 @@ export async function loadWasmModule(): Promise<void> {
     // Format: luminafast_wasm.js + luminafast_wasm_bg.wasm
     // Produits par: wasm-pack build --target web
 
-    // Import dynamique du module ES généré par wasm-bindgen
-    const wasmModule = (await import('@wasm/luminafast_wasm')) as unknown as WasmExports;
-
-    // Initialiser le module WASM (charge .wasm et instancie)
-    await wasmModule.default();
+    // Build multi-thread si la page est cross-origin isolée, sinon mono-thread
+    const threadedModule = await loadThreadedWasmModule();
+    let wasmModule: WasmExports;
+    if (threadedModule) {
+      wasmModule = threadedModule;
+    } else {
+      // Import dynamique du module ES généré par wasm-bindgen
+      wasmModule = (await import('@wasm/luminafast_wasm')) as unknown as WasmExports;
+
+      // Initialiser le module WASM (charge .wasm et instancie)
+      await wasmModule.default();
+    }
 
     // Vérifier que PixelFiltersWasm existe
     if (typeof wasmModule.PixelFiltersWasm !== 'function') {
@@ -93,7 +191,10 @@ export async function loadWasmModule(): Promise<void> {
 
     wasmStatus.available = true;
     wasmStatus.loaded = true;
-    console.warn('[WASM] Module chargé avec succès');
+    wasmStatus.threaded = threadedModule !== null;
+    console.warn(
+      `[WASM] Module chargé avec succès (${wasmStatus.threaded ? 'multi-thread' : 'mono-thread'})`,
+    );
   } catch (error) {
     const errorMsg = error instanceof Error ? error.message : String(error);
     wasmStatus.loaded = true;
@@ -103,6 +204,13 @@ export async function loadWasmModule(): Promise<void> {
   }
 }
 
+/**
+ * Vérifie si le build multi-thread est chargé (sinon rendu mono-thread)
+ */
+export function isWasmThreaded(): boolean {
+  return hasWasmSupport() && wasmStatus.threaded === true;
+}
+
 /**
  * Vérifie si WASM est disponible
  */
@@ -217,6 +325,7 @@ export function normalizeFiltersForWasm(filters: PixelFilterState): PixelFilterS
  * @param filters - États des filtres pixel (échelle UI: -100 à +100)
  * @param width - Largeur en pixels
  * @param height - Hauteur en pixels
+ * @param retouch - Retouches heal/clone appliquées avant les filtres (même ordre qu'à l'export)
  */
 export async function renderWithWasm(
   canvas: HTMLCanvasElement,
@@ -224,6 +333,7 @@ export async function renderWithWasm(
   _filters: PixelFilterState,
   width: number,
   height: number,
+  retouch: RetouchSpot[] = [],
 ): Promise<void> {
   // S'assurer que WASM est chargé
   await loadWasmModule();
@@ -271,34 +381,61 @@ export async function renderWithWasm(
           const normalizedFilters = normalizeFiltersForWasm(_filters);
           const t3 = performance.now();
 
-          // PERF: Réutiliser l'instance de filtres au lieu de recréer (économise 8ms/frame)
-          if (!cachedFiltersInstance) {
-            cachedFiltersInstance = new wasmModule.PixelFiltersWasm(0, 0, 1, 0, 0, 0, 0, 5500, 0);
+          let t4 = t3;
+          let t5 = t3;
+          let outputData = imageData;
+
+          if (typeof wasmModule.PipelineBuilder === 'function') {
+            // Rendu sans copie : la page écrit l'entrée et lit le rendu directement
+            // dans la mémoire WASM, sans buffer intermédiaire alloué par frame.
+            t4 = performance.now();
+            outputData = renderWithPipelineBuilder(
+              wasmModule,
+              imageUrl,
+              imageData,
+              _filters,
+              retouch,
+              width,
+              height,
+            );
+            t5 = performance.now();
+          } else {
+            // PERF: Réutiliser l'instance de filtres au lieu de recréer (économise 8ms/frame)
+            if (!cachedFiltersInstance) {
+              cachedFiltersInstance = new wasmModule.PixelFiltersWasm(0, 0, 1, 0, 0, 0, 0, 5500, 0);
+            }
+
+            // Mettre à jour les propriétés via setters (beaucoup plus rapide que new)
+            cachedFiltersInstance.exposure = normalizedFilters.exposure;
+            cachedFiltersInstance.contrast = normalizedFilters.contrast;
+            cachedFiltersInstance.saturation = normalizedFilters.saturation;
+            cachedFiltersInstance.highlights = normalizedFilters.highlights ?? 0;
+            cachedFiltersInstance.shadows = normalizedFilters.shadows ?? 0;
+            cachedFiltersInstance.clarity = normalizedFilters.clarity ?? 0;
+            cachedFiltersInstance.vibrance = normalizedFilters.vibrance ?? 0;
+            cachedFiltersInstance.color_temp = normalizedFilters.colorTemp ?? 5500;
+            cachedFiltersInstance.tint = normalizedFilters.tint ?? 0;
+            t4 = performance.now();
+
+            // Retouches heal/clone avant les réglages globaux, comme le pipeline d'export
+            if (retouch.length > 0) {
+              pixels.set(
+                wasmModule.apply_retouch_spots(pixels, width, height, JSON.stringify(retouch)),
+              );
+            }
+
+            // Appeler apply_filters() sur l'instance réutilisée
+            const processedPixels = cachedFiltersInstance.apply_filters(pixels, width, height);
+            t5 = performance.now();
+
+            // Copier les résultats back (itération complète)
+            // PERF: Utiliser .set() au lieu d'une boucle for (10x plus rapide)
+            imageData.data.set(processedPixels);
           }
-
-          // Mettre à jour les propriétés via setters (beaucoup plus rapide que new)
-          cachedFiltersInstance.exposure = normalizedFilters.exposure;
-          cachedFiltersInstance.contrast = normalizedFilters.contrast;
-          cachedFiltersInstance.saturation = normalizedFilters.saturation;
-          cachedFiltersInstance.highlights = normalizedFilters.highlights ?? 0;
-          cachedFiltersInstance.shadows = normalizedFilters.shadows ?? 0;
-          cachedFiltersInstance.clarity = normalizedFilters.clarity ?? 0;
-          cachedFiltersInstance.vibrance = normalizedFilters.vibrance ?? 0;
-          cachedFiltersInstance.color_temp = normalizedFilters.colorTemp ?? 5500;
-          cachedFiltersInstance.tint = normalizedFilters.tint ?? 0;
-          const t4 = performance.now();
-
-          // Appeler apply_filters() sur l'instance réutilisée
-          const processedPixels = cachedFiltersInstance.apply_filters(pixels, width, height);
-          const t5 = performance.now();
-
-          // Copier les résultats back (itération complète)
-          // PERF: Utiliser .set() au lieu d'une boucle for (10x plus rapide)
-          imageData.data.set(processedPixels);
           const t6 = performance.now();
 
           // Afficher sur le canvas
-          ctx.putImageData(imageData, 0, 0);
+          ctx.putImageData(outputData, 0, 0);
           const t7 = performance.now();
 
           // Profiling détaillé
@@ -336,6 +473,71 @@ export async function renderWithWasm(
   }
 }
 
+/**
+ * Rendu via PipelineBuilder sur la mémoire linéaire WASM.
+ *
+ * Les pixels source sont copiés une fois dans le buffer d'entrée WASM ; le
+ * résultat est exposé tel quel via un `ImageData` adossé à la mémoire WASM,
+ * valide jusqu'au prochain appel au module. Avec le build multi-thread (mémoire
+ * partagée), le résultat est recopié dans `imageData`.
+ * @internal
+ */
+function renderWithPipelineBuilder(
+  wasmModule: WasmExports,
+  imageUrl: string,
+  imageData: ImageData,
+  filters: PixelFilterState,
+  retouch: RetouchSpot[],
+  width: number,
+  height: number,
+): ImageData {
+  if (!cachedPipelineBuilder) {
+    cachedPipelineBuilder = new wasmModule.PipelineBuilder();
+    uploadedPipelineSource = null;
+  }
+  const builder = cachedPipelineBuilder;
+
+  // Recette en unités UI : le process version du core applique les mêmes
+  // facteurs d'échelle que normalizeFiltersForWasm
+  builder.set_recipe(
+    JSON.stringify({
+      schemaVersion: 1,
+      processVersion: 1,
+      adjustments: {
+        exposure: filters.exposure,
+        contrast: filters.contrast,
+        saturation: filters.saturation,
+        highlights: filters.highlights ?? 0,
+        shadows: filters.shadows ?? 0,
+        clarity: filters.clarity ?? 0,
+        vibrance: filters.vibrance ?? 0,
+        colorTemp: filters.colorTemp ?? 5500,
+        tint: filters.tint ?? 0,
+      },
+      retouch,
+    }),
+  );
+
+  const memory = wasmModule.wasm_memory();
+  const source = `${imageUrl}|${width}x${height}`;
+  if (source !== uploadedPipelineSource) {
+    // La vue doit être créée après prepare_input (la mémoire peut avoir grandi)
+    const inputPtr = builder.prepare_input(width, height);
+    new Uint8ClampedArray(memory.buffer, inputPtr, imageData.data.length).set(imageData.data);
+    uploadedPipelineSource = source;
+  }
+
+  builder.render();
+
+  const output = new Uint8ClampedArray(memory.buffer, builder.output_ptr(), builder.output_len());
+  if (!(memory.buffer instanceof ArrayBuffer)) {
+    // Build multi-thread : ImageData refuse une mémoire partagée, copie unique
+    imageData.data.set(output);
+    return imageData;
+  }
+  return new ImageData(output, width, height);
+}
+
 /**
  * Fallback : rendu avec CSS filters
  * @internal
@@ -420,5 +622,7 @@ export function resetWasmModule(): void {
     available: false,
     loaded: false,
   };
+  cachedPipelineBuilder = null;
+  uploadedPipelineSource = null;
   delete window.luminafastWasm;
 }
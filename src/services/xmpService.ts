@@ -1,4 +1,4 @@
-import type { XmpImportResult, XmpStatus } from '@/types/xmp';
+import type { ImageIptc, XmpImportResult, XmpStatus } from '@/types/xmp';
 
 /**
  * Service pour les opérations XMP sidecar — Phase 5.4
@@ -51,4 +51,21 @@ export class XmpService {
     const result = await invoke('get_xmp_status', { imageId });
     return result as XmpStatus;
   }
+
+  /**
+   * Lit les champs IPTC d'une image (null pour les champs jamais saisis).
+   */
+  static async getImageIptc(imageId: number): Promise<ImageIptc> {
+    const invoke = this.getInvoke();
+    const result = await invoke('get_image_iptc', { imageId });
+    return result as ImageIptc;
+  }
+
+  /**
+   * Enregistre les champs IPTC d'une image. Les mots-clés restent gérés par les tags.
+   */
+  static async setImageIptc(imageId: number, iptc: ImageIptc): Promise<void> {
+    const invoke = this.getInvoke();
+    await invoke('set_image_iptc', { imageId, iptc });
+  }
 }
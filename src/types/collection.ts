@@ -12,7 +12,25 @@ export type SmartQueryField =
   | 'lens'
   | 'flag'
   | 'color_label'
-  | 'filename';
+  | 'filename'
+  | 'sharpness'
+  | 'brightness'
+  | 'highlights_clipped'
+  | 'shadows_clipped'
+  | 'color';
+
+export type SmartQuerySortField =
+  | 'rating'
+  | 'iso'
+  | 'aperture'
+  | 'focal_length'
+  | 'filename'
+  | 'captured_at'
+  | 'imported_at'
+  | 'sharpness'
+  | 'brightness'
+  | 'highlights_clipped'
+  | 'shadows_clipped';
 
 export type SmartQueryOperator =
   | '='
@@ -26,15 +44,32 @@ export type SmartQueryOperator =
   | 'starts_with'
   | 'ends_with';
 
+/**
+ * Dominant colour criterion: a colour name or '#rrggbb', matched within `distance`
+ * (ΔE, default 20) by a palette colour covering at least `weight` of the image (default 0.1)
+ */
+export interface ColorFilterValue {
+  color: string;
+  distance?: number;
+  weight?: number;
+}
+
 export interface SmartQueryRule {
   field: SmartQueryField;
   operator: SmartQueryOperator;
-  value: string | number | boolean;
+  value: string | number | boolean | ColorFilterValue;
+}
+
+export interface SmartQuerySort {
+  field: SmartQuerySortField;
+  direction: 'asc' | 'desc';
 }
 
 export interface SmartQuery {
   rules: SmartQueryRule[];
   combinator: 'AND' | 'OR';
+  /** Defaults to most recently imported first */
+  sort?: SmartQuerySort;
 }
 
 export interface Collection {
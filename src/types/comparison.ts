@@ -36,6 +36,7 @@ export interface SplitViewComparisonProps {
   position: number; // 0-100, pourcentage de la largeur pour l'image avant
   onPositionChange: (position: number) => void;
   editState?: EditState;
+  imageId?: number; // Charge les retouches de la recette pour le rendu WASM
 }
 
 /**
@@ -50,6 +51,7 @@ export interface OverlayComparisonProps {
   opacity: number; // 0-100, opacité de l'image "Après"
   onOpacityChange: (opacity: number) => void;
   editState?: EditState;
+  imageId?: number; // Charge les retouches de la recette pour le rendu WASM
 }
 
 /**
@@ -62,6 +64,7 @@ export interface SideBySideComparisonProps {
   beforeUrl: string;
   afterUrl: string;
   editState?: EditState;
+  imageId?: number; // Charge les retouches de la recette pour le rendu WASM
   containerRef?: React.RefObject<HTMLDivElement>;
 }
 
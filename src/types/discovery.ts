@@ -5,6 +5,8 @@
  * for seamless communication between frontend and backend.
  */
 
+import type { NamingOptions } from './naming';
+
 // ============================================================================
 // RAW Format Types
 // ============================================================================
@@ -33,6 +35,7 @@ export enum RawFormat {
   JPEG = 'jpeg', // JPEG (alternate extension)
   PNG = 'png', // Portable Network Graphics
   WEBP = 'webp', // WebP
+  TIFF = 'tiff', // TIFF (floating-point HDR merges)
 }
 
 /** RAW format metadata */
@@ -300,6 +303,16 @@ export interface BatchIngestionRequest {
   skipExisting: boolean;
   /** Maximum number of files to ingest (null = all) */
   maxFiles: number | null;
+  /** Copy files into a renamed library folder and ingest the copies */
+  copyTo?: ImportCopyOptions | null;
+}
+
+/** Destination and naming of files copied during ingestion */
+export interface ImportCopyOptions {
+  /** Root folder of the copies */
+  destinationDir: string;
+  /** Path of each copy under `destinationDir`, without extension */
+  naming: NamingOptions;
 }
 
 /** Batch ingestion result */
@@ -636,6 +649,15 @@ export function getRawFormatInfo(format: RawFormat): RawFormatInfo {
       minSize: 1024, // 1KB
       maxSize: 100 * 1024 * 1024, // 100MB
     },
+    [RawFormat.TIFF]: {
+      format: RawFormat.TIFF,
+      extension: 'tiff',
+      mimeType: 'image/tiff',
+      description: 'TIFF',
+      signature: [0x49, 0x49, 0x2a, 0x00], // TIFF LE
+      minSize: 1024, // 1KB
+      maxSize: 2 * 1024 * 1024 * 1024, // 2GB
+    },
   };
 
   return formatMap[format];
@@ -39,6 +39,30 @@ export interface ExifMetadataDTO {
   color_space?: string;
 }
 
+/** Sharpness and exposure metrics computed from the standard preview */
+export interface QualityMetricsDTO {
+  /** Variance of Laplacian (8-bit luma scale) */
+  sharpness: number;
+  /** Mean gamma-encoded luma, 0-1 */
+  meanLuminance: number;
+  /** Fraction of pixels with a clipped channel */
+  highlightsClipped: number;
+  /** Fraction of pixels crushed to black */
+  shadowsClipped: number;
+}
+
+/** Dominant colour of an image, extracted from its thumbnail */
+export interface PaletteColorDTO {
+  /** CIELAB coordinates */
+  l: number;
+  a: number;
+  b: number;
+  /** Share of the thumbnail, 0-1 */
+  weight: number;
+  /** sRGB '#rrggbb' for display */
+  hex: string;
+}
+
 export interface CollectionDTO {
   id: number;
   name: string;
@@ -59,6 +83,8 @@ export interface ImageFilter {
   flag?: string;
   folder_id?: number;
   search_text?: string;
+  /** Hide non-cover members of collapsed stacks */
+  stack_tops_only?: boolean;
 }
 
 // Error type for Tauri commands
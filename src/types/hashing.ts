@@ -64,6 +64,20 @@ export interface HashBenchmarkResult {
   sample_hash: string;
 }
 
+// Recherche d'images similaires (hashes perceptuels dHash/pHash)
+export type PerceptualHashAlgorithm = 'dhash' | 'phash';
+
+export interface SimilarImage {
+  imageId: number;
+  filename: string;
+  /** Distance de Hamming à l'image recherchée (0-64) */
+  distance: number;
+}
+
+export interface SimilarImageGroup {
+  imageIds: number[];
+}
+
 // Types pour les callbacks et événements
 export type HashProgressCallback = (progress: HashProgress) => void;
 export type HashResultCallback = (result: FileHash | FileHash[]) => void;
@@ -28,4 +28,6 @@ export type {
   ImageFilter,
 } from './dto';
 
+export type { CollisionPolicy, NameAction, NamePreview, NamingOptions } from './naming';
+
 export type { XmpStatus, XmpImportResult } from './xmp';
@@ -0,0 +1,32 @@
+/**
+ * File and folder naming templates shared by exports and copying imports.
+ *
+ * Mirrors `services::naming` in the Rust backend. A template is a relative
+ * path without extension whose tokens are resolved per image:
+ * `{filename}`, `{yyyy}`, `{yy}`, `{mm}`, `{dd}`, `{hh}`, `{min}`, `{ss}`,
+ * `{seq}` / `{seq:N}`, `{camera}`, `{rating}`, `{collection}`, `{text}` and
+ * `{hash}` / `{hash:N}` (first N characters of the BLAKE3 hash, 8 by default).
+ */
+
+/** What to do when a resolved name already exists on disk. */
+export type CollisionPolicy = 'suffix' | 'overwrite' | 'skip';
+
+export interface NamingOptions {
+  template: string;
+  /** Value of the `{text}` token. */
+  customText?: string;
+  /** `{seq}` of the first image, 1 by default. */
+  sequenceStart?: number;
+  collision?: CollisionPolicy;
+}
+
+export type NameAction = 'create' | 'overwrite' | 'skip';
+
+/** Resolved destination of one source, as returned by the preview commands. */
+export interface NamePreview {
+  /** Catalog ID, for exports only. */
+  imageId: number | null;
+  source: string;
+  target: string;
+  action: NameAction;
+}
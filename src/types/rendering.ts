@@ -35,6 +35,37 @@ export interface CurvePoint {
   y: number; // Output value (0-255)
 }
 
+/**
+ * Point en coordonnées normalisées (0..1 sur chaque axe)
+ */
+export interface RetouchPoint {
+  x: number;
+  y: number;
+}
+
+/**
+ * Retouche heal/clone (miroir de `RetouchSpot` dans luminafast-image-core).
+ * Un seul point cible = spot circulaire, plusieurs points = trait au pinceau.
+ */
+export interface RetouchSpot {
+  mode: 'heal' | 'clone';
+  target: RetouchPoint[];
+  source: RetouchPoint;
+  radius: number; // Fraction du plus grand côté de l'image
+  feather?: number; // 0..1
+  opacity?: number; // 0..1
+}
+
+/**
+ * Recette d'édition résolue côté backend (miroir de `EditRecipe`, commande `get_edit_recipe`)
+ */
+export interface EditRecipe {
+  schemaVersion: number;
+  processVersion: number;
+  adjustments: Record<string, number>;
+  retouch?: RetouchSpot[]; // Absent quand aucune retouche
+}
+
 /**
  * Contexte de rendu (métadonnées + paramètres)
  */
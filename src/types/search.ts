@@ -3,14 +3,22 @@
 export type SearchOperator = ':' | '>' | '>=' | '<' | '<=' | '=';
 
 export interface ParsedFilter {
-  field: string; // ex: 'iso', 'star', 'camera', 'lens'
+  field: string; // ex: 'iso', 'star', 'camera', 'lens', 'sharpness', 'brightness', 'color'
   operator: SearchOperator;
   value: string | number;
 }
 
+export interface SearchSort {
+  // 'iso', 'star', 'filename', 'captured_at', 'imported_at', 'sharpness', 'brightness',
+  // 'highlights_clipped', 'shadows_clipped'
+  field: string;
+  direction?: 'asc' | 'desc'; // 'asc' par défaut
+}
+
 export interface SearchQuery {
   text: string; // texte libre (filename, tags, lieu)
   filters: ParsedFilter[];
+  sort?: SearchSort;
 }
 
 export interface SearchResult {
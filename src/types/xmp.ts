@@ -17,3 +17,13 @@ export interface XmpImportResult {
   /** Nombre de tags importés */
   tagsImported: number;
 }
+
+/** Champs IPTC saisis dans le catalogue (intégrés aux exports JPEG/PNG/TIFF) */
+export interface ImageIptc {
+  /** Auteur (dc:creator, IPTC By-line) */
+  author: string | null;
+  /** Légende (dc:description, IPTC Caption) */
+  description: string | null;
+  /** Mention de copyright (dc:rights, IPTC Copyright Notice) */
+  copyright: string | null;
+}
@@ -13,6 +13,12 @@ export default defineConfig({
     },
   },
   server: {
+    // Cross-origin isolation (SharedArrayBuffer) pour le build WASM multi-thread ;
+    // `credentialless` garde les images cross-origin sans en-tête CORP chargeables.
+    headers: {
+      'Cross-Origin-Opener-Policy': 'same-origin',
+      'Cross-Origin-Embedder-Policy': 'credentialless',
+    },
     host: 'localhost',
     port: 5173,
     hmr: {